@@ -0,0 +1,80 @@
+// cli.rs
+//
+// Minimal command-line handling for the driver:
+//   packet_builder [--emit <target>]... [--package <target>=<name>]... [input.packet]
+// With no --emit flags every target is generated.
+// --package replaces the file's `package` declaration for one target.
+
+pub const ALL_TARGETS: &[&str] = &[
+    "c", "rust", "zig", "net", "python", "go", "swift", "wireshark", "kaitai", "json",
+    "markdown",
+];
+
+/// Targets whose output is affected by a `package` declaration.
+pub const PACKAGE_TARGETS: &[&str] = &["c", "rust", "net", "python", "go"];
+
+#[derive(Debug, Clone)]
+pub struct CliOptions {
+    pub input: String,
+    pub targets: Vec<String>,
+    pub package_overrides: Vec<(String, String)>, // (target, dotted package name)
+}
+
+pub fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<CliOptions, String> {
+    let mut input: Option<String> = None;
+    let mut targets = Vec::<String>::new();
+    let mut package_overrides = Vec::<(String, String)>::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--emit" => {
+                let target = args
+                    .next()
+                    .ok_or_else(|| "--emit requires a target name".to_string())?;
+                if !ALL_TARGETS.contains(&target.as_str()) {
+                    return Err(format!(
+                        "unknown target '{}' (expected one of: {})",
+                        target,
+                        ALL_TARGETS.join(", ")
+                    ));
+                }
+                targets.push(target);
+            }
+            "--package" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| "--package requires <target>=<name>".to_string())?;
+                let (target, name) = value
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected <target>=<name>, got '{}'", value))?;
+                if !PACKAGE_TARGETS.contains(&target) {
+                    return Err(format!(
+                        "target '{}' has no package setting (expected one of: {})",
+                        target,
+                        PACKAGE_TARGETS.join(", ")
+                    ));
+                }
+                if name.is_empty() || name.split('.').any(str::is_empty) {
+                    return Err(format!("invalid package name '{}'", name));
+                }
+                package_overrides.push((target.to_string(), name.to_string()));
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option '{}'", arg)),
+            _ => {
+                if input.replace(arg).is_some() {
+                    return Err("only one input file may be given".to_string());
+                }
+            }
+        }
+    }
+
+    if targets.is_empty() {
+        targets = ALL_TARGETS.iter().map(|t| t.to_string()).collect();
+    }
+
+    Ok(CliOptions {
+        input: input.unwrap_or_else(|| "./test_packet.packet".to_string()),
+        targets,
+        package_overrides,
+    })
+}
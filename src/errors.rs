@@ -1,37 +1,132 @@
-use ariadne::{Color, Fmt, Label, Report, ReportKind, Source};
+use ariadne::{sources, Color, Label, Report, ReportKind};
 use pest::error::{Error, ErrorVariant, InputLocation};
-use pest::Span;
 
-fn span_range<'i>(src: &'i str, e: &Error<Rule>) -> std::ops::Range<usize> {
+use crate::loader::LoadError;
+use crate::models::parsing_models::SourceSpan;
+use crate::parser::Rule;
+
+fn span_range(e: &Error<Rule>) -> std::ops::Range<usize> {
     match &e.location {
-        InputLocation::Pos(p) => {
-            let i = p.pos();
-            i..(i.saturating_add(1))
-        }
-        InputLocation::Span((s, e)) => s.pos()..e.pos(),
+        InputLocation::Pos(p) => *p..p.saturating_add(1),
+        InputLocation::Span((s, e)) => *s..*e,
+    }
+}
+
+fn friendly(r: Rule) -> String {
+    match r {
+        Rule::identifier => "an identifier".to_string(),
+        Rule::type_name => "a type name".to_string(),
+        Rule::numeric_constant => "a number".to_string(),
+        Rule::string => "a string".to_string(),
+        Rule::expr => "an expression".to_string(),
+        Rule::packet | Rule::packet_kw => "`packet`".to_string(),
+        Rule::rule => "a field".to_string(),
+        Rule::import_stmt | Rule::import_kw => "`import`".to_string(),
+        Rule::doc_comment => "a `///` comment".to_string(),
+        Rule::endianness => "`le` or `be`".to_string(),
+        Rule::larray => "`[`".to_string(),
+        Rule::comma => "`,`".to_string(),
+        Rule::semicolon => "`;`".to_string(),
+        Rule::lbracket => "`{`".to_string(),
+        Rule::rbracket => "`}`".to_string(),
+        Rule::EOI => "end of file".to_string(),
+        other => format!("{:?}", other),
     }
 }
 
-pub fn report_with_ariadne(src_name: &str, src: &str, e: Error<Rule>) {
-    let range = span_range(src, &e);
+/// Prints a load error, pointing into the file (and import) it came from.
+pub fn report_load_error(e: &LoadError, files: &[(String, String)]) {
+    let cache = sources(files.iter().cloned());
+    let at = |span: &SourceSpan| (span.file.clone(), span.start..span.end);
 
-    let expected = match &e.variant {
-        ErrorVariant::ParsingError { positives, .. } if !positives.is_empty() => {
-            let names = positives.iter().map(|r| friendly(*r)).collect::<Vec<_>>();
-            format!("expected {}", names.join(", "))
+    let report = match e {
+        LoadError::Io { path, error, imported_at } => match imported_at {
+            Some(span) => Report::build(ReportKind::Error, span.file.clone(), span.start)
+                .with_message(format!("cannot read '{}': {}", path, error))
+                .with_label(Label::new(at(span)).with_message("imported here").with_color(Color::Red)),
+            None => {
+                eprintln!("error: cannot read '{}': {}", path, error);
+                return;
+            }
+        },
+        LoadError::Parse { file, error } => {
+            let range = span_range(error);
+            let expected = match &error.variant {
+                ErrorVariant::ParsingError { positives, .. } if !positives.is_empty() => {
+                    let names = positives.iter().map(|r| friendly(*r)).collect::<Vec<_>>();
+                    format!("expected {}", names.join(", "))
+                }
+                ErrorVariant::CustomError { message } => message.clone(),
+                _ => "parse error".to_string(),
+            };
+            Report::build(ReportKind::Error, file.clone(), range.start)
+                .with_message("Parse error")
+                .with_label(Label::new((file.clone(), range)).with_message(expected).with_color(Color::Red))
+        }
+        LoadError::ImportCycle { chain, span } => {
+            Report::build(ReportKind::Error, span.file.clone(), span.start)
+                .with_message(format!("import cycle: {}", chain.join(" -> ")))
+                .with_label(Label::new(at(span)).with_message("this import closes the cycle").with_color(Color::Red))
+        }
+        LoadError::DuplicatePacket { name, first, second } => {
+            Report::build(ReportKind::Error, second.file.clone(), second.start)
+                .with_message(format!("packet '{}' is defined more than once", name))
+                .with_label(Label::new(at(second)).with_message("redefined here").with_color(Color::Red))
+                .with_label(Label::new(at(first)).with_message("first defined here").with_color(Color::Blue))
+        }
+        LoadError::PackageMismatch { first, second } => {
+            Report::build(ReportKind::Error, second.span.file.clone(), second.span.start)
+                .with_message(format!(
+                    "conflicting packages '{}' and '{}'",
+                    first.name, second.name
+                ))
+                .with_label(
+                    Label::new(at(&second.span))
+                        .with_message(format!("declares '{}'", second.name))
+                        .with_color(Color::Red),
+                )
+                .with_label(
+                    Label::new(at(&first.span))
+                        .with_message(format!("declares '{}'", first.name))
+                        .with_color(Color::Blue),
+                )
+        }
+        LoadError::UnknownPacket { name, span } => Report::build(ReportKind::Error, span.file.clone(), span.start)
+            .with_message(format!("unknown packet '{}'", name))
+            .with_label(Label::new(at(span)).with_message("named here").with_color(Color::Red)),
+        LoadError::UnsizedPacket { name, span } => Report::build(ReportKind::Error, span.file.clone(), span.start)
+            .with_message(format!("packet '{}' has no fixed size", name))
+            .with_label(
+                Label::new(at(span))
+                    .with_message("`sizeof` taken here; its size depends on its values")
+                    .with_color(Color::Red),
+            ),
+        LoadError::DuplicateFunction { name, first, second } => {
+            Report::build(ReportKind::Error, second.file.clone(), second.start)
+                .with_message(format!("function '{}' is declared more than once", name))
+                .with_label(Label::new(at(second)).with_message("redeclared here").with_color(Color::Red))
+                .with_label(Label::new(at(first)).with_message("first declared here").with_color(Color::Blue))
+        }
+        LoadError::UnknownFunction { name, span } => Report::build(ReportKind::Error, span.file.clone(), span.start)
+            .with_message(format!("unknown function '{}'", name))
+            .with_label(
+                Label::new(at(span))
+                    .with_message("called here; declare it with `extern fn`")
+                    .with_color(Color::Red),
+            ),
+        LoadError::WrongArity { name, expected, found, span } => {
+            Report::build(ReportKind::Error, span.file.clone(), span.start)
+                .with_message(format!(
+                    "'{}' takes {} argument{} but {} {} given",
+                    name,
+                    expected,
+                    if *expected == 1 { "" } else { "s" },
+                    found,
+                    if *found == 1 { "was" } else { "were" }
+                ))
+                .with_label(Label::new(at(span)).with_message("called here").with_color(Color::Red))
         }
-        ErrorVariant::CustomError { message } => message.clone(),
-        _ => "parse error".to_string(),
     };
 
-    Report::build(ReportKind::Error, src_name, range.start)
-        .with_message("Parse error")
-        .with_label(
-            Label::new((src_name, range))
-                .with_message(expected)
-                .with_color(Color::Red),
-        )
-        .finish()
-        .print((src_name, Source::from(src)))
-        .unwrap();
+    report.finish().eprint(cache).unwrap();
 }
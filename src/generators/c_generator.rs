@@ -1,4 +1,7 @@
-use crate::models::parsing_models::{ExprNode, PacketExpr, PacketExprList, TypeExpr, TypeNode};
+use crate::models::parsing_models::{
+    BitSlot, Check, ExprNode, FixedBase, Leftover, PacketExpr, PacketExprList, RepeatBound, StringEncoding, TimeEncoding,
+    TypeExpr, TypeNode, VarIntEncoding, eval_intrinsic,
+};
 use tera::{Context, Tera};
 
 pub struct CGenerator;
@@ -26,14 +29,16 @@ impl CGenerator {
 
         // Precompute per-packet code blocks
         let mut packets_ctx = Vec::<PacketCtx>::new();
+        let prefix = c_prefix(model);
         for pkt in &model.packets {
-            packets_ctx.push(build_packet_ctx(pkt));
+            packets_ctx.push(build_packet_ctx(pkt, &prefix));
         }
 
         let mut ctx = Context::new();
         ctx.insert("headers", &headers);
         ctx.insert("support", &support);
         ctx.insert("packets", &packets_ctx);
+        ctx.insert("prefix", &prefix);
 
         // 3) Render
         tera.render("c_root", &ctx).expect("Tera render failed")
@@ -43,18 +48,22 @@ impl CGenerator {
 #[derive(serde::Serialize)]
 struct PacketCtx {
     name: String,
+    doc: String, // pre-rendered `/** ... */` block, or empty
     fields: Vec<FieldCtx>,
+    consts: String, // `#define`s for const fields, which the struct leaves out
     total_size_code: String,
     serialize_body: String,
     deserialize_body: String,
+    validate_body: String, // checks of `where` and `assert` rules; empty without any
 }
 
 #[derive(serde::Serialize)]
 struct FieldCtx {
     decl: String, // e.g. "uint16_t foo[3]" or "uint8_t* data"
+    doc: String,  // pre-rendered `/** ... */` block, or empty
 }
 
-fn build_packet_ctx(pkt: &PacketExpr) -> PacketCtx {
+fn build_packet_ctx(pkt: &PacketExpr, prefix: &str) -> PacketCtx {
     // 1) Struct declarations
     let mut fields_ctx = Vec::<FieldCtx>::new();
 
@@ -63,35 +72,285 @@ fn build_packet_ctx(pkt: &PacketExpr) -> PacketCtx {
     let mut serialize_body = String::new();
     let mut deserialize_body = String::new();
 
-    for field in &pkt.fields {
+    // `repeat [n]` counts are written from the array's length: size and
+    // serialize work on a copy with them filled in. Other counts already
+    // size their field in C, so they are consistent as written.
+    let mut consts = String::new();
+    let mut fill = String::new();
+    for (field, count) in pkt.fields.iter().zip(pkt.derived_counts()) {
+        if let Some(count) = count
+            && let TypeNode::Repeat(_, _) = pkt.fields[count.source].expr
+        {
+            fill.push_str(&format!(
+                "_copy.{} = ({})(packet->{}_count{}); ",
+                field.id,
+                c_scalar_type(&field.expr),
+                pkt.fields[count.source].id,
+                c_adjust(count.adjust)
+            ));
+        }
+    }
+    if !fill.is_empty() {
+        let fill = format!("{prefix}{} _copy = *packet; {fill}packet = &_copy;\n", pkt.name);
+        total_size_code.push_str(&fill);
+        serialize_body.push_str(&fill);
+    }
+
+    // offsets checksums need, declared up front since `within` blocks open a scope
+    let marks = pkt.checksum_marks();
+    let mut offsets = Vec::<String>::new();
+    for (field, &(start, end)) in pkt.fields.iter().zip(&marks) {
+        if start {
+            offsets.push(format!("_{}_start = 0", field.id));
+        }
+        if field.checksum().is_some() {
+            offsets.push(format!("_{}_at = 0", field.id));
+        }
+        if end {
+            offsets.push(format!("_{}_end = 0", field.id));
+        }
+    }
+    if !offsets.is_empty() {
+        let decl = format!("size_t {};\n", offsets.join(", "));
+        serialize_body.push_str(&decl);
+        deserialize_body.push_str(&decl);
+    }
+
+    for (i, (field, slot)) in pkt.fields.iter().zip(pkt.bit_slots()).enumerate() {
+        for (k, _) in pkt.regions_opening(i) {
+            deserialize_body.push_str(&region_open(pkt, k, prefix));
+        }
+        let (start, end) = marks[i];
+        if start {
+            serialize_body.push_str(&format!("_{}_start = pos;\n", field.id));
+            deserialize_body.push_str(&format!("_{}_start = pos;\n", field.id));
+        }
+
         // Declaration
-        let decl = c_field_decl(field);
-        fields_ctx.push(FieldCtx { decl });
+        let constant = format!("{prefix}{}_{}", pkt.name, field.id).to_uppercase();
+        match field.const_literal() {
+            Some(value) => {
+                consts.push_str(&doc_block(&field.doc, ""));
+                consts.push_str(&format!("#define {constant} {value}\n"));
+            }
+            None if field.is_filler() || field.checksum().is_some() => {} // not stored
+            None => {
+                let decl = c_member_decl(field, prefix);
+                fields_ctx.push(FieldCtx { decl, doc: doc_block(&field.doc, "    ") });
+            }
+        }
 
-        // Size calc + ser/de
-        let (size_snip, ser_snip, de_snip) = codegen_field_snippets(field);
+        // Size calc + ser/de; a const field is written from its macro and read into a local.
+        // An optional field checks the bytes left only when it is present.
+        let mut guard = read_guard(field, slot.as_ref(), &format!("{prefix}{}", pkt.name));
+        let (size_snip, ser_snip, de_snip) = match slot {
+            Some(slot) => bit_snippets(&field.id, &slot),
+            // `calloc` leaves a checksum zeroed until it is filled in below
+            None if field.checksum().is_some() => {
+                let w = scalar_width_bytes(&field.expr);
+                let at = format!("_{}_at = pos; pos += {w};\n", field.id);
+                (format!("total_size += {w};\n"), at.clone(), at)
+            }
+            None if field.value.is_some() => {
+                let (size, ser, de) = codegen_field_snippets(field, pkt, prefix);
+                let member = format!("packet->{}", field.id);
+                let de = format!(
+                    "{{ {ty} {id}; {de}\tif ({id} != {constant}) {{ free(*packet); *packet = NULL; return 0; }} }}\n",
+                    ty = c_scalar_type(&field.expr),
+                    id = field.id,
+                    de = de.replace(&format!("(*packet)->{}", field.id), &field.id)
+                );
+                (size.replace(&member, &constant), ser.replace(&member, &constant), de)
+            }
+            // an optional field is sized and written when its condition holds, and
+            // `has_<id>` records whether decoding read it
+            None if field.condition.is_some() => {
+                let cond = emit_c_expr(field.condition.as_ref().unwrap());
+                let ty = format!("{prefix}{}", pkt.name);
+                let (size, ser, de) = codegen_field_snippets(field, pkt, prefix);
+                let de = std::mem::take(&mut guard) + &de;
+                (
+                    format!("if ({cond}) {{\n{size}}}\n"),
+                    format!("if ({cond}) {{\n{ser}}}\n"),
+                    format!(
+                        "(*packet)->has_{id} = 0;\n\
+                         {{ int _on; {{ const {ty}* _self = *packet; const {ty}* packet = _self; _on = ({cond}) != 0; }}\n\
+                         if (_on) {{\n{de}(*packet)->has_{id} = 1; }} }}\n",
+                        id = field.id
+                    ),
+                )
+            }
+            None => codegen_field_snippets(field, pkt, prefix),
+        };
         total_size_code.push_str(&size_snip);
         serialize_body.push_str(&ser_snip);
+        deserialize_body.push_str(&guard);
         deserialize_body.push_str(&de_snip);
+        if end {
+            serialize_body.push_str(&format!("_{}_end = pos;\n", field.id));
+            deserialize_body.push_str(&format!("_{}_end = pos;\n", field.id));
+        }
+        if let Some(check) = &field.constraint {
+            deserialize_body.push_str(&check_decoded(check, &format!("{prefix}{}", pkt.name)));
+        }
+
+        for (k, _) in pkt.regions_closing(i) {
+            deserialize_body.push_str(&region_close(pkt, k));
+        }
+    }
+    // checksums are filled in field order, and checked with their own bytes read as zero
+    for field in &pkt.fields {
+        let Some(sum) = field.checksum() else { continue };
+        let bits = sum.kind.width_bytes() * 8;
+        let (first, last, id) = (sum.first, sum.last, &field.id);
+        serialize_body.push_str(&format!(
+            "store_u{bits}_be(&(*data)[_{id}_at], sum_{}(*data, _{first}_start, _{last}_end, 0, 0));\n",
+            sum.kind.name()
+        ));
+        deserialize_body.push_str(&format!(
+            "if (load_u{bits}_be(&data[_{id}_at]) != sum_{}(data, _{first}_start, _{last}_end, _{id}_at, {})) {{ free(*packet); *packet = NULL; return 0; }}\n",
+            sum.kind.name(),
+            sum.kind.width_bytes()
+        ));
+    }
+    for check in &pkt.asserts {
+        deserialize_body.push_str(&check_decoded(check, &format!("{prefix}{}", pkt.name)));
+    }
+
+    let mut validate_body = String::new();
+    for check in pkt.checks() {
+        validate_body.push_str(&format!(
+            "if (!({})) return \"{}\"; /* {} */\n    ",
+            emit_c_expr(&check.cond),
+            check.field,
+            check.rule.replace("*/", "* /")
+        ));
     }
 
     PacketCtx {
         name: pkt.name.clone(),
+        doc: doc_block(&pkt.doc, ""),
         fields: fields_ctx,
+        consts,
         total_size_code,
         serialize_body,
         deserialize_body,
+        validate_body,
     }
 }
 
-fn c_field_decl(field: &TypeExpr) -> String {
+/// Fails the decode unless a `where` or `assert` rule holds for the fields read so far.
+fn check_decoded(check: &Check, ty: &str) -> String {
+    format!(
+        "{{ int _ok; {{ const {ty}* _self = *packet; const {ty}* packet = _self; _ok = ({}) != 0; }}\n\
+         if (!_ok) {{ free(*packet); *packet = NULL; return 0; }} }} /* {} */\n",
+        emit_c_expr(&check.cond),
+        check.rule.replace("*/", "* /")
+    )
+}
+
+/// ` - a` for a length written `l + a`, so the count comes out as `l`.
+fn c_adjust(adjust: i64) -> String {
+    match adjust {
+        0 => String::new(),
+        a if a < 0 => format!(" + {}", -a),
+        a => format!(" - {a}"),
+    }
+}
+
+/// Identifier prefix for a package: `oscar.v1` -> `oscar_v1_`; empty without one.
+fn c_prefix(model: &PacketExprList) -> String {
+    model
+        .package_segments()
+        .iter()
+        .map(|s| format!("{}_", s.to_lowercase()))
+        .collect()
+}
+
+/// Renders DSL `///` docs as a Doxygen-style `/** ... */` block.
+fn doc_block(doc: &Option<String>, pad: &str) -> String {
+    let Some(doc) = doc else {
+        return String::new();
+    };
+    let doc = doc.replace("*/", "* /");
+    let mut lines = doc.lines();
+    match (lines.next(), lines.next()) {
+        (Some(only), None) => format!("{pad}/** {only} */\n"),
+        _ => {
+            let mut s = format!("{pad}/**\n");
+            for l in doc.lines() {
+                if l.is_empty() {
+                    s.push_str(&format!("{pad} *\n"));
+                } else {
+                    s.push_str(&format!("{pad} * {l}\n"));
+                }
+            }
+            s.push_str(&format!("{pad} */\n"));
+            s
+        }
+    }
+}
+
+/// Opens a `within` region: `len` becomes its end, so `until eof` stops there.
+/// The length is evaluated against the fields decoded so far.
+fn region_open(pkt: &PacketExpr, k: usize, prefix: &str) -> String {
+    let region = &pkt.regions[k];
+    let ty = format!("{prefix}{}", pkt.name);
+    format!(
+        "{{ size_t _end{k}, _outer{k} = len;\n\
+         {{ const {ty}* _self = *packet; const {ty}* packet = _self; _end{k} = (size_t)({}); }}\n\
+         {}\
+         _end{k} += pos; len = _end{k};\n",
+        emit_c_expr(&region.length),
+        truncated(&format!("_end{k} > len - pos"))
+    )
+}
+
+/// Closes a `within` region; fields reading past its end fail the decode,
+/// as do unread bytes unless the region skips them.
+fn region_close(pkt: &PacketExpr, k: usize) -> String {
+    let check = match pkt.regions[k].leftover {
+        Leftover::Error => format!("pos != _end{k}"),
+        Leftover::Skip => format!("pos > _end{k}"),
+    };
+    format!("{}pos = _end{k}; len = _outer{k}; }}\n", truncated(&check))
+}
+
+fn c_field_decl(field: &TypeExpr, prefix: &str) -> String {
+    // a growable array of decoded elements and its length
+    if let TypeNode::Repeat(elem, _) = &field.expr {
+        return format!("{prefix}{elem}* {name};\n    size_t {name}_count", name = field.id);
+    }
+    if let Some(enc) = string_encoding(&field.expr) {
+        return format!("{}* {}", c_string_type(enc), field.id);
+    }
     let base = c_scalar_type(&field.expr);
     let arr = array_decl(&field.expr);
     format!("{} {}{}", base, field.id, arr)
 }
 
+/// Declaration of a field, followed by its presence bit when the field is optional.
+fn c_member_decl(field: &TypeExpr, prefix: &str) -> String {
+    let decl = c_field_decl(field, prefix);
+    match field.condition {
+        Some(_) => format!("{decl};\n    unsigned has_{} : 1", field.id),
+        None => decl,
+    }
+}
+
+/// Snippets for a `bool(bit)` field; `calloc` leaves the byte zeroed, so
+/// serializing only ORs the bit in. `pos` moves past the byte after its last bit.
+fn bit_snippets(name: &str, slot: &BitSlot) -> (String, String, String) {
+    let shift = slot.shift();
+    let advance = if slot.ends_byte() { " pos += 1;" } else { "" };
+    let size = if slot.starts_byte() { "total_size += 1;\n".to_string() } else { String::new() };
+    let ser = format!("(*data)[pos] |= (uint8_t)((packet->{name} ? 1u : 0u) << {shift});{advance}\n");
+    let de = format!("(*packet)->{name} = (data[pos] >> {shift}) & 1u;{advance}\n");
+    (size, ser, de)
+}
+
 /// Returns (size_calc, serialize, deserialize) snippets.
-fn codegen_field_snippets(field: &TypeExpr) -> (String, String, String) {
+fn codegen_field_snippets(field: &TypeExpr, pkt: &PacketExpr, prefix: &str) -> (String, String, String) {
     use TypeNode::*;
     let name = &field.id;
     let width = scalar_width_bytes(&field.expr);
@@ -120,6 +379,142 @@ fn codegen_field_snippets(field: &TypeExpr) -> (String, String, String) {
 
     // Determine array-ness and element copy logic
     match &field.expr {
+        // Filler: `calloc` leaves it zeroed, and decoding passes over it. `align`
+        // rounds up the offset, which `total_size` also is while sizing.
+        Pad(n) => (format!("total_size += {n};\n"), format!("pos += {n};\n"), format!("pos += {n};\n")),
+        Align(n) => (
+            format!("total_size = (total_size + {n} - 1) / {n} * {n};\n"),
+            format!("pos = (pos + {n} - 1) / {n} * {n};\n"),
+            format!("pos = (pos + {n} - 1) / {n} * {n};\n"),
+        ),
+        // Strings: a decode failure frees the packet and leaves NULL behind
+        FixedString(len_expr, enc) => {
+            let count = match eval_len_const(len_expr) {
+                Some(n) => n.to_string(),
+                None => format!("(size_t)({})", emit_c_expr(len_expr)),
+            };
+            let size = format!("total_size += {count};\n");
+            let ser = format!(
+                "{{ size_t cnt = {count}; size_t len = {len}; {fail}\
+                 \t{write} pos += cnt; }}\n",
+                len = c_string_bytes(name, enc),
+                fail = encode_failure("len > cnt"),
+                write = c_string_write(name, enc, "len")
+            );
+            let de = format!(
+                "{{ size_t cnt = {count};\n\
+                 \t{short}\
+                 \t(*packet)->{name} = {decode}(&data[pos], nul_offset(&data[pos], cnt, {unit}));\n\
+                 \t{fail}\
+                 \tpos += cnt; }}\n",
+                short = truncated("len - pos < cnt"),
+                decode = c_decoder(enc),
+                unit = code_unit(enc),
+                fail = decode_failure(name)
+            );
+            (size, ser, de)
+        }
+        CString(enc) => {
+            let unit = code_unit(enc);
+            let size = format!("total_size += {} + {unit};\n", c_string_bytes(name, enc));
+            let ser = format!(
+                "{{ size_t len = {len}; {write} pos += len + {unit}; }}\n",
+                len = c_string_bytes(name, enc),
+                write = c_string_write(name, enc, "len")
+            );
+            // no terminator before the bound fails the decode
+            let de = format!(
+                "{{ size_t n = nul_offset(&data[pos], len - pos, {unit});\n\
+                 \t{short}\
+                 \t(*packet)->{name} = {decode}(&data[pos], n);\n\
+                 \t{fail}\
+                 \tpos += n + {unit}; }}\n",
+                short = truncated(&format!("len - pos - n < {unit}")),
+                decode = c_decoder(enc),
+                fail = decode_failure(name)
+            );
+            (size, ser, de)
+        }
+        PString(_, enc) => {
+            let w = width; // the length prefix
+            let size = format!("total_size += {w} + {};\n", c_string_bytes(name, enc));
+            let (store, load, max) = if w == 1 {
+                ("(*data)[pos] = (uint8_t)len;", "size_t n = data[pos];", "UINT8_MAX")
+            } else {
+                ("store_u16_be(&(*data)[pos], (uint16_t)len);", "size_t n = load_u16_be(&data[pos]);", "UINT16_MAX")
+            };
+            let ser = format!(
+                "{{ size_t len = {len}; {fail}\
+                 \t{store} pos += {w};\n\
+                 \t{write} pos += len; }}\n",
+                len = c_string_bytes(name, enc),
+                fail = encode_failure(&format!("len > {max}")),
+                write = c_string_write(name, enc, "len")
+            );
+            // the prefix itself is covered by `read_guard`
+            let de = format!(
+                "{{ {load} pos += {w};\n\
+                 \t{short}\
+                 \t(*packet)->{name} = {decode}(&data[pos], n);\n\
+                 \t{fail}\
+                 \tpos += n; }}\n",
+                short = truncated("len - pos < n"),
+                decode = c_decoder(enc),
+                fail = decode_failure(name)
+            );
+            (size, ser, de)
+        }
+
+        // Each element goes through its own packet's functions; deserializing
+        // hands the element the bytes left, and grows the array as it goes.
+        Repeat(elem, bound) => {
+            let ty = format!("{prefix}{elem}");
+            let size = format!(
+                "for (size_t k = 0; k < packet->{name}_count; ++k) total_size += {prefix}size_{elem}(&packet->{name}[k]);\n"
+            );
+            let ser = format!(
+                "for (size_t k = 0; k < packet->{name}_count; ++k) {{\n\
+                 \tuint8_t* buf = NULL; size_t n = {prefix}size_{elem}(&packet->{name}[k]);\n\
+                 \t{prefix}serialize_{elem}(&buf, &packet->{name}[k], verbose);\n\
+                 \tmemcpy(&(*data)[pos], buf, n); free(buf); pos += n; }}\n"
+            );
+            let (head, until) = match bound {
+                RepeatBound::Eof => ("while (pos < len) {".to_string(), String::new()),
+                RepeatBound::Until(cond) => (
+                    "for (;;) {".to_string(),
+                    // the condition reads the element's fields through `packet`
+                    format!(
+                        "\t{{ const {ty}* last = &(*packet)->{name}[(*packet)->{name}_count - 1];\n\
+                         \t  {{ const {ty}* packet = last; if ({}) break; }} }}\n",
+                        emit_c_expr(cond)
+                    ),
+                ),
+                // the count reads this packet's fields, which `*packet` holds while decoding
+                RepeatBound::Count(count) => (
+                    format!(
+                        "size_t cnt; {{ const {prefix}{owner}* _self = *packet; const {prefix}{owner}* packet = _self; cnt = (size_t)({}); }}\n\
+                         for (size_t k = 0; k < cnt; ++k) {{",
+                        emit_c_expr(count),
+                        owner = pkt.name
+                    ),
+                    String::new(),
+                ),
+            };
+            let de = format!(
+                "{{ size_t cap = 0; (*packet)->{name} = NULL; (*packet)->{name}_count = 0;\n\
+                 {head}\n\
+                 \t{ty}* item = NULL;\n\
+                 \tsize_t used = {prefix}deserialize_{elem}_bounded(&item, &data[pos], len - pos, verbose);\n\
+                 \tif (!item) {{ free((*packet)->{name}); free(*packet); *packet = NULL; return 0; }}\n\
+                 \tif ((*packet)->{name}_count == cap) {{\n\
+                 \t  cap = cap ? cap * 2 : 4;\n\
+                 \t  (*packet)->{name} = ({ty}*)realloc((*packet)->{name}, cap * sizeof({ty})); }}\n\
+                 \t(*packet)->{name}[(*packet)->{name}_count++] = *item; free(item); pos += used;\n\
+                 {until}}} }}\n"
+            );
+            (size, ser, de)
+        }
+
         Bytes(len_expr_opt) => {
             // bytes: width 1 per element
             if let Some(e) = len_expr_opt {
@@ -129,11 +524,11 @@ fn codegen_field_snippets(field: &TypeExpr) -> (String, String, String) {
                     "for (size_t i = 0; i < (size_t)({count}); ++i) {{ (*data)[pos++] = packet->{name}[i]; }}\n"
                 );
                 let de = format!(
-                    "{{ size_t cnt = (size_t)({count}); /* caller must ensure space */\n\
+                    "{{ size_t cnt = (size_t)({count});\n\
                      \tmemcpy((*packet)->{name}, &data[pos], cnt);\n\
                      \tpos += cnt; }}\n"
                 );
-                return (size, ser, de);
+                (size, ser, de)
             } else {
                 // Unknown-length bytes -> cannot pre-add to total; comment + no-op
                 let size = String::from(
@@ -145,7 +540,7 @@ fn codegen_field_snippets(field: &TypeExpr) -> (String, String, String) {
                 let de = format!(
                     "/* bytes {name}: unknown length at compile-time. Provide length expression in DSL. */\n"
                 );
-                return (size, ser, de);
+                (size, ser, de)
             }
         }
 
@@ -153,12 +548,12 @@ fn codegen_field_snippets(field: &TypeExpr) -> (String, String, String) {
             // Default 6 if None; if Some(expr) then treat as that many bytes
             if let Some(e) = len_opt {
                 if let Some(n) = eval_len_const(e) {
-                    let size = format!("total_size += {};\n", n * 1);
+                    let size = format!("total_size += {};\n", n);
                     let ser = format!(
                         "for (size_t i = 0; i < {n}; ++i) {{ (*data)[pos++] = packet->{name}[i]; }}\n"
                     );
                     let de = format!("memcpy((*packet)->{name}, &data[pos], {n}); pos += {n};\n");
-                    return (size, ser, de);
+                    (size, ser, de)
                 } else {
                     let count = emit_c_expr(e);
                     let size = format!("total_size += (size_t)({count});\n");
@@ -168,17 +563,147 @@ fn codegen_field_snippets(field: &TypeExpr) -> (String, String, String) {
                     let de = format!(
                         "{{ size_t cnt = (size_t)({count}); memcpy((*packet)->{name}, &data[pos], cnt); pos += cnt; }}\n"
                     );
-                    return (size, ser, de);
+                    (size, ser, de)
                 }
             } else {
                 let size = "total_size += 6;\n".to_string();
                 let ser = format!("memcpy(&(*data)[pos], packet->{name}, 6); pos += 6;\n");
                 let de = format!("memcpy((*packet)->{name}, &data[pos], 6); pos += 6;\n");
-                return (size, ser, de);
+                (size, ser, de)
+            }
+        }
+
+        // in_addr/in6_addr already hold network-order bytes: copy them as-is
+        Ipv4Address(len) | Ipv6Address(len) => {
+            let copy_out = |n: &str| format!("memcpy(&(*data)[pos], &packet->{n}, {width}); pos += {width};\n");
+            let copy_in = |n: &str| format!("memcpy(&(*packet)->{n}, &data[pos], {width}); pos += {width};\n");
+            match len {
+                None => (format!("total_size += {width};\n"), copy_out(name), copy_in(name)),
+                Some(expr) => {
+                    let count = match eval_len_const(expr) {
+                        Some(n) => n.to_string(),
+                        None => format!("(size_t)({})", emit_c_expr(expr)),
+                    };
+                    let elem = format!("{name}[i]");
+                    let size = format!("total_size += {count} * {width};\n");
+                    let ser = format!("for (size_t i = 0; i < {count}; ++i) {{\n  {}}}\n", copy_out(&elem));
+                    let de = format!("for (size_t i = 0; i < {count}; ++i) {{\n  {}}}\n", copy_in(&elem));
+                    (size, ser, de)
+                }
+            }
+        }
+
+        // timespec <-> 64-bit wire value through the ts_to_*/ts_from_* helpers
+        DateTime(len, unit) => {
+            let suffix = c_time_suffix(unit);
+            let write = |n: &str| {
+                format!("store_u64_be(&(*data)[pos], (uint64_t)ts_to_{suffix}(packet->{n})); pos += 8;\n")
+            };
+            let read = |n: &str| {
+                let wire = match unit {
+                    TimeEncoding::Ntp64 | TimeEncoding::FileTime => "uint64_t",
+                    _ => "int64_t",
+                };
+                format!("(*packet)->{n} = ts_from_{suffix}(({wire})load_u64_be(&data[pos])); pos += 8;\n")
+            };
+            match len {
+                None => ("total_size += 8;\n".to_string(), write(name), read(name)),
+                Some(expr) => {
+                    let count = match eval_len_const(expr) {
+                        Some(n) => n.to_string(),
+                        None => format!("(size_t)({})", emit_c_expr(expr)),
+                    };
+                    let elem = format!("{name}[i]");
+                    let size = format!("total_size += {count} * 8;\n");
+                    let ser = format!("for (size_t i = 0; i < {count}; ++i) {{\n  {}}}\n", write(&elem));
+                    let de = format!("for (size_t i = 0; i < {count}; ++i) {{\n  {}}}\n", read(&elem));
+                    (size, ser, de)
+                }
             }
         }
 
-        // All other numeric/floating/date types:
+        // double <-> raw integer scaled by 2^frac; llround breaks ties away from zero
+        Fixed(len, base, frac) => {
+            let w = base.width_bytes();
+            let (signed, unsigned) = c_fixed_types(base);
+            let scale = format!("{}.0", 1u128 << frac);
+            let write = |n: &str| {
+                let raw = if signed == unsigned {
+                    format!("({unsigned})llround(packet->{n} * {scale})")
+                } else {
+                    format!("({unsigned})({signed})llround(packet->{n} * {scale})")
+                };
+                match w {
+                    1 => format!("(*data)[pos] = {raw}; pos += 1;\n"),
+                    _ => format!("store_u{}_be(&(*data)[pos], {raw}); pos += {w};\n", w * 8),
+                }
+            };
+            let read = |n: &str| {
+                let raw = match w {
+                    1 => "data[pos]".to_string(),
+                    _ => format!("load_u{}_be(&data[pos])", w * 8),
+                };
+                format!("(*packet)->{n} = (double)({signed}){raw} / {scale}; pos += {w};\n")
+            };
+            match len {
+                None => (format!("total_size += {w};\n"), write(name), read(name)),
+                Some(expr) => {
+                    let count = match eval_len_const(expr) {
+                        Some(n) => n.to_string(),
+                        None => format!("(size_t)({})", emit_c_expr(expr)),
+                    };
+                    let elem = format!("{name}[i]");
+                    let size = format!("total_size += {count} * {w};\n");
+                    let ser = format!("for (size_t i = 0; i < {count}; ++i) {{\n  {}}}\n", write(&elem));
+                    let de = format!("for (size_t i = 0; i < {count}; ++i) {{\n  {}}}\n", read(&elem));
+                    (size, ser, de)
+                }
+            }
+        }
+
+        // Variable-length: size is summed per value, pos advances by what was written
+        VarInt(len, enc) => {
+            let (size_fn, store_fn, load_fn, span_fn) = match enc {
+                VarIntEncoding::Quic => ("quicvarint_size", "store_quicvarint", "load_quicvarint", "quicvarint_span"),
+                _ => ("varuint_size", "store_varuint", "load_varuint", "varuint_span"),
+            };
+            let wrap = |n: &str| match enc {
+                VarIntEncoding::ZigZag => format!("zigzag_encode(packet->{n})"),
+                _ => format!("packet->{n}"),
+            };
+            let size = |n: &str| format!("total_size += {size_fn}({});
+", wrap(n));
+            let write = |n: &str| format!("pos += {store_fn}(&(*data)[pos], {});
+", wrap(n));
+            // each value is checked to end before the bound, then loaded
+            let read = |n: &str| {
+                let load = match enc {
+                    VarIntEncoding::ZigZag => format!("(*packet)->{n} = zigzag_decode({load_fn}(data, &pos));
+"),
+                    _ => format!("(*packet)->{n} = {load_fn}(data, &pos);
+"),
+                };
+                format!("{}{load}", truncated(&format!("!{span_fn}(&data[pos], len - pos)")))
+            };
+            match len {
+                None => (size(name), write(name), read(name)),
+                Some(expr) => {
+                    let count = match eval_len_const(expr) {
+                        Some(n) => n.to_string(),
+                        None => format!("(size_t)({})", emit_c_expr(expr)),
+                    };
+                    let elem = format!("{name}[i]");
+                    let each = |f: &dyn Fn(&str) -> String| {
+                        format!("for (size_t i = 0; i < {count}; ++i) {{
+  {}}}
+", f(&elem))
+                    };
+                    (each(&size), each(&write), each(&read))
+                }
+            }
+        }
+
+        // All other numeric/floating types; bools are 0/1 bytes:
         Integer8(len)
         | UnsignedInteger8(len)
         | Integer16(len)
@@ -189,7 +714,8 @@ fn codegen_field_snippets(field: &TypeExpr) -> (String, String, String) {
         | UnsignedInteger64(len)
         | Float32(len)
         | Float64(len)
-        | DateTime(len) => {
+        | Bool(len, _)
+        | Port(len) => {
             match len {
                 None => {
                     // Scalar
@@ -235,6 +761,139 @@ fn codegen_field_snippets(field: &TypeExpr) -> (String, String, String) {
     }
 }
 
+fn string_encoding(t: &TypeNode) -> Option<&StringEncoding> {
+    match t {
+        TypeNode::FixedString(_, e) | TypeNode::CString(e) | TypeNode::PString(_, e) => Some(e),
+        _ => None,
+    }
+}
+
+/// In-memory element type: `char` strings for ascii/utf8, `uint16_t` code units for utf16le.
+fn c_string_type(e: &StringEncoding) -> &'static str {
+    match e {
+        StringEncoding::Utf16Le => "uint16_t",
+        _ => "char",
+    }
+}
+
+fn code_unit(e: &StringEncoding) -> usize {
+    match e {
+        StringEncoding::Utf16Le => 2,
+        _ => 1,
+    }
+}
+
+fn c_decoder(e: &StringEncoding) -> &'static str {
+    match e {
+        StringEncoding::Ascii => "decode_ascii",
+        StringEncoding::Utf8 => "decode_utf8",
+        StringEncoding::Utf16Le => "decode_utf16le",
+    }
+}
+
+/// Encoded byte length of a string field, without terminator.
+fn c_string_bytes(name: &str, e: &StringEncoding) -> String {
+    match e {
+        StringEncoding::Utf16Le => format!("utf16_len(packet->{name}) * 2"),
+        _ => format!("str_bytes(packet->{name})"),
+    }
+}
+
+/// Copies `len` encoded bytes of a string field to `(*data)[pos]`.
+fn c_string_write(name: &str, e: &StringEncoding, len: &str) -> String {
+    match e {
+        StringEncoding::Utf16Le => format!("encode_utf16le(&(*data)[pos], packet->{name}, {len} / 2);"),
+        _ => format!("memcpy(&(*data)[pos], packet->{name}, {len});"),
+    }
+}
+
+/// A value that cannot be encoded frees the buffer and leaves NULL behind.
+fn encode_failure(cond: &str) -> String {
+    format!("if ({cond}) {{ free(*data); *data = NULL; return; }}\n")
+}
+
+/// Signed and unsigned C integer types of a fixed-point base; the raw value
+/// goes through the signed one so negative values keep their sign.
+fn c_fixed_types(base: &FixedBase) -> (&'static str, &'static str) {
+    match base {
+        FixedBase::Int8 => ("int8_t", "uint8_t"),
+        FixedBase::UInt8 => ("uint8_t", "uint8_t"),
+        FixedBase::Int16 => ("int16_t", "uint16_t"),
+        FixedBase::UInt16 => ("uint16_t", "uint16_t"),
+        FixedBase::Int32 => ("int32_t", "uint32_t"),
+        FixedBase::UInt32 => ("uint32_t", "uint32_t"),
+        FixedBase::Int64 => ("int64_t", "uint64_t"),
+        FixedBase::UInt64 => ("uint64_t", "uint64_t"),
+    }
+}
+
+/// Suffix of the `ts_to_*` / `ts_from_*` support helpers for an encoding.
+fn c_time_suffix(unit: &TimeEncoding) -> &'static str {
+    match unit {
+        TimeEncoding::UnixSeconds => "unix_s",
+        TimeEncoding::UnixMillis => "unix_ms",
+        TimeEncoding::UnixMicros => "unix_us",
+        TimeEncoding::Ntp64 => "ntp64",
+        TimeEncoding::FileTime => "filetime",
+    }
+}
+
+/// Decoding stops with 0 and a NULL packet when `cond` says the input ran out.
+fn truncated(cond: &str) -> String {
+    format!("if ({cond}) {{ free(*packet); *packet = NULL; return 0; }}\n")
+}
+
+/// Bounds check ahead of a fixed-width field: its bytes must all be before `len`.
+/// A count that reads this packet's fields sees them through `*packet`, which
+/// holds what has been decoded so far. Strings, varints and repeats check
+/// themselves as they read, and a `bool(bit)` run only checks its first byte.
+fn read_guard(field: &TypeExpr, slot: Option<&BitSlot>, owner: &str) -> String {
+    use TypeNode::*;
+    if let Some(slot) = slot {
+        return if slot.starts_byte() { truncated("len - pos < 1") } else { String::new() };
+    }
+    let (width, count) = match &field.expr {
+        MacAddress(None) => (6, None),
+        MacAddress(Some(e)) | Bytes(Some(e)) => (1, Some(e)),
+        PString(_, _) => (scalar_width_bytes(&field.expr), None),
+        Pad(n) => (*n, None),
+        Align(n) => return truncated(&format!("(pos + {n} - 1) / {n} * {n} > len")),
+        Integer8(len)
+        | UnsignedInteger8(len)
+        | Integer16(len)
+        | UnsignedInteger16(len)
+        | Integer32(len)
+        | UnsignedInteger32(len)
+        | Integer64(len)
+        | UnsignedInteger64(len)
+        | Float32(len)
+        | Float64(len)
+        | DateTime(len, _)
+        | Bool(len, _)
+        | Fixed(len, _, _)
+        | Ipv4Address(len)
+        | Ipv6Address(len)
+        | Port(len) => (scalar_width_bytes(&field.expr), len.as_ref()),
+        Bytes(None) | FixedString(_, _) | CString(_) | VarInt(_, _) | Repeat(_, _) => return String::new(),
+    };
+    match count {
+        None => truncated(&format!("len - pos < {width}")),
+        Some(e) => match eval_len_const(e) {
+            Some(n) => truncated(&format!("len - pos < {}", n * width)),
+            None => format!(
+                "{{ size_t cnt; {{ const {owner}* _self = *packet; const {owner}* packet = _self; cnt = (size_t)({}); }}\n\
+                 \t{}}}\n",
+                emit_c_expr(e),
+                truncated(&format!("(len - pos) / {width} < cnt"))
+            ),
+        },
+    }
+}
+
+fn decode_failure(name: &str) -> String {
+    format!("if (!(*packet)->{name}) {{ free(*packet); *packet = NULL; return 0; }}\n")
+}
+
 fn ser_elem(name: &str, width: usize, idx: &str) -> String {
     match width {
         1 => format!("(*data)[pos++] = (uint8_t)(packet->{name}[{idx}]);\n"),
@@ -269,21 +928,38 @@ fn c_scalar_type(t: &TypeNode) -> &'static str {
         Integer64(_) => "int64_t",
         Float32(_) => "float",
         Float64(_) => "double",
-        DateTime(_) => "uint64_t",  // represent as epoch micros/nanos etc.
+        DateTime(_, _) => "struct timespec",
+        Bool(_, _) => "bool",
+        Fixed(_, _, _) => "double",
+        VarInt(_, VarIntEncoding::ZigZag) => "int64_t",
+        VarInt(_, _) => "uint64_t",
         MacAddress(_) => "uint8_t", // special-case array_decl below
         Bytes(_) => "uint8_t",
+        Ipv4Address(_) => "struct in_addr",
+        Ipv6Address(_) => "struct in6_addr",
+        Port(_) => "uint16_t", // host order in memory
+        FixedString(_, e) | CString(e) | PString(_, e) => c_string_type(e), // see c_field_decl
+        Repeat(_, _) => "uint8_t",                                          // see c_field_decl
+        Pad(_) | Align(_) => "uint8_t",                                     // never stored
     }
 }
 
 fn scalar_width_bytes(t: &TypeNode) -> usize {
     use TypeNode::*;
     match t {
-        UnsignedInteger8(_) | Integer8(_) => 1,
-        UnsignedInteger16(_) | Integer16(_) => 2,
-        UnsignedInteger32(_) | Integer32(_) | Float32(_) => 4,
-        UnsignedInteger64(_) | Integer64(_) | Float64(_) | DateTime(_) => 8,
+        UnsignedInteger8(_) | Integer8(_) | Bool(_, _) => 1,
+        UnsignedInteger16(_) | Integer16(_) | Port(_) => 2,
+        UnsignedInteger32(_) | Integer32(_) | Float32(_) | Ipv4Address(_) => 4,
+        UnsignedInteger64(_) | Integer64(_) | Float64(_) | DateTime(_, _) => 8,
+        Ipv6Address(_) => 16,
+        Fixed(_, base, _) => base.width_bytes(),
         MacAddress(_) => 1, // element width for array_decl; total handled separately where needed
         Bytes(_) => 1,
+        FixedString(_, _) | CString(_) => 1,
+        PString(_, _) => t.scalar_width_bytes(), // the length prefix
+        VarInt(_, _) => 0,                       // variable, sized per value
+        Repeat(_, _) => 0,                       // sized per element
+        Pad(_) | Align(_) => 1,
     }
 }
 
@@ -312,6 +988,7 @@ fn array_decl(t: &TypeNode) -> String {
                 String::from("*")
             }
         }
+        FixedString(_, _) | CString(_) | PString(_, _) | Repeat(_, _) | Pad(_) | Align(_) => String::new(), // see c_field_decl
         Integer8(len)
         | UnsignedInteger8(len)
         | Integer16(len)
@@ -322,7 +999,13 @@ fn array_decl(t: &TypeNode) -> String {
         | UnsignedInteger64(len)
         | Float32(len)
         | Float64(len)
-        | DateTime(len) => {
+        | DateTime(len, _)
+        | Bool(len, _)
+        | Fixed(len, _, _)
+        | VarInt(len, _)
+        | Ipv4Address(len)
+        | Ipv6Address(len)
+        | Port(len) => {
             match len {
                 None => String::new(),
                 Some(expr) => {
@@ -360,6 +1043,14 @@ fn eval_i128(e: &ExprNode) -> Option<i128> {
                 Some(eval_i128(a)? / d)
             }
         }
+        Mod(a, b) => {
+            let d = eval_i128(b)?;
+            if d == 0 {
+                None
+            } else {
+                Some(eval_i128(a)? % d)
+            }
+        }
         Pow(a, b) => {
             let base = eval_i128(a)?;
             let exp = eval_i128(b)?;
@@ -368,6 +1059,12 @@ fn eval_i128(e: &ExprNode) -> Option<i128> {
             }
             Some(ipow_i128(base, exp as u32)?)
         }
+        Negate(a) => eval_i128(a)?.checked_neg(),
+        BitAnd(a, b) => Some((eval_i128(a)? as i64 & eval_i128(b)? as i64) as i128),
+        BitOr(a, b) => Some((eval_i128(a)? as i64 | eval_i128(b)? as i64) as i128),
+        BitNot(a) => Some(!(eval_i128(a)? as i64) as i128),
+        ShiftLeft(a, b) => Some((eval_i128(a)? as i64).wrapping_shl((eval_i128(b)? & 63) as u32) as i128),
+        ShiftRight(a, b) => Some((eval_i128(a)? as i64).wrapping_shr((eval_i128(b)? & 63) as u32) as i128),
         // ternary/booleans unsupported for constant in general
         GuardExpression(_, _, _) => None,
         Gt(_, _)
@@ -377,9 +1074,10 @@ fn eval_i128(e: &ExprNode) -> Option<i128> {
         | Equals(_, _)
         | NotEquals(_, _)
         | And(_, _)
-        | Or(_, _) => None,
+        | Or(_, _)
+        | Not(_) => None,
+        ActivationRecord(name, args) => eval_intrinsic(name, args, eval_i128),
         ValueReference(_, _)
-        | ActivationRecord(_, _)
         | AggregateSum(_)
         | AggregateProduct(_)
         | NoExpr => None,
@@ -414,15 +1112,25 @@ fn emit_c_expr(e: &ExprNode) -> String {
             }
         }
         ActivationRecord(fname, args) => {
+            let args_s: Vec<String> = args.iter().map(emit_c_expr).collect();
+            match (fname.as_str(), &args[..]) {
+                ("clamp", _) => return format!("fmin(fmax({}, {}), {})", args_s[0], args_s[1], args_s[2]),
+                ("pow", [a, b]) => return emit_c_expr(&Pow(Box::new(a.clone()), Box::new(b.clone()))),
+                ("popcount", _) => return format!("popcount_u64((uint64_t)({}))", args_s[0]),
+                ("bswap16", _) => return format!("bswap_u16((uint16_t)({}))", args_s[0]),
+                ("bswap32", _) => return format!("bswap_u32((uint32_t)({}))", args_s[0]),
+                ("bswap64", _) => return format!("bswap_u64((uint64_t)({}))", args_s[0]),
+                _ => {}
+            }
             let mapped = match fname.as_str() {
                 "sqrt" => "sqrt",
                 "min" => "fmin",
                 "max" => "fmax",
+                "abs" => "fabs",
+                // floor, ceil, round and log2 are math.h's
                 _ => fname,
             };
 
-            let args_s: Vec<String> = args.iter().map(|arg| emit_c_expr(arg)).collect();
-
             format!("{}({})", mapped, args_s.join(", "))
         }
         AggregateSum(id) => format!("0/*sumof({}) unsupported here*/", id),
@@ -438,6 +1146,14 @@ fn emit_c_expr(e: &ExprNode) -> String {
         Minus(a, b) => format!("({}) - ({})", emit_c_expr(a), emit_c_expr(b)),
         Mult(a, b) => format!("({}) * ({})", emit_c_expr(a), emit_c_expr(b)),
         Div(a, b) => format!("({}) / ({})", emit_c_expr(a), emit_c_expr(b)),
+        Mod(a, b) => format!("(int64_t)({}) % (int64_t)({})", emit_c_expr(a), emit_c_expr(b)),
+        Negate(a) => format!("-({})", emit_c_expr(a)),
+        // bitwise ops work on 64-bit values; shift left via uint64_t to avoid signed overflow
+        BitAnd(a, b) => format!("(int64_t)({}) & (int64_t)({})", emit_c_expr(a), emit_c_expr(b)),
+        BitOr(a, b) => format!("(int64_t)({}) | (int64_t)({})", emit_c_expr(a), emit_c_expr(b)),
+        BitNot(a) => format!("~(int64_t)({})", emit_c_expr(a)),
+        ShiftLeft(a, b) => format!("(int64_t)((uint64_t)({}) << ((int64_t)({}) & 63))", emit_c_expr(a), emit_c_expr(b)),
+        ShiftRight(a, b) => format!("(int64_t)({}) >> ((int64_t)({}) & 63)", emit_c_expr(a), emit_c_expr(b)),
         Pow(a, b) => format!(
             "ipow_u64((uint64_t)({}), (uint64_t)({}))",
             emit_c_expr(a),
@@ -451,6 +1167,7 @@ fn emit_c_expr(e: &ExprNode) -> String {
         NotEquals(a, b) => format!("({}) != ({})", emit_c_expr(a), emit_c_expr(b)),
         And(a, b) => format!("({}) && ({})", emit_c_expr(a), emit_c_expr(b)),
         Or(a, b) => format!("({}) || ({})", emit_c_expr(a), emit_c_expr(b)),
+        Not(a) => format!("!({})", emit_c_expr(a)),
         NoExpr => "0".to_string(),
     }
 }
@@ -468,3 +1185,172 @@ fn c_escape(s: &str) -> String {
         .collect()
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::load_source;
+
+    fn c(src: &str) -> String {
+        CGenerator::generate(&load_source(src))
+    }
+
+    #[test]
+    fn shift_counts_use_the_low_six_bits() {
+        let out = c("packet Bits { n uint8, hi bytes[1 << n], lo bytes[256 >> n], k bytes[1 << 65] }");
+        assert!(out.contains("(size_t)((int64_t)((uint64_t)(1) << ((int64_t)(packet->n) & 63)))"));
+        assert!(out.contains("(size_t)((int64_t)(256) >> ((int64_t)(packet->n) & 63))"));
+        // constant lengths fold with the same rule
+        assert!(out.contains("    uint8_t k[2];\n"));
+    }
+
+    #[test]
+    fn strings_that_do_not_fit_are_rejected() {
+        let out = c("packet Names be { tag string[4], short pstring<uint8>, long pstring<uint16> }");
+        assert!(out.contains(
+            "{ size_t cnt = 4; size_t len = str_bytes(packet->tag); if (len > cnt) { free(*data); *data = NULL; return; }\n"
+        ));
+        assert!(out.contains("if (len > UINT8_MAX) { free(*data); *data = NULL; return; }"));
+        assert!(out.contains("if (len > UINT16_MAX) { free(*data); *data = NULL; return; }"));
+    }
+
+    #[test]
+    fn addresses_are_copied_in_network_order() {
+        let out = c("packet Net le { src ipv4, dst ipv6, sport port }");
+        assert!(out.contains("    struct in_addr src;\n\n    struct in6_addr dst;\n\n    uint16_t sport;\n"));
+        assert!(out.contains("memcpy(&(*data)[pos], &packet->src, 4); pos += 4;"));
+        assert!(out.contains("memcpy(&(*packet)->dst, &data[pos], 16); pos += 16;"));
+        assert!(out.contains("(*packet)->sport = (uint16_t)load_u16_be(&data[pos]); pos += 2;"));
+    }
+
+    #[test]
+    fn datetimes_are_timespecs() {
+        let out = c("packet T be { a datetime, c datetime(ntp64) }");
+        assert!(out.contains("    struct timespec a;\n"));
+        assert!(out.contains("store_u64_be(&(*data)[pos], (uint64_t)ts_to_unix_s(packet->a)); pos += 8;"));
+        assert!(out.contains("(*packet)->c = ts_from_ntp64((uint64_t)load_u64_be(&data[pos])); pos += 8;"));
+        assert!(out.contains("static inline uint64_t ts_to_filetime(struct timespec t) {"));
+    }
+
+    #[test]
+    fn bit_fields_share_a_byte() {
+        let out = c("packet F be { on bool, a bool(bit), b bool(bit), n uint8, gain fixed<int16, 8> }");
+        assert!(out.contains(
+            "(*data)[pos] |= (uint8_t)((packet->a ? 1u : 0u) << 7);\n(*data)[pos] |= (uint8_t)((packet->b ? 1u : 0u) << 6); pos += 1;\n"
+        ));
+        assert!(out.contains("(*packet)->a = (data[pos] >> 7) & 1u;\n(*packet)->b = (data[pos] >> 6) & 1u; pos += 1;\n"));
+        assert!(out.contains("store_u16_be(&(*data)[pos], (uint16_t)(int16_t)llround(packet->gain * 256.0)); pos += 2;"));
+        assert!(out.contains("(*packet)->gain = (double)(int16_t)load_u16_be(&data[pos]) / 256.0; pos += 2;"));
+    }
+
+    #[test]
+    fn varints_size_then_store() {
+        let out = c("packet V be { a varuint, b varint, c quicvarint }");
+        assert!(out.contains("total_size += varuint_size(zigzag_encode(packet->b));"));
+        assert!(out.contains("pos += store_quicvarint(&(*data)[pos], packet->c);"));
+        assert!(out.contains("(*packet)->b = zigzag_decode(load_varuint(data, &pos));"));
+        // 1, 2, 4 and 8 byte forms switch at 2^6, 2^14 and 2^30
+        assert!(out.contains("return v < 0x40 ? 1 : v < 0x4000 ? 2 : v < 0x40000000 ? 4 : 8;"));
+        assert!(out.contains("return ((uint64_t)v << 1) ^ (uint64_t)(v >> 63);"));
+    }
+
+    #[test]
+    fn repeat_fields() {
+        let out = c("packet Entry { id uint8, last uint8 }
+            packet List { count uint8, items repeat Entry[count], tail repeat Entry until(last != 0) }");
+        assert!(out.contains("    Entry* items;\n    size_t items_count;"));
+        assert!(out.contains(
+            "size_t cnt; { const List* _self = *packet; const List* packet = _self; cnt = (size_t)(packet->count); }\n\
+             for (size_t k = 0; k < cnt; ++k) {"
+        ));
+        assert!(out.contains("size_t used = deserialize_Entry_bounded(&item, &data[pos], len - pos, verbose);"));
+        assert!(out.contains("{ const Entry* packet = last; if ((packet->last) != (0)) break; }"));
+    }
+
+    #[test]
+    fn truncated_input_fails_instead_of_reading_past_len() {
+        let out = c("packet T be { a uint16, n uint8, on bool(bit), tag string[4], z cstring, p pstring<uint8>, v varuint, q quicvarint, m uint32[n] }");
+        let fail = "{ free(*packet); *packet = NULL; return 0; }";
+        assert!(out.contains(&format!("if (len - pos < 2) {fail}\n(*packet)->a = ")));
+        assert!(out.contains(&format!("if (len - pos < 1) {fail}\n(*packet)->on = ")));
+        assert!(out.contains(&format!("{{ size_t cnt = 4;\n\tif (len - pos < cnt) {fail}")));
+        assert!(out.contains(&format!(
+            "{{ size_t n = nul_offset(&data[pos], len - pos, 1);\n\tif (len - pos - n < 1) {fail}"
+        )));
+        assert!(out.contains(&format!("{{ size_t n = data[pos]; pos += 1;\n\tif (len - pos < n) {fail}")));
+        assert!(out.contains(&format!("if (!varuint_span(&data[pos], len - pos)) {fail}\n(*packet)->v = ")));
+        assert!(out.contains(&format!("if (!quicvarint_span(&data[pos], len - pos)) {fail}\n(*packet)->q = ")));
+        // a count read from the packet is checked against the bytes left before the loop
+        assert!(out.contains(&format!(
+            "{{ size_t cnt; {{ const T* _self = *packet; const T* packet = _self; cnt = (size_t)(packet->n); }}\n\tif ((len - pos) / 4 < cnt) {fail}\n}}"
+        )));
+        assert!(!out.contains("(void)len;"));
+    }
+
+    #[test]
+    fn within_blocks_fail_instead_of_overrunning() {
+        let out = c("packet W be { n uint8, within(n) { a uint16 }, within(2, skip) { b uint8 } }");
+        let fail = "{ free(*packet); *packet = NULL; return 0; }";
+        assert!(out.contains(&format!(
+            "{{ size_t _end0, _outer0 = len;\n\
+             {{ const W* _self = *packet; const W* packet = _self; _end0 = (size_t)(packet->n); }}\n\
+             if (_end0 > len - pos) {fail}\n_end0 += pos; len = _end0;\n"
+        )));
+        // reads inside the block check against its end, not the packet's
+        assert!(out.contains(&format!("len = _end0;\nif (len - pos < 2) {fail}\n(*packet)->a = ")));
+        assert!(out.contains(&format!("if (pos != _end0) {fail}\npos = _end0; len = _outer0; }}")));
+        assert!(out.contains(&format!("if (pos > _end1) {fail}\npos = _end1; len = _outer1; }}")));
+    }
+
+    #[test]
+    fn repeat_counts_come_from_the_element_count() {
+        let out = c("packet E { x uint8 } packet M { n uint8, items repeat E[n] }");
+        assert!(out.contains("M _copy = *packet; _copy.n = (uint8_t)(packet->items_count); packet = &_copy;"));
+    }
+
+    #[test]
+    fn bad_magic_fails_the_decode() {
+        let out = c("packet Flap be { start uint8 = 0x2a, v uint16 }");
+        assert!(out.contains("#define FLAP_START 0x2a\n"));
+        assert!(out.contains("(*data)[pos] = (uint8_t)(FLAP_START); pos += 1;"));
+        assert!(out.contains("if (start != FLAP_START) { free(*packet); *packet = NULL; return 0; } }"));
+    }
+
+    #[test]
+    fn optional_fields_have_presence_bits() {
+        let out = c("packet Opt be { flags uint16, extra uint32 if (flags & 0x8000) != 0 }");
+        assert!(out.contains("    uint32_t extra;\n    unsigned has_extra : 1;\n"));
+        assert!(out.contains(
+            "if (((int64_t)(packet->flags) & (int64_t)(32768)) != (0)) {\n\
+             store_u32_be(&(*data)[pos], (uint32_t)(packet->extra)); pos += 4;\n}\n"
+        ));
+        assert!(out.contains("(*packet)->has_extra = 0;\n"));
+        assert!(out.contains(
+            "(*packet)->extra = (uint32_t)load_u32_be(&data[pos]); pos += 4;\n(*packet)->has_extra = 1; } }\n"
+        ));
+        // the bytes left are checked only when the field is present
+        assert!(out.contains(
+            "if (_on) {\nif (len - pos < 4) { free(*packet); *packet = NULL; return 0; }\n(*packet)->extra = "
+        ));
+    }
+
+    #[test]
+    fn fillers_are_zeros_and_skipped() {
+        let out = c("packet F be { v uint8, pad(3), n uint32, reserved uint16, align(8) }");
+        assert!(out.contains("typedef struct F {\n\n    uint8_t v;\n\n    uint32_t n;\n\n} F;"));
+        assert!(out.contains("total_size += 3;\n"));
+        assert!(out.contains("total_size = (total_size + 8 - 1) / 8 * 8;\n"));
+        let fail = "{ free(*packet); *packet = NULL; return 0; }";
+        assert!(out.contains(&format!("if (len - pos < 3) {fail}\npos += 3;\n")));
+        assert!(out.contains(&format!("if (len - pos < 2) {fail}\npos += 2;\n")));
+        assert!(out.contains(&format!("if ((pos + 8 - 1) / 8 * 8 > len) {fail}\npos = (pos + 8 - 1) / 8 * 8;\n")));
+    }
+    #[test]
+    fn checksums_are_filled_on_encode_and_checked_on_decode() {
+        let out = c("packet Sum be { a uint16, b uint8, sum uint16 = inet_checksum(a..b) }");
+        assert!(out.contains("store_u16_be(&(*data)[_sum_at], sum_inet_checksum(*data, _a_start, _b_end, 0, 0));"));
+        assert!(out.contains(
+            "if (load_u16_be(&data[_sum_at]) != sum_inet_checksum(data, _a_start, _b_end, _sum_at, 2)) { free(*packet); *packet = NULL; return 0; }"
+        ));
+    }
+}
@@ -1,5 +1,6 @@
-use crate::models::codegen_models::TwoStringValue;
-use crate::models::parsing_models::{Endianness, ExprNode, PacketExpr, PacketExprList, TypeNode};
+use crate::models::parsing_models::{
+    Endianness, ExprNode, PacketExpr, PacketExprList, TypeNode, VarIntEncoding, eval_intrinsic,
+};
 use crate::utilities::capitalize_first;
 use serde::{Deserialize, Serialize};
 use tera::{Context, Tera};
@@ -8,13 +9,23 @@ use tera::{Context, Tera};
 pub struct GoPacketValue {
     pub name: String,                // Struct name (already exported/capitalized at template time)
     pub endian: String,              // "binary.LittleEndian" | "binary.BigEndian" | ""
-    pub types: Vec<TwoStringValue>,  // value1 = FieldName (Exported), value2 = Go type string
+    pub doc: Vec<String>,            // `///` lines from the DSL, markers stripped
+    pub types: Vec<GoFieldValue>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GoFieldValue {
+    pub name: String,     // Exported field name
+    pub go_type: String,  // Go type string
+    pub doc: Vec<String>, // `///` lines from the DSL, markers stripped
+    pub value: Option<String>, // const fields become `const <Packet><Field>` instead of a member
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GoPacketRenderContext {
     pub package_name: String,
     pub uses_time: bool,
+    pub uses_netip: bool,
     pub packets: Vec<GoPacketValue>,
 }
 
@@ -51,28 +62,42 @@ impl GoGenerator {
                     Some(Endianness::Be) => "binary.BigEndian".to_string(),
                     None => "".to_string(),
                 },
+                doc: doc_lines(&packet.doc),
                 types,
             });
         }
 
+        let uses_netip = packet_list
+            .packets
+            .iter()
+            .flat_map(|p| &p.fields)
+            .any(|f| matches!(f.expr, TypeNode::Ipv4Address(_) | TypeNode::Ipv6Address(_)));
+
         GoPacketRenderContext {
-            package_name: "packets".to_string(), // change if you want a different package name
+            package_name: go_package_name(packet_list),
             uses_time: uses_time_any,
+            uses_netip,
             packets: value_vec,
         }
     }
 
-    /// Returns (Vec<TwoStringValue>, uses_time)
-    pub fn get_go_types(packet_expr: &PacketExpr) -> (Vec<TwoStringValue>, bool) {
-        let mut str_vec = Vec::<TwoStringValue>::new();
+    /// Returns (Vec<GoFieldValue>, uses_time)
+    pub fn get_go_types(packet_expr: &PacketExpr) -> (Vec<GoFieldValue>, bool) {
+        let mut str_vec = Vec::<GoFieldValue>::new();
         let mut uses_time = false;
 
-        for field in &packet_expr.fields {
+        for field in packet_expr.fields.iter().filter(|f| !matches!(f.expr, TypeNode::Align(_))) {
             let (go_type, field_uses_time) = map_type_node_to_go(&field.expr);
             uses_time |= field_uses_time;
-
-            let name = capitalize_first(field.id.clone());
-            let value = TwoStringValue { value1: name, value2: go_type };
+            // optional fields are nil when absent; a slice is nil already
+            let go_type = if field.condition.is_some() && !go_type.starts_with("[]") {
+                format!("*{go_type}")
+            } else {
+                go_type
+            };
+
+            let name = if field.is_filler() { "_".to_string() } else { capitalize_first(field.id.clone()) };
+            let value = GoFieldValue { name, go_type, doc: doc_lines(&field.doc), value: field.const_literal() };
             str_vec.push(value);
         }
 
@@ -91,6 +116,9 @@ package {{ package_name }}
 
 import (
     "encoding/binary"
+    {%- if uses_netip %}
+    "net/netip"
+    {%- endif %}
     {%- if uses_time %}
     "time"
     {%- endif %}
@@ -99,12 +127,27 @@ import (
 {#- Emit one struct per packet -#}
 {%- for p in packets %}
 
-// {{ p.name }} (endianness: {{- if p.endian != "" -}} {{ p.endian }} {{- else -}} unspecified {{- endif -}})
+// {{ p.name }} (endianness: {% if p.endian != "" %}{{ p.endian }}{% else %}unspecified{% endif %})
+{%- if p.doc %}
+//
+{%- for line in p.doc %}
+//{% if line %} {{ line }}{% endif %}
+{%- endfor %}
+{%- endif %}
 type {{ p.name }} struct {
-{%- for t in p.types %}
-    {{ t.value1 }} {{ t.value2 }}
+{%- for t in p.types %}{% if not t.value %}
+{%- for line in t.doc %}
+    //{% if line %} {{ line }}{% endif %}
 {%- endfor %}
+    {{ t.name }} {{ t.go_type }}
+{%- endif %}{% endfor %}
 }
+{%- for t in p.types %}{% if t.value %}
+{% for line in t.doc %}
+//{% if line %} {{ line }}{% endif %}
+{%- endfor %}
+const {{ p.name }}{{ t.name }} {{ t.go_type }} = {{ t.value }}
+{%- endif %}{% endfor %}
 
 {%- endfor %}
 "#;
@@ -113,6 +156,27 @@ type {{ p.name }} struct {
 // Mapping helpers
 // ===================================================
 
+/// Go package names are a single lowercase word: `oscar.v1` -> `oscarv1`.
+fn go_package_name(packet_list: &PacketExprList) -> String {
+    let segments = packet_list.package_segments();
+    if segments.is_empty() {
+        return "packets".to_string();
+    }
+    segments
+        .concat()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Splits DSL `///` docs into lines for `//` comments in the template.
+fn doc_lines(doc: &Option<String>) -> Vec<String> {
+    doc.as_deref()
+        .map(|d| d.lines().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
 fn map_type_node_to_go(t: &TypeNode) -> (String, bool) {
     use TypeNode::*;
 
@@ -143,7 +207,15 @@ fn map_type_node_to_go(t: &TypeNode) -> (String, bool) {
         Float64(len)           => (array_or_slice("float64", len), false),
 
         // Represent datetime in Go as time.Time
-        DateTime(len)          => (array_or_slice("time.Time", len), true),
+        DateTime(len, _)       => (array_or_slice("time.Time", len), true),
+
+        // bool(bit) packs into shared bytes on the wire; in memory it is a plain bool
+        Bool(len, _)           => (array_or_slice("bool", len), false),
+        // fixed<base, n>: raw integer / 2^n
+        Fixed(len, _, _)       => (array_or_slice("float64", len), false),
+        // varints decode to 64-bit integers; varint is signed (zigzag)
+        VarInt(len, VarIntEncoding::ZigZag) => (array_or_slice("int64", len), false),
+        VarInt(len, _)         => (array_or_slice("uint64", len), false),
 
         // MacAddress defaults to [6]byte if no length; else [N]byte or []byte
         MacAddress(len_opt) => {
@@ -159,6 +231,10 @@ fn map_type_node_to_go(t: &TypeNode) -> (String, bool) {
             }
         }
 
+        // filler: a blank field keeps `pad` and `reserved` in the layout; `align` has no fixed width
+        Pad(n) => (format!("[{n}]byte"), false),
+        Align(_) => (String::new(), false),
+
         // bytes -> [N]byte if const N, else []byte
         Bytes(len_opt) => {
             match len_opt {
@@ -172,6 +248,16 @@ fn map_type_node_to_go(t: &TypeNode) -> (String, bool) {
                 }
             }
         }
+
+        // Addresses are network-order on the wire whatever the packet endianness
+        Ipv4Address(len) | Ipv6Address(len) => (array_or_slice("netip.Addr", len), false),
+        Port(len) => (array_or_slice("uint16", len), false),
+
+        // Go strings are UTF-8; other encodings are converted at the wire boundary
+        FixedString(_, _) | CString(_) | PString(_, _) => ("string".to_string(), false),
+
+        // Sub-packets; the element count is only known once decoded
+        Repeat(elem, _) => (format!("[]{}", elem), false),
     }
 }
 
@@ -198,19 +284,32 @@ fn eval_i128(e: &ExprNode) -> Option<i128> {
             let d = eval_i128(b)?;
             if d == 0 { None } else { Some(eval_i128(a)? / d) }
         }
+        Mod(a,b)   => {
+            let d = eval_i128(b)?;
+            if d == 0 { None } else { Some(eval_i128(a)? % d) }
+        }
         Pow(a,b)   => {
             let base = eval_i128(a)?;
             let exp  = eval_i128(b)?;
             if exp < 0 { return None; }
             Some(ipow_i128(base, exp as u32)?)
         }
+        Negate(a)  => eval_i128(a)?.checked_neg(),
+
+        // bitwise ops on the 64-bit value
+        BitAnd(a,b)     => Some((eval_i128(a)? as i64 & eval_i128(b)? as i64) as i128),
+        BitOr(a,b)      => Some((eval_i128(a)? as i64 | eval_i128(b)? as i64) as i128),
+        BitNot(a)       => Some(!(eval_i128(a)? as i64) as i128),
+        ShiftLeft(a,b)  => Some((eval_i128(a)? as i64).wrapping_shl((eval_i128(b)? & 63) as u32) as i128),
+        ShiftRight(a,b) => Some((eval_i128(a)? as i64).wrapping_shr((eval_i128(b)? & 63) as u32) as i128),
 
         // Treat conditionals/booleans as non-const by default (could be extended)
         GuardExpression(_,_,_) => None,
-        Gt(_,_) | Gte(_,_) | Lt(_,_) | Lte(_,_) | Equals(_,_) | NotEquals(_,_) | And(_,_) | Or(_,_) => None,
+        Gt(_,_) | Gte(_,_) | Lt(_,_) | Lte(_,_) | Equals(_,_) | NotEquals(_,_) | And(_,_) | Or(_,_) | Not(_) => None,
 
         // Not constant without runtime context:
-        ValueReference(_, _) | ActivationRecord(_, _) | AggregateSum(_) | AggregateProduct(_) | NoExpr => None,
+        ActivationRecord(name, args) => eval_intrinsic(name, args, eval_i128),
+        ValueReference(_, _) | AggregateSum(_) | AggregateProduct(_) | NoExpr => None,
     }
 }
 
@@ -0,0 +1,92 @@
+use crate::models::parsing_models::{ExternFn, PacketExpr, PacketExprList};
+
+/// Version of the exported document layout. Bump whenever the serde names in
+/// `parsing_models` change in a way consumers would notice.
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct JsonDocument<'a> {
+    format_version: u32,
+    package: Option<&'a str>,
+    packets: &'a [PacketExpr],
+    externs: &'a [ExternFn],
+}
+
+pub struct JsonGenerator {}
+
+impl JsonGenerator {
+    pub fn generate(model: &PacketExprList) -> String {
+        let document = JsonDocument {
+            format_version: FORMAT_VERSION,
+            package: model.package.as_deref(),
+            packets: &model.packets,
+            externs: &model.externs,
+        };
+        serde_json::to_string_pretty(&document).expect("packet model is always serializable")
+    }
+
+    /// JSON Schema describing the document produced by `generate`.
+    pub fn generate_schema() -> String {
+        include_str!("../../templates/json/packets.schema.json").to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::load_source;
+    use serde_json::Value;
+
+    const SOURCE: &str = "package demo.v1;
+    packet Hello be {
+        kind uint8,
+        size uint16 le,
+        body bytes[size * 2],
+        calc total uint32 = when size > 3 then sqrt(size) otherwise sumof body
+    }";
+
+    #[derive(Deserialize)]
+    struct Document {
+        format_version: u32,
+        package: Option<String>,
+        packets: Vec<PacketExpr>,
+    }
+
+    #[test]
+    fn export_reads_back_into_the_same_model() {
+        let model = load_source(SOURCE);
+        let document: Document = serde_json::from_str(&JsonGenerator::generate(&model)).unwrap();
+        assert_eq!(document.format_version, FORMAT_VERSION);
+        assert_eq!(document.package, model.package);
+        // Spans are not exported, so compare what is.
+        assert_eq!(serde_json::to_value(&document.packets).unwrap(), serde_json::to_value(&model.packets).unwrap());
+    }
+
+    #[test]
+    fn schema_names_every_exported_op_and_type() {
+        let schema: Value = serde_json::from_str(&JsonGenerator::generate_schema()).unwrap();
+        assert_eq!(schema["properties"]["format_version"]["const"], FORMAT_VERSION);
+
+        fn names(v: &Value, key: &str, out: &mut Vec<String>) {
+            match v {
+                Value::Object(map) => {
+                    if let Some(Value::String(s)) = map.get(key) {
+                        out.push(s.clone());
+                    }
+                    map.values().for_each(|v| names(v, key, out));
+                }
+                Value::Array(items) => items.iter().for_each(|v| names(v, key, out)),
+                _ => {}
+            }
+        }
+        let exported: Value = serde_json::from_str(&JsonGenerator::generate(&load_source(SOURCE))).unwrap();
+        let text = JsonGenerator::generate_schema();
+        let mut found = Vec::new();
+        names(&exported["packets"], "op", &mut found);
+        names(&exported["packets"], "type", &mut found);
+        assert!(found.len() > 10);
+        for name in found {
+            assert!(text.contains(&format!("\"{name}\"")), "{name} is missing from the schema");
+        }
+    }
+}
@@ -0,0 +1,871 @@
+use crate::models::parsing_models::{
+    BoolWidth, Check, Endianness, ExprNode, FixedBase, LengthPrefix, Leftover, PacketExpr, PacketExprList,
+    RepeatBound, StringEncoding, TimeEncoding, TypeNode, VarIntEncoding,
+};
+use std::fmt::Write as _;
+
+pub struct KaitaiGenerator {}
+
+impl KaitaiGenerator {
+    pub fn generate(model: &PacketExprList) -> String {
+        let varints: Vec<&VarIntEncoding> = model
+            .packets
+            .iter()
+            .flat_map(|p| &p.fields)
+            .filter_map(|f| match &f.expr {
+                TypeNode::VarInt(_, enc) => Some(enc),
+                _ => None,
+            })
+            .collect();
+        let mut out = String::new();
+        out.push_str(&Self::create_headers(varints.iter().any(|e| **e != VarIntEncoding::Quic)));
+
+        for pkt in &model.packets {
+            out.push_str(&Self::build_type(pkt));
+        }
+        if varints.iter().any(|e| **e == VarIntEncoding::Quic) {
+            out.push_str(QUIC_VARINT_TYPE);
+        }
+        out
+    }
+
+    fn create_headers(leb128: bool) -> String {
+        // The top-level type only hosts the packet definitions; each packet is
+        // addressable from Kaitai as `packets::<name>`.
+        let mut s = "\
+# Generated Kaitai Struct description
+meta:
+  id: packets
+  endian: le
+"
+        .to_string();
+        // LEB128 comes from the format gallery's common types
+        if leb128 {
+            s.push_str("  imports:\n    - /common/vlq_base128_le\n");
+        }
+        s.push_str("types:\n");
+        s
+    }
+
+    fn build_type(pkt: &PacketExpr) -> String {
+        let mut s = String::new();
+        let _ = writeln!(&mut s, "  {}:", ksy_identifier(&pkt.name));
+        s.push_str(&doc_key(&pkt.doc, "    "));
+        s.push_str(&endian_meta(pkt));
+
+        let blocks = Blocks::new(pkt);
+        // an assert is checked as the last field it reads, when every field it names is known
+        let mut asserts = vec![Vec::<&Check>::new(); pkt.fields.len()];
+        for check in &pkt.asserts {
+            let at = pkt
+                .fields
+                .iter()
+                .rposition(|f| !f.is_filler() && check.cond.mentions(&f.id))
+                .or_else(|| pkt.fields.iter().rposition(|f| !f.is_filler()));
+            match at {
+                Some(i) => asserts[i].push(check),
+                None => {
+                    let _ = writeln!(&mut s, "    # assert {}", check.rule);
+                }
+            }
+        }
+
+        if !pkt.fields.is_empty() {
+            let _ = writeln!(&mut s, "    seq:");
+            s.push_str(&seq_entries(pkt, &blocks, None, 0..pkt.fields.len(), &asserts));
+        }
+        let (summed, calcs): (Vec<_>, Vec<_>) =
+            pkt.calculated_fields.iter().partition(|cf| !cf.expr.checksums().is_empty());
+        for cf in summed {
+            let _ = writeln!(&mut s, "    # {} takes a checksum, which Kaitai cannot compute", cf.name);
+        }
+        let (calcs, uncomputable): (Vec<_>, Vec<_>) = calcs.into_iter().partition(|cf| unsupported(&cf.expr).is_none());
+        for cf in uncomputable {
+            let f = unsupported(&cf.expr).unwrap_or_default();
+            let _ = writeln!(&mut s, "    # {} uses {f}, which Kaitai cannot compute", cf.name);
+        }
+
+        let raw = raw_instances(pkt, &blocks, None);
+        if !calcs.is_empty() || !raw.is_empty() {
+            let _ = writeln!(&mut s, "    instances:");
+            s.push_str(&raw);
+            for cf in calcs {
+                let _ = writeln!(&mut s, "      {}:", ksy_identifier(&cf.name));
+                let _ = writeln!(&mut s, "        value: {}", yaml_quote(&emit_ksy_expr(&blocks.qualify(&cf.expr, None))));
+                s.push_str(&doc_key(&cf.doc, "        "));
+            }
+        }
+
+        // each `within` block is a type of its own, read from a substream of its length
+        for (k, region) in pkt.regions.iter().enumerate() {
+            let _ = writeln!(&mut s, "  {}:", blocks.type_name(k));
+            s.push_str(&endian_meta(pkt));
+            let _ = writeln!(&mut s, "    seq:");
+            s.push_str(&seq_entries(pkt, &blocks, Some(k), region.first..region.end, &asserts));
+            if region.leftover == Leftover::Error {
+                let _ = writeln!(&mut s, "      - id: unread");
+                let _ = writeln!(&mut s, "        size-eos: true");
+                let _ = writeln!(&mut s, "        valid:");
+                let _ = writeln!(&mut s, "          expr: '_.length == 0'");
+            }
+            let raw = raw_instances(pkt, &blocks, Some(k));
+            if !raw.is_empty() {
+                let _ = writeln!(&mut s, "    instances:");
+                s.push_str(&raw);
+            }
+        }
+        s
+    }
+}
+
+fn endian_meta(pkt: &PacketExpr) -> String {
+    match &pkt.endianness {
+        Some(e) => format!("    meta:\n      endian: {}\n", endian_suffix(e)),
+        None => String::new(),
+    }
+}
+
+/// The `within` blocks of a packet: which one each field is read in, and how an
+/// expression read in one block reaches the fields of another.
+struct Blocks<'a> {
+    pkt: &'a PacketExpr,
+    paths: Vec<(&'a str, Vec<usize>)>, // field or calc name, blocks around it outermost first
+}
+
+impl<'a> Blocks<'a> {
+    fn new(pkt: &'a PacketExpr) -> Self {
+        let mut paths: Vec<(&str, Vec<usize>)> = pkt
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(i, f)| {
+                // blocks nest or are disjoint, so the last one around a field is the innermost
+                let inner = pkt.regions.iter().rposition(|r| r.first <= i && i < r.end);
+                (f.id.as_str(), Self::path_of(pkt, inner))
+            })
+            .collect();
+        paths.extend(pkt.calculated_fields.iter().map(|cf| (cf.name.as_str(), Vec::new())));
+        Blocks { pkt, paths }
+    }
+
+    fn path_of(pkt: &PacketExpr, block: Option<usize>) -> Vec<usize> {
+        let mut path = Vec::new();
+        let mut cur = block;
+        while let Some(k) = cur {
+            path.push(k);
+            cur = pkt.enclosing_region(k);
+        }
+        path.reverse();
+        path
+    }
+
+    fn type_name(&self, k: usize) -> String {
+        format!("{}_within_{k}", ksy_identifier(&self.pkt.name))
+    }
+
+    /// The block field `i` is read in, if any.
+    fn block_of(&self, i: usize) -> Option<usize> {
+        self.paths[i].1.last().copied()
+    }
+
+    /// `e` as evaluated in block `from` (`None` for the packet itself): names read in
+    /// another block are reached through `_parent.` and `within_<k>.`.
+    fn qualify(&self, e: &ExprNode, from: Option<usize>) -> ExprNode {
+        let here = Self::path_of(self.pkt, from);
+        let mut e = e.clone();
+        for (name, path) in &self.paths {
+            let common = here.iter().zip(path).take_while(|(a, b)| a == b).count();
+            let mut prefix = "_parent.".repeat(here.len() - common);
+            for k in &path[common..] {
+                let _ = write!(&mut prefix, "within_{k}.");
+            }
+            if !prefix.is_empty() {
+                e = rename_field(&e, name, &format!("{prefix}{name}"));
+            }
+        }
+        e
+    }
+}
+
+/// The `seq` entries for fields `range`, read in block `block` (`None` for the packet
+/// itself); a block directly inside it is a single entry of its own type.
+fn seq_entries(
+    pkt: &PacketExpr,
+    blocks: &Blocks,
+    block: Option<usize>,
+    range: std::ops::Range<usize>,
+    asserts: &[Vec<&Check>],
+) -> String {
+    let mut s = String::new();
+    let mut i = range.start;
+    while i < range.end {
+        let inner = pkt
+            .regions_opening(i)
+            .into_iter()
+            .find(|&(k, _)| Some(k) != block && pkt.enclosing_region(k) == block);
+        if let Some((k, r)) = inner {
+            let _ = writeln!(&mut s, "      - id: within_{k}");
+            let _ = writeln!(&mut s, "        type: {}", blocks.type_name(k));
+            let _ = writeln!(&mut s, "        size: {}", yaml_quote(&emit_ksy_expr(&blocks.qualify(&r.length, block))));
+            if let Some(name) = unsupported(&r.length) {
+                let _ = writeln!(&mut s, "        # sized with {name}, which Kaitai cannot compute; 0 stands in for it");
+            }
+            i = r.end;
+            continue;
+        }
+
+        let f = &pkt.fields[i];
+        let last = i + 1 == range.end;
+        // Only fields overriding the packet endianness need an explicit suffix.
+        let endian = f.endianness.as_ref().filter(|e| Some(*e) != pkt.endianness.as_ref());
+        let q = |e: &ExprNode| blocks.qualify(e, block);
+        s.push_str(&seq_entry(&f.id, &f.expr, endian, last, &q));
+        let until = match &f.expr {
+            TypeNode::Repeat(_, RepeatBound::Until(e)) => Some(e),
+            _ => None,
+        };
+        let read = f.expr.length_exprs().into_iter().chain(until).chain(&f.condition);
+        if let Some(name) = read.filter_map(unsupported).next() {
+            let _ = writeln!(&mut s, "        # read with {name}, which Kaitai cannot compute; 0 stands in for it");
+        }
+        if let Some(cond) = &f.condition {
+            let _ = writeln!(&mut s, "        if: {}", yaml_quote(&emit_ksy_cond_in(&q(cond), "")));
+        }
+        // Kaitai has no checksum functions, so the field is read but not verified
+        if let Some(sum) = f.checksum() {
+            let _ = writeln!(&mut s, "        # {sum}");
+        }
+
+        // varints are read as a vlq object, which `valid` cannot compare
+        let value = f.const_literal().filter(|_| !matches!(f.expr, TypeNode::VarInt(_, _)));
+        let mut rules = Vec::new();
+        // `_` is the value just read; converted raw values and consts are noted instead
+        if let Some(check) = &f.constraint {
+            if f.value.is_some()
+                || matches!(f.expr, TypeNode::VarInt(None, _) | TypeNode::Fixed(None, _, _))
+                || unsupported(&check.cond).is_some()
+            {
+                let _ = writeln!(&mut s, "        # where {}", check.rule);
+            } else {
+                rules.push(emit_ksy_cond_in(&q(&rename_field(&check.cond, &f.id, "_")), ""));
+            }
+        }
+        // asserts name the field itself, which is set before `valid` runs
+        for check in &asserts[i] {
+            if unsupported(&check.cond).is_some() {
+                let _ = writeln!(&mut s, "        # assert {}", check.rule);
+            } else {
+                rules.push(emit_ksy_cond_in(&q(&check.cond), ""));
+            }
+        }
+        match value {
+            Some(value) if rules.is_empty() => {
+                let _ = writeln!(&mut s, "        valid: {value}");
+            }
+            _ if !rules.is_empty() => {
+                if let Some(value) = value {
+                    rules.insert(0, format!("_ == {value}"));
+                }
+                let _ = writeln!(&mut s, "        valid:");
+                let _ = writeln!(&mut s, "          expr: {}", yaml_quote(&rules.join(" and ")));
+            }
+            _ => {}
+        }
+        s.push_str(&doc_key(&f.doc, "        "));
+        i += 1;
+    }
+    s
+}
+
+/// Scalar fixed-point and varint fields are read raw as `<id>_raw` and converted in
+/// instances of the type that reads them, under the same condition when optional.
+fn raw_instances(pkt: &PacketExpr, blocks: &Blocks, block: Option<usize>) -> String {
+    let mut s = String::new();
+    for (i, f) in pkt.fields.iter().enumerate() {
+        if blocks.block_of(i) != block {
+            continue;
+        }
+        let id = ksy_identifier(&f.id);
+        let value = match &f.expr {
+            TypeNode::Fixed(None, _, frac) => format!("{id}_raw / {}.0", 1u128 << frac),
+            TypeNode::VarInt(None, VarIntEncoding::ZigZag) => {
+                format!("(({id}_raw.value >> 1) ^ -({id}_raw.value & 1))")
+            }
+            TypeNode::VarInt(None, _) => format!("{id}_raw.value"),
+            _ => continue,
+        };
+        let _ = writeln!(&mut s, "      {id}:");
+        let _ = writeln!(&mut s, "        value: {}", yaml_quote(&value));
+        if let Some(cond) = &f.condition {
+            let _ = writeln!(&mut s, "        if: {}", yaml_quote(&emit_ksy_cond_in(&blocks.qualify(cond, block), "")));
+        }
+    }
+    s
+}
+
+/* ============================================================
+ * seq mapping
+ * ============================================================
+*/
+
+/// `q` rewrites a length expression for the block the field is read in.
+fn seq_entry(id: &str, t: &TypeNode, endian: Option<&Endianness>, is_last: bool, q: &dyn Fn(&ExprNode) -> ExprNode) -> String {
+    use TypeNode::*;
+    let mut s = String::new();
+
+    // filler has no id; Kaitai skips an unnamed entry of the given size. `_io.pos` counts
+    // from the start of the stream, which is the packet start unless it is nested.
+    match t {
+        Pad(n) => {
+            let _ = writeln!(&mut s, "      - size: {n}");
+            return s;
+        }
+        Align(n) => {
+            let _ = writeln!(&mut s, "      - size: {}", yaml_quote(&format!("({n} - _io.pos % {n}) % {n}")));
+            return s;
+        }
+        _ => {}
+    }
+    // a pstring's byte count is its own seq entry, `<id>_len`
+    if let PString(_, _) = t {
+        let suffix = match endian {
+            Some(e) if t.scalar_width_bytes() > 1 => endian_suffix(e),
+            _ => "",
+        };
+        let _ = writeln!(&mut s, "      - id: {}_len", ksy_identifier(id));
+        let _ = writeln!(&mut s, "        type: {}{}", scalar_type(t), suffix);
+    }
+    match t {
+        TypeNode::Fixed(None, _, _) | TypeNode::VarInt(None, _) => {
+            let _ = writeln!(&mut s, "      - id: {}_raw", ksy_identifier(id));
+        }
+        _ => {
+            let _ = writeln!(&mut s, "      - id: {}", ksy_identifier(id));
+        }
+    }
+
+    match t {
+        // bit fields are read MSB first; Kaitai realigns at the next byte-sized type
+        Bool(_, BoolWidth::Bit) => {
+            let _ = writeln!(&mut s, "        type: b1");
+        }
+        // Kaitai terminators are single bytes, which UTF-16LE text contains in every
+        // ASCII character: the fixed region keeps its NUL padding there
+        FixedString(len, enc) => {
+            let _ = writeln!(&mut s, "        type: str");
+            let _ = writeln!(&mut s, "        size: {}", yaml_quote(&emit_ksy_expr(&q(len))));
+            if *enc == StringEncoding::Utf16Le {
+                let _ = writeln!(&mut s, "        # trailing U+0000 padding is kept");
+            } else {
+                let _ = writeln!(&mut s, "        terminator: 0");
+            }
+            let _ = writeln!(&mut s, "        encoding: {}", ksy_encoding(enc));
+        }
+        // a UTF-16LE cstring ends at a zero code unit, so it is read as code units
+        CString(StringEncoding::Utf16Le) => {
+            let _ = writeln!(&mut s, "        type: u2le");
+            let _ = writeln!(&mut s, "        repeat: until");
+            let _ = writeln!(&mut s, "        repeat-until: '_ == 0'");
+            let _ = writeln!(&mut s, "        # UTF-16LE code units, ending with the 0 terminator");
+        }
+        CString(enc) => {
+            let _ = writeln!(&mut s, "        type: strz");
+            let _ = writeln!(&mut s, "        encoding: {}", ksy_encoding(enc));
+        }
+        PString(_, enc) => {
+            let _ = writeln!(&mut s, "        type: str");
+            let _ = writeln!(&mut s, "        size: {}_len", ksy_identifier(id));
+            let _ = writeln!(&mut s, "        encoding: {}", ksy_encoding(enc));
+        }
+        Bytes(len_opt) => match len_opt {
+            Some(expr) => {
+                let _ = writeln!(&mut s, "        size: {}", yaml_quote(&emit_ksy_expr(&q(expr))));
+            }
+            None if is_last => {
+                let _ = writeln!(&mut s, "        size-eos: true");
+            }
+            None => {
+                let _ = writeln!(&mut s, "        # open-ended bytes not at end; no size available");
+            }
+        },
+        MacAddress(len_opt) => {
+            let size = len_opt
+                .as_ref()
+                .map(|e| yaml_quote(&emit_ksy_expr(&q(e))))
+                .unwrap_or_else(|| "6".to_string());
+            let _ = writeln!(&mut s, "        size: {size}");
+        }
+        Repeat(elem, bound) => {
+            let _ = writeln!(&mut s, "        type: {}", ksy_identifier(elem));
+            match bound {
+                RepeatBound::Eof => {
+                    let _ = writeln!(&mut s, "        repeat: eos");
+                }
+                RepeatBound::Until(cond) => {
+                    let _ = writeln!(&mut s, "        repeat: until");
+                    let _ = writeln!(&mut s, "        repeat-until: {}", yaml_quote(&emit_ksy_cond_in(cond, "_.")));
+                }
+                RepeatBound::Count(count) => {
+                    let _ = writeln!(&mut s, "        repeat: expr");
+                    let _ = writeln!(&mut s, "        repeat-expr: {}", yaml_quote(&emit_ksy_expr(&q(count))));
+                }
+            }
+        }
+        // no 128-bit integer type: keep the 16 network-order bytes
+        Ipv6Address(len_opt) => {
+            let _ = writeln!(&mut s, "        size: 16");
+            if let Some(expr) = len_opt {
+                let _ = writeln!(&mut s, "        repeat: expr");
+                let _ = writeln!(&mut s, "        repeat-expr: {}", yaml_quote(&emit_ksy_expr(&q(expr))));
+            }
+        }
+        _ => {
+            let suffix = match endian {
+                // single-byte types carry no endianness
+                Some(e) if t.scalar_width_bytes() > 1 => endian_suffix(e),
+                _ => "",
+            };
+            let _ = writeln!(&mut s, "        type: {}{}", scalar_type(t), suffix);
+            if let Some(expr) = type_len_expr(t) {
+                let _ = writeln!(&mut s, "        repeat: expr");
+                let _ = writeln!(&mut s, "        repeat-expr: {}", yaml_quote(&emit_ksy_expr(&q(expr))));
+            }
+        }
+    }
+    s
+}
+
+/// QUIC variable-length integer (RFC 9000 section 16): the top two bits give the length.
+const QUIC_VARINT_TYPE: &str = r#"  quic_varint:
+    seq:
+      - id: prefix
+        type: b2
+      - id: value
+        type:
+          switch-on: prefix
+          cases:
+            0: b6
+            1: b14
+            2: b30
+            3: b62
+"#;
+
+/// Renders DSL `///` docs as a `doc:` literal block.
+fn doc_key(doc: &Option<String>, pad: &str) -> String {
+    let Some(doc) = doc else {
+        return String::new();
+    };
+    let mut s = format!("{pad}doc: |\n");
+    for l in doc.lines() {
+        if l.is_empty() {
+            s.push('\n');
+        } else {
+            let _ = writeln!(&mut s, "{pad}  {l}");
+        }
+    }
+    s
+}
+
+fn ksy_encoding(e: &StringEncoding) -> &'static str {
+    match e {
+        StringEncoding::Ascii => "ASCII",
+        StringEncoding::Utf8 => "UTF-8",
+        StringEncoding::Utf16Le => "UTF-16LE",
+    }
+}
+
+fn scalar_type(t: &TypeNode) -> &'static str {
+    use TypeNode::*;
+    match t {
+        UnsignedInteger8(_) | Bytes(_) | MacAddress(_) => "u1",
+        FixedString(_, _) | CString(_) => "u1",
+        // the length prefix
+        PString(LengthPrefix::UnsignedInteger8, _) => "u1",
+        PString(LengthPrefix::UnsignedInteger16, _) => "u2",
+        Integer8(_) => "s1",
+        UnsignedInteger16(_) | Port(_) => "u2",
+        Integer16(_) => "s2",
+        UnsignedInteger32(_) | Ipv4Address(_) => "u4",
+        Ipv6Address(_) => "u1", // read as raw bytes, see seq_entry
+        Repeat(_, _) => "u1",   // typed by the element, see seq_entry
+        Pad(_) | Align(_) => "u1", // skipped, see seq_entry
+        Integer32(_) => "s4",
+        UnsignedInteger64(_) => "u8",
+        Integer64(_) => "s8",
+        DateTime(_, TimeEncoding::Ntp64 | TimeEncoding::FileTime) => "u8",
+        DateTime(_, _) => "s8",
+        Float32(_) => "f4",
+        Float64(_) => "f8",
+        Bool(_, _) => "u1",
+        VarInt(_, VarIntEncoding::Quic) => "quic_varint",
+        VarInt(_, _) => "vlq_base128_le",
+        Fixed(_, base, _) => match base {
+            FixedBase::Int8 => "s1",
+            FixedBase::UInt8 => "u1",
+            FixedBase::Int16 => "s2",
+            FixedBase::UInt16 => "u2",
+            FixedBase::Int32 => "s4",
+            FixedBase::UInt32 => "u4",
+            FixedBase::Int64 => "s8",
+            FixedBase::UInt64 => "u8",
+        },
+    }
+}
+
+fn endian_suffix(e: &Endianness) -> &'static str {
+    match e {
+        Endianness::Le => "le",
+        Endianness::Be => "be",
+    }
+}
+
+fn type_len_expr(t: &TypeNode) -> Option<&ExprNode> {
+    use TypeNode::*;
+    match t {
+        UnsignedInteger8(e) | Integer8(e) | UnsignedInteger16(e) | Integer16(e)
+        | UnsignedInteger32(e) | Integer32(e) | UnsignedInteger64(e) | Integer64(e)
+        | Float32(e) | Float64(e) | DateTime(e, _) | Bytes(e) | MacAddress(e) => e.as_ref(),
+        Bool(e, _) | Fixed(e, _, _) | VarInt(e, _) => e.as_ref(),
+        Ipv4Address(e) | Ipv6Address(e) | Port(e) => e.as_ref(),
+        FixedString(e, _) => Some(e),
+        CString(_) | PString(_, _) | Pad(_) | Align(_) => None,
+        Repeat(_, RepeatBound::Count(e)) => Some(e),
+        Repeat(_, _) => None,
+    }
+}
+
+/// Kaitai identifiers must be lower_snake_case: `FlapSnac` → `flap_snac`,
+/// `TLVStream` → `tlv_stream`.
+fn ksy_identifier(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut out = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            if prev.is_lowercase() || prev.is_ascii_digit() || (prev.is_uppercase() && next_lower) {
+                out.push('_');
+            }
+        }
+        out.extend(c.to_lowercase());
+    }
+    out
+}
+
+fn yaml_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/* ============================================================
+ * Expr → Kaitai expression language
+ * ============================================================
+*/
+
+fn emit_ksy_expr(e: &ExprNode) -> String {
+    emit_ksy_expr_in(e, "")
+}
+
+/// `root` prefixes field references; `repeat-until` conditions use `_.` for
+/// the element just read.
+fn emit_ksy_expr_in(e: &ExprNode, root: &str) -> String {
+    use ExprNode::*;
+    match e {
+        UnsignedInteger64Value(u) => format!("{}", u),
+        Integer64Value(i) => format!("{}", i),
+        Float64Value(f) => format!("{:?}", f),
+        StringValue(s) => format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"")),
+        ValueReference(name, idx) => {
+            if let Some(ix) = idx {
+                format!("{}{}[{}]", root, ksy_identifier(name), emit_ksy_expr_in(ix, root))
+            } else {
+                format!("{}{}", root, ksy_identifier(name))
+            }
+        }
+        ActivationRecord(fname, args) => {
+            let args_s: Vec<String> = args.iter().map(|a| emit_ksy_expr_in(a, root)).collect();
+            match fname.as_str() {
+                // Kaitai has no free functions; min/max are array methods.
+                "min" | "max" => format!("[{}].{}", args_s.join(", "), fname),
+                "clamp" => format!("[[{}, {}].max, {}].min", args_s[0], args_s[1], args_s[2]),
+                "abs" => format!("({0} < 0 ? -{0} : {0})", args_s[0]),
+                "pow" => emit_ksy_expr_in(&Pow(Box::new(args[0].clone()), Box::new(args[1].clone())), root),
+                "bswap16" => format!("((({0} & 0xff) << 8) | (({0} >> 8) & 0xff))", args_s[0]),
+                "bswap32" => format!(
+                    "((({0} & 0xff) << 24) | ((({0} >> 8) & 0xff) << 16) | ((({0} >> 16) & 0xff) << 8) | (({0} >> 24) & 0xff))",
+                    args_s[0]
+                ),
+                // `/` divides integers, so a quotient is rounded by adjusting the dividend
+                "floor" | "ceil" | "round" => match quotient(&args[0]) {
+                    Some((a, b)) => {
+                        let (a, b) = (emit_ksy_expr_in(a, root), emit_ksy_expr_in(b, root));
+                        match fname.as_str() {
+                            "floor" => format!("({a} / {b})"),
+                            "ceil" => format!("(({a} + {b} - 1) / {b})"),
+                            _ => format!("((2 * {a} + {b}) / (2 * {b}))"),
+                        }
+                    }
+                    None => "0".to_string(),
+                },
+                // callers leave out what `unsupported` names
+                _ => "0".to_string(),
+            }
+        }
+        AggregateSum(_) | AggregateProduct(_) => "0".to_string(),
+        ParenthesizedExpr(inner) => format!("({})", emit_ksy_expr_in(inner, root)),
+        GuardExpression(c, t, f) => format!(
+            "({} ? {} : {})",
+            emit_ksy_cond_in(c, root),
+            emit_ksy_expr_in(t, root),
+            emit_ksy_expr_in(f, root)
+        ),
+
+        Plus(a, b) => bin(a, b, "+", root),
+        Minus(a, b) => bin(a, b, "-", root),
+        Mult(a, b) => bin(a, b, "*", root),
+        Div(a, b) => bin(a, b, "/", root),
+        Mod(a, b) => bin(a, b, "%", root),
+        Negate(a) => format!("(-{})", emit_ksy_expr_in(a, root)),
+        BitAnd(a, b) => bin(a, b, "&", root),
+        BitOr(a, b) => bin(a, b, "|", root),
+        BitNot(a) => format!("(~{})", emit_ksy_expr_in(a, root)),
+        ShiftLeft(a, b) => format!("({} << ({} & 63))", emit_ksy_expr_in(a, root), emit_ksy_expr_in(b, root)),
+        ShiftRight(a, b) => format!("({} >> ({} & 63))", emit_ksy_expr_in(a, root), emit_ksy_expr_in(b, root)),
+        // No power operator in Kaitai: unroll small constant exponents.
+        Pow(a, b) => match eval_small_exponent(b) {
+            Some(0) => "1".to_string(),
+            Some(n) => format!("({})", vec![emit_ksy_expr_in(a, root); n as usize].join(" * ")),
+            None => "0".to_string(),
+        },
+
+        Gt(a, b) => bin(a, b, ">", root),
+        Gte(a, b) => bin(a, b, ">=", root),
+        Lt(a, b) => bin(a, b, "<", root),
+        Lte(a, b) => bin(a, b, "<=", root),
+        Equals(a, b) => bin(a, b, "==", root),
+        NotEquals(a, b) => bin(a, b, "!=", root),
+        And(a, b) => format!("({} and {})", emit_ksy_cond_in(a, root), emit_ksy_cond_in(b, root)),
+        Or(a, b) => format!("({} or {})", emit_ksy_cond_in(a, root), emit_ksy_cond_in(b, root)),
+        Not(a) => format!("(not {})", emit_ksy_cond_in(a, root)),
+
+        NoExpr => "0".to_string(),
+    }
+}
+
+/// Kaitai conditions must be booleans; numeric expressions are compared against zero.
+fn emit_ksy_cond_in(e: &ExprNode, root: &str) -> String {
+    use ExprNode::*;
+    match e {
+        Gt(..) | Gte(..) | Lt(..) | Lte(..) | Equals(..) | NotEquals(..) | And(..) | Or(..) | Not(..) => {
+            emit_ksy_expr_in(e, root)
+        }
+        ParenthesizedExpr(inner) => emit_ksy_cond_in(inner, root),
+        _ => format!("({} != 0)", emit_ksy_expr_in(e, root)),
+    }
+}
+
+/// `e` with references to the field `from` renamed to `to`.
+fn rename_field(e: &ExprNode, from: &str, to: &str) -> ExprNode {
+    use ExprNode::*;
+    let r = |x: &ExprNode| Box::new(rename_field(x, from, to));
+    match e {
+        ValueReference(id, index) => ValueReference(
+            if id == from { to.to_string() } else { id.clone() },
+            index.as_ref().map(|ix| r(ix)),
+        ),
+        ActivationRecord(name, args) => ActivationRecord(name.clone(), args.iter().map(|a| rename_field(a, from, to)).collect()),
+        NoExpr | UnsignedInteger64Value(_) | Integer64Value(_) | Float64Value(_) | StringValue(_)
+        | AggregateSum(_) | AggregateProduct(_) => e.clone(),
+        ParenthesizedExpr(a) => ParenthesizedExpr(r(a)),
+        Negate(a) => Negate(r(a)),
+        BitNot(a) => BitNot(r(a)),
+        Not(a) => Not(r(a)),
+        GuardExpression(c, a, b) => GuardExpression(r(c), r(a), r(b)),
+        Plus(a, b) => Plus(r(a), r(b)),
+        Minus(a, b) => Minus(r(a), r(b)),
+        Mult(a, b) => Mult(r(a), r(b)),
+        Div(a, b) => Div(r(a), r(b)),
+        Mod(a, b) => Mod(r(a), r(b)),
+        Pow(a, b) => Pow(r(a), r(b)),
+        BitAnd(a, b) => BitAnd(r(a), r(b)),
+        BitOr(a, b) => BitOr(r(a), r(b)),
+        ShiftLeft(a, b) => ShiftLeft(r(a), r(b)),
+        ShiftRight(a, b) => ShiftRight(r(a), r(b)),
+        Gt(a, b) => Gt(r(a), r(b)),
+        Gte(a, b) => Gte(r(a), r(b)),
+        Lt(a, b) => Lt(r(a), r(b)),
+        Lte(a, b) => Lte(r(a), r(b)),
+        Equals(a, b) => Equals(r(a), r(b)),
+        NotEquals(a, b) => NotEquals(r(a), r(b)),
+        And(a, b) => And(r(a), r(b)),
+        Or(a, b) => Or(r(a), r(b)),
+    }
+}
+
+/// The first call or operator in `e` that the expression language cannot
+/// express: logarithms, square roots, bit counts, 64-bit byte swaps, rounding
+/// of anything but a quotient, `extern fn` calls and aggregates.
+fn unsupported(e: &ExprNode) -> Option<String> {
+    use ExprNode::*;
+    match e {
+        NoExpr | UnsignedInteger64Value(_) | Integer64Value(_) | Float64Value(_) | StringValue(_) => None,
+        AggregateSum(_) => Some("sumof".to_string()),
+        AggregateProduct(_) => Some("productof".to_string()),
+        ValueReference(_, index) => index.as_deref().and_then(unsupported),
+        ActivationRecord(name, args) => {
+            let known = match (name.as_str(), &args[..]) {
+                ("min" | "max" | "clamp" | "abs" | "bswap16" | "bswap32", _) => true,
+                ("pow", [_, n]) => eval_small_exponent(n).is_some(),
+                ("floor" | "ceil" | "round", [x]) => quotient(x).is_some(),
+                _ => false,
+            };
+            if known { args.iter().find_map(unsupported) } else { Some(name.clone()) }
+        }
+        Pow(a, b) => match eval_small_exponent(b) {
+            Some(_) => unsupported(a),
+            None => Some("^".to_string()),
+        },
+        ParenthesizedExpr(a) | Negate(a) | BitNot(a) | Not(a) => unsupported(a),
+        GuardExpression(c, a, b) => unsupported(c).or_else(|| unsupported(a)).or_else(|| unsupported(b)),
+        Plus(a, b) | Minus(a, b) | Mult(a, b) | Div(a, b) | Mod(a, b)
+        | BitAnd(a, b) | BitOr(a, b) | ShiftLeft(a, b) | ShiftRight(a, b)
+        | Gt(a, b) | Gte(a, b) | Lt(a, b) | Lte(a, b) | Equals(a, b) | NotEquals(a, b)
+        | And(a, b) | Or(a, b) => unsupported(a).or_else(|| unsupported(b)),
+    }
+}
+
+/// The dividend and divisor of `e` when it is a division.
+fn quotient(e: &ExprNode) -> Option<(&ExprNode, &ExprNode)> {
+    match e {
+        ExprNode::ParenthesizedExpr(inner) => quotient(inner),
+        ExprNode::Div(a, b) => Some((a, b)),
+        _ => None,
+    }
+}
+
+fn eval_small_exponent(e: &ExprNode) -> Option<u32> {
+    match e {
+        ExprNode::UnsignedInteger64Value(u) if *u <= 16 => Some(*u as u32),
+        ExprNode::ParenthesizedExpr(inner) => eval_small_exponent(inner),
+        _ => None,
+    }
+}
+
+fn bin(a: &ExprNode, b: &ExprNode, op: &str, root: &str) -> String {
+    format!("({} {} {})", emit_ksy_expr_in(a, root), op, emit_ksy_expr_in(b, root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::load_source;
+
+    fn ksy(src: &str) -> String {
+        KaitaiGenerator::generate(&load_source(src))
+    }
+
+    #[test]
+    fn packets_are_types_with_a_seq() {
+        let out = ksy("packet Hello be { kind uint8, size uint16, body bytes[size], calc total uint32 = size + 3 }");
+        assert!(out.contains(
+            "  hello:\n    meta:\n      endian: be\n    seq:\n      - id: kind\n        type: u1\n      - id: size\n        type: u2\n"
+        ));
+        assert!(out.contains("      - id: body\n        size: 'size'\n"));
+        assert!(out.contains("    instances:\n      total:\n        value: '(size + 3)'\n"));
+    }
+
+    #[test]
+    fn strings() {
+        let out = ksy("packet Names {
+            tag string[4] ascii,
+            nick string[8] utf16le,
+            host cstring,
+            away cstring utf16le,
+            name pstring<uint8>
+        }");
+        assert!(out.contains(
+            "      - id: tag\n        type: str\n        size: '4'\n        terminator: 0\n        encoding: ASCII\n"
+        ));
+        assert!(out.contains(
+            "      - id: nick\n        type: str\n        size: '8'\n        # trailing U+0000 padding is kept\n        encoding: UTF-16LE\n"
+        ));
+        assert!(out.contains("      - id: host\n        type: strz\n        encoding: UTF-8\n"));
+        assert!(out.contains("      - id: away\n        type: u2le\n        repeat: until\n        repeat-until: '_ == 0'\n"));
+        assert!(out.contains("      - id: name\n        type: str\n        size: name_len\n"));
+        assert!(!out.contains("strz\n        size"));
+    }
+
+    #[test]
+    fn intrinsics_are_translated_or_left_out() {
+        let out = ksy("extern fn lz(x) -> uint8 { rust: \"u64::leading_zeros\" }
+        packet Bits be {
+            port uint16,
+            count uint32 where log2(count) < 8,
+            calc port_le uint16 = bswap16(port),
+            calc blocks uint16 = ceil((count + 2) / 8),
+            calc half uint16 = round(count / 2),
+            calc bits uint8 = popcount(count),
+            calc zeros uint8 = lz(count)
+        }");
+        assert!(out.contains("      port_le:\n        value: '(((port & 0xff) << 8) | ((port >> 8) & 0xff))'\n"));
+        assert!(out.contains("      blocks:\n        value: '(((count + 2) + 8 - 1) / 8)'\n"));
+        assert!(out.contains("      half:\n        value: '((2 * count + 2) / (2 * 2))'\n"));
+        assert!(out.contains("    # bits uses popcount, which Kaitai cannot compute\n"));
+        assert!(out.contains("    # zeros uses lz, which Kaitai cannot compute\n"));
+        assert!(out.contains("        # where log2(count) < 8\n"));
+        assert!(!out.contains("'0'"));
+    }
+
+    #[test]
+    fn within_blocks_are_substreams() {
+        let out = ksy("packet Outer be {
+            length uint16,
+            within(length) {
+                family uint16,
+                sub_len uint8,
+                within(sub_len, skip) { a uint8, rest bytes },
+                body bytes[length - 3 - sub_len]
+            },
+            trailer uint8,
+            calc total uint16 = length + a
+        }");
+        assert!(out.contains("      - id: within_0\n        type: outer_within_0\n        size: 'length'\n      - id: trailer\n"));
+        assert!(out.contains("  outer_within_0:\n    meta:\n      endian: be\n    seq:\n      - id: family\n"));
+        assert!(out.contains("      - id: within_1\n        type: outer_within_1\n        size: 'sub_len'\n"));
+        // lengths reach fields of the enclosing type, and calcs those of the blocks
+        assert!(out.contains("        size: '((_parent.length - 3) - sub_len)'\n"));
+        assert!(out.contains("        value: '(length + within_0.within_1.a)'\n"));
+        // the open-ended field stops at the block end; only the strict block checks for leftovers
+        assert!(out.contains("      - id: rest\n        size-eos: true\n"));
+        assert_eq!(out.matches("      - id: unread\n        size-eos: true\n        valid:\n          expr: '_.length == 0'\n").count(), 1);
+        assert!(!out.contains("# within"));
+    }
+
+    #[test]
+    fn asserts_are_checked_on_the_last_field_they_read() {
+        let out = ksy("packet Frame {
+            kind uint8 = 1,
+            channel uint8 where channel < 5,
+            length uint16,
+            tag uint8,
+            assert channel + tag > 2,
+            assert length >= 4,
+            assert kind == 1
+        }");
+        assert!(out.contains("      - id: channel\n        type: u1\n        valid:\n          expr: '(_ < 5)'\n"));
+        assert!(out.contains("      - id: length\n        type: u2\n        valid:\n          expr: '(length >= 4)'\n"));
+        // consts are folded into rules, so `kind == 1` reads no field and goes to the last one
+        assert!(out.contains("      - id: tag\n        type: u1\n        valid:\n          expr: '((channel + tag) > 2) and (1 == 1)'\n"));
+        assert!(out.contains("      - id: kind\n        type: u1\n        valid: 0x1\n"));
+        assert!(!out.contains("# assert"));
+    }
+
+    #[test]
+    fn fillers_are_unnamed_sizes() {
+        let out = ksy("packet F be { v uint8, pad(3), n uint32, reserved uint16, align(8) } packet G { a uint8, b uint8, pad(1), assert a < b }");
+        assert!(out.contains("      - size: 3\n"));
+        assert!(out.contains("      - size: 2\n"));
+        assert!(out.contains("      - size: '(8 - _io.pos % 8) % 8'\n"));
+        // an assert is checked on the last field it names, never on filler
+        assert!(out.contains("      - id: b\n        type: u1\n        valid:\n          expr: '(a < b)'\n"));
+    }
+}
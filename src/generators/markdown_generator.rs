@@ -0,0 +1,639 @@
+use crate::models::parsing_models::{
+    BoolWidth, Endianness, ExprNode, ExternFn, FixedBase, Leftover, LengthPrefix, PacketExpr, PacketExprList, RepeatBound,
+    StringEncoding, TimeEncoding, TypeExpr, TypeNode, VarIntEncoding,
+};
+use std::fmt::Write as _;
+
+/// Bits drawn per diagram row, as in RFC packet diagrams.
+const ROW_BITS: usize = 32;
+
+pub struct MarkdownGenerator {}
+
+impl MarkdownGenerator {
+    pub fn generate(model: &PacketExprList) -> String {
+        let mut out = String::new();
+        out.push_str(&Self::create_headers());
+
+        for pkt in &model.packets {
+            out.push_str(&Self::build_packet_doc(pkt));
+            out.push_str(&Self::create_spacer());
+        }
+        if !model.externs.is_empty() {
+            out.push_str(&Self::build_externs_doc(&model.externs));
+        }
+        out
+    }
+
+    fn build_externs_doc(externs: &[ExternFn]) -> String {
+        let mut s = String::new();
+        let _ = writeln!(&mut s, "## Functions\n");
+        let _ = writeln!(&mut s, "| Function | Returns | Targets | Description |");
+        let _ = writeln!(&mut s, "|---|---|---|---|");
+        for x in externs {
+            let targets: Vec<String> = x.targets.iter().map(|(t, f)| format!("{}: `{}`", t, f)).collect();
+            let _ = writeln!(
+                &mut s,
+                "| `{}({})` | {} | {} | {} |",
+                x.name,
+                x.params.join(", "),
+                x.returns,
+                targets.join("<br>"),
+                table_cell(&x.doc)
+            );
+        }
+        s
+    }
+
+    fn create_spacer() -> String {
+        "\n".to_string()
+    }
+
+    fn create_headers() -> String {
+        "# Packet reference\n\n".to_string()
+    }
+
+    fn build_packet_doc(pkt: &PacketExpr) -> String {
+        let mut s = String::new();
+        let packet_endian = pkt.endianness.clone().unwrap_or(Endianness::Le);
+        let _ = writeln!(&mut s, "## {}\n", pkt.name);
+        if let Some(doc) = &pkt.doc {
+            let _ = writeln!(&mut s, "{}\n", doc.trim_end());
+        }
+        let _ = writeln!(
+            &mut s,
+            "Byte order: {}{}\n",
+            endian_name(&packet_endian),
+            if pkt.endianness.is_none() { " (default)" } else { "" }
+        );
+
+        // 1) field table
+        let _ = writeln!(
+            &mut s,
+            "| Offset | Width (bytes) | Field | Type | Endianness | Length | Description |"
+        );
+        let _ = writeln!(&mut s, "|---|---|---|---|---|---|---|");
+        let mut offset = Offset::default();
+        for (f, slot) in pkt.fields.iter().zip(pkt.bit_slots()) {
+            // bit fields share a byte: they all show its offset, and it is passed after the last
+            if let Some(slot) = slot {
+                let _ = writeln!(
+                    &mut s,
+                    "| {} | 1 bit | `{}` | `bool(bit)` | - |  | {} |",
+                    offset.render(),
+                    f.id,
+                    table_cell(&f.doc)
+                );
+                if slot.ends_byte() {
+                    offset.advance(&Width::Const(1));
+                }
+                continue;
+            }
+            let width = match f.expr {
+                TypeNode::Align(n) => offset.padding_to(n),
+                _ => declared_width(f),
+            };
+            let endian = if f.expr.scalar_width_bytes() > 1 {
+                endian_name(f.endianness.as_ref().unwrap_or(&packet_endian)).to_string()
+            } else {
+                "-".to_string()
+            };
+            let length = type_len_expr(&f.expr)
+                .map(|e| format!("`{}`", dsl_expr(e)))
+                .unwrap_or_default();
+            let _ = writeln!(
+                &mut s,
+                "| {} | {} | {} | `{}` | {} | {} | {} |",
+                offset.render(),
+                width.render(),
+                // filler names are generated and not worth showing
+                if f.is_filler() { String::new() } else { format!("`{}`", f.id) },
+                match (f.const_literal(), f.checksum()) {
+                    (Some(value), _) => format!("{} = {value}", type_name(&f.expr)),
+                    (None, Some(sum)) => format!("{} = {sum}", type_name(&f.expr)),
+                    (None, None) => type_name(&f.expr),
+                },
+                endian,
+                length,
+                table_cell(&f.doc)
+            );
+            offset.advance(&width);
+        }
+        let _ = writeln!(&mut s);
+        for r in &pkt.regions {
+            let _ = writeln!(
+                &mut s,
+                "`{}` through `{}` take exactly `{}` bytes; {}.\n",
+                pkt.fields[r.first].id,
+                pkt.fields[r.end - 1].id,
+                dsl_expr(&r.length),
+                match r.leftover {
+                    Leftover::Error => "unread bytes are an error",
+                    Leftover::Skip => "unread bytes are skipped",
+                }
+            );
+        }
+
+        let checks = pkt.checks();
+        if !checks.is_empty() {
+            let _ = writeln!(&mut s, "Rules (checked when decoding):\n");
+            for c in checks {
+                let _ = writeln!(&mut s, "- `{}`: `{}`", c.field, c.rule);
+            }
+            let _ = writeln!(&mut s);
+        }
+
+        // 2) calculated fields
+        if !pkt.calculated_fields.is_empty() {
+            let _ = writeln!(&mut s, "Calculated fields (not on the wire):\n");
+            let _ = writeln!(&mut s, "| Field | Type | Expression | Description |");
+            let _ = writeln!(&mut s, "|---|---|---|---|");
+            for cf in &pkt.calculated_fields {
+                let _ = writeln!(
+                    &mut s,
+                    "| `{}` | `{}` | `{}` | {} |",
+                    cf.name,
+                    cf.data_type,
+                    dsl_expr(&cf.expr),
+                    table_cell(&cf.doc)
+                );
+            }
+            let _ = writeln!(&mut s);
+        }
+
+        // 3) bit diagram
+        let _ = writeln!(&mut s, "```");
+        s.push_str(&bit_diagram(pkt));
+        let _ = writeln!(&mut s, "```");
+        s
+    }
+}
+
+/// Flattens a doc comment into a single table cell.
+fn table_cell(doc: &Option<String>) -> String {
+    doc.as_deref()
+        .map(|d| {
+            d.lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ")
+                .replace('|', "\\|")
+        })
+        .unwrap_or_default()
+}
+
+/* ============================================================
+ * Offsets & widths
+ * ============================================================
+*/
+
+/// A field width: constant bytes, or a DSL expression evaluated at runtime.
+enum Width {
+    Const(usize),
+    Dynamic(String),
+    Variable(&'static str), // self-delimiting (terminator or length prefix)
+    Remaining,
+    Optional(Box<Width>, String), // present only when the condition holds
+}
+
+impl Width {
+    fn render(&self) -> String {
+        match self {
+            Width::Const(n) => n.to_string(),
+            Width::Dynamic(e) => format!("`{e}`"),
+            Width::Variable(how) => how.to_string(),
+            Width::Remaining => "rest of packet".to_string(),
+            Width::Optional(w, cond) => format!("{} if `{cond}`, else 0", w.render()),
+        }
+    }
+}
+
+/// Running offset: a constant part plus any symbolic terms from variable fields.
+#[derive(Default)]
+struct Offset {
+    constant: usize,
+    terms: Vec<String>,
+    unbounded: bool,
+}
+
+impl Offset {
+    fn advance(&mut self, width: &Width) {
+        match width {
+            Width::Const(n) => self.constant += n,
+            Width::Dynamic(e) => self.terms.push(e.clone()),
+            Width::Variable(_) | Width::Remaining | Width::Optional(..) => self.unbounded = true,
+        }
+    }
+
+    /// Bytes an `align(n)` adds here; only known while the offset is constant.
+    fn padding_to(&self, n: usize) -> Width {
+        if self.unbounded || !self.terms.is_empty() {
+            return Width::Variable("up to the next multiple");
+        }
+        Width::Const((n - self.constant % n) % n)
+    }
+
+    fn render(&self) -> String {
+        if self.unbounded {
+            return "-".to_string();
+        }
+        if self.terms.is_empty() {
+            return self.constant.to_string();
+        }
+        let mut parts = Vec::<String>::new();
+        if self.constant > 0 {
+            parts.push(self.constant.to_string());
+        }
+        parts.extend(self.terms.iter().map(|t| wrap_if_compound(t)));
+        format!("`{}`", parts.join(" + "))
+    }
+}
+
+/// Width of a field as declared, counting its `if` condition.
+fn declared_width(f: &TypeExpr) -> Width {
+    match &f.condition {
+        Some(cond) => Width::Optional(Box::new(field_width(&f.expr)), dsl_expr(cond)),
+        None => field_width(&f.expr),
+    }
+}
+
+fn field_width(t: &TypeNode) -> Width {
+    let elem = t.scalar_width_bytes();
+    if let TypeNode::VarInt(len, enc) = t {
+        return Width::Variable(match (enc, len.is_some()) {
+            (VarIntEncoding::Quic, false) => "1, 2, 4 or 8 bytes",
+            (VarIntEncoding::Quic, true) => "1, 2, 4 or 8 bytes each",
+            (_, false) => "1-10 bytes",
+            (_, true) => "1-10 bytes each",
+        });
+    }
+    // element sizes are only known from their contents
+    if let TypeNode::Repeat(_, bound) = t {
+        return match bound {
+            RepeatBound::Eof => Width::Remaining,
+            _ => Width::Variable("sum of elements"),
+        };
+    }
+    match type_len_expr(t) {
+        None => match t {
+            TypeNode::Bytes(_) => Width::Remaining,
+            TypeNode::CString(_) => Width::Variable("NUL-terminated"),
+            TypeNode::PString(LengthPrefix::UnsignedInteger8, _) => Width::Variable("1 + length"),
+            TypeNode::PString(LengthPrefix::UnsignedInteger16, _) => Width::Variable("2 + length"),
+            _ => Width::Const(t.get_length_bytes()),
+        },
+        Some(e) => {
+            let n = t.get_length_bytes();
+            if n > 0 || is_zero_const(e) {
+                Width::Const(n)
+            } else if elem == 1 {
+                Width::Dynamic(dsl_expr(e))
+            } else {
+                Width::Dynamic(format!("{} * {}", wrap_if_compound(&dsl_expr(e)), elem))
+            }
+        }
+    }
+}
+
+fn is_zero_const(e: &ExprNode) -> bool {
+    matches!(e, ExprNode::UnsignedInteger64Value(0))
+}
+
+fn wrap_if_compound(s: &str) -> String {
+    if s.contains(' ') || s.starts_with(['-', '~']) {
+        format!("({s})")
+    } else {
+        s.to_string()
+    }
+}
+
+/* ============================================================
+ * ASCII bit diagram
+ * ============================================================
+*/
+
+struct Segment {
+    start: usize, // bit within the row
+    end: usize,   // exclusive
+    label: String,
+    continues: bool, // field carries on into the next row
+}
+
+fn bit_diagram(pkt: &PacketExpr) -> String {
+    let mut s = String::new();
+
+    // ruler
+    let mut tens = String::from(" ");
+    let mut ones = String::from(" ");
+    for bit in 0..ROW_BITS {
+        tens.push_str(&if bit % 10 == 0 { format!("{} ", bit / 10) } else { "  ".to_string() });
+        ones.push_str(&format!("{} ", bit % 10));
+    }
+    let _ = writeln!(&mut s, "{}", tens.trim_end());
+    let _ = writeln!(&mut s, "{}", ones.trim_end());
+    let _ = writeln!(&mut s, "{}", separator(&[], ROW_BITS));
+
+    let mut row = Vec::<Segment>::new();
+    let mut bit = 0usize;
+    let mut offset = Offset::default();
+
+    for (f, slot) in pkt.fields.iter().zip(pkt.bit_slots()) {
+        if let Some(slot) = slot {
+            place(&mut s, &mut row, &mut bit, 1, &f.id);
+            // unused low bits of the shared byte
+            let pad = 7 - slot.index % 8;
+            if slot.last && pad > 0 {
+                place(&mut s, &mut row, &mut bit, pad, "");
+            }
+            if slot.ends_byte() {
+                offset.advance(&Width::Const(1));
+            }
+            continue;
+        }
+        let width = match f.expr {
+            TypeNode::Align(n) => offset.padding_to(n),
+            _ => declared_width(f),
+        };
+        offset.advance(&width);
+        match width {
+            // filler is left unlabelled, like the unused bits of a flag byte
+            Width::Const(n) if f.is_filler() => place(&mut s, &mut row, &mut bit, n * 8, ""),
+            Width::Const(n) => place(&mut s, &mut row, &mut bit, n * 8, &f.id),
+            width => {
+                // Variable-length regions get their own full-width block, RFC style.
+                if bit > 0 {
+                    flush_row(&mut s, &mut row, bit);
+                    bit = 0;
+                }
+                let label = match width {
+                    Width::Dynamic(e) => format!("{} ({} bytes)", f.id, e),
+                    Width::Variable(how) => format!("{} ({})", f.id, how),
+                    Width::Optional(_, cond) => format!("{} (if {})", f.id, cond),
+                    _ => format!("{} (remaining bytes)", f.id),
+                };
+                let _ = writeln!(&mut s, ":{}:", center(&label, ROW_BITS * 2 - 1));
+                let _ = writeln!(&mut s, "{}", separator(&[], ROW_BITS));
+            }
+        }
+    }
+    if bit > 0 {
+        flush_row(&mut s, &mut row, bit);
+    }
+    s
+}
+
+/// Lays `bits` bits labelled `label` into the current row, wrapping onto new rows.
+fn place(s: &mut String, row: &mut Vec<Segment>, bit: &mut usize, bits: usize, label: &str) {
+    let mut remaining = bits;
+    let mut first = true;
+    while remaining > 0 {
+        let take = remaining.min(ROW_BITS - *bit);
+        remaining -= take;
+        row.push(Segment {
+            start: *bit,
+            end: *bit + take,
+            label: if first { label.to_string() } else { String::new() },
+            continues: remaining > 0,
+        });
+        first = false;
+        *bit += take;
+        if *bit == ROW_BITS {
+            flush_row(s, row, ROW_BITS);
+            *bit = 0;
+        }
+    }
+}
+
+fn flush_row(s: &mut String, row: &mut Vec<Segment>, bits: usize) {
+    let mut line = String::from("|");
+    for seg in row.iter() {
+        line.push_str(&center(&seg.label, (seg.end - seg.start) * 2 - 1));
+        line.push('|');
+    }
+    let _ = writeln!(s, "{}", line);
+    let _ = writeln!(s, "{}", separator(row, bits));
+    row.clear();
+}
+
+/// `+-+-+` rule under a row; cells of fields that continue on the next row stay open.
+fn separator(row: &[Segment], bits: usize) -> String {
+    let mut line = String::from("+");
+    for bit in 0..bits {
+        let seg = row.iter().find(|sg| bit >= sg.start && bit < sg.end);
+        let open = seg.is_some_and(|sg| sg.continues);
+        line.push(if open { ' ' } else { '-' });
+        let boundary = bit + 1 == bits || seg.is_none_or(|sg| bit + 1 == sg.end);
+        line.push(if open && !boundary { ' ' } else { '+' });
+    }
+    line
+}
+
+fn center(label: &str, width: usize) -> String {
+    let label: String = label.chars().take(width).collect();
+    let len = label.chars().count();
+    let left = (width - len) / 2;
+    let right = width - len - left;
+    format!("{}{}{}", " ".repeat(left), label, " ".repeat(right))
+}
+
+/* ============================================================
+ * DSL rendering helpers
+ * ============================================================
+*/
+
+fn endian_name(e: &Endianness) -> &'static str {
+    match e {
+        Endianness::Le => "little-endian",
+        Endianness::Be => "big-endian",
+    }
+}
+
+fn type_name(t: &TypeNode) -> String {
+    use TypeNode::*;
+    let base = match t {
+        Integer8(_) => "int8",
+        UnsignedInteger8(_) => "uint8",
+        Integer16(_) => "int16",
+        UnsignedInteger16(_) => "uint16",
+        Integer32(_) => "int32",
+        UnsignedInteger32(_) => "uint32",
+        Integer64(_) => "int64",
+        UnsignedInteger64(_) => "uint64",
+        Float32(_) => "float32",
+        Float64(_) => "float64",
+        MacAddress(_) => "macaddress",
+        DateTime(_, unit) => match unit {
+            TimeEncoding::UnixSeconds => "datetime(unix_s)",
+            TimeEncoding::UnixMillis => "datetime(unix_ms)",
+            TimeEncoding::UnixMicros => "datetime(unix_us)",
+            TimeEncoding::Ntp64 => "datetime(ntp64)",
+            TimeEncoding::FileTime => "datetime(filetime)",
+        },
+        Bytes(_) => "bytes",
+        Ipv4Address(_) => "ipv4",
+        Ipv6Address(_) => "ipv6",
+        Port(_) => "port",
+        Bool(_, BoolWidth::Byte) => "bool",
+        Bool(_, BoolWidth::Bit) => "bool(bit)",
+        VarInt(_, VarIntEncoding::Leb128) => "varuint",
+        VarInt(_, VarIntEncoding::ZigZag) => "varint",
+        VarInt(_, VarIntEncoding::Quic) => "quicvarint",
+        Fixed(len, base, frac) => {
+            let base = format!("fixed<{}, {}>", fixed_base_name(base), frac);
+            return match len {
+                Some(e) => format!("{}[{}]", base, dsl_expr(e)),
+                None => base,
+            };
+        }
+        FixedString(len, enc) => return format!("string[{}] {}", dsl_expr(len), encoding_name(enc)),
+        CString(enc) => return format!("cstring {}", encoding_name(enc)),
+        PString(LengthPrefix::UnsignedInteger8, enc) => return format!("pstring<uint8> {}", encoding_name(enc)),
+        PString(LengthPrefix::UnsignedInteger16, enc) => return format!("pstring<uint16> {}", encoding_name(enc)),
+        Pad(n) => return format!("pad({n})"),
+        Align(n) => return format!("align({n})"),
+        Repeat(elem, bound) => {
+            return match bound {
+                RepeatBound::Eof => format!("repeat {} until eof", elem),
+                RepeatBound::Until(cond) => format!("repeat {} until ({})", elem, dsl_expr(cond)),
+                RepeatBound::Count(count) => format!("repeat {}[{}]", elem, dsl_expr(count)),
+            };
+        }
+    };
+    match type_len_expr(t) {
+        Some(e) => format!("{}[{}]", base, dsl_expr(e)),
+        None => base.to_string(),
+    }
+}
+
+fn fixed_base_name(b: &FixedBase) -> &'static str {
+    match b {
+        FixedBase::Int8 => "int8",
+        FixedBase::UInt8 => "uint8",
+        FixedBase::Int16 => "int16",
+        FixedBase::UInt16 => "uint16",
+        FixedBase::Int32 => "int32",
+        FixedBase::UInt32 => "uint32",
+        FixedBase::Int64 => "int64",
+        FixedBase::UInt64 => "uint64",
+    }
+}
+
+fn encoding_name(e: &StringEncoding) -> &'static str {
+    match e {
+        StringEncoding::Ascii => "ascii",
+        StringEncoding::Utf8 => "utf8",
+        StringEncoding::Utf16Le => "utf16le",
+    }
+}
+
+fn type_len_expr(t: &TypeNode) -> Option<&ExprNode> {
+    use TypeNode::*;
+    match t {
+        UnsignedInteger8(e) | Integer8(e) | UnsignedInteger16(e) | Integer16(e)
+        | UnsignedInteger32(e) | Integer32(e) | UnsignedInteger64(e) | Integer64(e)
+        | Float32(e) | Float64(e) | DateTime(e, _) | Bytes(e) | MacAddress(e) => e.as_ref(),
+        Ipv4Address(e) | Ipv6Address(e) | Port(e) => e.as_ref(),
+        Bool(e, _) | Fixed(e, _, _) | VarInt(e, _) => e.as_ref(),
+        FixedString(e, _) => Some(e),
+        CString(_) | PString(_, _) | Pad(_) | Align(_) => None,
+        Repeat(_, RepeatBound::Count(e)) => Some(e),
+        Repeat(_, _) => None,
+    }
+}
+
+/// Renders an expression back into `.packet` syntax.
+fn dsl_expr(e: &ExprNode) -> String {
+    use ExprNode::*;
+    if let Some(sum) = e.as_checksum() {
+        return sum.to_string();
+    }
+    match e {
+        UnsignedInteger64Value(u) => u.to_string(),
+        Integer64Value(i) => i.to_string(),
+        Float64Value(f) => format!("{:?}", f),
+        StringValue(s) => format!("\"{}\"", s),
+        ValueReference(name, idx) => match idx {
+            Some(ix) => format!("{}[{}]", name, dsl_expr(ix)),
+            None => name.clone(),
+        },
+        ActivationRecord(fname, args) => {
+            let args_s: Vec<String> = args.iter().map(dsl_expr).collect();
+            format!("{}({})", fname, args_s.join(", "))
+        }
+        AggregateSum(id) => format!("sumof {}", id),
+        AggregateProduct(id) => format!("productof {}", id),
+        ParenthesizedExpr(inner) => format!("({})", dsl_expr(inner)),
+        GuardExpression(c, t, f) => format!(
+            "when {} then {} otherwise {}",
+            dsl_operand(c),
+            dsl_operand(t),
+            dsl_operand(f)
+        ),
+        Plus(a, b) => dsl_bin(a, b, "+"),
+        Minus(a, b) => dsl_bin(a, b, "-"),
+        Mult(a, b) => dsl_bin(a, b, "*"),
+        Div(a, b) => dsl_bin(a, b, "/"),
+        Pow(a, b) => dsl_bin(a, b, "^"),
+        Mod(a, b) => dsl_bin(a, b, "%"),
+        Negate(a) => format!("-{}", dsl_operand(a)),
+        BitAnd(a, b) => dsl_bin(a, b, "&"),
+        BitOr(a, b) => dsl_bin(a, b, "|"),
+        BitNot(a) => format!("~{}", dsl_operand(a)),
+        ShiftLeft(a, b) => dsl_bin(a, b, "<<"),
+        ShiftRight(a, b) => dsl_bin(a, b, ">>"),
+        Gt(a, b) => dsl_bin(a, b, ">"),
+        Gte(a, b) => dsl_bin(a, b, ">="),
+        Lt(a, b) => dsl_bin(a, b, "<"),
+        Lte(a, b) => dsl_bin(a, b, "<="),
+        Equals(a, b) => dsl_bin(a, b, "=="),
+        NotEquals(a, b) => dsl_bin(a, b, "!="),
+        And(a, b) => dsl_bin(a, b, "and"),
+        Or(a, b) => dsl_bin(a, b, "or"),
+        Not(a) => format!("not {}", dsl_operand(a)),
+        NoExpr => String::new(),
+    }
+}
+
+fn dsl_bin(a: &ExprNode, b: &ExprNode, op: &str) -> String {
+    format!("{} {} {}", dsl_operand(a), op, dsl_operand(b))
+}
+
+/// Nested compound operands are parenthesized so precedence survives rendering.
+fn dsl_operand(e: &ExprNode) -> String {
+    wrap_if_compound(&dsl_expr(e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::load_source;
+
+    fn md(src: &str) -> String {
+        MarkdownGenerator::generate(&load_source(src))
+    }
+
+    #[test]
+    fn tables_list_offsets_and_widths() {
+        let out = md("/// A greeting.
+            packet Hello be {
+                kind uint8,
+                /// Length of `body`.
+                size uint16,
+                body bytes[size],
+                calc total uint32 = size + 3
+            }");
+        assert!(out.contains("## Hello\n\nA greeting.\n\nByte order: big-endian\n"));
+        assert!(out.contains("| 1 | 2 | `size` | `uint16` | big-endian |  | Length of `body`. |\n"));
+        assert!(out.contains("| 3 | `size` | `body` | `bytes[size]` | - | `size` |  |\n"));
+        assert!(out.contains("| `total` | `uint32` | `size + 3` |  |\n"));
+    }
+
+    #[test]
+    fn diagrams_pack_fields_into_32_bit_rows() {
+        let out = md("packet Hello be { kind uint8, flags uint8, size uint16, addr uint32, body bytes[size] }");
+        let rule = "+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+";
+        assert!(out.contains(&format!("{rule}\n|     kind      |     flags     |             size              |\n{rule}\n")));
+        assert!(out.contains(&format!("|                             addr                              |\n{rule}\n")));
+        assert!(out.contains(&format!(":                       body (size bytes)                       :\n{rule}\n```")));
+    }
+}
@@ -3,4 +3,9 @@ pub mod zig_generator;
 pub mod rust_generator;
 pub mod net_generator;
 pub mod python_generator;
-pub mod go_generator;
\ No newline at end of file
+pub mod go_generator;
+pub mod swift_generator;
+pub mod wireshark_generator;
+pub mod kaitai_generator;
+pub mod json_generator;
+pub mod markdown_generator;
\ No newline at end of file
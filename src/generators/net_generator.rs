@@ -1,5 +1,6 @@
 use crate::models::parsing_models::{
-    Endianness, ExprNode, PacketExpr, PacketExprList, TypeNode,
+    BitSlot, Check, Endianness, ExprNode, FixedBase, Leftover, PacketExpr, PacketExprList, RepeatBound, StringEncoding,
+    TimeEncoding, TypeNode, VarIntEncoding, eval_intrinsic,
 };
 use crate::utilities::{CaseWrapper, Casing};
 use std::fmt::Write as _;
@@ -9,7 +10,30 @@ impl CSharpGenerator {
     pub fn generate(model: &PacketExprList) -> String {
         let mut out = String::new();
         out.push_str(&Self::create_headers());
+        if let Some(ns) = cs_namespace(model) {
+            let _ = writeln!(&mut out, "namespace {};", ns);
+        }
         out.push_str(&Self::create_spacer());
+        if model.packets.iter().flat_map(|p| &p.fields).any(|f| is_string(&f.expr)) {
+            out.push_str(STRING_HELPERS);
+            out.push_str(&Self::create_spacer());
+        }
+        if model.packets.iter().flat_map(|p| &p.fields).any(|f| matches!(f.expr, TypeNode::DateTime(_, _))) {
+            out.push_str(TIME_HELPERS);
+            out.push_str(&Self::create_spacer());
+        }
+        if model.packets.iter().flat_map(|p| &p.fields).any(|f| matches!(f.expr, TypeNode::VarInt(_, _))) {
+            out.push_str(VARINT_HELPERS);
+            out.push_str(&Self::create_spacer());
+        }
+        if model.packets.iter().any(|p| !p.checksums().is_empty()) {
+            out.push_str(CHECKSUM_HELPERS);
+            out.push_str(&Self::create_spacer());
+        }
+        if model.packets.iter().any(|p| !p.checks().is_empty()) {
+            out.push_str(VALIDATION_EXCEPTION);
+            out.push_str(&Self::create_spacer());
+        }
 
         for pkt in &model.packets {
             out.push_str(&Self::build_class(pkt));
@@ -23,11 +47,13 @@ impl CSharpGenerator {
     }
 
     fn create_headers() -> String {
-        // We keep it minimal; consumers can add namespaces/partials as desired.
+        // We keep it minimal; a `package` declaration adds a file-scoped namespace.
         // Uses BinaryPrimitives + BitConverter + Buffer.BlockCopy.
         "\
 using System;
 using System.Buffers.Binary;
+using System.Net;
+using System.Text;
 
 "
         .to_string()
@@ -38,9 +64,18 @@ using System.Buffers.Binary;
 
         // 1) properties
         let mut props = String::new();
-        for f in &pkt.fields {
+        // filler is not stored, nor are checksums, which are filled in and checked by the codec
+        for f in pkt.fields.iter().filter(|f| !f.is_filler() && f.checksum().is_none()) {
             let prop_ty = cs_field_type(&f.expr);
             let prop_name = CaseWrapper(f.id.clone()).to_pascal_case();
+            props.push_str(&xml_doc(&f.doc, "    "));
+            // const fields are written as given and checked when read
+            if let Some(value) = f.const_literal() {
+                let _ = writeln!(&mut props, "    public const {prop_ty} {prop_name} = {value};");
+                continue;
+            }
+            // optional fields are null when absent
+            let prop_ty = if f.condition.is_some() { cs_optional_type(&prop_ty) } else { prop_ty };
             let _ = writeln!(
                 &mut props,
                 "    public {} {} {{ get; set; }}",
@@ -51,29 +86,176 @@ using System.Buffers.Binary;
         // 2) Serialize method
         let mut size_code = String::new();
         let mut ser_body = String::new();
-        for f in &pkt.fields {
-            let prop_name = CaseWrapper(f.id.clone()).to_pascal_case();
+        let slots = pkt.bit_slots();
+        let derived = pkt.derived_counts();
+        let marks = pkt.checksum_marks();
+        for (((f, slot), count), &(start, end)) in pkt.fields.iter().zip(&slots).zip(&derived).zip(&marks) {
+            let mut prop_name = CaseWrapper(f.id.clone()).to_pascal_case();
+            if start {
+                let _ = writeln!(&mut ser_body, "int _{}_start = pos;", f.id);
+            }
+            // count fields are written from the field they size; checked() throws on overflow
+            if let Some(count) = count {
+                let source = CaseWrapper(pkt.fields[count.source].id.clone()).to_pascal_case();
+                let _ = writeln!(
+                    &mut size_code,
+                    "var _{prop_name} = checked(({})(({source}?.Length ?? 0){}));",
+                    cs_field_type(&f.expr),
+                    cs_adjust(count.adjust)
+                );
+                prop_name = format!("_{prop_name}");
+            }
+            if let Some(slot) = slot {
+                let (size, ser, _) = bit_snippets(&prop_name, slot);
+                size_code.push_str(&size);
+                ser_body.push_str(&ser);
+                if end {
+                    let _ = writeln!(&mut ser_body, "int _{}_end = pos;", f.id);
+                }
+                continue;
+            }
+            // left as zero, then filled in once the packet is encoded
+            if let Some(sum) = f.checksum() {
+                let _ = writeln!(&mut size_code, "total += {};", sum.kind.width_bytes());
+                let _ = writeln!(&mut ser_body, "int _{}_at = pos; pos += {};", f.id, sum.kind.width_bytes());
+                if end {
+                    let _ = writeln!(&mut ser_body, "int _{}_end = pos;", f.id);
+                }
+                continue;
+            }
             let endian = f
                 .endianness
                 .as_ref()
                 .or(pkt.endianness.as_ref())
                 .unwrap_or(&Endianness::Le);
+            // optional fields are written when their condition holds, and must be set then
+            if let Some(cond) = &f.condition {
+                let open = format!(
+                    "if ({} != 0.0)\n{{\n    var _{prop_name} = {prop_name} ?? throw new InvalidOperationException(\"{}: {} must be set when its condition holds\");\n",
+                    emit_cs_expr(cond, ""),
+                    pkt.name,
+                    f.id
+                );
+                let local = format!("_{prop_name}");
+                size_code.push_str(&open);
+                size_code.push_str(&indent(&size_calc_snippet(&f.expr, &local), 1));
+                size_code.push_str("}\n");
+                ser_body.push_str(&open);
+                ser_body.push_str(&indent(&serialize_snippet(&f.expr, &local, endian.clone()), 1));
+                ser_body.push_str("}\n");
+                continue;
+            }
             size_code.push_str(&size_calc_snippet(&f.expr, &prop_name));
             ser_body.push_str(&serialize_snippet(&f.expr, &prop_name, endian.clone()));
+            if end {
+                let _ = writeln!(&mut ser_body, "int _{}_end = pos;", f.id);
+            }
+        }
+        for f in &pkt.fields {
+            let Some(sum) = f.checksum() else { continue };
+            let endian = f.endianness.as_ref().or(pkt.endianness.as_ref()).unwrap_or(&Endianness::Le);
+            let _ = writeln!(
+                &mut ser_body,
+                "BinaryPrimitives.Write{}{}(data.AsSpan(_{}_at), PacketChecksum.{}(data.AsSpan(_{}_start.._{}_end)));",
+                if sum.kind.width_bytes() == 2 { "UInt16" } else { "UInt32" },
+                if *endian == Endianness::Be { "BigEndian" } else { "LittleEndian" },
+                f.id,
+                CaseWrapper(sum.kind.name().to_string()).to_pascal_case(),
+                sum.first,
+                sum.last
+            );
         }
 
         // 3) Deserialize method
         let mut de_body = String::new();
-        let n = pkt.fields.len();
-        for (i, f) in pkt.fields.iter().enumerate() {
-            let last = i + 1 == n;
+        for (i, (f, slot)) in pkt.fields.iter().zip(&slots).enumerate() {
+            for (k, _) in pkt.regions_opening(i) {
+                de_body.push_str(&region_open(pkt, k));
+            }
             let prop_name = CaseWrapper(f.id.clone()).to_pascal_case();
-            let endian = f.endianness.as_ref().or(pkt.endianness.as_ref()).unwrap_or(&Endianness::Le);
-            de_body.push_str(&deserialize_snippet(&f.expr, &prop_name, endian.clone(), last));
+            let (start, end) = marks[i];
+            if start {
+                let _ = writeln!(&mut de_body, "int _{}_start = pos;", f.id);
+            }
+            if let Some(slot) = slot {
+                de_body.push_str(&bit_snippets(&prop_name, slot).2);
+            } else if f.checksum().is_some() {
+                let endian = f.endianness.as_ref().or(pkt.endianness.as_ref()).unwrap_or(&Endianness::Le);
+                let _ = writeln!(&mut de_body, "int _{}_at = pos;", f.id);
+                let _ = writeln!(&mut de_body, "{} _{prop_name};", cs_field_type(&f.expr));
+                de_body.push_str(&deserialize_snippet(&f.expr, &format!("_{prop_name}"), endian.clone(), false));
+            } else if let Some(value) = f.const_literal() {
+                let endian = f.endianness.as_ref().or(pkt.endianness.as_ref()).unwrap_or(&Endianness::Le);
+                let _ = writeln!(&mut de_body, "{} _{prop_name};", cs_field_type(&f.expr));
+                de_body.push_str(&deserialize_snippet(&f.expr, &format!("_{prop_name}"), endian.clone(), false));
+                let _ = writeln!(
+                    &mut de_body,
+                    "if (_{prop_name} != {prop_name}) throw new FormatException($\"{}: {} must be {value}, got 0x{{_{prop_name}:x}}\");",
+                    pkt.name,
+                    f.id
+                );
+            } else if let Some(cond) = &f.condition {
+                let endian = f.endianness.as_ref().or(pkt.endianness.as_ref()).unwrap_or(&Endianness::Le);
+                let local = format!("_{prop_name}");
+                let _ = writeln!(&mut de_body, "if ({} != 0.0)\n{{", emit_cs_expr(cond, "result."));
+                let _ = writeln!(&mut de_body, "    {} {local};", cs_field_type(&f.expr));
+                de_body.push_str(&indent(&deserialize_snippet(&f.expr, &local, endian.clone(), pkt.is_last_in_scope(i)), 1));
+                let _ = writeln!(&mut de_body, "    result.{prop_name} = {local};\n}}");
+            } else {
+                let endian = f.endianness.as_ref().or(pkt.endianness.as_ref()).unwrap_or(&Endianness::Le);
+                de_body.push_str(&deserialize_snippet(&f.expr, &prop_name, endian.clone(), pkt.is_last_in_scope(i)));
+            }
+            if end {
+                let _ = writeln!(&mut de_body, "int _{}_end = pos;", f.id);
+            }
+            if let Some(check) = &f.constraint {
+                de_body.push_str(&check_snippet(pkt, check, "result."));
+            }
+            for (k, _) in pkt.regions_closing(i) {
+                de_body.push_str(&region_close(pkt, k));
+            }
+        }
+        // checksums are recomputed with their own field zeroed, as when they were filled in
+        for f in &pkt.fields {
+            let Some(sum) = f.checksum() else { continue };
+            let prop_name = CaseWrapper(f.id.clone()).to_pascal_case();
+            let _ = writeln!(
+                &mut de_body,
+                "var _{prop_name}Sum = PacketChecksum.{}(PacketChecksum.Without(data, _{}_start, _{}_end, _{}_at, {}));",
+                CaseWrapper(sum.kind.name().to_string()).to_pascal_case(),
+                sum.first,
+                sum.last,
+                f.id,
+                sum.kind.width_bytes()
+            );
+            let _ = writeln!(
+                &mut de_body,
+                "if (_{prop_name} != _{prop_name}Sum) throw new FormatException($\"{}: {} is 0x{{_{prop_name}:x}} but the {} of {}..{} is 0x{{_{prop_name}Sum:x}}\");",
+                pkt.name,
+                f.id,
+                sum.kind.name(),
+                sum.first,
+                sum.last
+            );
         }
+        for check in &pkt.asserts {
+            de_body.push_str(&check_snippet(pkt, check, "result."));
+        }
+
+        // 4) Validate method, for checking a packet before serializing it
+        let checks = pkt.checks();
+        let validate = if checks.is_empty() {
+            String::new()
+        } else {
+            let body: String = checks.iter().map(|c| check_snippet(pkt, c, "")).collect();
+            format!(
+                "\n    /// <summary>\n    /// Throws PacketValidationException if a `where` or `assert` rule fails; Deserialize checks them too.\n    /// </summary>\n    public void Validate()\n    {{\n{}    }}\n",
+                indent(&body, 2)
+            )
+        };
 
         format!(
-            r#"public class {class_name}
+            r#"{doc}public class {class_name}
 {{
 {props}
     public byte[] Serialize()
@@ -88,16 +270,23 @@ using System.Buffers.Binary;
         return data;
     }}
 
-    public static {class_name} Deserialize(byte[] data)
+    public static {class_name} Deserialize(byte[] data) => DeserializePrefix(data, out _);
+
+    /// <summary>
+    /// Decodes one {class_name} from the front of <paramref name="data"/>; <paramref name="used"/> is the bytes it took.
+    /// </summary>
+    public static {class_name} DeserializePrefix(byte[] data, out int used)
     {{
         var result = new {class_name}();
         int pos = 0;
 
 {de_body}
+        used = pos;
         return result;
     }}
-}}
+{validate}}}
 "#,
+            doc = xml_doc(&pkt.doc, ""),
             class_name = class_name,
             props = props,
             size_code = indent(&size_code, 2),
@@ -107,11 +296,93 @@ using System.Buffers.Binary;
     }
 }
 
+/// Strict encodings for string fields (decoding invalid bytes throws
+/// DecoderFallbackException), emitted only when a packet has one.
+const STRING_HELPERS: &str = r#"internal static class PacketStrings
+{
+    public static readonly Encoding Ascii =
+        Encoding.GetEncoding("us-ascii", EncoderFallback.ReplacementFallback, DecoderFallback.ExceptionFallback);
+    public static readonly Encoding Utf8 = new UTF8Encoding(false, true);
+    public static readonly Encoding Utf16Le = new UnicodeEncoding(false, false, true);
+
+    /// <summary>
+    /// Index of the first NUL code unit (1 byte, or 2 for UTF-16) in data[start..end), or -1.
+    /// </summary>
+    public static int NulIndex(byte[] data, int start, int end, int unit)
+    {
+        for (int i = start; i + unit <= end; i += unit)
+        {
+            if (data[i] == 0 && (unit == 1 || data[i + 1] == 0)) return i;
+        }
+        return -1;
+    }
+}
+"#;
+
+/// Conversions between DateTimeOffset and the wire value of each datetime
+/// encoding, emitted only when a packet has a datetime field.
+const TIME_HELPERS: &str = r#"internal static class PacketTime
+{
+    static readonly DateTimeOffset NtpEpoch = new DateTimeOffset(1900, 1, 1, 0, 0, 0, TimeSpan.Zero);
+
+    public static long ToUnixS(DateTimeOffset t) => t.ToUnixTimeSeconds();
+    public static DateTimeOffset FromUnixS(long v) => DateTimeOffset.FromUnixTimeSeconds(v);
+    public static long ToUnixMs(DateTimeOffset t) => t.ToUnixTimeMilliseconds();
+    public static DateTimeOffset FromUnixMs(long v) => DateTimeOffset.FromUnixTimeMilliseconds(v);
+    public static long ToUnixUs(DateTimeOffset t) => (t.UtcTicks - DateTimeOffset.UnixEpoch.UtcTicks) / 10;
+    public static DateTimeOffset FromUnixUs(long v) => DateTimeOffset.UnixEpoch.AddTicks(v * 10);
+
+    /// <summary>
+    /// NTP 32.32 fixed point seconds since 1900-01-01; wraps at the end of each era.
+    /// </summary>
+    public static ulong ToNtp64(DateTimeOffset t)
+    {
+        long ticks = t.UtcTicks - NtpEpoch.UtcTicks;
+        ulong secs = (ulong)(ticks / TimeSpan.TicksPerSecond);
+        ulong frac = ((ulong)(ticks % TimeSpan.TicksPerSecond) << 32) / 10_000_000UL;
+        return (secs << 32) | frac;
+    }
+    public static DateTimeOffset FromNtp64(ulong v)
+    {
+        long secs = (long)(v >> 32);
+        long frac = (long)(((v & 0xFFFFFFFFUL) * 10_000_000UL) >> 32);
+        return NtpEpoch.AddTicks(secs * TimeSpan.TicksPerSecond + frac);
+    }
+
+    public static ulong ToFileTime(DateTimeOffset t) => (ulong)t.ToFileTime();
+    public static DateTimeOffset FromFileTime(ulong v) => DateTimeOffset.FromFileTime((long)v).ToUniversalTime();
+}
+"#;
+
 /* ============================================================
  * Mapping & helpers
  * ============================================================
 */
 
+/// File-scoped namespace for a package: `oscar.v1` -> `Oscar.V1`.
+fn cs_namespace(model: &PacketExprList) -> Option<String> {
+    let segments = model.package_segments();
+    if segments.is_empty() {
+        return None;
+    }
+    Some(
+        segments
+            .iter()
+            .map(|s| CaseWrapper(s.to_string()).to_pascal_case())
+            .collect::<Vec<_>>()
+            .join("."),
+    )
+}
+
+/// Nullable form of a property type; arrays, strings and addresses are references already.
+fn cs_optional_type(ty: &str) -> String {
+    if ty.ends_with("[]") || ty == "string" || ty == "IPAddress" {
+        ty.to_string()
+    } else {
+        format!("{ty}?")
+    }
+}
+
 fn cs_field_type(t: &TypeNode) -> String {
     use TypeNode::*;
     let array_of = |base: &str, len: &Option<ExprNode>| {
@@ -135,24 +406,57 @@ fn cs_field_type(t: &TypeNode) -> String {
         Float32(len) => array_of("float", len),
         Float64(len) => array_of("double", len),
 
-        // DateTime: represent on-wire as 64-bit (epoch/ticks). Expose long(s).
-        DateTime(len) => array_of("long", len),
+        // DateTime: 64-bit on the wire, converted through PacketTime
+        DateTime(len, _) => array_of("DateTimeOffset", len),
+
+        Bool(len, _) => array_of("bool", len),
+        // Fixed point: the raw integer scaled by 2^-frac
+        Fixed(len, _, _) => array_of("double", len),
+        VarInt(len, VarIntEncoding::ZigZag) => array_of("long", len),
+        VarInt(len, _) => array_of("ulong", len),
 
         // Opaque byte blobs
         Bytes(_len) => "byte[]".to_string(),
         // Mac addresses as bytes (6 when const)
         MacAddress(_len) => "byte[]".to_string(),
+
+        Ipv4Address(len) | Ipv6Address(len) => array_of("IPAddress", len),
+        Port(len) => array_of("ushort", len),
+
+        FixedString(_, _) | CString(_) | PString(_, _) => "string".to_string(),
+        Repeat(elem, _) => format!("{}[]", CaseWrapper(elem.clone()).to_pascal_case()),
+        Pad(_) | Align(_) => "byte[]".to_string(), // never stored
     }
 }
 
 fn scalar_width_bytes(t: &TypeNode) -> usize {
     use TypeNode::*;
     match t {
-        UnsignedInteger8(_) | Integer8(_) => 1,
-        UnsignedInteger16(_) | Integer16(_) => 2,
-        UnsignedInteger32(_) | Integer32(_) | Float32(_) => 4,
-        UnsignedInteger64(_) | Integer64(_) | Float64(_) | DateTime(_) => 8,
+        UnsignedInteger8(_) | Integer8(_) | Bool(_, _) => 1,
+        UnsignedInteger16(_) | Integer16(_) | Port(_) => 2,
+        UnsignedInteger32(_) | Integer32(_) | Float32(_) | Ipv4Address(_) => 4,
+        UnsignedInteger64(_) | Integer64(_) | Float64(_) | DateTime(_, _) => 8,
+        Ipv6Address(_) => 16,
+        Fixed(_, base, _) => base.width_bytes(),
         Bytes(_) | MacAddress(_) => 1, // element width; handled separately
+        FixedString(_, _) | CString(_) => 1,
+        PString(_, _) => t.scalar_width_bytes(), // the length prefix
+        VarInt(_, _) => 0,                       // variable, sized per value
+        Repeat(_, _) => 0,                       // sized by its elements
+        Pad(_) | Align(_) => 1,
+    }
+}
+
+fn is_string(t: &TypeNode) -> bool {
+    matches!(t, TypeNode::FixedString(_, _) | TypeNode::CString(_) | TypeNode::PString(_, _))
+}
+
+/// `PacketStrings` member and code-unit width (for NUL terminators) of an encoding.
+fn cs_encoding(e: &StringEncoding) -> (&'static str, usize) {
+    match e {
+        StringEncoding::Ascii => ("PacketStrings.Ascii", 1),
+        StringEncoding::Utf8 => ("PacketStrings.Utf8", 1),
+        StringEncoding::Utf16Le => ("PacketStrings.Utf16Le", 2),
     }
 }
 
@@ -160,6 +464,7 @@ fn is_array_like(t: &TypeNode) -> bool {
     use TypeNode::*;
     match t {
         Bytes(_) | MacAddress(_) => true,
+        FixedString(_, _) | CString(_) | PString(_, _) | Repeat(_, _) | Pad(_) | Align(_) => false,
         UnsignedInteger8(len)
         | Integer8(len)
         | UnsignedInteger16(len)
@@ -170,8 +475,90 @@ fn is_array_like(t: &TypeNode) -> bool {
         | Integer64(len)
         | Float32(len)
         | Float64(len)
-        | DateTime(len) => len.is_some(),
+        | DateTime(len, _)
+        | Bool(len, _)
+        | Fixed(len, _, _)
+        | VarInt(len, _)
+        | Ipv4Address(len)
+        | Ipv6Address(len)
+        | Port(len) => len.is_some(),
+    }
+}
+
+/// C# integer type and `BinaryPrimitives` name of a fixed-point base.
+fn cs_fixed(base: &FixedBase) -> (&'static str, &'static str) {
+    match base {
+        FixedBase::Int8 => ("sbyte", "SByte"),
+        FixedBase::UInt8 => ("byte", "Byte"),
+        FixedBase::Int16 => ("short", "Int16"),
+        FixedBase::UInt16 => ("ushort", "UInt16"),
+        FixedBase::Int32 => ("int", "Int32"),
+        FixedBase::UInt32 => ("uint", "UInt32"),
+        FixedBase::Int64 => ("long", "Int64"),
+        FixedBase::UInt64 => ("ulong", "UInt64"),
+    }
+}
+
+/// Start of a `within` region: `data` is swapped for a copy cut at the region's
+/// end (positions stay absolute), so reads past it throw.
+fn region_open(pkt: &PacketExpr, k: usize) -> String {
+    let region = &pkt.regions[k];
+    let at = &pkt.fields[region.first].id;
+    let mut s = String::new();
+    let _ = writeln!(&mut s, "int _end{k} = pos + (int)({});", emit_cs_expr(&region.length, "result."));
+    let _ = writeln!(
+        &mut s,
+        "if (_end{k} > data.Length) throw new FormatException(\"{}: within block at {at} runs past the end of the data\");",
+        pkt.name
+    );
+    let _ = writeln!(&mut s, "var _outer{k} = data; data = data[.._end{k}];");
+    s
+}
+
+fn region_close(pkt: &PacketExpr, k: usize) -> String {
+    let region = &pkt.regions[k];
+    let at = &pkt.fields[region.first].id;
+    let mut s = String::new();
+    match region.leftover {
+        Leftover::Error => {
+            let _ = writeln!(
+                &mut s,
+                "if (pos < _end{k}) throw new FormatException($\"{}: within block at {at} left {{_end{k} - pos}} bytes unread\");",
+                pkt.name
+            );
+        }
+        Leftover::Skip => {
+            let _ = writeln!(&mut s, "pos = _end{k};");
+        }
     }
+    let _ = writeln!(&mut s, "data = _outer{k};");
+    s
+}
+
+/// (size, serialize, deserialize) snippets of a `bool(bit)` property. `data`
+/// starts zeroed, so only set bits are written; `pos` moves after a byte's last bit.
+fn bit_snippets(name: &str, slot: &BitSlot) -> (String, String, String) {
+    let mask = 1u8 << slot.shift();
+    let advance = if slot.ends_byte() { " pos += 1;" } else { "" };
+    let size = if slot.starts_byte() { "total += 1;\n".to_string() } else { String::new() };
+    let ser = format!("if ({name}) data[pos] |= 0x{mask:02X};{advance}\n");
+    let de = format!("{name} = (data[pos] & 0x{mask:02X}) != 0;{advance}\n");
+    (size, ser, de)
+}
+
+/// `PacketTime` method suffix of an encoding and whether its wire value is signed.
+fn cs_time(unit: &TimeEncoding) -> (&'static str, bool) {
+    match unit {
+        TimeEncoding::UnixSeconds => ("UnixS", true),
+        TimeEncoding::UnixMillis => ("UnixMs", true),
+        TimeEncoding::UnixMicros => ("UnixUs", true),
+        TimeEncoding::Ntp64 => ("Ntp64", false),
+        TimeEncoding::FileTime => ("FileTime", false),
+    }
+}
+
+fn is_address(t: &TypeNode) -> bool {
+    matches!(t, TypeNode::Ipv4Address(_) | TypeNode::Ipv6Address(_))
 }
 
 fn eval_len_const(expr: &ExprNode) -> Option<usize> {
@@ -213,12 +600,198 @@ fn eval_len_const(expr: &ExprNode) -> Option<usize> {
                 }
                 Some(acc)
             }
+            Mod(a, b) => {
+                let d = eval_i128(b)?;
+                if d == 0 {
+                    None
+                } else {
+                    Some(eval_i128(a)? % d)
+                }
+            }
+            Negate(a) => eval_i128(a)?.checked_neg(),
+            BitAnd(a, b) => Some((eval_i128(a)? as i64 & eval_i128(b)? as i64) as i128),
+            BitOr(a, b) => Some((eval_i128(a)? as i64 | eval_i128(b)? as i64) as i128),
+            BitNot(a) => Some(!(eval_i128(a)? as i64) as i128),
+            ShiftLeft(a, b) => {
+                Some((eval_i128(a)? as i64).wrapping_shl((eval_i128(b)? & 63) as u32) as i128)
+            }
+            ShiftRight(a, b) => {
+                Some((eval_i128(a)? as i64).wrapping_shr((eval_i128(b)? & 63) as u32) as i128)
+            }
+            ActivationRecord(name, args) => eval_intrinsic(name, args, eval_i128),
             _ => None,
         }
     }
     eval_i128(expr).and_then(|n| if n >= 0 { Some(n as usize) } else { None })
 }
 
+/// Checksum intrinsics, emitted only when a packet uses one.
+const CHECKSUM_HELPERS: &str = r#"internal static class PacketChecksum
+{
+    /// <summary>CRC-32 (IEEE 802.3).</summary>
+    public static uint Crc32(ReadOnlySpan<byte> data)
+    {
+        uint crc = 0xFFFFFFFF;
+        foreach (byte b in data)
+        {
+            crc ^= b;
+            for (int i = 0; i < 8; i++)
+                crc = (crc & 1) != 0 ? (crc >> 1) ^ 0xEDB88320 : crc >> 1;
+        }
+        return ~crc;
+    }
+
+    /// <summary>CRC-16/CCITT-FALSE.</summary>
+    public static ushort Crc16Ccitt(ReadOnlySpan<byte> data)
+    {
+        ushort crc = 0xFFFF;
+        foreach (byte b in data)
+        {
+            crc ^= (ushort)(b << 8);
+            for (int i = 0; i < 8; i++)
+                crc = (crc & 0x8000) != 0 ? (ushort)((crc << 1) ^ 0x1021) : (ushort)(crc << 1);
+        }
+        return crc;
+    }
+
+    /// <summary>RFC 1071 Internet checksum; an odd last byte is padded with zero.</summary>
+    public static ushort InetChecksum(ReadOnlySpan<byte> data)
+    {
+        ulong sum = 0;
+        for (int i = 0; i < data.Length; i += 2)
+            sum += (ulong)(data[i] << 8 | (i + 1 < data.Length ? data[i + 1] : 0));
+        while (sum > 0xFFFF)
+            sum = (sum & 0xFFFF) + (sum >> 16);
+        return (ushort)~sum;
+    }
+
+    /// <summary>Adler-32 (RFC 1950).</summary>
+    public static uint Adler32(ReadOnlySpan<byte> data)
+    {
+        uint a = 1, b = 0;
+        foreach (byte x in data)
+        {
+            a = (a + x) % 65521;
+            b = (b + a) % 65521;
+        }
+        return b << 16 | a;
+    }
+
+    /// <summary>
+    /// data[start..end) with the <paramref name="width"/> bytes at <paramref name="at"/> zeroed,
+    /// as they were when the checksum stored there was computed.
+    /// </summary>
+    public static byte[] Without(byte[] data, int start, int end, int at, int width)
+    {
+        var copy = data[start..end];
+        for (int i = Math.Max(at, start); i < Math.Min(at + width, end); i++)
+            copy[i - start] = 0;
+        return copy;
+    }
+}
+"#;
+
+/// Exception for a broken `where` or `assert` rule, emitted only when a packet has one.
+const VALIDATION_EXCEPTION: &str = r#"/// <summary>
+/// A packet field broke one of its `where` or `assert` rules.
+/// </summary>
+public class PacketValidationException : FormatException
+{
+    public string Packet { get; }
+    public string Field { get; }
+    public string Rule { get; }
+
+    public PacketValidationException(string packet, string field, string rule)
+        : base($"{packet}: {field} breaks `{rule}`")
+    {
+        Packet = packet;
+        Field = field;
+        Rule = rule;
+    }
+}
+"#;
+
+/// LEB128, zigzag and QUIC varint codecs, emitted only when a packet has a
+/// varint field. Reads throw FormatException on an LEB128 value over 10 bytes.
+const VARINT_HELPERS: &str = r#"internal static class PacketVarint
+{
+    public static int UIntSize(ulong v)
+    {
+        int n = 1;
+        while (v >= 0x80) { v >>= 7; ++n; }
+        return n;
+    }
+
+    public static void WriteUInt(byte[] data, ref int pos, ulong v)
+    {
+        while (v >= 0x80) { data[pos++] = (byte)(v | 0x80); v >>= 7; }
+        data[pos++] = (byte)v;
+    }
+
+    public static ulong ReadUInt(byte[] data, ref int pos)
+    {
+        ulong v = 0;
+        for (int shift = 0; shift < 70; shift += 7)
+        {
+            byte b = data[pos++];
+            v |= (ulong)(b & 0x7F) << shift;
+            if ((b & 0x80) == 0) return v;
+        }
+        throw new FormatException("varint longer than 10 bytes");
+    }
+
+    public static ulong ZigZag(long v) => (ulong)((v << 1) ^ (v >> 63));
+    public static long UnZigZag(ulong u) => (long)(u >> 1) ^ -(long)(u & 1);
+
+    /// <summary>
+    /// Encoded length of a QUIC varint; values of 2^62 and up do not fit.
+    /// </summary>
+    public static int QuicSize(ulong v)
+    {
+        if (v >= 1UL << 62) throw new ArgumentOutOfRangeException(nameof(v), "too large for a QUIC varint");
+        return v < 0x40 ? 1 : v < 0x4000 ? 2 : v < 0x40000000 ? 4 : 8;
+    }
+
+    public static void WriteQuic(byte[] data, ref int pos, ulong v)
+    {
+        int n = QuicSize(v);
+        for (int i = 0; i < n; ++i) data[pos + i] = (byte)(v >> (8 * (n - 1 - i)));
+        data[pos] |= (byte)((n == 1 ? 0 : n == 2 ? 1 : n == 4 ? 2 : 3) << 6);
+        pos += n;
+    }
+
+    public static ulong ReadQuic(byte[] data, ref int pos)
+    {
+        int n = 1 << (data[pos] >> 6);
+        ulong v = (ulong)(data[pos] & 0x3F);
+        for (int i = 1; i < n; ++i) v = (v << 8) | data[pos + i];
+        pos += n;
+        return v;
+    }
+}
+"#;
+
+/// (size, write, read) expressions of a varint value `v`; write and read advance `pos`.
+fn cs_varint(enc: &VarIntEncoding, v: &str) -> (String, String, String) {
+    match enc {
+        VarIntEncoding::Leb128 => (
+            format!("PacketVarint.UIntSize({v})"),
+            format!("PacketVarint.WriteUInt(data, ref pos, {v})"),
+            "PacketVarint.ReadUInt(data, ref pos)".to_string(),
+        ),
+        VarIntEncoding::ZigZag => (
+            format!("PacketVarint.UIntSize(PacketVarint.ZigZag({v}))"),
+            format!("PacketVarint.WriteUInt(data, ref pos, PacketVarint.ZigZag({v}))"),
+            "PacketVarint.UnZigZag(PacketVarint.ReadUInt(data, ref pos))".to_string(),
+        ),
+        VarIntEncoding::Quic => (
+            format!("PacketVarint.QuicSize({v})"),
+            format!("PacketVarint.WriteQuic(data, ref pos, {v})"),
+            "PacketVarint.ReadQuic(data, ref pos)".to_string(),
+        ),
+    }
+}
+
 /* ============================================================
  * Codegen snippets
  * ============================================================
@@ -228,6 +801,13 @@ fn size_calc_snippet(t: &TypeNode, name: &str) -> String {
     use TypeNode::*;
     let mut s = String::new();
     match t {
+        // `total` is the offset so far, so it rounds up like `pos` does
+        Pad(n) => {
+            let _ = writeln!(&mut s, "total += {n};");
+        }
+        Align(n) => {
+            let _ = writeln!(&mut s, "total = (total + {n} - 1) / {n} * {n};");
+        }
         Bytes(len_opt) => {
             if let Some(expr) = len_opt {
                 if let Some(n) = eval_len_const(expr) {
@@ -250,6 +830,34 @@ fn size_calc_snippet(t: &TypeNode, name: &str) -> String {
                 let _ = writeln!(&mut s, "total += 6;");
             }
         }
+        FixedString(len, enc) => match eval_len_const(len) {
+            Some(n) => {
+                let _ = writeln!(&mut s, "total += {n};");
+            }
+            None => {
+                let (enc, _) = cs_encoding(enc);
+                let _ = writeln!(&mut s, "total += {enc}.GetByteCount({name} ?? \"\");");
+            }
+        },
+        CString(enc) => {
+            let (enc, unit) = cs_encoding(enc);
+            let _ = writeln!(&mut s, "total += {enc}.GetByteCount({name} ?? \"\") + {unit};");
+        }
+        PString(_, enc) => {
+            let (enc, _) = cs_encoding(enc);
+            let w = scalar_width_bytes(t);
+            let _ = writeln!(&mut s, "total += {w} + {enc}.GetByteCount({name} ?? \"\");");
+        }
+        Repeat(_, _) => {
+            let _ = writeln!(&mut s, "if ({name} != null) foreach (var v in {name}) total += v.Serialize().Length;");
+        }
+        VarInt(len, enc) => {
+            if len.is_some() {
+                let _ = writeln!(&mut s, "if ({name} != null) foreach (var v in {name}) total += {};", cs_varint(enc, "v").0);
+            } else {
+                let _ = writeln!(&mut s, "total += {};", cs_varint(enc, name).0);
+            }
+        }
         _ => {
             let w = scalar_width_bytes(t);
             if is_array_like(t) {
@@ -262,10 +870,31 @@ fn size_calc_snippet(t: &TypeNode, name: &str) -> String {
     s
 }
 
+/// ` - a` for a length written `l + a`, so the count comes out as `l`.
+fn cs_adjust(adjust: i64) -> String {
+    match adjust {
+        0 => String::new(),
+        a if a < 0 => format!(" + {}", -a),
+        a => format!(" - {a}"),
+    }
+}
+
 fn serialize_snippet(t: &TypeNode, name: &str, endian: Endianness) -> String {
     use Endianness::*;
     let mut s = String::new();
     match t {
+        // filler: `data` starts zeroed, so it is only passed over
+        TypeNode::Pad(n) => {
+            let _ = writeln!(&mut s, "pos += {n};");
+        }
+        TypeNode::Align(n) => {
+            let _ = writeln!(&mut s, "pos = (pos + {n} - 1) / {n} * {n};");
+        }
+        TypeNode::Repeat(_, _) => {
+            let _ = writeln!(&mut s, "if ({name} != null) foreach (var v in {name}) {{");
+            let _ = writeln!(&mut s, "    var b = v.Serialize(); Buffer.BlockCopy(b, 0, data, pos, b.Length); pos += b.Length;");
+            let _ = writeln!(&mut s, "}}");
+        }
         TypeNode::Bytes(_) | TypeNode::MacAddress(_) => {
             // Copy bytes as-is (mac default length is accounted in size)
             let _ = writeln!(
@@ -273,9 +902,101 @@ fn serialize_snippet(t: &TypeNode, name: &str, endian: Endianness) -> String {
                 "if ({name} != null) {{ Buffer.BlockCopy({name}, 0, data, pos, {name}.Length); pos += {name}.Length; }}"
             );
         }
+        // `data` starts zeroed, so padding and terminators only need `pos` to advance
+        TypeNode::FixedString(len, enc) => {
+            let (enc, _) = cs_encoding(enc);
+            let _ = writeln!(&mut s, "{{ var b = {enc}.GetBytes({name} ?? \"\");");
+            match eval_len_const(len) {
+                Some(n) => {
+                    let _ = writeln!(
+                        &mut s,
+                        "  if (b.Length > {n}) throw new ArgumentException(\"{name}: string longer than {n} bytes\");"
+                    );
+                    let _ = writeln!(&mut s, "  Buffer.BlockCopy(b, 0, data, pos, b.Length); pos += {n}; }}");
+                }
+                None => {
+                    let _ = writeln!(&mut s, "  Buffer.BlockCopy(b, 0, data, pos, b.Length); pos += b.Length; }}");
+                }
+            }
+        }
+        TypeNode::CString(enc) => {
+            let (enc, unit) = cs_encoding(enc);
+            let _ = writeln!(
+                &mut s,
+                "{{ var b = {enc}.GetBytes({name} ?? \"\"); Buffer.BlockCopy(b, 0, data, pos, b.Length); pos += b.Length + {unit}; }}"
+            );
+        }
+        TypeNode::PString(_, enc) => {
+            let (enc, _) = cs_encoding(enc);
+            let max = if scalar_width_bytes(t) == 1 { "byte.MaxValue" } else { "ushort.MaxValue" };
+            let _ = writeln!(&mut s, "{{ var b = {enc}.GetBytes({name} ?? \"\");");
+            let _ = writeln!(
+                &mut s,
+                "  if (b.Length > {max}) throw new ArgumentException($\"{name}: string longer than {{{max}}} bytes\");"
+            );
+            let _ = match (endian, scalar_width_bytes(t)) {
+                (_, 1) => writeln!(&mut s, "  data[pos++] = (byte)b.Length;"),
+                (Le, _) => writeln!(&mut s, "  BinaryPrimitives.WriteUInt16LittleEndian(data.AsSpan(pos), (ushort)b.Length); pos += 2;"),
+                (Be, _) => writeln!(&mut s, "  BinaryPrimitives.WriteUInt16BigEndian(data.AsSpan(pos), (ushort)b.Length); pos += 2;"),
+            };
+            let _ = writeln!(&mut s, "  Buffer.BlockCopy(b, 0, data, pos, b.Length); pos += b.Length; }}");
+        }
+        // GetAddressBytes is already in network order
+        TypeNode::Ipv4Address(_) | TypeNode::Ipv6Address(_) => {
+            let w = scalar_width_bytes(t);
+            let write_addr = |dst: &mut String, expr: String| {
+                let _ = writeln!(dst, "{{ var b = {expr}.GetAddressBytes();");
+                let _ = writeln!(
+                    dst,
+                    "  if (b.Length != {w}) throw new ArgumentException(\"{name}: expected a {w}-byte address\");"
+                );
+                let _ = writeln!(dst, "  Buffer.BlockCopy(b, 0, data, pos, {w}); pos += {w}; }}");
+            };
+            if is_array_like(t) {
+                let _ = writeln!(&mut s, "if ({name} != null) {{");
+                let _ = writeln!(&mut s, "    for (int i = 0; i < {name}.Length; ++i) {{");
+                write_addr(&mut s, format!("{name}[i]"));
+                let _ = writeln!(&mut s, "    }}");
+                let _ = writeln!(&mut s, "}}");
+            } else {
+                write_addr(&mut s, name.to_string());
+            }
+        }
         _ => {
             let w = scalar_width_bytes(t);
             let write_scalar = |dst: &mut String, expr: String| {
+                if let TypeNode::DateTime(_, unit) = t {
+                    let (m, signed) = cs_time(unit);
+                    let prim = if signed { "Int64" } else { "UInt64" };
+                    let order = if endian == Be { "BigEndian" } else { "LittleEndian" };
+                    let _ = writeln!(
+                        dst,
+                        "BinaryPrimitives.Write{prim}{order}(data.AsSpan(pos), PacketTime.To{m}({expr})); pos += 8;"
+                    );
+                    return;
+                }
+                if let TypeNode::Bool(_, _) = t {
+                    let _ = writeln!(dst, "data[pos++] = (byte)({expr} ? 1 : 0);");
+                    return;
+                }
+                if let TypeNode::VarInt(_, enc) = t {
+                    let _ = writeln!(dst, "{};", cs_varint(enc, &expr).1);
+                    return;
+                }
+                if let TypeNode::Fixed(_, base, frac) = t {
+                    let (ty, prim) = cs_fixed(base);
+                    let raw = format!(
+                        "({ty})Math.Round({expr} * {}.0, MidpointRounding.AwayFromZero)",
+                        1u128 << frac
+                    );
+                    let order = if endian == Be { "BigEndian" } else { "LittleEndian" };
+                    let _ = match w {
+                        1 if ty == "byte" => writeln!(dst, "data[pos++] = unchecked({raw});"),
+                        1 => writeln!(dst, "data[pos++] = unchecked((byte){raw});"),
+                        _ => writeln!(dst, "BinaryPrimitives.Write{prim}{order}(data.AsSpan(pos), {raw}); pos += {w};"),
+                    };
+                    return;
+                }
                 match (endian, w) {
                     (_, 1) => {
                         let _ = writeln!(dst, "data[pos++] = unchecked((byte)({expr}));");
@@ -327,7 +1048,7 @@ fn serialize_snippet(t: &TypeNode, name: &str, endian: Endianness) -> String {
                                 "BinaryPrimitives.WriteInt64LittleEndian(data.AsSpan(pos), BitConverter.DoubleToInt64Bits((double)({expr}))); pos += 8;"
                             );
                         }
-                        TypeNode::Integer64(_) | TypeNode::DateTime(_) => {
+                        TypeNode::Integer64(_) => {
                             let _ = writeln!(
                                 dst,
                                 "BinaryPrimitives.WriteInt64LittleEndian(data.AsSpan(pos), (long)({expr})); pos += 8;"
@@ -347,7 +1068,7 @@ fn serialize_snippet(t: &TypeNode, name: &str, endian: Endianness) -> String {
                                 "BinaryPrimitives.WriteInt64BigEndian(data.AsSpan(pos), BitConverter.DoubleToInt64Bits((double)({expr}))); pos += 8;"
                             );
                         }
-                        TypeNode::Integer64(_) | TypeNode::DateTime(_) => {
+                        TypeNode::Integer64(_) => {
                             let _ = writeln!(
                                 dst,
                                 "BinaryPrimitives.WriteInt64BigEndian(data.AsSpan(pos), (long)({expr})); pos += 8;"
@@ -371,7 +1092,7 @@ fn serialize_snippet(t: &TypeNode, name: &str, endian: Endianness) -> String {
                 let _ = writeln!(&mut s, "    }}");
                 let _ = writeln!(&mut s, "}}");
             } else {
-                write_scalar(&mut s, format!("{name}"));
+                write_scalar(&mut s, name.to_string());
             }
         }
     }
@@ -383,6 +1104,36 @@ fn deserialize_snippet(t: &TypeNode, name: &str, endian: Endianness, is_last: bo
     let mut s = String::new();
 
     match t {
+        // filler is skipped, whatever it holds; AsSpan throws when the data ends first
+        TypeNode::Pad(n) => {
+            let _ = writeln!(&mut s, "_ = data.AsSpan(pos, {n}); pos += {n};");
+        }
+        TypeNode::Align(n) => {
+            let _ = writeln!(&mut s, "pos = (pos + {n} - 1) / {n} * {n}; _ = data.AsSpan(0, pos);");
+        }
+        // each element is decoded from the rest of the data and reports the bytes it used
+        TypeNode::Repeat(elem, bound) => {
+            let ty = CaseWrapper(elem.clone()).to_pascal_case();
+            let read = format!("var item = {ty}.DeserializePrefix(data[pos..], out int used); pos += used; list.Add(item);");
+            let _ = writeln!(&mut s, "{{ var list = new System.Collections.Generic.List<{ty}>();");
+            match bound {
+                RepeatBound::Eof => {
+                    let _ = writeln!(&mut s, "  while (pos < data.Length) {{ {read} }}");
+                }
+                RepeatBound::Until(cond) => {
+                    let _ = writeln!(&mut s, "  while (true) {{ {read}");
+                    let _ = writeln!(&mut s, "    if ({} != 0.0) break; }}", emit_cs_expr(cond, "item."));
+                }
+                RepeatBound::Count(expr) => {
+                    let count = match eval_len_const(expr) {
+                        Some(n) => n.to_string(),
+                        None => format!("(int)({})", emit_cs_expr(expr, "result.")),
+                    };
+                    let _ = writeln!(&mut s, "  for (int i = 0; i < {count}; ++i) {{ {read} }}");
+                }
+            }
+            let _ = writeln!(&mut s, "  {name} = list.ToArray(); }}");
+        }
         TypeNode::Bytes(len_opt) => {
             if let Some(expr) = len_opt {
                 if let Some(n) = eval_len_const(expr) {
@@ -438,10 +1189,77 @@ fn deserialize_snippet(t: &TypeNode, name: &str, endian: Endianness, is_last: bo
                 );
             }
         }
+        // GetString throws DecoderFallbackException on bytes invalid in the encoding
+        TypeNode::FixedString(len, enc) => {
+            let (enc, unit) = cs_encoding(enc);
+            let end = match eval_len_const(len) {
+                Some(n) => format!("pos + {n}"),
+                None if is_last => "data.Length".to_string(),
+                None => {
+                    let _ = writeln!(
+                        &mut s,
+                        r#"throw new NotSupportedException("Dynamic-length string field not at end of buffer");"#
+                    );
+                    return s;
+                }
+            };
+            let _ = writeln!(&mut s, "{{ int end = {end}; int nul = PacketStrings.NulIndex(data, pos, end, {unit});");
+            let _ = writeln!(&mut s, "  {name} = {enc}.GetString(data, pos, (nul < 0 ? end : nul) - pos); pos = end; }}");
+        }
+        TypeNode::CString(enc) => {
+            let (enc, unit) = cs_encoding(enc);
+            let _ = writeln!(&mut s, "{{ int nul = PacketStrings.NulIndex(data, pos, data.Length, {unit});");
+            let _ = writeln!(&mut s, "  if (nul < 0) throw new FormatException(\"{name}: unterminated string\");");
+            let _ = writeln!(&mut s, "  {name} = {enc}.GetString(data, pos, nul - pos); pos = nul + {unit}; }}");
+        }
+        TypeNode::PString(_, enc) => {
+            let (enc, _) = cs_encoding(enc);
+            let _ = match (endian, scalar_width_bytes(t)) {
+                (_, 1) => writeln!(&mut s, "{{ int len = data[pos++];"),
+                (Le, _) => writeln!(&mut s, "{{ int len = BinaryPrimitives.ReadUInt16LittleEndian(data.AsSpan(pos)); pos += 2;"),
+                (Be, _) => writeln!(&mut s, "{{ int len = BinaryPrimitives.ReadUInt16BigEndian(data.AsSpan(pos)); pos += 2;"),
+            };
+            let _ = writeln!(&mut s, "  {name} = {enc}.GetString(data, pos, len); pos += len; }}");
+        }
         _ => {
             let w = scalar_width_bytes(t);
 
             let read_scalar = |dst: &mut String, lhs: String| {
+                if is_address(t) {
+                    let _ = writeln!(dst, "{lhs} = new IPAddress(data.AsSpan(pos, {w})); pos += {w};");
+                    return;
+                }
+                if let TypeNode::DateTime(_, unit) = t {
+                    let (m, signed) = cs_time(unit);
+                    let prim = if signed { "Int64" } else { "UInt64" };
+                    let order = if endian == Be { "BigEndian" } else { "LittleEndian" };
+                    let _ = writeln!(
+                        dst,
+                        "{lhs} = PacketTime.From{m}(BinaryPrimitives.Read{prim}{order}(data.AsSpan(pos))); pos += 8;"
+                    );
+                    return;
+                }
+                if let TypeNode::Bool(_, _) = t {
+                    let _ = writeln!(dst, "{lhs} = data[pos++] != 0;");
+                    return;
+                }
+                if let TypeNode::VarInt(_, enc) = t {
+                    let _ = writeln!(dst, "{lhs} = {};", cs_varint(enc, "").2);
+                    return;
+                }
+                if let TypeNode::Fixed(_, base, frac) = t {
+                    let (ty, prim) = cs_fixed(base);
+                    let scale = format!("{}.0", 1u128 << frac);
+                    let order = if endian == Be { "BigEndian" } else { "LittleEndian" };
+                    let _ = match w {
+                        1 => writeln!(dst, "{lhs} = unchecked(({ty})data[pos++]) / {scale};"),
+                        _ => writeln!(
+                            dst,
+                            "{lhs} = BinaryPrimitives.Read{prim}{order}(data.AsSpan(pos)) / {scale}; pos += {w};"
+                        ),
+                    };
+                    return;
+                }
                 match (endian, w) {
                     (_, 1) => {
                         // byte/sbyte
@@ -505,7 +1323,7 @@ fn deserialize_snippet(t: &TypeNode, name: &str, endian: Endianness, is_last: bo
                                 "{{ var bits = BinaryPrimitives.ReadInt64LittleEndian(data.AsSpan(pos)); pos += 8; {lhs} = BitConverter.Int64BitsToDouble(bits); }}"
                             );
                         }
-                        TypeNode::Integer64(_) | TypeNode::DateTime(_) => {
+                        TypeNode::Integer64(_) => {
                             let _ = writeln!(
                                 dst,
                                 "{lhs} = BinaryPrimitives.ReadInt64LittleEndian(data.AsSpan(pos)); pos += 8;"
@@ -526,7 +1344,7 @@ fn deserialize_snippet(t: &TypeNode, name: &str, endian: Endianness, is_last: bo
                                 "{{ var bits = BinaryPrimitives.ReadInt64BigEndian(data.AsSpan(pos)); pos += 8; {lhs} = BitConverter.Int64BitsToDouble(bits); }}"
                             );
                         }
-                        TypeNode::Integer64(_) | TypeNode::DateTime(_) => {
+                        TypeNode::Integer64(_) => {
                             let _ = writeln!(
                                 dst,
                                 "{lhs} = BinaryPrimitives.ReadInt64BigEndian(data.AsSpan(pos)); pos += 8;"
@@ -553,6 +1371,15 @@ fn deserialize_snippet(t: &TypeNode, name: &str, endian: Endianness, is_last: bo
                         let _ = writeln!(&mut s, "for (int i = 0; i < {n}; ++i) {{");
                         read_scalar(&mut s, format!("{name}[i]"));
                         let _ = writeln!(&mut s, "}}");
+                    } else if is_last && matches!(t, TypeNode::VarInt(_, _)) {
+                        // varints have no fixed element width: read until the buffer ends
+                        let ty = cs_field_type(t).trim_end_matches("[]").to_string();
+                        let _ = writeln!(
+                            &mut s,
+                            "{{ var list = new System.Collections.Generic.List<{ty}>(); while (pos < data.Length) {{"
+                        );
+                        read_scalar(&mut s, "var v".to_string());
+                        let _ = writeln!(&mut s, "list.Add(v); }} {name} = list.ToArray(); }}");
                     } else if is_last {
                         let ty = cs_field_type(t).trim_end_matches("[]").to_string();
                         let elem = w;
@@ -573,7 +1400,7 @@ fn deserialize_snippet(t: &TypeNode, name: &str, endian: Endianness, is_last: bo
                     read_scalar(&mut s, name.to_string());
                 }
             } else {
-                read_scalar(&mut s, format!("{name}"));
+                read_scalar(&mut s, name.to_string());
             }
         }
     }
@@ -588,7 +1415,7 @@ fn map_cs_scalar_for_width(t: &TypeNode, w: usize) -> &'static str {
         (UnsignedInteger16(_), 2) => "ushort",
         (Integer32(_), 4) => "int",
         (UnsignedInteger32(_), 4) => "uint",
-        (Integer64(_), 8) | (DateTime(_), 8) => "long",
+        (Integer64(_), 8) => "long",
         (UnsignedInteger64(_), 8) => "ulong",
         // Fallback to unsigned width for other combos (we cast on store/read as needed)
         _ => match w {
@@ -600,16 +1427,125 @@ fn map_cs_scalar_for_width(t: &TypeNode, w: usize) -> &'static str {
     }
 }
 
-fn type_len_expr<'a>(t: &'a TypeNode) -> Option<&'a ExprNode> {
+fn type_len_expr(t: &TypeNode) -> Option<&ExprNode> {
     use TypeNode::*;
     match t {
         UnsignedInteger8(e) | Integer8(e) | UnsignedInteger16(e) | Integer16(e)
         | UnsignedInteger32(e) | Integer32(e) | UnsignedInteger64(e) | Integer64(e)
-        | Float32(e) | Float64(e) | DateTime(e) => e.as_ref(),
+        | Float32(e) | Float64(e) | DateTime(e, _) | Bool(e, _) | Fixed(e, _, _) => e.as_ref(),
+        VarInt(e, _) => e.as_ref(),
+        Ipv4Address(e) | Ipv6Address(e) | Port(e) => e.as_ref(),
         Bytes(e) | MacAddress(e) => e.as_ref(), // used for const detection
+        FixedString(e, _) => Some(e),
+        CString(_) | PString(_, _) | Pad(_) | Align(_) => None,
+        Repeat(_, RepeatBound::Count(e)) => Some(e),
+        Repeat(_, _) => None,
+    }
+}
+
+/* ============================================================
+ * Expr → C# emitter (repeat counts and conditions)
+ * ============================================================
+*/
+
+// Values are doubles, as in the Rust generator; comparisons and logic give 1.0 or 0.0.
+fn emit_cs_expr(e: &ExprNode, root: &str) -> String {
+    use ExprNode::*;
+    let bin = |a: &ExprNode, b: &ExprNode, op: &str| format!("({} {op} {})", emit_cs_expr(a, root), emit_cs_expr(b, root));
+    let int_bin = |a: &ExprNode, b: &ExprNode, op: &str| {
+        format!("(double)((long){} {op} (long){})", emit_cs_expr(a, root), emit_cs_expr(b, root))
+    };
+    let test = |cond: String| format!("({cond} ? 1.0 : 0.0)");
+    match e {
+        UnsignedInteger64Value(u) => format!("{}.0", u),
+        Integer64Value(i) => format!("({}.0)", i),
+        Float64Value(f) => format!("{:?}", f),
+        StringValue(_) => "0.0".into(), // not expected in counts
+        ValueReference(name, idx) => {
+            let prop = CaseWrapper(name.clone()).to_pascal_case();
+            match idx {
+                Some(ix) => format!("(double){root}{prop}[(int){}]", emit_cs_expr(ix, root)),
+                None => format!("(double){root}{prop}"),
+            }
+        }
+        ParenthesizedExpr(x) => emit_cs_expr(x, root),
+        Plus(a, b) => bin(a, b, "+"),
+        Minus(a, b) => bin(a, b, "-"),
+        Mult(a, b) => bin(a, b, "*"),
+        Div(a, b) => bin(a, b, "/"),
+        Mod(a, b) => bin(a, b, "%"),
+        Pow(a, b) => format!("Math.Pow({}, {})", emit_cs_expr(a, root), emit_cs_expr(b, root)),
+        Negate(a) => format!("(-{})", emit_cs_expr(a, root)),
+        BitAnd(a, b) => int_bin(a, b, "&"),
+        BitOr(a, b) => int_bin(a, b, "|"),
+        BitNot(a) => format!("(double)(~(long){})", emit_cs_expr(a, root)),
+        ShiftLeft(a, b) => format!("(double)((long){} << (int){})", emit_cs_expr(a, root), emit_cs_expr(b, root)),
+        ShiftRight(a, b) => format!("(double)((long){} >> (int){})", emit_cs_expr(a, root), emit_cs_expr(b, root)),
+        Gt(a, b) => test(bin(a, b, ">")),
+        Gte(a, b) => test(bin(a, b, ">=")),
+        Lt(a, b) => test(bin(a, b, "<")),
+        Lte(a, b) => test(bin(a, b, "<=")),
+        Equals(a, b) => test(bin(a, b, "==")),
+        NotEquals(a, b) => test(bin(a, b, "!=")),
+        And(a, b) => test(format!("({} != 0.0 && {} != 0.0)", emit_cs_expr(a, root), emit_cs_expr(b, root))),
+        Or(a, b) => test(format!("({} != 0.0 || {} != 0.0)", emit_cs_expr(a, root), emit_cs_expr(b, root))),
+        Not(a) => test(format!("({} == 0.0)", emit_cs_expr(a, root))),
+        GuardExpression(c, t, f) => format!(
+            "({} != 0.0 ? {} : {})",
+            emit_cs_expr(c, root),
+            emit_cs_expr(t, root),
+            emit_cs_expr(f, root)
+        ),
+        ActivationRecord(name, args) => {
+            let args_s: Vec<String> = args.iter().map(|a| emit_cs_expr(a, root)).collect();
+            match name.as_str() {
+                "sqrt" => format!("Math.Sqrt({})", args_s.join(", ")),
+                "min" => format!("Math.Min({})", args_s.join(", ")),
+                "max" => format!("Math.Max({})", args_s.join(", ")),
+                "abs" => format!("Math.Abs({})", args_s[0]),
+                "floor" => format!("Math.Floor({})", args_s[0]),
+                "ceil" => format!("Math.Ceiling({})", args_s[0]),
+                "round" => format!("Math.Round({}, MidpointRounding.AwayFromZero)", args_s[0]),
+                "log2" => format!("Math.Log2({})", args_s[0]),
+                "clamp" => format!("Math.Min(Math.Max({}, {}), {})", args_s[0], args_s[1], args_s[2]),
+                "pow" => format!("Math.Pow({}, {})", args_s[0], args_s[1]),
+                "popcount" => format!("(double)System.Numerics.BitOperations.PopCount((ulong)(long){})", args_s[0]),
+                "bswap16" => format!("(double)BinaryPrimitives.ReverseEndianness((ushort)(long){})", args_s[0]),
+                "bswap32" => format!("(double)BinaryPrimitives.ReverseEndianness((uint)(long){})", args_s[0]),
+                "bswap64" => format!("(double)BinaryPrimitives.ReverseEndianness((ulong)(long){})", args_s[0]),
+                // an `extern fn` taking doubles
+                _ => format!("(double){}({})", name, args_s.join(", ")),
+            }
+        }
+        AggregateSum(_) | AggregateProduct(_) | NoExpr => "0.0".into(),
     }
 }
 
+/// Throws unless the rule holds; `root` is "result." in Deserialize and "" in Validate.
+fn check_snippet(pkt: &PacketExpr, check: &Check, root: &str) -> String {
+    let rule = check.rule.replace('\\', "\\\\").replace('"', "\\\"");
+    format!(
+        "if ({} == 0.0) throw new PacketValidationException(\"{}\", \"{}\", \"{rule}\");\n",
+        emit_cs_expr(&check.cond, root),
+        pkt.name,
+        check.field
+    )
+}
+
+/// Renders DSL `///` docs as an XML `<summary>` block.
+fn xml_doc(doc: &Option<String>, pad: &str) -> String {
+    let Some(doc) = doc else {
+        return String::new();
+    };
+    let mut s = format!("{pad}/// <summary>\n");
+    for l in doc.lines() {
+        let escaped = l.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+        let _ = writeln!(&mut s, "{pad}/// {escaped}");
+    }
+    let _ = writeln!(&mut s, "{pad}/// </summary>");
+    s
+}
+
 fn indent(s: &str, tabs: usize) -> String {
     let pad = "    ".repeat(tabs);
     s.lines()
@@ -622,3 +1558,30 @@ fn indent(s: &str, tabs: usize) -> String {
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::load_source;
+
+    fn cs(src: &str) -> String {
+        CSharpGenerator::generate(&load_source(src))
+    }
+
+    #[test]
+    fn strings_that_do_not_fit_are_rejected() {
+        let out = cs("packet Names be { tag string[4], short pstring<uint8>, long pstring<uint16> }");
+        assert!(out.contains("if (b.Length > 4) throw new ArgumentException(\"Tag: string longer than 4 bytes\");"));
+        assert!(out.contains(
+            "if (b.Length > byte.MaxValue) throw new ArgumentException($\"Short: string longer than {byte.MaxValue} bytes\");"
+        ));
+        assert!(out.contains("if (b.Length > ushort.MaxValue)"));
+    }
+
+    #[test]
+    fn bad_magic_throws() {
+        let out = cs("packet Flap be { start uint8 = 0x2a, v uint16 }");
+        assert!(out.contains("    public const byte Start = 0x2a;\n"));
+        assert!(out.contains("if (_Start != Start) throw new FormatException($\"Flap: start must be 0x2a, got 0x{_Start:x}\");"));
+    }
+}
@@ -1,5 +1,6 @@
 use crate::models::parsing_models::{
-    Endianness, ExprNode, PacketExpr, PacketExprList, TypeNode,
+    BitSlot, Check, Endianness, ExprNode, FixedBase, Leftover, PacketExpr, PacketExprList, RepeatBound, StringEncoding,
+    TimeEncoding, TypeNode, VarIntEncoding,
 };
 use std::fmt::Write as _;
 
@@ -8,7 +9,22 @@ pub struct PythonGenerator {}
 impl PythonGenerator {
     pub fn generate(model: &PacketExprList) -> String {
         let mut out = String::new();
-        out.push_str("import struct\nimport math\n\n\n");
+        out.push_str("import datetime\nimport ipaddress\nimport struct\nimport math\n\n\n");
+        if model.packets.iter().flat_map(|p| &p.fields).any(|f| is_string(&f.expr)) {
+            out.push_str(STRING_HELPERS);
+        }
+        if model.packets.iter().flat_map(|p| &p.fields).any(|f| matches!(f.expr, TypeNode::DateTime(_, _))) {
+            out.push_str(TIME_HELPERS);
+        }
+        if model.packets.iter().flat_map(|p| &p.fields).any(|f| matches!(f.expr, TypeNode::VarInt(_, _))) {
+            out.push_str(VARINT_HELPERS);
+        }
+        if model.packets.iter().any(|p| !p.checksums().is_empty()) {
+            out.push_str(CHECKSUM_HELPERS);
+        }
+        if model.packets.iter().any(|p| !p.checks().is_empty()) {
+            out.push_str(VALIDATION_ERROR);
+        }
 
         for pkt in &model.packets {
             out.push_str(&Self::build_class(pkt));
@@ -20,10 +36,24 @@ impl PythonGenerator {
     fn build_class(pkt: &PacketExpr) -> String {
         let class_name = &pkt.name;
 
-        // __init__ fields
+        // const fields are class attributes, not __init__ parameters
+        let mut consts = String::new();
+        for f in pkt.fields.iter().filter(|f| f.value.is_some() && f.checksum().is_none()) {
+            consts.push_str(&attr_doc(&f.doc, "    "));
+            let _ = writeln!(&mut consts, "    {} = {}", f.id, f.const_literal().unwrap_or_default());
+        }
+        if !consts.is_empty() {
+            consts.push('\n');
+        }
+
+        // __init__ fields; filler is not stored
         let mut init_body = String::new();
-        for f in &pkt.fields {
-            let default = py_default_value(&f.expr);
+        for f in pkt.fields.iter().filter(|f| f.value.is_none() && !f.is_filler()) {
+            let default = match f.condition {
+                Some(_) => "None".to_string(), // optional fields stay None when absent
+                None => py_default_value(&f.expr),
+            };
+            init_body.push_str(&attr_doc(&f.doc, "        "));
             let _ = writeln!(
                 &mut init_body,
                 "        self.{name} = {default}",
@@ -34,23 +64,154 @@ impl PythonGenerator {
 
         // to_bytes body (serialize)
         let mut ser_body = String::new();
+        let slots = pkt.bit_slots();
+        let derived = pkt.derived_counts();
+        let marks = pkt.checksum_marks();
+        for (((f, slot), count), &(start, end_mark)) in pkt.fields.iter().zip(&slots).zip(&derived).zip(&marks) {
+            if start {
+                let _ = writeln!(&mut ser_body, "_{}_start = len(data)", f.id);
+            }
+            if let Some(slot) = slot {
+                ser_body.push_str(&bit_snippets(&f.id, slot).0);
+                if end_mark {
+                    let _ = writeln!(&mut ser_body, "_{}_end = len(data)", f.id);
+                }
+                continue;
+            }
+            let end = f.endianness.as_ref().or(pkt.endianness.as_ref()).unwrap_or(&Endianness::Le);
+            // count fields are written from the field they size; struct.pack rejects overflow
+            let owner = match (count, &f.value) {
+                (Some(count), _) => {
+                    let source = &pkt.fields[count.source].id;
+                    let _ = writeln!(&mut ser_body, "{} = len(self.{source}){}", f.id, py_adjust(count.adjust));
+                    ""
+                }
+                // written as zero, then filled in once the packet is encoded
+                (None, Some(_)) if f.checksum().is_some() => {
+                    let _ = writeln!(&mut ser_body, "_{id}_at = len(data)\n{id} = 0", id = f.id);
+                    ""
+                }
+                (None, Some(_)) => "type(self).",
+                (None, None) => "self.",
+            };
+            let write = serialize_snippet(&f.expr, &f.id, end.clone(), owner);
+            match &f.condition {
+                Some(cond) => {
+                    let _ = writeln!(&mut ser_body, "if {}:", emit_py_expr(cond, "self"));
+                    let _ = writeln!(
+                        &mut ser_body,
+                        "    if self.{id} is None: raise ValueError('{}: {id} must be set when its condition holds')",
+                        pkt.name,
+                        id = f.id
+                    );
+                    ser_body.push_str(&indent(&write, 1));
+                }
+                None => ser_body.push_str(&write),
+            }
+            if end_mark {
+                let _ = writeln!(&mut ser_body, "_{}_end = len(data)", f.id);
+            }
+        }
         for f in &pkt.fields {
+            let Some(sum) = f.checksum() else { continue };
             let end = f.endianness.as_ref().or(pkt.endianness.as_ref()).unwrap_or(&Endianness::Le);
-            ser_body.push_str(&serialize_snippet(&f.expr, &f.id, end.clone()));
+            let _ = writeln!(
+                &mut ser_body,
+                "data[_{id}_at:_{id}_at + {w}] = struct.pack('{}{}', _{}(bytes(data[_{}_start:_{}_end])))",
+                if *end == Endianness::Be { ">" } else { "<" },
+                if sum.kind.width_bytes() == 2 { "H" } else { "I" },
+                sum.kind.name(),
+                sum.first,
+                sum.last,
+                id = f.id,
+                w = sum.kind.width_bytes()
+            );
         }
 
         // from_bytes body (deserialize)
         let mut de_body = String::new();
-        let n = pkt.fields.len();
-        for (i, f) in pkt.fields.iter().enumerate() {
-            let last = i + 1 == n;
-            let end = f.endianness.as_ref().or(pkt.endianness.as_ref()).unwrap_or(&Endianness::Le);
-            de_body.push_str(&deserialize_snippet(&f.expr, &f.id, end.clone(), last));
+        for (i, (f, slot)) in pkt.fields.iter().zip(&slots).enumerate() {
+            for (k, _) in pkt.regions_opening(i) {
+                de_body.push_str(&region_open(pkt, k));
+            }
+            let (start, end_mark) = marks[i];
+            if start {
+                let _ = writeln!(&mut de_body, "_{}_start = pos", f.id);
+            }
+            if f.checksum().is_some() {
+                let _ = writeln!(&mut de_body, "_{}_at = pos", f.id);
+            }
+            if let Some(slot) = slot {
+                de_body.push_str(&bit_snippets(&f.id, slot).1);
+            } else {
+                let end = f.endianness.as_ref().or(pkt.endianness.as_ref()).unwrap_or(&Endianness::Le);
+                let read = deserialize_snippet(&f.expr, &f.id, end.clone(), pkt.is_last_in_scope(i));
+                match &f.condition {
+                    Some(cond) => {
+                        let _ = writeln!(&mut de_body, "if {}:", emit_py_expr(cond, "result"));
+                        de_body.push_str(&indent(&read, 1));
+                    }
+                    None => de_body.push_str(&read),
+                }
+            }
+            if end_mark {
+                let _ = writeln!(&mut de_body, "_{}_end = pos", f.id);
+            }
+            if let Some(value) = f.const_literal() {
+                let _ = writeln!(
+                    &mut de_body,
+                    "if result.{id} != {value}: raise ValueError(f\"{}: {id} must be {value}, got {{result.{id}:#x}}\")",
+                    pkt.name,
+                    id = f.id
+                );
+            }
+            if let Some(check) = &f.constraint {
+                de_body.push_str(&check_snippet(pkt, check, "result"));
+            }
+            for (k, _) in pkt.regions_closing(i) {
+                de_body.push_str(&region_close(pkt, k));
+            }
         }
+        // checksums are recomputed with their own field zeroed, as when they were filled in
+        for f in &pkt.fields {
+            let Some(sum) = f.checksum() else { continue };
+            let _ = writeln!(
+                &mut de_body,
+                "_sum = _{}(_without(data, _{}_start, _{}_end, _{id}_at, {}))",
+                sum.kind.name(),
+                sum.first,
+                sum.last,
+                sum.kind.width_bytes(),
+                id = f.id
+            );
+            let _ = writeln!(
+                &mut de_body,
+                "if result.{id} != _sum: raise ValueError(f\"{}: {id} is {{result.{id}:#x}} but the {} of {}..{} is {{_sum:#x}}\")",
+                pkt.name,
+                sum.kind.name(),
+                sum.first,
+                sum.last,
+                id = f.id
+            );
+        }
+        for check in &pkt.asserts {
+            de_body.push_str(&check_snippet(pkt, check, "result"));
+        }
+
+        let checks = pkt.checks();
+        let validate = if checks.is_empty() {
+            String::new()
+        } else {
+            let body: String = checks.iter().map(|c| check_snippet(pkt, c, "self")).collect();
+            format!(
+                "\n    def validate(self) -> None:\n        \"\"\"Raises ValidationError if a `where` or `assert` rule fails; from_bytes checks them too.\"\"\"\n{}",
+                indent(&body, 2)
+            )
+        };
 
         format!(
 r#"class {class_name}:
-    def __init__(self):
+{docstring}{consts}    def __init__(self):
 {init_body}
     def to_bytes(self, verbose: bool = False) -> bytes:
         data = bytearray()
@@ -59,12 +220,18 @@ r#"class {class_name}:
 
     @classmethod
     def from_bytes(cls, data: bytes, verbose: bool = False) -> "{class_name}":
+        return cls.from_prefix(data, verbose)[0]
+
+    @classmethod
+    def from_prefix(cls, data: bytes, verbose: bool = False) -> "tuple[{class_name}, int]":
+        """Decodes one {class_name} from the front of `data`; also returns the bytes it used."""
         result = cls()
         pos = 0
 {de_body}
-        return result
-"#,
+        return result, pos
+{validate}"#,
             class_name = class_name,
+            docstring = docstring(&pkt.doc, "    "),
             init_body = init_body,
             ser_body = indent(&ser_body, 2),
             de_body = indent(&de_body, 2),
@@ -72,11 +239,200 @@ r#"class {class_name}:
     }
 }
 
+/// Error raised for a broken `where` or `assert` rule, emitted only when a packet has one.
+const VALIDATION_ERROR: &str = r#"class ValidationError(ValueError):
+    """A packet field broke one of its `where` or `assert` rules."""
+
+    def __init__(self, packet: str, field: str, rule: str):
+        super().__init__(f"{packet}: {field} breaks `{rule}`")
+        self.packet = packet
+        self.field = field
+        self.rule = rule
+
+
+"#;
+
+/// Checksum intrinsics, emitted only when a packet uses one.
+const CHECKSUM_HELPERS: &str = r#"def _crc32(data: bytes) -> int:
+    """CRC-32 (IEEE 802.3)."""
+    crc = 0xFFFFFFFF
+    for b in data:
+        crc ^= b
+        for _ in range(8):
+            crc = (crc >> 1) ^ 0xEDB88320 if crc & 1 else crc >> 1
+    return crc ^ 0xFFFFFFFF
+
+
+def _crc16_ccitt(data: bytes) -> int:
+    """CRC-16/CCITT-FALSE."""
+    crc = 0xFFFF
+    for b in data:
+        crc ^= b << 8
+        for _ in range(8):
+            crc = ((crc << 1) ^ 0x1021 if crc & 0x8000 else crc << 1) & 0xFFFF
+    return crc
+
+
+def _inet_checksum(data: bytes) -> int:
+    """RFC 1071 Internet checksum; an odd last byte is padded with zero."""
+    if len(data) % 2:
+        data += b'\0'
+    s = sum(struct.unpack(f'>{len(data) // 2}H', data))
+    while s > 0xFFFF:
+        s = (s & 0xFFFF) + (s >> 16)
+    return ~s & 0xFFFF
+
+
+def _adler32(data: bytes) -> int:
+    """Adler-32 (RFC 1950)."""
+    a, b = 1, 0
+    for x in data:
+        a = (a + x) % 65521
+        b = (b + a) % 65521
+    return b << 16 | a
+
+
+def _without(data: bytes, start: int, end: int, at: int, width: int) -> bytes:
+    """`data[start:end]` with the checksum field at `at` zeroed, as it was when computed."""
+    out = bytearray(data[start:end])
+    for i in range(max(at, start), min(at + width, end)):
+        out[i - start] = 0
+    return bytes(out)
+
+
+"#;
+
+/// Module-level helpers for NUL handling, emitted only when a packet has a string field.
+const STRING_HELPERS: &str = r#"def _strip_nul(raw: bytes, unit: int) -> bytes:
+    """Cuts `raw` at its first NUL code unit (1 byte, or 2 for UTF-16)."""
+    for i in range(0, len(raw) - unit + 1, unit):
+        if raw[i:i + unit] == bytes(unit):
+            return raw[:i]
+    return raw
+
+
+def _read_cstring(data: bytes, pos: int, unit: int) -> tuple:
+    """Returns the bytes before the NUL at or after `pos` and the position past it."""
+    for i in range(pos, len(data) - unit + 1, unit):
+        if data[i:i + unit] == bytes(unit):
+            return bytes(data[pos:i]), i + unit
+    raise ValueError('unterminated string')
+
+
+"#;
+
+/// Epochs and tick conversions for datetime fields, emitted only when a packet has one.
+const TIME_HELPERS: &str = r#"_EPOCH = datetime.datetime(1970, 1, 1, tzinfo=datetime.timezone.utc)
+_NTP_EPOCH = datetime.datetime(1900, 1, 1, tzinfo=datetime.timezone.utc)
+_FILETIME_EPOCH = datetime.datetime(1601, 1, 1, tzinfo=datetime.timezone.utc)
+
+
+def _to_ticks(t: datetime.datetime, epoch: datetime.datetime, per_second: int) -> int:
+    """Whole ticks from `epoch` to `t`, rounded down; naive datetimes are taken as UTC."""
+    if t.tzinfo is None:
+        t = t.replace(tzinfo=datetime.timezone.utc)
+    d = t - epoch
+    return (d.days * 86400 + d.seconds) * per_second + d.microseconds * per_second // 1_000_000
+
+
+def _from_ticks(v: int, epoch: datetime.datetime, per_second: int) -> datetime.datetime:
+    """Inverse of `_to_ticks`, truncated to microseconds."""
+    return epoch + datetime.timedelta(seconds=v // per_second, microseconds=(v % per_second) * 1_000_000 // per_second)
+
+
+"#;
+
+/// LEB128, zigzag and QUIC varint codecs, emitted only when a packet has a varint.
+const VARINT_HELPERS: &str = r#"def _write_varuint(v: int) -> bytes:
+    """LEB128: 7 bits per byte, least significant group first."""
+    if not 0 <= v < 2**64:
+        raise ValueError('varuint out of range')
+    out = bytearray()
+    while v >= 0x80:
+        out.append((v & 0x7F) | 0x80)
+        v >>= 7
+    out.append(v)
+    return bytes(out)
+
+
+def _read_varuint(data: bytes, pos: int) -> tuple:
+    """Returns the LEB128 value at `pos` and the position past it."""
+    v = 0
+    for i in range(10):
+        b = data[pos + i]
+        v |= (b & 0x7F) << (7 * i)
+        if not b & 0x80:
+            return v, pos + i + 1
+    raise ValueError('varint longer than 10 bytes')
+
+
+def _zigzag(v: int) -> int:
+    return ((v << 1) ^ (v >> 63)) & 0xFFFFFFFFFFFFFFFF
+
+
+def _unzigzag(u: int) -> int:
+    return (u >> 1) ^ -(u & 1)
+
+
+def _write_quicvarint(v: int) -> bytes:
+    """RFC 9000 varint: the top two bits of the first byte give its length."""
+    for n, tag in ((1, 0), (2, 1), (4, 2), (8, 3)):
+        if 0 <= v < 1 << (8 * n - 2):
+            return (v | tag << (8 * n - 2)).to_bytes(n, 'big')
+    raise ValueError('quicvarint out of range')
+
+
+def _read_quicvarint(data: bytes, pos: int) -> tuple:
+    n = 1 << (data[pos] >> 6)
+    return int.from_bytes(data[pos:pos + n], 'big') & ((1 << (8 * n - 2)) - 1), pos + n
+
+
+"#;
+
+/// (write, read) expressions of a varint value `v`; the read yields `(value, pos)`.
+fn py_varint(enc: &VarIntEncoding, v: &str) -> (String, String) {
+    match enc {
+        VarIntEncoding::Leb128 => (format!("_write_varuint({v})"), "_read_varuint(data, pos)".to_string()),
+        VarIntEncoding::ZigZag => (format!("_write_varuint(_zigzag({v}))"), "_read_varuint(data, pos)".to_string()),
+        VarIntEncoding::Quic => (format!("_write_quicvarint({v})"), "_read_quicvarint(data, pos)".to_string()),
+    }
+}
+
 /* ============================================================
  * Python emission helpers
  * ============================================================
 */
 
+/// Renders DSL `///` docs as a class docstring (followed by a blank line).
+fn docstring(doc: &Option<String>, pad: &str) -> String {
+    let Some(doc) = doc else {
+        return String::new();
+    };
+    let body = doc.replace('\\', "\\\\").replace("\"\"\"", "\\\"\\\"\\\"");
+    if !body.contains('\n') {
+        return format!("{pad}\"\"\"{body}\"\"\"\n\n");
+    }
+    let mut s = format!("{pad}\"\"\"");
+    for (i, l) in body.lines().enumerate() {
+        if i > 0 && !l.is_empty() {
+            s.push_str(pad);
+        }
+        let _ = writeln!(&mut s, "{l}");
+    }
+    let _ = writeln!(&mut s, "{pad}\"\"\"\n");
+    s
+}
+
+/// Renders DSL `///` docs as Sphinx-style `#:` attribute comments.
+fn attr_doc(doc: &Option<String>, pad: &str) -> String {
+    let Some(doc) = doc else {
+        return String::new();
+    };
+    doc.lines()
+        .map(|l| if l.is_empty() { format!("{pad}#:\n") } else { format!("{pad}#: {l}\n") })
+        .collect()
+}
+
 fn py_endian_prefix(e: Endianness) -> &'static str {
     match e {
         Endianness::Le => "<",
@@ -97,19 +453,33 @@ fn scalar_struct_code(t: &TypeNode) -> Option<&'static str> {
         Integer64(_) => "q",
         Float32(_) => "f",
         Float64(_) => "d",
-        DateTime(_) => "q", // 64-bit signed on wire
-        Bytes(_) | MacAddress(_) => return None,
+        DateTime(_, unit) => py_time(unit).2,
+        Bool(_, _) => "?",
+        Fixed(_, base, _) => py_fixed_code(base),
+        Port(_) => "H",
+        PString(_, _) => match t.scalar_width_bytes() {
+            1 => "B",
+            _ => "H",
+        }, // the length prefix
+        Bytes(_) | MacAddress(_) | FixedString(_, _) | CString(_) | Ipv4Address(_) | Ipv6Address(_)
+        | VarInt(_, _) | Repeat(_, _) | Pad(_) | Align(_) => return None,
     })
 }
 
 fn scalar_width_bytes(t: &TypeNode) -> usize {
     use TypeNode::*;
     match t {
-        UnsignedInteger8(_) | Integer8(_) => 1,
-        UnsignedInteger16(_) | Integer16(_) => 2,
-        UnsignedInteger32(_) | Integer32(_) | Float32(_) => 4,
-        UnsignedInteger64(_) | Integer64(_) | Float64(_) | DateTime(_) => 8,
-        Bytes(_) | MacAddress(_) => 1,
+        UnsignedInteger8(_) | Integer8(_) | Bool(_, _) => 1,
+        UnsignedInteger16(_) | Integer16(_) | Port(_) => 2,
+        UnsignedInteger32(_) | Integer32(_) | Float32(_) | Ipv4Address(_) => 4,
+        UnsignedInteger64(_) | Integer64(_) | Float64(_) | DateTime(_, _) => 8,
+        Ipv6Address(_) => 16,
+        Fixed(_, base, _) => base.width_bytes(),
+        Bytes(_) | MacAddress(_) | FixedString(_, _) | CString(_) => 1,
+        PString(_, _) => t.scalar_width_bytes(),
+        VarInt(_, _) => 0, // variable
+        Repeat(_, _) => 0, // sized by its elements
+        Pad(_) | Align(_) => 1,
     }
 }
 
@@ -117,6 +487,9 @@ fn py_default_value(t: &TypeNode) -> String {
     use TypeNode::*;
     match t {
         Bytes(_) | MacAddress(_) => "b''".to_string(),
+        FixedString(_, _) | CString(_) | PString(_, _) => "''".to_string(),
+        Repeat(_, _) => "[]".to_string(),
+        Pad(_) | Align(_) => "None".to_string(), // never stored
         UnsignedInteger8(len)
         | Integer8(len)
         | UnsignedInteger16(len)
@@ -127,12 +500,21 @@ fn py_default_value(t: &TypeNode) -> String {
         | Integer64(len)
         | Float32(len)
         | Float64(len)
-        | DateTime(len) => {
+        | DateTime(len, _)
+        | Bool(len, _)
+        | Fixed(len, _, _)
+        | VarInt(len, _)
+        | Ipv4Address(len)
+        | Ipv6Address(len)
+        | Port(len) => {
             if len.is_some() {
                 "[]".to_string()
             } else {
                 match t {
-                    Float32(_) | Float64(_) => "0.0".to_string(),
+                    Float32(_) | Float64(_) | Fixed(_, _, _) => "0.0".to_string(),
+                    Bool(_, _) => "False".to_string(),
+                    Ipv4Address(_) | Ipv6Address(_) => format!("{}(0)", py_address_class(t)),
+                    DateTime(_, _) => "_EPOCH".to_string(),
                     _ => "0".to_string(),
                 }
             }
@@ -144,6 +526,7 @@ fn is_array_like(t: &TypeNode) -> bool {
     use TypeNode::*;
     match t {
         Bytes(_) | MacAddress(_) => true,
+        FixedString(_, _) | CString(_) | PString(_, _) | Repeat(_, _) | Pad(_) | Align(_) => false,
         UnsignedInteger8(len)
         | Integer8(len)
         | UnsignedInteger16(len)
@@ -154,14 +537,24 @@ fn is_array_like(t: &TypeNode) -> bool {
         | Integer64(len)
         | Float32(len)
         | Float64(len)
-        | DateTime(len) => len.is_some(),
+        | DateTime(len, _)
+        | Bool(len, _)
+        | Fixed(len, _, _)
+        | VarInt(len, _)
+        | Ipv4Address(len)
+        | Ipv6Address(len)
+        | Port(len) => len.is_some(),
     }
 }
 
-fn type_len_expr<'a>(t: &'a TypeNode) -> Option<&'a ExprNode> {
+fn type_len_expr(t: &TypeNode) -> Option<&ExprNode> {
     use TypeNode::*;
     match t {
         Bytes(e) | MacAddress(e) => e.as_ref(),
+        FixedString(e, _) => Some(e),
+        CString(_) | PString(_, _) | Pad(_) | Align(_) => None,
+        Repeat(_, RepeatBound::Count(e)) => Some(e),
+        Repeat(_, _) => None,
         UnsignedInteger8(e)
         | Integer8(e)
         | UnsignedInteger16(e)
@@ -172,7 +565,114 @@ fn type_len_expr<'a>(t: &'a TypeNode) -> Option<&'a ExprNode> {
         | Integer64(e)
         | Float32(e)
         | Float64(e)
-        | DateTime(e) => e.as_ref(),
+        | DateTime(e, _)
+        | Bool(e, _)
+        | Fixed(e, _, _)
+        | VarInt(e, _)
+        | Ipv4Address(e)
+        | Ipv6Address(e)
+        | Port(e) => e.as_ref(),
+    }
+}
+
+/// `ipaddress` class for an address type; its `.packed` form is in network order.
+fn py_address_class(t: &TypeNode) -> &'static str {
+    match t {
+        TypeNode::Ipv6Address(_) => "ipaddress.IPv6Address",
+        _ => "ipaddress.IPv4Address",
+    }
+}
+
+/// Epoch, ticks per second and struct code of a datetime encoding; ntp64 is
+/// 32.32 fixed point, so its ticks are 2**32 per second.
+fn py_time(unit: &TimeEncoding) -> (&'static str, &'static str, &'static str) {
+    match unit {
+        TimeEncoding::UnixSeconds => ("_EPOCH", "1", "q"),
+        TimeEncoding::UnixMillis => ("_EPOCH", "1_000", "q"),
+        TimeEncoding::UnixMicros => ("_EPOCH", "1_000_000", "q"),
+        TimeEncoding::Ntp64 => ("_NTP_EPOCH", "2**32", "Q"),
+        TimeEncoding::FileTime => ("_FILETIME_EPOCH", "10_000_000", "Q"),
+    }
+}
+
+/// Struct code of a fixed-point base.
+fn py_fixed_code(base: &FixedBase) -> &'static str {
+    match base {
+        FixedBase::Int8 => "b",
+        FixedBase::UInt8 => "B",
+        FixedBase::Int16 => "h",
+        FixedBase::UInt16 => "H",
+        FixedBase::Int32 => "i",
+        FixedBase::UInt32 => "I",
+        FixedBase::Int64 => "q",
+        FixedBase::UInt64 => "Q",
+    }
+}
+
+/// (serialize, deserialize) lines of a `bool(bit)` field; a new byte is
+/// appended at its first bit and `pos` moves past it after its last.
+fn bit_snippets(name: &str, slot: &BitSlot) -> (String, String) {
+    let mask = 1u8 << slot.shift();
+    let mut ser = String::new();
+    if slot.starts_byte() {
+        ser.push_str("data.append(0)\n");
+    }
+    let _ = writeln!(&mut ser, "data[-1] |= 0x{mask:02X} if self.{name} else 0");
+    let mut de = format!("result.{name} = bool(data[pos] & 0x{mask:02X})\n");
+    if slot.ends_byte() {
+        de.push_str("pos += 1\n");
+    }
+    (ser, de)
+}
+
+/// Start of a `within` region: `data` is cut at its end (positions stay
+/// absolute) so reads to the end of the data stop there.
+fn region_open(pkt: &PacketExpr, k: usize) -> String {
+    let region = &pkt.regions[k];
+    let at = &pkt.fields[region.first].id;
+    let mut s = String::new();
+    let _ = writeln!(&mut s, "_end{k} = pos + int({})", emit_py_expr(&region.length, "result"));
+    let _ = writeln!(&mut s, "if _end{k} > len(data):");
+    let _ = writeln!(&mut s, "    raise ValueError('{}: within block at {at} runs past the end of the data')", pkt.name);
+    let _ = writeln!(&mut s, "_outer{k} = data");
+    let _ = writeln!(&mut s, "data = data[:_end{k}]");
+    s
+}
+
+/// End of a `within` region; slices never fail, so an overrun shows up here.
+fn region_close(pkt: &PacketExpr, k: usize) -> String {
+    let region = &pkt.regions[k];
+    let at = &pkt.fields[region.first].id;
+    let mut s = String::new();
+    let _ = writeln!(&mut s, "if pos > _end{k}:");
+    let _ = writeln!(&mut s, "    raise ValueError('{}: fields overrun the within block at {at}')", pkt.name);
+    match region.leftover {
+        Leftover::Error => {
+            let _ = writeln!(&mut s, "if pos < _end{k}:");
+            let _ = writeln!(
+                &mut s,
+                "    raise ValueError(f'{}: within block at {at} left {{_end{k} - pos}} bytes unread')",
+                pkt.name
+            );
+        }
+        Leftover::Skip => {
+            let _ = writeln!(&mut s, "pos = _end{k}");
+        }
+    }
+    let _ = writeln!(&mut s, "data = _outer{k}");
+    s
+}
+
+fn is_string(t: &TypeNode) -> bool {
+    matches!(t, TypeNode::FixedString(_, _) | TypeNode::CString(_) | TypeNode::PString(_, _))
+}
+
+/// Python codec name and code-unit width (for NUL terminators) of an encoding.
+fn py_codec(e: &StringEncoding) -> (&'static str, usize) {
+    match e {
+        StringEncoding::Ascii => ("ascii", 1),
+        StringEncoding::Utf8 => ("utf-8", 1),
+        StringEncoding::Utf16Le => ("utf-16-le", 2),
     }
 }
 
@@ -181,9 +681,28 @@ fn type_len_expr<'a>(t: &'a TypeNode) -> Option<&'a ExprNode> {
  * ============================================================
 */
 
-fn serialize_snippet(t: &TypeNode, name: &str, endian: Endianness) -> String {
+/// ` - a` for a length written `l + a`, so the count comes out as `l`.
+fn py_adjust(adjust: i64) -> String {
+    match adjust {
+        0 => String::new(),
+        a if a < 0 => format!(" + {}", -a),
+        a => format!(" - {a}"),
+    }
+}
+
+fn serialize_snippet(t: &TypeNode, name: &str, endian: Endianness, owner: &str) -> String {
     let mut s = String::new();
     match t {
+        // filler: zeros, up to the next multiple of n for `align(n)`
+        TypeNode::Pad(n) => {
+            let _ = writeln!(&mut s, "data += bytes({n})");
+        }
+        TypeNode::Align(n) => {
+            let _ = writeln!(&mut s, "data += bytes(-len(data) % {n})");
+        }
+        TypeNode::Repeat(_, _) => {
+            let _ = writeln!(&mut s, "for _item in self.{name}: data += _item.to_bytes()");
+        }
         TypeNode::Bytes(_) | TypeNode::MacAddress(_) => {
             // raw bytes
             let _ = writeln!(
@@ -191,6 +710,73 @@ fn serialize_snippet(t: &TypeNode, name: &str, endian: Endianness) -> String {
                 "data += (self.{name} if isinstance(self.{name}, (bytes, bytearray)) else bytes(self.{name}))"
             );
         }
+        // fixed strings are NUL-padded to exactly n bytes; longer ones are rejected
+        TypeNode::FixedString(len, enc) => {
+            let (codec, _) = py_codec(enc);
+            let py = emit_py_expr(len, "self");
+            let _ = writeln!(&mut s, "count = int({py})");
+            let _ = writeln!(&mut s, "_b = self.{name}.encode('{codec}')");
+            let _ = writeln!(
+                &mut s,
+                "if len(_b) > count: raise ValueError(f'{name}: string longer than {{count}} bytes')"
+            );
+            let _ = writeln!(&mut s, "data += _b.ljust(count, b'\\x00')");
+        }
+        TypeNode::CString(enc) => {
+            let (codec, unit) = py_codec(enc);
+            let _ = writeln!(&mut s, "data += self.{name}.encode('{codec}') + bytes({unit})");
+        }
+        TypeNode::PString(_, enc) => {
+            let (codec, _) = py_codec(enc);
+            let code = scalar_struct_code(t).expect("prefix code");
+            let prefix = py_endian_prefix(endian);
+            let max = if t.scalar_width_bytes() == 1 { 255 } else { 65535 };
+            let _ = writeln!(&mut s, "_b = self.{name}.encode('{codec}')");
+            let _ = writeln!(&mut s, "if len(_b) > {max}: raise ValueError('{name}: string longer than {max} bytes')");
+            let _ = writeln!(&mut s, "data += struct.pack('{prefix}{code}', len(_b)) + _b");
+        }
+        // the constructor also accepts str/int and rejects the other address family
+        TypeNode::Ipv4Address(_) | TypeNode::Ipv6Address(_) => {
+            let class = py_address_class(t);
+            if is_array_like(t) {
+                let _ = writeln!(&mut s, "for _v in (self.{name} or []): data += {class}(_v).packed");
+            } else {
+                let _ = writeln!(&mut s, "data += {class}(self.{name}).packed");
+            }
+        }
+        // round half away from zero, matching the other targets
+        TypeNode::Fixed(_, base, frac) => {
+            let code = py_fixed_code(base);
+            let prefix = py_endian_prefix(endian);
+            let scale = 1u128 << frac;
+            let pack = |v: &str| {
+                format!("struct.pack('{prefix}{code}', int(math.copysign(math.floor(abs({v}) * {scale} + 0.5), {v})))")
+            };
+            if is_array_like(t) {
+                let _ = writeln!(&mut s, "for _v in (self.{name} or []): data += {}", pack("_v"));
+            } else {
+                let _ = writeln!(&mut s, "data += {}", pack(&format!("self.{name}")));
+            }
+        }
+        TypeNode::VarInt(_, enc) => {
+            if is_array_like(t) {
+                let _ = writeln!(&mut s, "for _v in (self.{name} or []): data += {}", py_varint(enc, "_v").0);
+            } else {
+                let _ = writeln!(&mut s, "data += {}", py_varint(enc, &format!("{owner}{name}")).0);
+            }
+        }
+        // NTP wraps at the end of each era
+        TypeNode::DateTime(_, unit) => {
+            let (epoch, per_second, code) = py_time(unit);
+            let prefix = py_endian_prefix(endian);
+            let wrap = if matches!(unit, TimeEncoding::Ntp64) { " & 0xFFFFFFFFFFFFFFFF" } else { "" };
+            let pack = |v: &str| format!("struct.pack('{prefix}{code}', _to_ticks({v}, {epoch}, {per_second}){wrap})");
+            if is_array_like(t) {
+                let _ = writeln!(&mut s, "for _v in (self.{name} or []): data += {}", pack("_v"));
+            } else {
+                let _ = writeln!(&mut s, "data += {}", pack(&format!("self.{name}")));
+            }
+        }
         _ => {
             let code = scalar_struct_code(t).expect("scalar code");
             let width = scalar_width_bytes(t);
@@ -205,7 +791,7 @@ fn serialize_snippet(t: &TypeNode, name: &str, endian: Endianness) -> String {
             } else {
                 let _ = writeln!(
                     &mut s,
-                    "data += struct.pack('{prefix}{code}', self.{name})"
+                    "data += struct.pack('{prefix}{code}', {owner}{name})"
                 );
             }
 
@@ -219,6 +805,32 @@ fn serialize_snippet(t: &TypeNode, name: &str, endian: Endianness) -> String {
 fn deserialize_snippet(t: &TypeNode, name: &str, endian: Endianness, is_last: bool) -> String {
     let mut s = String::new();
     match t {
+        // filler is skipped, whatever it holds; the `x` format still fails on short data
+        TypeNode::Pad(n) => {
+            let _ = writeln!(&mut s, "struct.unpack_from('{n}x', data, pos); pos += {n}");
+        }
+        TypeNode::Align(n) => {
+            let _ = writeln!(&mut s, "_skip = -pos % {n}; struct.unpack_from(f'{{_skip}}x', data, pos); pos += _skip");
+        }
+        // each element is decoded from the rest of the data and reports the bytes it used
+        TypeNode::Repeat(elem, bound) => {
+            let read = format!("_item, _n = {elem}.from_prefix(data[pos:]); pos += _n; result.{name}.append(_item)");
+            let _ = writeln!(&mut s, "result.{name} = []");
+            match bound {
+                RepeatBound::Eof => {
+                    let _ = writeln!(&mut s, "while pos < len(data): {read}");
+                }
+                RepeatBound::Until(cond) => {
+                    let _ = writeln!(&mut s, "while True:");
+                    let _ = writeln!(&mut s, "    {read}");
+                    let _ = writeln!(&mut s, "    if {}: break", emit_py_expr(cond, "_item"));
+                }
+                RepeatBound::Count(expr) => {
+                    let py = emit_py_expr(expr, "result");
+                    let _ = writeln!(&mut s, "for _ in range(int({py})): {read}");
+                }
+            }
+        }
         TypeNode::Bytes(len_opt) => {
             if let Some(expr) = len_opt {
                 let py = emit_py_expr(expr, "result");
@@ -260,6 +872,99 @@ fn deserialize_snippet(t: &TypeNode, name: &str, endian: Endianness, is_last: bo
                 );
             }
         }
+        // decode() raises UnicodeDecodeError (a ValueError) on invalid input
+        TypeNode::FixedString(len, enc) => {
+            let (codec, unit) = py_codec(enc);
+            let py = emit_py_expr(len, "result");
+            let _ = writeln!(&mut s, "count = int({py})");
+            let _ = writeln!(
+                &mut s,
+                "result.{name} = _strip_nul(bytes(data[pos:pos+count]), {unit}).decode('{codec}'); pos += count"
+            );
+        }
+        TypeNode::CString(enc) => {
+            let (codec, unit) = py_codec(enc);
+            let _ = writeln!(&mut s, "_raw, pos = _read_cstring(data, pos, {unit})");
+            let _ = writeln!(&mut s, "result.{name} = _raw.decode('{codec}')");
+        }
+        TypeNode::PString(_, enc) => {
+            let (codec, _) = py_codec(enc);
+            let code = scalar_struct_code(t).expect("prefix code");
+            let width = scalar_width_bytes(t);
+            let prefix = py_endian_prefix(endian);
+            let _ = writeln!(&mut s, "count, = struct.unpack_from('{prefix}{code}', data, pos); pos += {width}");
+            let _ = writeln!(&mut s, "result.{name} = bytes(data[pos:pos+count]).decode('{codec}'); pos += count");
+        }
+        TypeNode::Ipv4Address(len_opt) | TypeNode::Ipv6Address(len_opt) => {
+            let class = py_address_class(t);
+            let width = scalar_width_bytes(t);
+            let read = format!("{class}(bytes(data[pos:pos+{width}])); pos += {width}");
+            match len_opt {
+                Some(expr) => {
+                    let py = emit_py_expr(expr, "result");
+                    let _ = writeln!(&mut s, "count = int({py})");
+                    let _ = writeln!(&mut s, "result.{name} = []");
+                    let _ = writeln!(&mut s, "for _ in range(count): _v = {read}; result.{name}.append(_v)");
+                }
+                None => {
+                    let _ = writeln!(&mut s, "result.{name} = {read}");
+                }
+            }
+        }
+        TypeNode::VarInt(len_opt, enc) => {
+            let read = py_varint(enc, "").1;
+            let value = if *enc == VarIntEncoding::ZigZag { "_unzigzag(_v)" } else { "_v" };
+            match len_opt {
+                Some(expr) => {
+                    let py = emit_py_expr(expr, "result");
+                    let _ = writeln!(&mut s, "count = int({py})");
+                    let _ = writeln!(&mut s, "result.{name} = []");
+                    let _ = writeln!(&mut s, "for _ in range(count): _v, pos = {read}; result.{name}.append({value})");
+                }
+                None => {
+                    let _ = writeln!(&mut s, "_v, pos = {read}");
+                    let _ = writeln!(&mut s, "result.{name} = {value}");
+                }
+            }
+        }
+        TypeNode::Fixed(len_opt, base, frac) => {
+            let code = py_fixed_code(base);
+            let prefix = py_endian_prefix(endian);
+            let read = format!(
+                "struct.unpack_from('{prefix}{code}', data, pos)[0] / {}; pos += {}",
+                1u128 << frac,
+                base.width_bytes()
+            );
+            match len_opt {
+                Some(expr) => {
+                    let py = emit_py_expr(expr, "result");
+                    let _ = writeln!(&mut s, "count = int({py})");
+                    let _ = writeln!(&mut s, "result.{name} = []");
+                    let _ = writeln!(&mut s, "for _ in range(count): _v = {read}; result.{name}.append(_v)");
+                }
+                None => {
+                    let _ = writeln!(&mut s, "result.{name} = {read}");
+                }
+            }
+        }
+        TypeNode::DateTime(len_opt, unit) => {
+            let (epoch, per_second, code) = py_time(unit);
+            let prefix = py_endian_prefix(endian);
+            let read = format!(
+                "_from_ticks(struct.unpack_from('{prefix}{code}', data, pos)[0], {epoch}, {per_second}); pos += 8"
+            );
+            match len_opt {
+                Some(expr) => {
+                    let py = emit_py_expr(expr, "result");
+                    let _ = writeln!(&mut s, "count = int({py})");
+                    let _ = writeln!(&mut s, "result.{name} = []");
+                    let _ = writeln!(&mut s, "for _ in range(count): _v = {read}; result.{name}.append(_v)");
+                }
+                None => {
+                    let _ = writeln!(&mut s, "result.{name} = {read}");
+                }
+            }
+        }
         _ => {
             let code = scalar_struct_code(t).expect("scalar code");
             let width = scalar_width_bytes(t);
@@ -310,6 +1015,17 @@ fn deserialize_snippet(t: &TypeNode, name: &str, endian: Endianness, is_last: bo
  * ============================================================
 */
 
+/// Raises ValidationError unless the rule holds for the fields of `root`.
+fn check_snippet(pkt: &PacketExpr, check: &Check, root: &str) -> String {
+    format!(
+        "if not ({}):\n    raise ValidationError('{}', '{}', '{}')\n",
+        emit_py_expr(&check.cond, root),
+        pkt.name,
+        check.field,
+        py_escape(&check.rule)
+    )
+}
+
 fn emit_py_expr(e: &ExprNode, root_ident: &str) -> String {
     use ExprNode::*;
     match e {
@@ -325,13 +1041,35 @@ fn emit_py_expr(e: &ExprNode, root_ident: &str) -> String {
             }
         }
         ActivationRecord(fname, args) => {
+            let args_s: Vec<String> = args.iter().map(|a| emit_py_expr(a, root_ident)).collect();
+            let bswap = |width: usize| {
+                format!(
+                    "int.from_bytes((int({}) & 0x{}).to_bytes({width}, 'little'), 'big')",
+                    args_s[0],
+                    "FF".repeat(width)
+                )
+            };
+            match (fname.as_str(), &args[..]) {
+                // Python's `round` rounds halves to even; the DSL's rounds them away from zero
+                ("round", _) => return format!("int(math.copysign(math.floor(abs({0}) + 0.5), {0}))", args_s[0]),
+                ("clamp", _) => return format!("min(max({}, {}), {})", args_s[0], args_s[1], args_s[2]),
+                ("pow", [a, b]) => return emit_py_expr(&Pow(Box::new(a.clone()), Box::new(b.clone())), root_ident),
+                ("popcount", _) => return format!("bin(int({}) & 0xFFFFFFFFFFFFFFFF).count('1')", args_s[0]),
+                ("bswap16", _) => return bswap(2),
+                ("bswap32", _) => return bswap(4),
+                ("bswap64", _) => return bswap(8),
+                _ => {}
+            }
             let mapped = match fname.as_str() {
                 "sqrt" => "math.sqrt",
                 "min" => "min",
                 "max" => "max",
+                "abs" => "abs",
+                "floor" => "math.floor",
+                "ceil" => "math.ceil",
+                "log2" => "math.log2",
                 _ => fname,
             };
-            let args_s: Vec<String> = args.iter().map(|a| emit_py_expr(a, root_ident)).collect();
             format!("{}({})", mapped, args_s.join(", "))
         }
         AggregateSum(id) => format!("0  # sumof({}) unsupported in size expr", id),
@@ -348,6 +1086,18 @@ fn emit_py_expr(e: &ExprNode, root_ident: &str) -> String {
         Minus(a, b) => bin(a, b, "-", root_ident),
         Mult(a, b) => bin(a, b, "*", root_ident),
         Div(a, b) => bin(a, b, "/", root_ident),
+        // DSL `%` truncates like C; Python's `%` floors
+        Mod(a, b) => format!(
+            "int(math.fmod({}, {}))",
+            emit_py_expr(a, root_ident),
+            emit_py_expr(b, root_ident)
+        ),
+        Negate(a) => format!("-({})", emit_py_expr(a, root_ident)),
+        BitAnd(a, b) => int_bin(a, b, "&", root_ident),
+        BitOr(a, b) => int_bin(a, b, "|", root_ident),
+        BitNot(a) => format!("~int({})", emit_py_expr(a, root_ident)),
+        ShiftLeft(a, b) => shift(a, b, "<<", root_ident),
+        ShiftRight(a, b) => shift(a, b, ">>", root_ident),
         Pow(a, b) => format!(
             "int(pow({}, {}))",
             emit_py_expr(a, root_ident),
@@ -362,6 +1112,7 @@ fn emit_py_expr(e: &ExprNode, root_ident: &str) -> String {
         NotEquals(a, b) => bin(a, b, "!=", root_ident),
         And(a, b) => bin(a, b, "and", root_ident),
         Or(a, b) => bin(a, b, "or", root_ident),
+        Not(a) => format!("(not ({}))", emit_py_expr(a, root_ident)),
 
         NoExpr => "0".to_string(),
     }
@@ -376,6 +1127,26 @@ fn bin(a: &ExprNode, b: &ExprNode, op: &str, root_ident: &str) -> String {
     )
 }
 
+/// Bitwise operators need ints; operands may be floats after `/` or `sqrt`.
+fn int_bin(a: &ExprNode, b: &ExprNode, op: &str, root_ident: &str) -> String {
+    format!(
+        "int({}) {} int({})",
+        emit_py_expr(a, root_ident),
+        op,
+        emit_py_expr(b, root_ident)
+    )
+}
+
+/// Shifts use the low six bits of the count, like the other targets.
+fn shift(a: &ExprNode, b: &ExprNode, op: &str, root_ident: &str) -> String {
+    format!(
+        "(int({}) {} (int({}) & 63))",
+        emit_py_expr(a, root_ident),
+        op,
+        emit_py_expr(b, root_ident)
+    )
+}
+
 fn py_escape(s: &str) -> String {
     s.chars()
         .flat_map(|c| match c {
@@ -406,3 +1177,47 @@ fn indent(s: &str, tabs: usize) -> String {
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::load_source;
+
+    fn py(src: &str) -> String {
+        PythonGenerator::generate(&load_source(src))
+    }
+
+    #[test]
+    fn strings_that_do_not_fit_are_rejected() {
+        let out = py("packet Names be { tag string[4], short pstring<uint8>, long pstring<uint16> }");
+        assert!(out.contains("if len(_b) > count: raise ValueError(f'tag: string longer than {count} bytes')"));
+        assert!(out.contains("if len(_b) > 255: raise ValueError('short: string longer than 255 bytes')"));
+        assert!(out.contains("if len(_b) > 65535: raise ValueError('long: string longer than 65535 bytes')"));
+        assert!(!out.contains("[:count]"));
+    }
+
+    #[test]
+    fn counts_are_written_from_the_field_they_size() {
+        let out = py("packet E { x uint8 } packet L be { l uint16, v bytes[l], length uint8, body uint16[length - 10], n uint8, items repeat E[n] }");
+        assert!(out.contains("        l = len(self.v)\n        data += struct.pack('>H', l)\n"));
+        assert!(out.contains("        length = len(self.body) + 10\n        data += struct.pack('>B', length)\n"));
+        assert!(out.contains("        n = len(self.items)\n"));
+    }
+
+    #[test]
+    fn bad_magic_raises() {
+        let out = py("packet Flap be { start uint8 = 0x2a, v uint16 }");
+        assert!(out.contains("    start = 0x2a\n"));
+        assert!(out.contains(
+            "if result.start != 0x2a: raise ValueError(f\"Flap: start must be 0x2a, got {result.start:#x}\")"
+        ));
+    }
+
+    #[test]
+    fn fillers_are_zeros_and_skipped() {
+        let out = py("packet F be { v uint8, pad(3), n uint32, reserved uint16, align(8) }");
+        assert!(out.contains("struct.unpack_from('3x', data, pos); pos += 3"));
+        assert!(out.contains("struct.unpack_from('2x', data, pos); pos += 2"));
+        assert!(out.contains("_skip = -pos % 8; struct.unpack_from(f'{_skip}x', data, pos); pos += _skip"));
+    }
+}
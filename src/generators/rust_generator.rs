@@ -1,5 +1,6 @@
 use crate::models::parsing_models::{
-    Endianness, ExprNode, PacketExpr, PacketExprList, TypeNode,
+    BitSlot, Check, DerivedCount, Endianness, ExprNode, FixedBase, Leftover, PacketExpr, PacketExprList, RepeatBound, StringEncoding,
+    TimeEncoding, TypeNode, VarIntEncoding,
 };
 use std::fmt::Write as _;
 pub struct RustGenerator {}
@@ -9,6 +10,26 @@ impl RustGenerator {
         let mut out = String::new();
         out.push_str(&Self::create_headers());
         out.push_str(&Self::create_spacer());
+        if model.packets.iter().flat_map(|p| &p.fields).any(|f| is_string(&f.expr)) {
+            out.push_str(STRING_CODEC);
+            out.push_str(&Self::create_spacer());
+        }
+        if model.packets.iter().flat_map(|p| &p.fields).any(|f| matches!(f.expr, TypeNode::DateTime(_, _))) {
+            out.push_str(TIME_CODEC);
+            out.push_str(&Self::create_spacer());
+        }
+        if model.packets.iter().flat_map(|p| &p.fields).any(|f| matches!(f.expr, TypeNode::VarInt(_, _))) {
+            out.push_str(VARINT_CODEC);
+            out.push_str(&Self::create_spacer());
+        }
+        if model.packets.iter().any(|p| !p.checksums().is_empty()) {
+            out.push_str(CHECKSUM);
+            out.push_str(&Self::create_spacer());
+        }
+        if !model.packets.is_empty() {
+            out.push_str(VALIDATION_ERROR);
+            out.push_str(&Self::create_spacer());
+        }
 
         for pkt in &model.packets {
             out.push_str(&Self::build_struct(pkt));
@@ -16,7 +37,16 @@ impl RustGenerator {
             out.push_str(&Self::create_spacer());
         }
 
-        out
+        Self::wrap_in_modules(out, &model.package_segments())
+    }
+
+    /// Nests the output in one `pub mod` per package segment (`oscar.v1` -> `oscar::v1`).
+    fn wrap_in_modules(body: String, segments: &[&str]) -> String {
+        let Some((outer, inner)) = segments.split_first() else {
+            return body;
+        };
+        let body = Self::wrap_in_modules(body, inner);
+        format!("pub mod {} {{\n{}}}\n", outer.to_lowercase(), indent(&body, 1))
     }
 
     fn create_spacer() -> String {
@@ -39,40 +69,176 @@ use serde::{Serialize, Deserialize};
     fn build_struct(pkt: &PacketExpr) -> String {
         let mut fields = String::new();
 
-        for f in &pkt.fields {
-            let ty = rust_field_type(&f.expr);
+        // const fields live on the impl, see create_serialization_impl; filler is not stored
+        for f in pkt.fields.iter().filter(|f| f.value.is_none() && !f.is_filler()) {
+            let ty = match f.condition {
+                Some(_) => format!("Option<{}>", rust_field_type(&f.expr)),
+                None => rust_field_type(&f.expr),
+            };
+            fields.push_str(&doc_comment(&f.doc, "    "));
             let _ = writeln!(&mut fields, "    pub {}: {},", f.id, ty);
         }
 
         for cf in &pkt.calculated_fields {
             let ty = rust_type_from_type_name(&cf.data_type);
+            fields.push_str(&doc_comment(&cf.doc, "    "));
             let _ = writeln!(&mut fields, "    pub {}: {},", cf.name, ty);
         }
 
         format!(
-            r#"#[derive(Debug, Clone, Serialize, Deserialize)]
+            r#"{doc}#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct {name} {{
 {fields}
 }}
 
 "#,
+            doc = doc_comment(&pkt.doc, ""),
             name = pkt.name,
             fields = fields
         )
     }
 
     fn create_serialization_impl(pkt: &PacketExpr) -> String {
+        let slots = pkt.bit_slots();
+        let derived = pkt.derived_counts();
+        let marks = pkt.checksum_marks();
         let mut ser_body = String::new();
+        for (((f, slot), count), &(start, end)) in pkt.fields.iter().zip(&slots).zip(&derived).zip(&marks) {
+            if start {
+                let _ = writeln!(&mut ser_body, "let _{}_start = data.len();", f.id);
+            }
+            if let Some(slot) = slot {
+                ser_body.push_str(&emit_bit_serialize(&f.id, slot));
+                if end {
+                    let _ = writeln!(&mut ser_body, "let _{}_end = data.len();", f.id);
+                }
+                continue;
+            }
+            let endian = f.endianness.as_ref().or(pkt.endianness.as_ref()).unwrap_or(&Endianness::Le);
+            // count fields are written from the field they size, whatever the struct holds
+            let owner = match (count, &f.value) {
+                (Some(count), _) => {
+                    ser_body.push_str(&emit_derived_count(pkt, &f.id, &f.expr, count));
+                    ""
+                }
+                // written as zero, then filled in once the packet is encoded
+                (None, Some(_)) if f.checksum().is_some() => {
+                    let _ = writeln!(&mut ser_body, "let _{}_at = data.len();", f.id);
+                    let _ = writeln!(&mut ser_body, "let {}: {} = 0;", f.id, rust_field_type(&f.expr));
+                    ""
+                }
+                (None, Some(_)) => {
+                    let _ = writeln!(&mut ser_body, "let {} = Self::{};", f.id, f.id.to_uppercase());
+                    ""
+                }
+                (None, None) => "self.",
+            };
+            // optional fields are written when their condition holds, and must be set then
+            match &f.condition {
+                Some(cond) => {
+                    let _ = writeln!(&mut ser_body, "if {} != 0.0 {{", emit_rust_numeric_expr(cond, "self."));
+                    let _ = writeln!(
+                        &mut ser_body,
+                        "    let {id} = self.{id}.clone().expect(\"{}: {id} must be set when its condition holds\");",
+                        pkt.name,
+                        id = f.id
+                    );
+                    ser_body.push_str(&indent(&emit_field_serialize(&f.id, &f.expr, endian.clone(), ""), 1));
+                    let _ = writeln!(&mut ser_body, "}}");
+                }
+                None => ser_body.push_str(&emit_field_serialize(&f.id, &f.expr, endian.clone(), owner)),
+            }
+            if end {
+                let _ = writeln!(&mut ser_body, "let _{}_end = data.len();", f.id);
+            }
+        }
         for f in &pkt.fields {
+            let Some(sum) = f.checksum() else { continue };
             let endian = f.endianness.as_ref().or(pkt.endianness.as_ref()).unwrap_or(&Endianness::Le);
-            ser_body.push_str(&emit_field_serialize(&f.id, &f.expr, endian.clone()));
+            let _ = writeln!(
+                &mut ser_body,
+                "let {id} = checksum::{}(&data[_{}_start.._{}_end]);",
+                sum.kind.name(),
+                sum.first,
+                sum.last,
+                id = f.id
+            );
+            let _ = writeln!(
+                &mut ser_body,
+                "data[_{id}_at.._{id}_at + {w}].copy_from_slice(&{id}.to_{}_bytes());",
+                if *endian == Endianness::Be { "be" } else { "le" },
+                id = f.id,
+                w = sum.kind.width_bytes()
+            );
         }
 
         let mut de_body = String::new();
-        for (i, f) in pkt.fields.iter().enumerate() {
-            let endian = f.endianness.as_ref().or(pkt.endianness.as_ref()).unwrap_or(&Endianness::Le);
-            let last = i + 1 == pkt.fields.len();
-            de_body.push_str(&emit_field_deserialize(&f.id, &f.expr, endian.clone(), last));
+        for (i, (f, slot)) in pkt.fields.iter().zip(&slots).enumerate() {
+            for (k, _) in pkt.regions_opening(i) {
+                de_body.push_str(&emit_region_open(pkt, k));
+            }
+            let (start, end) = marks[i];
+            if start {
+                let _ = writeln!(&mut de_body, "let _{}_start = cur.position() as usize;", f.id);
+            }
+            if f.checksum().is_some() {
+                let _ = writeln!(&mut de_body, "let _{}_at = cur.position() as usize;", f.id);
+            }
+            if let Some(slot) = slot {
+                de_body.push_str(&emit_bit_deserialize(&f.id, slot));
+            } else {
+                let endian = f.endianness.as_ref().or(pkt.endianness.as_ref()).unwrap_or(&Endianness::Le);
+                let last = pkt.is_last_in_scope(i);
+                let read = emit_field_deserialize(&f.id, &f.expr, endian.clone(), last);
+                match &f.condition {
+                    Some(cond) => {
+                        let _ = writeln!(&mut de_body, "let {} = if {} != 0.0 {{", f.id, emit_rust_numeric_expr(cond, ""));
+                        de_body.push_str(&indent(&read, 1));
+                        let _ = writeln!(&mut de_body, "    Some({})\n}} else {{\n    None\n}};", f.id);
+                    }
+                    None => de_body.push_str(&read),
+                }
+            }
+            if end {
+                let _ = writeln!(&mut de_body, "let _{}_end = cur.position() as usize;", f.id);
+            }
+            if f.value.is_some() && f.checksum().is_none() {
+                let rule = format!("{} == {}", f.id, f.const_literal().unwrap_or_default());
+                let _ = writeln!(
+                    &mut de_body,
+                    "if {id} != Self::{c} {{\n    return Err({});\n}}",
+                    validation_error(pkt, &f.id, &rule),
+                    id = f.id,
+                    c = f.id.to_uppercase()
+                );
+            }
+            if let Some(check) = &f.constraint {
+                de_body.push_str(&emit_check(pkt, check, ""));
+            }
+            for (k, _) in pkt.regions_closing(i) {
+                de_body.push_str(&emit_region_close(pkt, k));
+            }
+        }
+
+        // checksums are recomputed with their own field zeroed, as when they were filled in
+        for f in &pkt.fields {
+            let Some(sum) = f.checksum() else { continue };
+            let _ = writeln!(
+                &mut de_body,
+                "let _sum = checksum::{}(&checksum::without(data, _{}_start, _{}_end, _{id}_at, {}));",
+                sum.kind.name(),
+                sum.first,
+                sum.last,
+                sum.kind.width_bytes(),
+                id = f.id
+            );
+            let rule = format!("{} == {}({}..{})", f.id, sum.kind.name(), sum.first, sum.last);
+            let _ = writeln!(
+                &mut de_body,
+                "if {} != _sum {{\n    return Err({});\n}}",
+                f.id,
+                validation_error(pkt, &f.id, &rule)
+            );
         }
 
         // calculated fields
@@ -88,42 +254,310 @@ pub struct {name} {{
                 expr = expr
             );
         }
+        for check in &pkt.asserts {
+            calc_lets.push_str(&indent(&emit_check(pkt, check, ""), 2));
+        }
+
+        let mut consts = String::new();
+        for f in pkt.fields.iter().filter(|f| f.value.is_some() && f.checksum().is_none()) {
+            consts.push_str(&doc_comment(&f.doc, "    "));
+            let _ = writeln!(
+                &mut consts,
+                "    pub const {}: {} = {};",
+                f.id.to_uppercase(),
+                rust_field_type(&f.expr),
+                f.const_literal().unwrap_or_default()
+            );
+        }
+
+        let checks = pkt.checks();
+        let validate = if checks.is_empty() {
+            String::new()
+        } else {
+            let body: String = checks.iter().map(|c| emit_check(pkt, c, "self.")).collect();
+            format!(
+                "\n    /// Checks the packet's `where` and `assert` rules, as decoding does.\n    pub fn validate(&self) -> Result<(), ValidationError> {{\n{}        Ok(())\n    }}\n",
+                indent(&body, 2)
+            )
+        };
 
         let mut build_fields = String::new();
-        for f in &pkt.fields {
+        for f in pkt.fields.iter().filter(|f| f.value.is_none() && !f.is_filler()) {
             let _ = writeln!(&mut build_fields, "            {name},", name = f.id);
         }
         for cf in &pkt.calculated_fields {
             let _ = writeln!(&mut build_fields, "            {name},", name = cf.name);
         }
 
+        // decoding returns the first rule the data breaks, including running out
+        // of bytes; the plain functions panic with it
+        let decode = format!(
+            r#"    pub fn deserialize(data: &[u8]) -> {name} {{
+        Self::deserialize_prefix(data).0
+    }}
+
+    /// Decodes one {name} from the front of `data`; also returns the bytes it used.
+    /// Panics on data that does not decode; see `try_deserialize_prefix`.
+    pub fn deserialize_prefix(data: &[u8]) -> ({name}, usize) {{
+        Self::try_deserialize_prefix(data).unwrap_or_else(|e| panic!("{{}}", e))
+    }}
+
+    /// Decodes a {name}, returning the first rule the data breaks.
+    pub fn try_deserialize(data: &[u8]) -> Result<{name}, ValidationError> {{
+        Ok(Self::try_deserialize_prefix(data)?.0)
+    }}
+
+    /// Decodes one {name} from the front of `data`; also returns the bytes it used,
+    /// or the first rule the data breaks: a `where` or `assert` rule, a constant,
+    /// a `within` length, or running out of bytes.
+    pub fn try_deserialize_prefix(data: &[u8]) -> Result<({name}, usize), ValidationError> {{
+        let mut cur = Cursor::new(data);
+        let _read = |field: &'static str| ValidationError::read({name:?}, field);
+
+{de_body}{calc_lets}
+        let packet = {name} {{
+{build_fields}        }};
+        Ok((packet, cur.position() as usize))
+    }}
+"#,
+            name = pkt.name,
+            de_body = indent(&de_body, 2),
+        );
+
         format!(
             r#"impl {name} {{
-    pub fn serialize(&self) -> Vec<u8> {{
+{consts}    pub fn serialize(&self) -> Vec<u8> {{
         let mut data: Vec<u8> = Vec::new();
 {ser_body}
         data
     }}
 
-    pub fn deserialize(data: &[u8]) -> {name} {{
-        let mut cur = Cursor::new(data);
-
-{de_body}{calc_lets}
-        {name} {{
-{build_fields}        }}
-    }}
-}}
+{decode}{validate}}}
 
 "#,
             name = pkt.name,
             ser_body = indent(&ser_body, 2),
-            de_body = indent(&de_body, 2),
-            calc_lets = calc_lets,
-            build_fields = build_fields
         )
     }
 }
 
+/// Checksum intrinsics, emitted only when a packet uses one.
+const CHECKSUM: &str = r#"mod checksum {
+    #![allow(dead_code)]
+
+    /// CRC-32 (IEEE 802.3).
+    pub fn crc32(data: &[u8]) -> u32 {
+        let mut crc = 0xFFFF_FFFFu32;
+        for &b in data {
+            crc ^= u32::from(b);
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            }
+        }
+        !crc
+    }
+
+    /// CRC-16/CCITT-FALSE.
+    pub fn crc16_ccitt(data: &[u8]) -> u16 {
+        let mut crc = 0xFFFFu16;
+        for &b in data {
+            crc ^= u16::from(b) << 8;
+            for _ in 0..8 {
+                crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+            }
+        }
+        crc
+    }
+
+    /// RFC 1071 Internet checksum; an odd last byte is padded with zero.
+    pub fn inet_checksum(data: &[u8]) -> u16 {
+        let mut sum: u64 = data
+            .chunks(2)
+            .map(|w| u64::from(w[0]) << 8 | u64::from(w.get(1).copied().unwrap_or(0)))
+            .sum();
+        while sum > 0xFFFF {
+            sum = (sum & 0xFFFF) + (sum >> 16);
+        }
+        !(sum as u16)
+    }
+
+    /// Adler-32 (RFC 1950).
+    pub fn adler32(data: &[u8]) -> u32 {
+        let (mut a, mut b) = (1u32, 0u32);
+        for &x in data {
+            a = (a + u32::from(x)) % 65521;
+            b = (b + a) % 65521;
+        }
+        b << 16 | a
+    }
+
+    /// `data[start..end]` with the `width` bytes at `at` zeroed, as they were
+    /// when the checksum stored there was computed.
+    pub fn without(data: &[u8], start: usize, end: usize, at: usize, width: usize) -> Vec<u8> {
+        let mut out = data[start..end].to_vec();
+        for i in at.max(start)..(at + width).min(end) {
+            out[i - start] = 0;
+        }
+        out
+    }
+}
+"#;
+
+/// Error for data that does not decode: a broken `where` or `assert` rule, a
+/// constant, a `within` length, or a read past the end of the data.
+const VALIDATION_ERROR: &str = r#"/// A packet field broke one of its rules. Besides `where` and `assert` rules
+/// these are `field == constant`, the `within` checks, `enough data` for a read
+/// past the end and `valid encoding` for malformed text or varints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub packet: &'static str,
+    pub field: &'static str,
+    pub rule: &'static str,
+}
+
+impl ValidationError {
+    /// Maps a failed read of `field` to the rule it breaks.
+    pub fn read(packet: &'static str, field: &'static str) -> impl Fn(std::io::Error) -> ValidationError {
+        move |e| ValidationError {
+            packet,
+            field,
+            rule: match e.kind() {
+                std::io::ErrorKind::UnexpectedEof => "enough data",
+                _ => "valid encoding",
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} breaks `{}`", self.packet, self.field, self.rule)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+"#;
+
+/// Helpers for string fields, emitted only when a packet has one. Decoding
+/// fails with `InvalidData` on bytes that are not valid in the declared encoding.
+const STRING_CODEC: &str = r#"mod string_codec {
+    #![allow(dead_code)]
+    use std::io::{self, Cursor, Read};
+
+    pub fn encode_utf16le(s: &str) -> Vec<u8> {
+        s.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    /// Cuts `raw` at its first NUL code unit (1 byte, or 2 for UTF-16).
+    pub fn strip_nul(raw: &[u8], unit: usize) -> &[u8] {
+        let end = raw
+            .chunks_exact(unit)
+            .position(|c| c.iter().all(|&b| b == 0))
+            .map_or(raw.len(), |i| i * unit);
+        &raw[..end]
+    }
+
+    /// Reads through the next NUL code unit and returns the bytes before it.
+    pub fn read_cstring(cur: &mut Cursor<&[u8]>, unit: usize) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        let mut c = vec![0u8; unit];
+        loop {
+            cur.read_exact(&mut c)?;
+            if c.iter().all(|&b| b == 0) {
+                return Ok(out);
+            }
+            out.extend_from_slice(&c);
+        }
+    }
+
+    fn invalid(what: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, what)
+    }
+
+    pub fn decode_ascii(raw: &[u8]) -> io::Result<String> {
+        if !raw.is_ascii() {
+            return Err(invalid("invalid ascii"));
+        }
+        String::from_utf8(raw.to_vec()).map_err(|_| invalid("invalid ascii"))
+    }
+
+    pub fn decode_utf8(raw: &[u8]) -> io::Result<String> {
+        String::from_utf8(raw.to_vec()).map_err(|_| invalid("invalid utf8"))
+    }
+
+    pub fn decode_utf16le(raw: &[u8]) -> io::Result<String> {
+        if raw.len() % 2 != 0 {
+            return Err(invalid("odd utf16le length"));
+        }
+        let units: Vec<u16> = raw.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+        String::from_utf16(&units).map_err(|_| invalid("invalid utf16le"))
+    }
+}
+"#;
+
+/// SystemTime <-> wire integer conversions for `datetime` fields, emitted only when used.
+const TIME_CODEC: &str = r#"mod time_codec {
+    #![allow(dead_code)]
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    const NANOS: i128 = 1_000_000_000;
+    const NTP_EPOCH_OFFSET: i128 = 2_208_988_800; // seconds from 1900-01-01 to 1970-01-01
+    const FILETIME_EPOCH_OFFSET: i128 = 11_644_473_600; // seconds from 1601-01-01 to 1970-01-01
+
+    /// Signed nanoseconds since the Unix epoch.
+    fn nanos(t: SystemTime) -> i128 {
+        match t.duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_nanos() as i128,
+            Err(e) => -(e.duration().as_nanos() as i128),
+        }
+    }
+
+    fn from_nanos(n: i128) -> SystemTime {
+        let abs = n.unsigned_abs();
+        let d = Duration::new((abs / 1_000_000_000) as u64, (abs % 1_000_000_000) as u32);
+        if n >= 0 { UNIX_EPOCH + d } else { UNIX_EPOCH - d }
+    }
+
+    pub fn to_unix_s(t: SystemTime) -> i64 { nanos(t).div_euclid(NANOS) as i64 }
+    pub fn from_unix_s(v: i64) -> SystemTime { from_nanos(v as i128 * NANOS) }
+    pub fn to_unix_ms(t: SystemTime) -> i64 { nanos(t).div_euclid(1_000_000) as i64 }
+    pub fn from_unix_ms(v: i64) -> SystemTime { from_nanos(v as i128 * 1_000_000) }
+    pub fn to_unix_us(t: SystemTime) -> i64 { nanos(t).div_euclid(1_000) as i64 }
+    pub fn from_unix_us(v: i64) -> SystemTime { from_nanos(v as i128 * 1_000) }
+
+    /// 32.32 fixed point seconds since 1900; wraps at the end of each NTP era.
+    pub fn to_ntp64(t: SystemTime) -> u64 {
+        let n = nanos(t) + NTP_EPOCH_OFFSET * NANOS;
+        let secs = n.div_euclid(NANOS) as u64;
+        let frac = ((n.rem_euclid(NANOS) << 32) / NANOS) as u64;
+        (secs << 32) | frac
+    }
+
+    pub fn from_ntp64(v: u64) -> SystemTime {
+        let secs = (v >> 32) as i128 - NTP_EPOCH_OFFSET;
+        let frac = ((v & 0xffff_ffff) as i128 * NANOS) >> 32;
+        from_nanos(secs * NANOS + frac)
+    }
+
+    pub fn to_filetime(t: SystemTime) -> u64 {
+        (nanos(t).div_euclid(100) + FILETIME_EPOCH_OFFSET * 10_000_000) as u64
+    }
+
+    pub fn from_filetime(v: u64) -> SystemTime {
+        from_nanos((v as i128 - FILETIME_EPOCH_OFFSET * 10_000_000) * 100)
+    }
+}
+"#;
+
+/// LEB128, zigzag and QUIC varint codecs, emitted only when a packet has a varint.
+/// Decoding fails on truncated input or an LEB128 value over 10 bytes. The body
+/// lives in `templates/rust/varint_codec.rs` so the tests below can run it.
+const VARINT_CODEC: &str = concat!(
+    "mod varint_codec {\n    #![allow(dead_code)]\n",
+    include_str!("../../templates/rust/varint_codec.rs"),
+    "}\n"
+);
+
 /* =====================================
  * Type mapping helpers
  * ===================================== */
@@ -142,10 +576,20 @@ fn rust_field_type(t: &TypeNode) -> String {
         Integer64(len)        => if len.is_some() { "Vec<i64>".into() } else { "i64".into() },
         Float32(len)          => if len.is_some() { "Vec<f32>".into() } else { "f32".into() },
         Float64(len)          => if len.is_some() { "Vec<f64>".into() } else { "f64".into() },
-        DateTime(len)         => if len.is_some() { "Vec<i64>".into() } else { "i64".into() },
+        DateTime(len, _)      => if len.is_some() { "Vec<std::time::SystemTime>".into() } else { "std::time::SystemTime".into() },
+        Bool(len, _)          => if len.is_some() { "Vec<bool>".into() } else { "bool".into() },
+        Fixed(len, _, _)      => if len.is_some() { "Vec<f64>".into() } else { "f64".into() },
+        VarInt(len, _)        => if len.is_some() { format!("Vec<{}>", base_scalar_rust(t)) } else { base_scalar_rust(t).into() },
+        Ipv4Address(len) | Ipv6Address(len) | Port(len) => {
+            if len.is_some() { format!("Vec<{}>", base_scalar_rust(t)) } else { base_scalar_rust(t).into() }
+        }
 
         Bytes(_len)           => "Vec<u8>".into(),
         MacAddress(len)       => if len.is_some() { "Vec<u8>".into() } else { "[u8; 6]".into() },
+
+        FixedString(_, _) | CString(_) | PString(_, _) => "String".into(),
+        Repeat(elem, _) => format!("Vec<{elem}>"),
+        Pad(_) | Align(_) => "()".into(), // never stored
     }
 }
 
@@ -178,11 +622,85 @@ fn endian_ident(e: Endianness) -> &'static str {
 fn elem_size_bytes(t: &TypeNode) -> usize {
     use TypeNode::*;
     match t {
-        UnsignedInteger8(_) | Integer8(_) => 1,
-        UnsignedInteger16(_) | Integer16(_) => 2,
-        UnsignedInteger32(_) | Integer32(_) | Float32(_) => 4,
-        UnsignedInteger64(_) | Integer64(_) | Float64(_) | DateTime(_) => 8,
-        Bytes(_) | MacAddress(_) => 1,
+        UnsignedInteger8(_) | Integer8(_) | Bool(_, _) => 1,
+        UnsignedInteger16(_) | Integer16(_) | Port(_) => 2,
+        UnsignedInteger32(_) | Integer32(_) | Float32(_) | Ipv4Address(_) => 4,
+        Ipv6Address(_) => 16,
+        UnsignedInteger64(_) | Integer64(_) | Float64(_) | DateTime(_, _) => 8,
+        Fixed(_, base, _) => base.width_bytes(),
+        Bytes(_) | MacAddress(_) | FixedString(_, _) | CString(_) => 1,
+        PString(_, _) => t.scalar_width_bytes(), // the length prefix
+        VarInt(_, _) => 1,                       // at least; read one value at a time
+        Repeat(_, _) => 0,                       // each element reports its own size
+        Pad(_) | Align(_) => 1,
+    }
+}
+
+/// `time_codec` function suffix and byteorder method suffix for a datetime encoding.
+fn time_codec_fn(unit: &TimeEncoding) -> (&'static str, &'static str) {
+    match unit {
+        TimeEncoding::UnixSeconds => ("unix_s", "i64"),
+        TimeEncoding::UnixMillis => ("unix_ms", "i64"),
+        TimeEncoding::UnixMicros => ("unix_us", "i64"),
+        TimeEncoding::Ntp64 => ("ntp64", "u64"),
+        TimeEncoding::FileTime => ("filetime", "u64"),
+    }
+}
+
+/// byteorder method suffix (and integer type) holding a fixed-point value.
+/// `varint_codec` function suffix for an encoding.
+fn varint_codec_fn(enc: &VarIntEncoding) -> &'static str {
+    match enc {
+        VarIntEncoding::Leb128 => "leb128",
+        VarIntEncoding::ZigZag => "zigzag",
+        VarIntEncoding::Quic => "quic",
+    }
+}
+
+fn fixed_wire(base: &FixedBase) -> &'static str {
+    match base {
+        FixedBase::Int8 => "i8",
+        FixedBase::UInt8 => "u8",
+        FixedBase::Int16 => "i16",
+        FixedBase::UInt16 => "u16",
+        FixedBase::Int32 => "i32",
+        FixedBase::UInt32 => "u32",
+        FixedBase::Int64 => "i64",
+        FixedBase::UInt64 => "u64",
+    }
+}
+
+/// 2^frac as an f64 literal.
+fn fixed_scale(frac: u8) -> String {
+    format!("{}.0", 1u128 << frac)
+}
+
+fn is_string(t: &TypeNode) -> bool {
+    matches!(t, TypeNode::FixedString(_, _) | TypeNode::CString(_) | TypeNode::PString(_, _))
+}
+
+/// Expression producing the encoded bytes of the string `value`.
+fn encode_string(value: &str, e: &StringEncoding) -> String {
+    match e {
+        StringEncoding::Ascii | StringEncoding::Utf8 => format!("{value}.as_bytes().to_vec()"),
+        StringEncoding::Utf16Le => format!("string_codec::encode_utf16le(&{value})"),
+    }
+}
+
+/// Expression decoding `raw` (a `&[u8]`) into a String, panicking on invalid input.
+fn decode_string(raw: &str, e: &StringEncoding) -> String {
+    let decoder = match e {
+        StringEncoding::Ascii => "decode_ascii",
+        StringEncoding::Utf8 => "decode_utf8",
+        StringEncoding::Utf16Le => "decode_utf16le",
+    };
+    format!("string_codec::{decoder}({raw})")
+}
+
+fn code_unit(e: &StringEncoding) -> usize {
+    match e {
+        StringEncoding::Utf16Le => 2,
+        _ => 1,
     }
 }
 
@@ -190,21 +708,139 @@ fn elem_size_bytes(t: &TypeNode) -> usize {
  * Field (de)serialization emitters
  * ===================================== */
 
-fn emit_field_serialize(name: &str, t: &TypeNode, e: Endianness) -> String {
+// A run of bit fields opens a zero byte at every 8th bit and ORs each bit in.
+fn emit_bit_serialize(name: &str, slot: &BitSlot) -> String {
+    let mut s = String::new();
+    if slot.starts_byte() {
+        let _ = writeln!(&mut s, "data.push(0);");
+    }
+    let _ = writeln!(&mut s, "*data.last_mut().unwrap() |= u8::from(self.{name}) << {};", slot.shift());
+    s
+}
+
+// A `within` region reads through a cursor over the data cut at its end, so
+// reads past it fail and `until eof` stops there; positions stay absolute.
+fn emit_region_open(pkt: &PacketExpr, k: usize) -> String {
+    let region = &pkt.regions[k];
+    let at = &pkt.fields[region.first].id;
+    let mut s = String::new();
+    let _ = writeln!(&mut s, "let _end{k} = ({}) as usize;", emit_rust_len_expr(&region.length));
+    let _ = writeln!(
+        &mut s,
+        "if _end{k} > cur.get_ref().len() - cur.position() as usize {{\n    return Err({});\n}}",
+        validation_error(pkt, at, "within length fits the data")
+    );
+    let _ = writeln!(&mut s, "let _end{k} = cur.position() as usize + _end{k};");
+    let _ = writeln!(&mut s, "let _pos = cur.position();");
+    let _ = writeln!(&mut s, "let mut cur = Cursor::new(&data[.._end{k}]);");
+    let _ = writeln!(&mut s, "cur.set_position(_pos);");
+    s
+}
+
+fn emit_region_close(pkt: &PacketExpr, k: usize) -> String {
+    let region = &pkt.regions[k];
+    let at = &pkt.fields[region.first].id;
+    let outer = match pkt.enclosing_region(k) {
+        Some(j) => format!("&data[.._end{j}]"),
+        None => "data".to_string(),
+    };
+    let mut s = String::new();
+    if region.leftover == Leftover::Error {
+        let _ = writeln!(
+            &mut s,
+            "if cur.position() as usize != _end{k} {{\n    return Err({});\n}}",
+            validation_error(pkt, at, "within block is read to its end")
+        );
+    }
+    let _ = writeln!(&mut s, "let mut cur = Cursor::new({outer});");
+    let _ = writeln!(&mut s, "cur.set_position(_end{k} as u64);");
+    s
+}
+
+fn emit_derived_count(pkt: &PacketExpr, name: &str, t: &TypeNode, count: &DerivedCount) -> String {
+    let source = &pkt.fields[count.source].id;
+    let len = match count.adjust {
+        0 => format!("self.{source}.len()"),
+        a if a < 0 => format!("self.{source}.len() + {}", -a),
+        a => format!("self.{source}.len() as i64 - {a}"),
+    };
+    format!(
+        "let {name} = {}::try_from({len}).expect(\"{}: {source} does not fit in {name}\");\n",
+        rust_field_type(t),
+        pkt.name
+    )
+}
+
+fn emit_bit_deserialize(name: &str, slot: &BitSlot) -> String {
+    let mut s = String::new();
+    if slot.starts_byte() {
+        let _ = writeln!(&mut s, "let _bits = cur.read_u8().map_err(_read({name:?}))?;");
+    }
+    let _ = writeln!(&mut s, "let {name} = (_bits >> {}) & 1 != 0;", slot.shift());
+    s
+}
+
+fn emit_field_serialize(name: &str, t: &TypeNode, e: Endianness, owner: &str) -> String {
     let mut s = String::new();
     let ee = endian_ident(e);
 
     match t {
+        // filler: zeros, up to the next multiple of n for `align(n)`
+        TypeNode::Pad(n) => {
+            let _ = writeln!(&mut s, "data.resize(data.len() + {n}, 0);");
+        }
+        TypeNode::Align(n) => {
+            let _ = writeln!(&mut s, "data.resize(data.len().next_multiple_of({n}), 0);");
+        }
+
         // raw byte blobs
         TypeNode::Bytes(_) => {
-            let _ = writeln!(&mut s, "(&mut data).write_all(&self.{name}).unwrap();");
+            let _ = writeln!(&mut s, "(&mut data).write_all(&{owner}{name}).unwrap();");
         }
         TypeNode::MacAddress(len) => {
             if len.is_some() {
-                let _ = writeln!(&mut s, "(&mut data).write_all(&self.{name}).unwrap();");
+                let _ = writeln!(&mut s, "(&mut data).write_all(&{owner}{name}).unwrap();");
+            } else {
+                let _ = writeln!(&mut s, "(&mut data).write_all(&{owner}{name}).unwrap(); // 6 bytes");
+            }
+        }
+
+        // strings: NUL-padded, NUL-terminated, or length-prefixed; one that does not
+        // fit its width or prefix panics like a derived count
+        TypeNode::FixedString(len, enc) => {
+            let len_s = emit_rust_numeric_expr(len, "self.");
+            let _ = writeln!(&mut s, "let mut _b = {};", encode_string(&format!("{owner}{name}"), enc));
+            let _ = writeln!(&mut s, "let _n = ({len_s}) as usize;");
+            let _ = writeln!(&mut s, "assert!(_b.len() <= _n, \"{name}: string longer than {{}} bytes\", _n);");
+            let _ = writeln!(&mut s, "_b.resize(_n, 0);");
+            let _ = writeln!(&mut s, "(&mut data).write_all(&_b).unwrap();");
+        }
+        TypeNode::CString(enc) => {
+            let unit = code_unit(enc);
+            let _ = writeln!(&mut s, "(&mut data).write_all(&{}).unwrap();", encode_string(&format!("{owner}{name}"), enc));
+            let _ = writeln!(&mut s, "(&mut data).write_all(&[0u8; {unit}]).unwrap();");
+        }
+        TypeNode::PString(_, enc) => {
+            let _ = writeln!(&mut s, "let _b = {};", encode_string(&format!("{owner}{name}"), enc));
+            if t.scalar_width_bytes() == 1 {
+                let _ = writeln!(
+                    &mut s,
+                    "(&mut data).write_u8(u8::try_from(_b.len()).expect(\"{name}: string longer than 255 bytes\")).unwrap();"
+                );
             } else {
-                let _ = writeln!(&mut s, "(&mut data).write_all(&self.{name}).unwrap(); // 6 bytes");
+                let _ = writeln!(
+                    &mut s,
+                    "(&mut data).write_u16::<{ee}>(u16::try_from(_b.len()).expect(\"{name}: string longer than 65535 bytes\")).unwrap();"
+                );
             }
+            let _ = writeln!(&mut s, "(&mut data).write_all(&_b).unwrap();");
+        }
+
+        // sub-packets: each element writes itself
+        TypeNode::Repeat(_, _) => {
+            let _ = writeln!(&mut s, "for v in &{owner}{name} {{");
+            let _ = writeln!(&mut s, "    (&mut data).write_all(&v.serialize()).unwrap();");
+            let _ = writeln!(&mut s, "}}");
         }
 
         // numeric families
@@ -232,25 +868,57 @@ fn emit_field_serialize(name: &str, t: &TypeNode, e: Endianness) -> String {
                     TypeNode::UnsignedInteger64(_) => {
                         let _ = writeln!(dst, "(&mut data).write_u64::<{ee}>({expr}).unwrap();");
                     }
-                    TypeNode::Integer64(_) | TypeNode::DateTime(_) => {
+                    TypeNode::Integer64(_) => {
                         let _ = writeln!(dst, "(&mut data).write_i64::<{ee}>({expr}).unwrap();");
                     }
+                    TypeNode::DateTime(_, unit) => {
+                        let (suffix, wire) = time_codec_fn(unit);
+                        let _ = writeln!(
+                            dst,
+                            "(&mut data).write_{wire}::<{ee}>(time_codec::to_{suffix}({expr})).unwrap();"
+                        );
+                    }
                     TypeNode::Float32(_) => {
                         let _ = writeln!(dst, "(&mut data).write_f32::<{ee}>({expr}).unwrap();");
                     }
                     TypeNode::Float64(_) => {
                         let _ = writeln!(dst, "(&mut data).write_f64::<{ee}>({expr}).unwrap();");
                     }
+                    TypeNode::Ipv4Address(_) => {
+                        let _ = writeln!(dst, "(&mut data).write_u32::<{ee}>(u32::from({expr})).unwrap();");
+                    }
+                    TypeNode::Ipv6Address(_) => {
+                        let _ = writeln!(dst, "(&mut data).write_u128::<{ee}>(u128::from({expr})).unwrap();");
+                    }
+                    TypeNode::Port(_) => {
+                        let _ = writeln!(dst, "(&mut data).write_u16::<{ee}>({expr}).unwrap();");
+                    }
+                    TypeNode::Bool(_, _) => {
+                        let _ = writeln!(dst, "(&mut data).write_u8(u8::from({expr})).unwrap();");
+                    }
+                    // `as` saturates out-of-range values; round() breaks ties away from zero
+                    TypeNode::Fixed(_, base, frac) => {
+                        let wire = fixed_wire(base);
+                        let order = if base.width_bytes() == 1 { String::new() } else { format!("::<{ee}>") };
+                        let _ = writeln!(
+                            dst,
+                            "(&mut data).write_{wire}{order}(({expr} * {}).round() as {wire}).unwrap();",
+                            fixed_scale(*frac)
+                        );
+                    }
+                    TypeNode::VarInt(_, enc) => {
+                        let _ = writeln!(dst, "varint_codec::write_{}(&mut data, {expr});", varint_codec_fn(enc));
+                    }
                     _ => {}
                 }
             };
 
             if is_array_like(t) {
-                let _ = writeln!(&mut s, "for v in &self.{name} {{");
+                let _ = writeln!(&mut s, "for v in &{owner}{name} {{");
                 write_scalar(&mut s, " *v".into(), t);
                 let _ = writeln!(&mut s, "}}");
             } else {
-                write_scalar(&mut s, format!("self.{name}"), t);
+                write_scalar(&mut s, format!("{owner}{name}"), t);
             }
         }
     }
@@ -258,25 +926,36 @@ fn emit_field_serialize(name: &str, t: &TypeNode, e: Endianness) -> String {
     s
 }
 
+/// Reads fail with `_read`, the packet's `ValidationError::read`.
 fn emit_field_deserialize(name: &str, t: &TypeNode, e: Endianness, is_last: bool) -> String {
     let mut s = String::new();
+    let read = format!(".map_err(_read({name:?}))?");
     let ee = endian_ident(e);
     let w = elem_size_bytes(t);
 
     match t {
+        // filler is skipped, whatever it holds
+        TypeNode::Pad(n) => {
+            let _ = writeln!(&mut s, "cur.read_exact(&mut [0u8; {n}]){read};");
+        }
+        TypeNode::Align(n) => {
+            let _ = writeln!(&mut s, "let _skip = (cur.position() as usize).next_multiple_of({n}) - cur.position() as usize;");
+            let _ = writeln!(&mut s, "cur.read_exact(&mut vec![0u8; _skip]){read};");
+        }
+
         // byte blobs
         TypeNode::Bytes(len_opt) => {
             if let Some(expr) = len_opt {
                 let expr_s = emit_rust_len_expr(expr);
                 let _ = writeln!(
                     &mut s,
-                    "let mut {name}: Vec<u8> = vec![0u8; ({expr}) as usize]; cur.read_exact(&mut {name}).unwrap();",
+                    "let mut {name}: Vec<u8> = vec![0u8; ({expr}) as usize]; cur.read_exact(&mut {name}){read};",
                     expr = expr_s
                 );
             } else if is_last {
                 let _ = writeln!(
                     &mut s,
-                    "let mut {name}: Vec<u8> = Vec::new(); cur.read_to_end(&mut {name}).unwrap();"
+                    "let mut {name}: Vec<u8> = Vec::new(); cur.read_to_end(&mut {name}){read};"
                 );
             } else {
                 let _ = writeln!(
@@ -290,50 +969,143 @@ fn emit_field_deserialize(name: &str, t: &TypeNode, e: Endianness, is_last: bool
                 let expr_s = emit_rust_len_expr(expr);
                 let _ = writeln!(
                     &mut s,
-                    "let mut {name}: Vec<u8> = vec![0u8; ({expr}) as usize]; cur.read_exact(&mut {name}).unwrap();",
+                    "let mut {name}: Vec<u8> = vec![0u8; ({expr}) as usize]; cur.read_exact(&mut {name}){read};",
                     expr = expr_s
                 );
             } else {
                 let _ = writeln!(
                     &mut s,
-                    "let mut {name}: [u8; 6] = [0u8; 6]; cur.read_exact(&mut {name}).unwrap();"
+                    "let mut {name}: [u8; 6] = [0u8; 6]; cur.read_exact(&mut {name}){read};"
                 );
             }
         }
 
+        // strings
+        TypeNode::FixedString(len, enc) => {
+            let len_s = emit_rust_len_expr(len);
+            let unit = code_unit(enc);
+            let _ = writeln!(&mut s, "let mut _raw = vec![0u8; ({len_s}) as usize]; cur.read_exact(&mut _raw){read};");
+            let raw = format!("string_codec::strip_nul(&_raw, {unit})");
+            let _ = writeln!(&mut s, "let {name} = {}{read};", decode_string(&raw, enc));
+        }
+        TypeNode::CString(enc) => {
+            let unit = code_unit(enc);
+            let _ = writeln!(&mut s, "let _raw = string_codec::read_cstring(&mut cur, {unit}){read};");
+            let _ = writeln!(&mut s, "let {name} = {}{read};", decode_string("&_raw", enc));
+        }
+        TypeNode::PString(_, enc) => {
+            if w == 1 {
+                let _ = writeln!(&mut s, "let _n = cur.read_u8(){read} as usize;");
+            } else {
+                let _ = writeln!(&mut s, "let _n = cur.read_u16::<{ee}>(){read} as usize;");
+            }
+            let _ = writeln!(&mut s, "let mut _raw = vec![0u8; _n]; cur.read_exact(&mut _raw){read};");
+            let _ = writeln!(&mut s, "let {name} = {}{read};", decode_string("&_raw", enc));
+        }
+
+        // sub-packets: each element is decoded from the rest of the data and
+        // reports how many bytes it used
+        TypeNode::Repeat(elem, bound) => {
+            let call = format!("{elem}::try_deserialize_prefix(&cur.get_ref()[cur.position() as usize..])?");
+            let read_one = format!(
+                "let (_item, _n) = {call};\n\
+                 cur.set_position(cur.position() + _n as u64);\n"
+            );
+            match bound {
+                RepeatBound::Eof => {
+                    let _ = writeln!(&mut s, "let mut {name}: Vec<{elem}> = Vec::new();");
+                    let _ = writeln!(&mut s, "while (cur.position() as usize) < cur.get_ref().len() {{");
+                    s.push_str(&indent(&read_one, 1));
+                    let _ = writeln!(&mut s, "    {name}.push(_item);");
+                    let _ = writeln!(&mut s, "}}");
+                }
+                RepeatBound::Until(cond) => {
+                    let _ = writeln!(&mut s, "let mut {name}: Vec<{elem}> = Vec::new();");
+                    let _ = writeln!(&mut s, "loop {{");
+                    s.push_str(&indent(&read_one, 1));
+                    let _ = writeln!(&mut s, "    let _done = {} != 0.0;", emit_rust_numeric_expr(cond, "_item."));
+                    let _ = writeln!(&mut s, "    {name}.push(_item);");
+                    let _ = writeln!(&mut s, "    if _done {{ break; }}");
+                    let _ = writeln!(&mut s, "}}");
+                }
+                RepeatBound::Count(expr) => {
+                    let expr_s = emit_rust_len_expr(expr);
+                    let _ = writeln!(
+                        &mut s,
+                        "let mut {name}: Vec<{elem}> = Vec::with_capacity(({expr_s}) as usize);"
+                    );
+                    let _ = writeln!(&mut s, "for _ in 0..(({expr_s}) as usize) {{");
+                    s.push_str(&indent(&read_one, 1));
+                    let _ = writeln!(&mut s, "    {name}.push(_item);");
+                    let _ = writeln!(&mut s, "}}");
+                }
+            }
+        }
+
         // numeric families
         _ => {
             let read_scalar = |dst: &mut String, lhs: String, t: &TypeNode| {
                 match t {
                     TypeNode::UnsignedInteger8(_) => {
-                        let _ = writeln!(dst, "let {lhs} = cur.read_u8().unwrap();");
+                        let _ = writeln!(dst, "let {lhs} = cur.read_u8(){read};");
                     }
                     TypeNode::Integer8(_) => {
-                        let _ = writeln!(dst, "let {lhs} = cur.read_i8().unwrap();");
+                        let _ = writeln!(dst, "let {lhs} = cur.read_i8(){read};");
                     }
                     TypeNode::UnsignedInteger16(_) => {
-                        let _ = writeln!(dst, "let {lhs} = cur.read_u16::<{ee}>().unwrap();");
+                        let _ = writeln!(dst, "let {lhs} = cur.read_u16::<{ee}>(){read};");
                     }
                     TypeNode::Integer16(_) => {
-                        let _ = writeln!(dst, "let {lhs} = cur.read_i16::<{ee}>().unwrap();");
+                        let _ = writeln!(dst, "let {lhs} = cur.read_i16::<{ee}>(){read};");
                     }
                     TypeNode::UnsignedInteger32(_) => {
-                        let _ = writeln!(dst, "let {lhs} = cur.read_u32::<{ee}>().unwrap();");
+                        let _ = writeln!(dst, "let {lhs} = cur.read_u32::<{ee}>(){read};");
                     }
                     TypeNode::Integer32(_) => {
-                        let _ = writeln!(dst, "let {lhs} = cur.read_i32::<{ee}>().unwrap();");
+                        let _ = writeln!(dst, "let {lhs} = cur.read_i32::<{ee}>(){read};");
                     }
                     TypeNode::UnsignedInteger64(_) => {
-                        let _ = writeln!(dst, "let {lhs} = cur.read_u64::<{ee}>().unwrap();");
+                        let _ = writeln!(dst, "let {lhs} = cur.read_u64::<{ee}>(){read};");
+                    }
+                    TypeNode::Integer64(_) => {
+                        let _ = writeln!(dst, "let {lhs} = cur.read_i64::<{ee}>(){read};");
                     }
-                    TypeNode::Integer64(_) | TypeNode::DateTime(_) => {
-                        let _ = writeln!(dst, "let {lhs} = cur.read_i64::<{ee}>().unwrap();");
+                    TypeNode::DateTime(_, unit) => {
+                        let (suffix, wire) = time_codec_fn(unit);
+                        let _ = writeln!(
+                            dst,
+                            "let {lhs} = time_codec::from_{suffix}(cur.read_{wire}::<{ee}>(){read});"
+                        );
                     }
                     TypeNode::Float32(_) => {
-                        let _ = writeln!(dst, "let {lhs} = cur.read_f32::<{ee}>().unwrap();");
+                        let _ = writeln!(dst, "let {lhs} = cur.read_f32::<{ee}>(){read};");
                     }
                     TypeNode::Float64(_) => {
-                        let _ = writeln!(dst, "let {lhs} = cur.read_f64::<{ee}>().unwrap();");
+                        let _ = writeln!(dst, "let {lhs} = cur.read_f64::<{ee}>(){read};");
+                    }
+                    TypeNode::Ipv4Address(_) => {
+                        let _ = writeln!(dst, "let {lhs} = std::net::Ipv4Addr::from(cur.read_u32::<{ee}>(){read});");
+                    }
+                    TypeNode::Ipv6Address(_) => {
+                        let _ = writeln!(dst, "let {lhs} = std::net::Ipv6Addr::from(cur.read_u128::<{ee}>(){read});");
+                    }
+                    TypeNode::Port(_) => {
+                        let _ = writeln!(dst, "let {lhs} = cur.read_u16::<{ee}>(){read};");
+                    }
+                    TypeNode::Bool(_, _) => {
+                        let _ = writeln!(dst, "let {lhs} = cur.read_u8(){read} != 0;");
+                    }
+                    TypeNode::Fixed(_, base, frac) => {
+                        let wire = fixed_wire(base);
+                        let order = if base.width_bytes() == 1 { String::new() } else { format!("::<{ee}>") };
+                        let _ = writeln!(
+                            dst,
+                            "let {lhs} = cur.read_{wire}{order}(){read} as f64 / {};",
+                            fixed_scale(*frac)
+                        );
+                    }
+                    TypeNode::VarInt(_, enc) => {
+                        let _ = writeln!(dst, "let {lhs} = varint_codec::read_{}(&mut cur){read};", varint_codec_fn(enc));
                     }
                     _ => {}
                 }
@@ -377,7 +1149,7 @@ fn emit_field_deserialize(name: &str, t: &TypeNode, e: Endianness, is_last: bool
                     );
                 }
             } else {
-                read_scalar(&mut s, format!("{name}"), t);
+                read_scalar(&mut s, name.to_string(), t);
             }
         }
     }
@@ -395,8 +1167,26 @@ fn emit_rust_len_expr(e: &ExprNode) -> String {
     emit_rust_numeric_expr(e, "")
 }
 
-fn emit_rust_numeric_expr(e: &ExprNode, _root: &str) -> String {
+/// `if !cond { ... }` for a `where` or `assert` rule: decoders and `validate`
+/// return the error.
+fn emit_check(pkt: &PacketExpr, check: &Check, root: &str) -> String {
+    format!(
+        "if {} == 0.0 {{\n    return Err({});\n}}\n",
+        emit_rust_numeric_expr(&check.cond, root),
+        validation_error(pkt, &check.field, &check.rule)
+    )
+}
+
+fn validation_error(pkt: &PacketExpr, field: &str, rule: &str) -> String {
+    format!("ValidationError {{ packet: {:?}, field: {:?}, rule: {:?} }}", pkt.name, field, rule)
+}
+
+fn emit_rust_numeric_expr(e: &ExprNode, root: &str) -> String {
     use ExprNode::*;
+    // calc fields only: sums the bytes the packet was decoded from
+    if let Some(sum) = e.as_checksum() {
+        return format!("(checksum::{}(&data[_{}_start.._{}_end]) as f64)", sum.kind.name(), sum.first, sum.last);
+    }
     match e {
         UnsignedInteger64Value(u) => format!("({}f64)", *u as f64),
         Integer64Value(i) => format!("({}f64)", *i as f64),
@@ -406,45 +1196,71 @@ fn emit_rust_numeric_expr(e: &ExprNode, _root: &str) -> String {
 
         ValueReference(name, idx) => {
             if let Some(ix) = idx {
-                format!("({name}[({ix}) as usize] as f64)", ix = emit_rust_numeric_expr(ix, ""))
+                format!("({root}{name}[({ix}) as usize] as f64)", ix = emit_rust_numeric_expr(ix, root))
             } else {
-                format!("({name} as f64)")
+                format!("({root}{name} as f64)")
             }
         }
 
-        ParenthesizedExpr(x) => format!("({})", emit_rust_numeric_expr(x, "")),
+        ParenthesizedExpr(x) => format!("({})", emit_rust_numeric_expr(x, root)),
+
+        Plus(a, b) => format!("({} + {})", emit_rust_numeric_expr(a, root), emit_rust_numeric_expr(b, root)),
+        Minus(a, b) => format!("({} - {})", emit_rust_numeric_expr(a, root), emit_rust_numeric_expr(b, root)),
+        Mult(a, b) => format!("({} * {})", emit_rust_numeric_expr(a, root), emit_rust_numeric_expr(b, root)),
+        Div(a, b) => format!("({} / {})", emit_rust_numeric_expr(a, root), emit_rust_numeric_expr(b, root)),
+        Pow(a, b) => format!("({}).powf({})", emit_rust_numeric_expr(a, root), emit_rust_numeric_expr(b, root)),
+        Mod(a, b) => format!("({} % {})", emit_rust_numeric_expr(a, root), emit_rust_numeric_expr(b, root)),
+        Negate(a) => format!("(-{})", emit_rust_numeric_expr(a, root)),
 
-        Plus(a, b) => format!("({} + {})", emit_rust_numeric_expr(a, ""), emit_rust_numeric_expr(b, "")),
-        Minus(a, b) => format!("({} - {})", emit_rust_numeric_expr(a, ""), emit_rust_numeric_expr(b, "")),
-        Mult(a, b) => format!("({} * {})", emit_rust_numeric_expr(a, ""), emit_rust_numeric_expr(b, "")),
-        Div(a, b) => format!("({} / {})", emit_rust_numeric_expr(a, ""), emit_rust_numeric_expr(b, "")),
-        Pow(a, b) => format!("({}).powf({})", emit_rust_numeric_expr(a, ""), emit_rust_numeric_expr(b, "")),
+        // bitwise → round-trip through i64
+        BitAnd(a, b) => format!("((({} as i64) & ({} as i64)) as f64)", emit_rust_numeric_expr(a, root), emit_rust_numeric_expr(b, root)),
+        BitOr(a, b) => format!("((({} as i64) | ({} as i64)) as f64)", emit_rust_numeric_expr(a, root), emit_rust_numeric_expr(b, root)),
+        BitNot(a) => format!("((!({} as i64)) as f64)", emit_rust_numeric_expr(a, root)),
+        ShiftLeft(a, b) => format!("(({} as i64).wrapping_shl(({} as i64 & 63) as u32) as f64)", emit_rust_numeric_expr(a, root), emit_rust_numeric_expr(b, root)),
+        ShiftRight(a, b) => format!("(({} as i64).wrapping_shr(({} as i64 & 63) as u32) as f64)", emit_rust_numeric_expr(a, root), emit_rust_numeric_expr(b, root)),
 
         // comparisons/logic → booleans; map to 1.0/0.0 to keep expression numeric
-        Gt(a, b) => format!("(if {} > {} {{ 1.0 }} else {{ 0.0 }})", emit_rust_numeric_expr(a, ""), emit_rust_numeric_expr(b, "")),
-        Gte(a, b)=> format!("(if {} >= {} {{ 1.0 }} else {{ 0.0 }})", emit_rust_numeric_expr(a, ""), emit_rust_numeric_expr(b, "")),
-        Lt(a, b) => format!("(if {} < {} {{ 1.0 }} else {{ 0.0 }})", emit_rust_numeric_expr(a, ""), emit_rust_numeric_expr(b, "")),
-        Lte(a, b)=> format!("(if {} <= {} {{ 1.0 }} else {{ 0.0 }})", emit_rust_numeric_expr(a, ""), emit_rust_numeric_expr(b, "")),
-        Equals(a,b)=> format!("(if ({} - {}).abs() < 1e-9 {{ 1.0 }} else {{ 0.0 }})", emit_rust_numeric_expr(a, ""), emit_rust_numeric_expr(b, "")),
-        NotEquals(a,b)=> format!("(if ({} - {}).abs() >= 1e-9 {{ 1.0 }} else {{ 0.0 }})", emit_rust_numeric_expr(a, ""), emit_rust_numeric_expr(b, "")),
-        And(a, b) => format!("(if ({} != 0.0) && ({} != 0.0) {{ 1.0 }} else {{ 0.0 }})", emit_rust_numeric_expr(a, ""), emit_rust_numeric_expr(b, "")),
-        Or(a, b)  => format!("(if ({} != 0.0) || ({} != 0.0) {{ 1.0 }} else {{ 0.0 }})", emit_rust_numeric_expr(a, ""), emit_rust_numeric_expr(b, "")),
+        Gt(a, b) => format!("(if {} > {} {{ 1.0 }} else {{ 0.0 }})", emit_rust_numeric_expr(a, root), emit_rust_numeric_expr(b, root)),
+        Gte(a, b)=> format!("(if {} >= {} {{ 1.0 }} else {{ 0.0 }})", emit_rust_numeric_expr(a, root), emit_rust_numeric_expr(b, root)),
+        Lt(a, b) => format!("(if {} < {} {{ 1.0 }} else {{ 0.0 }})", emit_rust_numeric_expr(a, root), emit_rust_numeric_expr(b, root)),
+        Lte(a, b)=> format!("(if {} <= {} {{ 1.0 }} else {{ 0.0 }})", emit_rust_numeric_expr(a, root), emit_rust_numeric_expr(b, root)),
+        Equals(a,b)=> format!("(if ({} - {}).abs() < 1e-9 {{ 1.0 }} else {{ 0.0 }})", emit_rust_numeric_expr(a, root), emit_rust_numeric_expr(b, root)),
+        NotEquals(a,b)=> format!("(if ({} - {}).abs() >= 1e-9 {{ 1.0 }} else {{ 0.0 }})", emit_rust_numeric_expr(a, root), emit_rust_numeric_expr(b, root)),
+        And(a, b) => format!("(if ({} != 0.0) && ({} != 0.0) {{ 1.0 }} else {{ 0.0 }})", emit_rust_numeric_expr(a, root), emit_rust_numeric_expr(b, root)),
+        Or(a, b)  => format!("(if ({} != 0.0) || ({} != 0.0) {{ 1.0 }} else {{ 0.0 }})", emit_rust_numeric_expr(a, root), emit_rust_numeric_expr(b, root)),
+        Not(a) => format!("(if {} == 0.0 {{ 1.0 }} else {{ 0.0 }})", emit_rust_numeric_expr(a, root)),
 
         ActivationRecord(name, args) => {
-            let args_s: Vec<String> = args.iter().map(|a| emit_rust_numeric_expr(a, "")).collect();
+            let args_s: Vec<String> = args.iter().map(|a| emit_rust_numeric_expr(a, root)).collect();
             match name.as_str() {
                 "sqrt" => format!("({}).sqrt()", args_s[0]),
                 "min"  => format!("{}.min({})", args_s[0], args_s[1]),
                 "max"  => format!("{}.max({})", args_s[0], args_s[1]),
-                _ => "0.0".into(),
+                // called as functions: a method on a bare literal has no type to resolve to
+                "abs"   => format!("f64::abs({})", args_s[0]),
+                "floor" => format!("f64::floor({})", args_s[0]),
+                "ceil"  => format!("f64::ceil({})", args_s[0]),
+                "round" => format!("f64::round({})", args_s[0]),
+                "log2"  => format!("f64::log2({})", args_s[0]),
+                "clamp" => format!("f64::min(f64::max({}, {}), {})", args_s[0], args_s[1], args_s[2]),
+                "pow"   => format!("f64::powf({}, {})", args_s[0], args_s[1]),
+                "popcount" => format!("(({} as i64).count_ones() as f64)", args_s[0]),
+                "bswap16"  => format!("(({} as i64 as u16).swap_bytes() as f64)", args_s[0]),
+                "bswap32"  => format!("(({} as i64 as u32).swap_bytes() as f64)", args_s[0]),
+                "bswap64"  => format!("(({} as i64 as u64).swap_bytes() as f64)", args_s[0]),
+                // an `extern fn`; its arguments and result cross over from f64
+                _ => {
+                    let args_s: Vec<String> = args_s.iter().map(|a| format!("({}) as _", a)).collect();
+                    format!("({}({}) as f64)", name, args_s.join(", "))
+                }
             }
         }
 
         GuardExpression(c, t, f) => format!(
             "(if {} != 0.0 {{ {} }} else {{ {} }})",
-            emit_rust_numeric_expr(c, ""),
-            emit_rust_numeric_expr(t, ""),
-            emit_rust_numeric_expr(f, "")
+            emit_rust_numeric_expr(c, root),
+            emit_rust_numeric_expr(t, root),
+            emit_rust_numeric_expr(f, root)
         ),
 
         AggregateSum(_)
@@ -462,6 +1278,7 @@ fn is_array_like(t: &TypeNode) -> bool {
     use TypeNode::*;
     match t {
         Bytes(_) | MacAddress(_) => true,
+        FixedString(_, _) | CString(_) | PString(_, _) | Repeat(_, _) | Pad(_) | Align(_) => false,
         UnsignedInteger8(e)
         | Integer8(e)
         | UnsignedInteger16(e)
@@ -472,7 +1289,13 @@ fn is_array_like(t: &TypeNode) -> bool {
         | Integer64(e)
         | Float32(e)
         | Float64(e)
-        | DateTime(e) => e.is_some(),
+        | DateTime(e, _)
+        | Bool(e, _)
+        | Fixed(e, _, _)
+        | VarInt(e, _)
+        | Ipv4Address(e)
+        | Ipv6Address(e)
+        | Port(e) => e.is_some(),
     }
 }
 
@@ -486,17 +1309,31 @@ fn base_scalar_rust(t: &TypeNode) -> &'static str {
         UnsignedInteger32(_) => "u32",
         Integer32(_) => "i32",
         UnsignedInteger64(_) => "u64",
-        Integer64(_) | DateTime(_) => "i64",
+        Integer64(_) => "i64",
+        DateTime(_, _) => "std::time::SystemTime",
+        Bool(_, _) => "bool",
+        Fixed(_, _, _) => "f64",
+        VarInt(_, VarIntEncoding::ZigZag) => "i64",
+        VarInt(_, _) => "u64",
         Float32(_) => "f32",
         Float64(_) => "f64",
-        Bytes(_) | MacAddress(_) => "u8",
+        Ipv4Address(_) => "std::net::Ipv4Addr",
+        Ipv6Address(_) => "std::net::Ipv6Addr",
+        Port(_) => "u16",
+        Bytes(_) | MacAddress(_) | FixedString(_, _) | CString(_) | PString(_, _) | Repeat(_, _) | Pad(_) | Align(_) => {
+            "u8"
+        }
     }
 }
 
-fn type_len_expr<'a>(t: &'a TypeNode) -> Option<&'a ExprNode> {
+fn type_len_expr(t: &TypeNode) -> Option<&ExprNode> {
     use TypeNode::*;
     match t {
         Bytes(e) | MacAddress(e) => e.as_ref(),
+        FixedString(e, _) => Some(e),
+        CString(_) | PString(_, _) | Pad(_) | Align(_) => None,
+        Repeat(_, RepeatBound::Count(e)) => Some(e),
+        Repeat(_, _) => None,
         UnsignedInteger8(e)
         | Integer8(e)
         | UnsignedInteger16(e)
@@ -507,13 +1344,294 @@ fn type_len_expr<'a>(t: &'a TypeNode) -> Option<&'a ExprNode> {
         | Integer64(e)
         | Float32(e)
         | Float64(e)
-        | DateTime(e) => e.as_ref(),
+        | DateTime(e, _)
+        | Bool(e, _)
+        | Fixed(e, _, _)
+        | VarInt(e, _)
+        | Ipv4Address(e)
+        | Ipv6Address(e)
+        | Port(e) => e.as_ref(),
     }
 }
 
+/// Renders DSL `///` docs as rustdoc lines.
+fn doc_comment(doc: &Option<String>, pad: &str) -> String {
+    let Some(doc) = doc else {
+        return String::new();
+    };
+    doc.lines()
+        .map(|l| if l.is_empty() { format!("{pad}///\n") } else { format!("{pad}/// {l}\n") })
+        .collect()
+}
+
 fn indent(s: &str, tabs: usize) -> String {
     let pad = "    ".repeat(tabs);
     s.lines()
         .map(|l| if l.is_empty() { "\n".to_string() } else { format!("{pad}{l}\n") })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::load_source;
+
+    fn rust(src: &str) -> String {
+        RustGenerator::generate(&load_source(src))
+    }
+
+    #[test]
+    fn shift_counts_use_the_low_six_bits() {
+        let out = rust("packet Bits { n uint8, hi bytes[1 << n] }");
+        assert!(out.contains("((1f64) as i64).wrapping_shl(((n as f64) as i64 & 63) as u32)"));
+    }
+
+    #[test]
+    fn strings_that_do_not_fit_panic() {
+        let out = rust("packet Names { tag string[4], short pstring<uint8>, long pstring<uint16> }");
+        assert!(out.contains("assert!(_b.len() <= _n, \"tag: string longer than {} bytes\", _n);"));
+        assert!(out.contains("u8::try_from(_b.len()).expect(\"short: string longer than 255 bytes\")"));
+        assert!(out.contains("u16::try_from(_b.len()).expect(\"long: string longer than 65535 bytes\")"));
+        assert!(!out.contains("as u8).unwrap()"));
+    }
+
+    #[test]
+    fn addresses_and_ports_are_in_network_order() {
+        let out = rust("packet Net le { src ipv4, dst ipv6, hosts ipv4[2], sport port }");
+        assert!(out.contains("    pub src: std::net::Ipv4Addr,\n    pub dst: std::net::Ipv6Addr,\n    pub hosts: Vec<std::net::Ipv4Addr>,\n    pub sport: u16,\n"));
+        assert!(out.contains("(&mut data).write_u32::<BigEndian>(u32::from(self.src)).unwrap();"));
+        assert!(out.contains("(&mut data).write_u128::<BigEndian>(u128::from(self.dst)).unwrap();"));
+        assert!(out.contains("(&mut data).write_u16::<BigEndian>(self.sport).unwrap();"));
+        assert!(out.contains("let src = std::net::Ipv4Addr::from(cur.read_u32::<BigEndian>().map_err(_read(\"src\"))?);"));
+        assert!(out.contains("let _tmp = std::net::Ipv4Addr::from(cur.read_u32::<BigEndian>().map_err(_read(\"hosts\"))?);"));
+        assert!(out.contains("let sport = cur.read_u16::<BigEndian>().map_err(_read(\"sport\"))?;"));
+    }
+
+    #[test]
+    fn datetimes_convert_through_the_chosen_encoding() {
+        let out = rust("packet T be { a datetime, b datetime(unix_ms) le, c datetime(ntp64), d datetime(filetime) }");
+        assert!(out.contains("    pub a: std::time::SystemTime,\n"));
+        assert!(out.contains("(&mut data).write_i64::<BigEndian>(time_codec::to_unix_s(self.a)).unwrap();"));
+        assert!(out.contains("(&mut data).write_i64::<LittleEndian>(time_codec::to_unix_ms(self.b)).unwrap();"));
+        assert!(out.contains("(&mut data).write_u64::<BigEndian>(time_codec::to_ntp64(self.c)).unwrap();"));
+        assert!(out.contains("let d = time_codec::from_filetime(cur.read_u64::<BigEndian>().map_err(_read(\"d\"))?);"));
+        assert!(out.contains("const NTP_EPOCH_OFFSET: i128 = 2_208_988_800;"));
+        assert!(out.contains("const FILETIME_EPOCH_OFFSET: i128 = 11_644_473_600;"));
+    }
+
+    #[test]
+    fn bools_bit_fields_and_fixed_point() {
+        let out = rust("packet F be { on bool, a bool(bit), b bool(bit), n uint8, gain fixed<int16, 8>, big fixed<uint32, 16>[2] }");
+        assert!(out.contains("    pub on: bool,\n    pub a: bool,\n    pub b: bool,\n    pub n: u8,\n    pub gain: f64,\n    pub big: Vec<f64>,\n"));
+        assert!(out.contains("(&mut data).write_u8(u8::from(self.on)).unwrap();"));
+        // a and b share one byte, most significant bit first
+        assert!(out.contains(
+            "data.push(0);\n        *data.last_mut().unwrap() |= u8::from(self.a) << 7;\n        *data.last_mut().unwrap() |= u8::from(self.b) << 6;\n        (&mut data).write_u8(self.n)"
+        ));
+        assert!(out.contains("let on = cur.read_u8().map_err(_read(\"on\"))? != 0;"));
+        assert!(out.contains("let _bits = cur.read_u8().map_err(_read(\"a\"))?;\n        let a = (_bits >> 7) & 1 != 0;\n        let b = (_bits >> 6) & 1 != 0;"));
+        assert!(out.contains("(&mut data).write_i16::<BigEndian>((self.gain * 256.0).round() as i16).unwrap();"));
+        assert!(out.contains("let gain = cur.read_i16::<BigEndian>().map_err(_read(\"gain\"))? as f64 / 256.0;"));
+        assert!(out.contains("(&mut data).write_u32::<BigEndian>(( *v * 65536.0).round() as u32).unwrap();"));
+    }
+
+    /// The codec the generator emits, compiled here so its encodings can be checked.
+    mod varint_codec {
+        include!("../../templates/rust/varint_codec.rs");
+    }
+
+    fn encode(write: fn(&mut Vec<u8>, u64), v: u64) -> Vec<u8> {
+        let mut data = Vec::new();
+        write(&mut data, v);
+        data
+    }
+
+    #[test]
+    fn varint_fields_use_the_codec() {
+        let out = rust("packet V be { a varuint, b varint, c quicvarint, d varuint[2] }");
+        assert!(out.contains("    pub a: u64,\n    pub b: i64,\n    pub c: u64,\n    pub d: Vec<u64>,\n"));
+        assert!(out.contains("varint_codec::write_leb128(&mut data, self.a);"));
+        assert!(out.contains("varint_codec::write_zigzag(&mut data, self.b);"));
+        assert!(out.contains("varint_codec::write_quic(&mut data, self.c);"));
+        assert!(out.contains("let a = varint_codec::read_leb128(&mut cur).map_err(_read(\"a\"))?;"));
+        assert!(out.contains("mod varint_codec {\n    #![allow(dead_code)]\n    use std::io::{self, Cursor, Read};"));
+    }
+
+    #[test]
+    fn leb128_grows_a_byte_every_seven_bits() {
+        use std::io::Cursor;
+        let cases: &[(u64, &[u8])] = &[
+            (0, &[0x00]),
+            (127, &[0x7f]),
+            (128, &[0x80, 0x01]),
+            (16383, &[0xff, 0x7f]),
+            (16384, &[0x80, 0x80, 0x01]),
+            (u64::MAX, &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]),
+        ];
+        for &(v, wire) in cases {
+            assert_eq!(encode(varint_codec::write_leb128, v), wire, "{v}");
+            assert_eq!(varint_codec::read_leb128(&mut Cursor::new(wire)).unwrap(), v);
+        }
+    }
+
+    #[test]
+    fn zigzag_interleaves_the_sign() {
+        use std::io::Cursor;
+        let cases: &[(i64, &[u8])] = &[
+            (0, &[0x00]),
+            (-1, &[0x01]),
+            (1, &[0x02]),
+            (-2, &[0x03]),
+            (-64, &[0x7f]),
+            (64, &[0x80, 0x01]),
+            (i64::MAX, &[0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]),
+            (i64::MIN, &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]),
+        ];
+        for &(v, wire) in cases {
+            let mut data = Vec::new();
+            varint_codec::write_zigzag(&mut data, v);
+            assert_eq!(data, wire, "{v}");
+            assert_eq!(varint_codec::read_zigzag(&mut Cursor::new(wire)).unwrap(), v);
+        }
+    }
+
+    #[test]
+    fn quic_varints_switch_width_at_each_boundary() {
+        use std::io::Cursor;
+        let cases: &[(u64, &[u8])] = &[
+            (63, &[0x3f]),
+            (64, &[0x40, 0x40]),
+            (16383, &[0x7f, 0xff]),
+            (16384, &[0x80, 0x00, 0x40, 0x00]),
+            ((1 << 30) - 1, &[0xbf, 0xff, 0xff, 0xff]),
+            (1 << 30, &[0xc0, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00]),
+            ((1 << 62) - 1, &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+        ];
+        for &(v, wire) in cases {
+            assert_eq!(encode(varint_codec::write_quic, v), wire, "{v}");
+            assert_eq!(varint_codec::read_quic(&mut Cursor::new(wire)).unwrap(), v);
+        }
+    }
+
+    #[test]
+    fn varints_that_end_early_are_errors() {
+        use std::io::{Cursor, ErrorKind};
+        let kind = |r: std::io::Result<u64>| r.unwrap_err().kind();
+        assert_eq!(kind(varint_codec::read_leb128(&mut Cursor::new(&[0x80, 0x80]))), ErrorKind::UnexpectedEof);
+        assert_eq!(kind(varint_codec::read_leb128(&mut Cursor::new(&[0xff; 11]))), ErrorKind::InvalidData);
+        assert_eq!(kind(varint_codec::read_quic(&mut Cursor::new(&[0x80, 0x00, 0x40]))), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not fit in a QUIC varint")]
+    fn quic_varints_stop_below_two_to_the_62() {
+        encode(varint_codec::write_quic, 1 << 62);
+    }
+
+    #[test]
+    fn counts_are_written_from_the_field_they_size() {
+        let out = rust("packet L be { l uint16, v bytes[l], length uint8, body uint16[length - 10], k uint8, b bytes[k + 2] }");
+        assert!(out.contains("let l = u16::try_from(self.v.len()).expect(\"L: v does not fit in l\");"));
+        assert!(out.contains("let length = u8::try_from(self.body.len() + 10).expect(\"L: body does not fit in length\");"));
+        // a count below its constant is an error too, not a wrapped usize
+        assert!(out.contains("let k = u8::try_from(self.b.len() as i64 - 2).expect(\"L: b does not fit in k\");"));
+    }
+
+    #[test]
+    fn optional_fields_are_options() {
+        let out = rust("packet Opt be { flags uint16, extra uint32 if (flags & 0x8000) != 0 }");
+        assert!(out.contains("    pub extra: Option<u32>,\n"));
+        assert!(out.contains(
+            "            let extra = self.extra.clone().expect(\"Opt: extra must be set when its condition holds\");\n\
+             \x20           (&mut data).write_u32::<BigEndian>(extra).unwrap();\n        }\n"
+        ));
+        assert!(out.contains("let extra = if (if (((((flags as f64) as i64) & ((32768f64) as i64)) as f64) - (0f64)).abs() >= 1e-9"));
+        assert!(out.contains("            Some(extra)\n        } else {\n            None\n        };\n"));
+    }
+
+    #[test]
+    fn checksums_are_filled_on_encode_and_checked_on_decode() {
+        let out = rust("packet Sum be { a uint16, b uint8, sum uint16 = inet_checksum(a..b), calc c32 uint32 = crc32(a..b) }");
+        assert!(out.contains("mod checksum {"));
+        assert!(out.contains(
+            "        let sum = checksum::inet_checksum(&data[_a_start.._b_end]);\n\
+             \x20       data[_sum_at.._sum_at + 2].copy_from_slice(&sum.to_be_bytes());\n"
+        ));
+        assert!(out.contains("let _sum = checksum::inet_checksum(&checksum::without(data, _a_start, _b_end, _sum_at, 2));"));
+        assert!(out.contains("if sum != _sum {"));
+        assert!(out.contains("return Err(ValidationError { packet: \"Sum\", field: \"sum\", rule: \"sum == inet_checksum(a..b)\" });"));
+        assert!(out.contains("let c32: u32 = ((checksum::crc32(&data[_a_start.._b_end]) as f64)) as u32;"));
+        assert!(!rust("packet Plain { a uint8 }").contains("mod checksum"));
+    }
+
+    #[test]
+    fn extern_functions_use_the_rust_mapping() {
+        let model = load_source("extern fn lz(x) -> uint8 { rust: \"u64::leading_zeros\" }
+            extern fn mix(a, b) -> uint16 { python: \"mixer\" }
+            packet Ext { x uint32, calc z uint8 = lz(x), calc m uint16 = mix(x, 2) }");
+        let out = RustGenerator::generate(&model.for_target("rust"));
+        assert!(out.contains("let z: u8 = ((u64::leading_zeros(((x as f64)) as _) as f64)) as u8;"));
+        // without a mapping the DSL name is called
+        assert!(out.contains("let m: u16 = ((mix(((x as f64)) as _, ((2f64)) as _) as f64)) as u16;"));
+        assert!(!out.contains("mixer"));
+    }
+
+    #[test]
+    fn rules_decode_to_typed_errors() {
+        let out = rust("packet Entry { id uint8 where id < 10 }
+            packet List { count uint8, items repeat Entry[count], tail uint8, assert tail >= count }
+            packet Plain { a uint8 }");
+        assert!(out.contains("pub fn try_deserialize_prefix(data: &[u8]) -> Result<(Entry, usize), ValidationError> {"));
+        assert!(out.contains("pub fn try_deserialize(data: &[u8]) -> Result<List, ValidationError> {"));
+        assert!(out.contains("return Err(ValidationError { packet: \"Entry\", field: \"id\", rule: \"id < 10\" });"));
+        assert!(out.contains("return Err(ValidationError { packet: \"List\", field: \"count\", rule: \"tail >= count\" });"));
+        // a list of packets with rules returns their errors too
+        assert!(out.contains("let (_item, _n) = Entry::try_deserialize_prefix(&cur.get_ref()[cur.position() as usize..])?;"));
+        assert!(out.contains("Self::try_deserialize_prefix(data).unwrap_or_else(|e| panic!(\"{}\", e))"));
+        // without rules a packet can still run out of data
+        assert!(out.contains("pub fn try_deserialize(data: &[u8]) -> Result<Plain, ValidationError> {"));
+        assert!(!out.contains("panic!(\"{}\", ValidationError"));
+    }
+
+    #[test]
+    fn truncated_input_is_an_error() {
+        let out = rust("packet T { a uint16, s cstring, v varuint, b bool(bit), tail bytes[a] }");
+        assert!(out.contains("let _read = |field: &'static str| ValidationError::read(\"T\", field);"));
+        assert!(out.contains("let a = cur.read_u16::<LittleEndian>().map_err(_read(\"a\"))?;"));
+        assert!(out.contains("let _raw = string_codec::read_cstring(&mut cur, 1).map_err(_read(\"s\"))?;"));
+        assert!(out.contains("let s = string_codec::decode_utf8(&_raw).map_err(_read(\"s\"))?;"));
+        assert!(out.contains("let v = varint_codec::read_leb128(&mut cur).map_err(_read(\"v\"))?;"));
+        assert!(out.contains("let _bits = cur.read_u8().map_err(_read(\"b\"))?;"));
+        assert!(out.contains("cur.read_exact(&mut tail).map_err(_read(\"tail\"))?;"));
+        assert!(out.contains("std::io::ErrorKind::UnexpectedEof => \"enough data\","));
+        assert!(!out.contains("cur.read_exact(&mut c).unwrap()"));
+        assert!(!out.contains("panic!(\"{field}"));
+    }
+
+    #[test]
+    fn bad_magic_and_within_lengths_are_errors() {
+        let out = rust("packet M { magic uint16 = 0xCAFE, n uint8, within(n) { a uint8 } }");
+        assert!(out.contains(
+            "if magic != Self::MAGIC {\n            return Err(ValidationError { packet: \"M\", field: \"magic\", rule: \"magic == 0xcafe\" });\n        }"
+        ));
+        assert!(out.contains(
+            "if _end0 > cur.get_ref().len() - cur.position() as usize {\n            return Err(ValidationError { packet: \"M\", field: \"a\", rule: \"within length fits the data\" });"
+        ));
+        assert!(out.contains(
+            "if cur.position() as usize != _end0 {\n            return Err(ValidationError { packet: \"M\", field: \"a\", rule: \"within block is read to its end\" });"
+        ));
+        assert!(!out.contains("assert!(magic"));
+    }
+
+    #[test]
+    fn fillers_are_zeros_and_skipped() {
+        let out = rust("packet F be { v uint8, pad(3), n uint32, reserved uint16, align(8) }");
+        assert!(out.contains("pub struct F {\n    pub v: u8,\n    pub n: u32,\n\n}"));
+        assert!(out.contains("data.resize(data.len() + 3, 0);"));
+        assert!(out.contains("data.resize(data.len() + 2, 0);"));
+        assert!(out.contains("data.resize(data.len().next_multiple_of(8), 0);"));
+        assert!(out.contains("cur.read_exact(&mut [0u8; 3]).map_err(_read(\"_pad1\"))?;"));
+        assert!(out.contains("cur.read_exact(&mut [0u8; 2]).map_err(_read(\"_reserved3\"))?;"));
+        assert!(out.contains("cur.read_exact(&mut vec![0u8; _skip]).map_err(_read(\"_align4\"))?;"));
+    }
+}
@@ -0,0 +1,1251 @@
+use crate::models::parsing_models::{
+    BitSlot, Check, ChecksumKind, Endianness, ExprNode, FixedBase, Leftover, PacketExpr, PacketExprList, RepeatBound, StringEncoding,
+    TimeEncoding, TypeNode, VarIntEncoding,
+};
+use std::fmt::Write as _;
+
+pub struct SwiftGenerator {}
+
+impl SwiftGenerator {
+    pub fn generate(model: &PacketExprList) -> String {
+        let mut out = String::new();
+        out.push_str(&Self::create_headers());
+        out.push_str(&Self::create_spacer());
+        if model.packets.iter().any(|p| !p.checksums().is_empty()) {
+            out.push_str(CHECKSUM_HELPERS);
+            out.push_str(&Self::create_spacer());
+        }
+
+        for pkt in &model.packets {
+            out.push_str(&Self::build_struct(pkt));
+            out.push_str(&Self::create_spacer());
+        }
+        out
+    }
+
+    fn create_spacer() -> String {
+        "\n".to_string()
+    }
+
+    fn create_headers() -> String {
+        // Shared support code: the PacketCodable protocol, decode errors,
+        // a bounds-checked reader over Data, an endian-aware Data.append,
+        // string encode/decode helpers, datetime conversions and varint codecs.
+        r#"import Foundation
+
+public enum PacketDecodingError: Error {
+    case truncated(field: String, needed: Int, remaining: Int)
+    case unboundedField(field: String)
+    case unterminatedString(field: String)
+    case invalidEncoding(field: String)
+    case overlongVarint(field: String)
+    case leftoverBytes(field: String, count: Int)
+    case wrongConstant(field: String, expected: String, found: String)
+    case wrongChecksum(field: String, expected: String, found: String)
+    case validationFailed(field: String, rule: String)
+}
+
+public protocol PacketCodable {
+    func serialize() -> Data
+    static func deserialize(_ data: Data) throws -> Self
+}
+
+struct PacketReader {
+    let data: Data
+    var pos: Int = 0
+    var end: Int // reads stop here; narrowed inside `within` blocks
+
+    init(_ data: Data) {
+        self.data = data
+        self.end = data.count
+    }
+
+    var remaining: Int {
+        return end - pos
+    }
+
+    /// Narrows the reader to the next `count` bytes and returns the previous end for `leaveRegion`.
+    mutating func enterRegion(_ field: String, count: Int) throws -> Int {
+        guard count >= 0, count <= remaining else {
+            throw PacketDecodingError.truncated(field: field, needed: count, remaining: remaining)
+        }
+        let outer = end
+        end = pos + count
+        return outer
+    }
+
+    /// Moves past the region and restores `outer`; unread bytes throw unless `skip` is set.
+    mutating func leaveRegion(_ field: String, outer: Int, skip: Bool) throws {
+        if remaining > 0 && !skip {
+            throw PacketDecodingError.leftoverBytes(field: field, count: remaining)
+        }
+        pos = end
+        end = outer
+    }
+
+    mutating func readBytes(_ field: String, count: Int) throws -> Data {
+        guard count >= 0, count <= remaining else {
+            throw PacketDecodingError.truncated(field: field, needed: count, remaining: remaining)
+        }
+        let start = data.startIndex + pos
+        pos += count
+        return data.subdata(in: start..<(start + count))
+    }
+
+    mutating func readRest() -> Data {
+        let start = data.startIndex + pos
+        pos = end
+        return data.subdata(in: start..<(data.startIndex + end))
+    }
+
+    /// Reads through the next NUL code unit (1 byte, or 2 for UTF-16) and returns the bytes before it.
+    mutating func readCString(_ field: String, unit: Int) throws -> Data {
+        var i = pos
+        while i + unit <= end {
+            let start = data.startIndex + i
+            if data[start..<(start + unit)].allSatisfy({ $0 == 0 }) {
+                let bytes = try readBytes(field, count: i - pos)
+                pos += unit
+                return bytes
+            }
+            i += unit
+        }
+        throw PacketDecodingError.unterminatedString(field: field)
+    }
+
+    mutating func readInteger<T: FixedWidthInteger>(_ field: String, as type: T.Type, bigEndian: Bool) throws -> T {
+        let raw = try readBytes(field, count: MemoryLayout<T>.size)
+        let value = raw.withUnsafeBytes { $0.loadUnaligned(as: T.self) }
+        return bigEndian ? T(bigEndian: value) : T(littleEndian: value)
+    }
+
+    /// LEB128: 7 bits per byte, least significant group first, at most 10 bytes.
+    mutating func readVarUInt(_ field: String) throws -> UInt64 {
+        var value: UInt64 = 0
+        for i in 0..<10 {
+            let b = try readInteger(field, as: UInt8.self, bigEndian: false)
+            value |= UInt64(b & 0x7F) << (7 * i)
+            if b & 0x80 == 0 {
+                return value
+            }
+        }
+        throw PacketDecodingError.overlongVarint(field: field)
+    }
+
+    /// RFC 9000 varint: the top two bits of the first byte give its length.
+    mutating func readQuicVarInt(_ field: String) throws -> UInt64 {
+        let first = try readInteger(field, as: UInt8.self, bigEndian: false)
+        var value = UInt64(first & 0x3F)
+        for b in try readBytes(field, count: (1 << (first >> 6)) - 1) {
+            value = (value << 8) | UInt64(b)
+        }
+        return value
+    }
+}
+
+extension Data {
+    mutating func appendInteger<T: FixedWidthInteger>(_ value: T, bigEndian: Bool) {
+        var v = bigEndian ? value.bigEndian : value.littleEndian
+        Swift.withUnsafeBytes(of: &v) { append(contentsOf: $0) }
+    }
+
+    mutating func appendVarUInt(_ value: UInt64) {
+        var v = value
+        while v >= 0x80 {
+            append(UInt8(truncatingIfNeeded: v) | 0x80)
+            v >>= 7
+        }
+        append(UInt8(v))
+    }
+
+    /// Values of 2^62 and up do not fit a QUIC varint and trap.
+    mutating func appendQuicVarInt(_ value: UInt64) {
+        precondition(value < 1 << 62, "too large for a QUIC varint")
+        switch value {
+        case ..<0x40: append(UInt8(value))
+        case ..<0x4000: appendInteger(UInt16(value) | 0x4000, bigEndian: true)
+        case ..<0x4000_0000: appendInteger(UInt32(value) | 0x8000_0000, bigEndian: true)
+        default: appendInteger(value | 0xC000_0000_0000_0000, bigEndian: true)
+        }
+    }
+}
+
+func zigzag(_ v: Int64) -> UInt64 {
+    return UInt64(bitPattern: (v << 1) ^ (v >> 63))
+}
+
+func unzigzag(_ u: UInt64) -> Int64 {
+    return Int64(bitPattern: u >> 1) ^ -Int64(bitPattern: u & 1)
+}
+
+func encodeString(_ value: String, _ encoding: String.Encoding) -> Data {
+    return value.data(using: encoding, allowLossyConversion: true) ?? Data()
+}
+
+/// NUL-pads `raw` to exactly `count` bytes; a longer `raw` is a programming error.
+func padded(_ field: String, _ raw: Data, to count: Int) -> Data {
+    precondition(raw.count <= count, "\(field): string longer than \(count) bytes")
+    var out = raw
+    out.append(Data(count: count - raw.count))
+    return out
+}
+
+/// Cuts `raw` at its first NUL code unit.
+func stripNul(_ raw: Data, unit: Int) -> Data {
+    var i = 0
+    while i + unit <= raw.count {
+        let start = raw.startIndex + i
+        if raw[start..<(start + unit)].allSatisfy({ $0 == 0 }) {
+            return raw.subdata(in: raw.startIndex..<start)
+        }
+        i += unit
+    }
+    return raw
+}
+
+func decodeString(_ field: String, _ raw: Data, _ encoding: String.Encoding) throws -> String {
+    if encoding == .ascii && !raw.allSatisfy({ $0 < 0x80 }) {
+        throw PacketDecodingError.invalidEncoding(field: field)
+    }
+    guard let value = String(data: raw, encoding: encoding) else {
+        throw PacketDecodingError.invalidEncoding(field: field)
+    }
+    return value
+}
+
+/// Conversions between Date and the 64-bit wire value of each datetime encoding.
+enum PacketTime {
+    static let ntpOffset: Double = 2_208_988_800
+    static let fileTimeOffset: Double = 11_644_473_600
+
+    static func toUnix(_ date: Date, perSecond: Double) -> Int64 {
+        return Int64((date.timeIntervalSince1970 * perSecond).rounded(.down))
+    }
+
+    static func fromUnix(_ value: Int64, perSecond: Double) -> Date {
+        return Date(timeIntervalSince1970: Double(value) / perSecond)
+    }
+
+    /// NTP 32.32 fixed point seconds since 1900-01-01; wraps at the end of each era.
+    static func toNtp64(_ date: Date) -> UInt64 {
+        let t = date.timeIntervalSince1970 + ntpOffset
+        let secs = t.rounded(.down)
+        return (UInt64(truncatingIfNeeded: Int64(secs)) << 32) | UInt64((t - secs) * 4_294_967_296)
+    }
+
+    static func fromNtp64(_ value: UInt64) -> Date {
+        let frac = Double(value & 0xFFFF_FFFF) / 4_294_967_296
+        return Date(timeIntervalSince1970: Double(value >> 32) - ntpOffset + frac)
+    }
+
+    /// FILETIME: 100 ns ticks since 1601-01-01.
+    static func toFileTime(_ date: Date) -> UInt64 {
+        let ticks = ((date.timeIntervalSince1970 + fileTimeOffset) * 10_000_000).rounded(.down)
+        return UInt64(truncatingIfNeeded: Int64(ticks))
+    }
+
+    static func fromFileTime(_ value: UInt64) -> Date {
+        return Date(timeIntervalSince1970: Double(value) / 10_000_000 - fileTimeOffset)
+    }
+}
+"#
+        .to_string()
+    }
+
+    fn build_struct(pkt: &PacketExpr) -> String {
+        let name = &pkt.name;
+
+        // 1) stored properties + memberwise init
+        let mut props = String::new();
+        let mut init_params = Vec::<String>::new();
+        let mut init_body = String::new();
+        // filler is not stored, nor are checksums, which are filled in and checked by the codec
+        for f in pkt.fields.iter().filter(|f| !f.is_filler() && f.checksum().is_none()) {
+            let ty = swift_field_type(&f.expr);
+            props.push_str(&doc_comment(&f.doc, "    "));
+            // const fields are static: written as given, checked when read
+            if let Some(value) = f.const_literal() {
+                let _ = writeln!(&mut props, "    public static let {}: {} = {}", f.id, ty, value);
+                continue;
+            }
+            // optional fields are nil when absent
+            let ty = if f.condition.is_some() { format!("{ty}?") } else { ty };
+            let _ = writeln!(&mut props, "    public var {}: {}", f.id, ty);
+            init_params.push(format!("{}: {}", f.id, ty));
+            let _ = writeln!(&mut init_body, "self.{id} = {id}", id = f.id);
+        }
+
+        // 2) calculated fields as computed properties; checksums in them re-encode the packet
+        let mut calc_props = String::new();
+        let encodes = pkt.calculated_fields.iter().any(|cf| !cf.expr.checksums().is_empty());
+        for cf in &pkt.calculated_fields {
+            let ty = swift_type_from_type_name(&cf.data_type);
+            let expr = emit_swift_numeric_expr(&cf.expr);
+            calc_props.push_str(&doc_comment(&cf.doc, "    "));
+            if cf.expr.checksums().is_empty() {
+                let _ = writeln!(
+                    &mut calc_props,
+                    "    public var {}: {} {{ {} }}",
+                    cf.name,
+                    ty,
+                    swift_cast(ty, &expr)
+                );
+            } else {
+                let _ = writeln!(
+                    &mut calc_props,
+                    "    public var {}: {} {{
+        let encoded = encoded()
+        return {}
+    }}",
+                    cf.name,
+                    ty,
+                    swift_cast(ty, &expr)
+                );
+            }
+        }
+
+        // 3) serialize
+        let mut ser_body = String::new();
+        let slots = pkt.bit_slots();
+        let derived = pkt.derived_counts();
+        let marks = pkt.checksum_marks();
+        for (((f, slot), count), &(start, end)) in pkt.fields.iter().zip(&slots).zip(&derived).zip(&marks) {
+            if start {
+                let _ = writeln!(&mut ser_body, "let {}_start = data.count", f.id);
+            }
+            if let Some(slot) = slot {
+                ser_body.push_str(&serialize_bit(&f.id, slot));
+                if end {
+                    let _ = writeln!(&mut ser_body, "let {}_end = data.count", f.id);
+                }
+                continue;
+            }
+            // count fields are written from the field they size, shadowing the property;
+            // the conversion traps if the count does not fit
+            if let Some(count) = count {
+                let _ = writeln!(
+                    &mut ser_body,
+                    "let {} = {}({}.count{})",
+                    f.id,
+                    swift_field_type(&f.expr),
+                    pkt.fields[count.source].id,
+                    swift_adjust(count.adjust)
+                );
+            } else if f.checksum().is_some() {
+                // written as zero, then filled in once the packet is encoded
+                let _ = writeln!(&mut ser_body, "let {}_at = data.count", f.id);
+                let _ = writeln!(&mut ser_body, "let {}: {} = 0", f.id, swift_field_type(&f.expr));
+            } else if f.value.is_some() {
+                let _ = writeln!(&mut ser_body, "let {id} = Self.{id}", id = f.id);
+            }
+            let endian = f.endianness.as_ref().or(pkt.endianness.as_ref()).unwrap_or(&Endianness::Le);
+            // optional fields are written when their condition holds, and must be set then
+            match &f.condition {
+                Some(cond) => {
+                    let _ = writeln!(&mut ser_body, "if {} != 0.0 {{", emit_swift_numeric_expr_in(cond, ""));
+                    let _ = writeln!(
+                        &mut ser_body,
+                        "    guard let {id} = {id} else {{ preconditionFailure(\"{}: {id} must be set when its condition holds\") }}",
+                        pkt.name,
+                        id = f.id
+                    );
+                    ser_body.push_str(&indent(&serialize_snippet(&f.expr, &f.id, endian.clone()), 1));
+                    let _ = writeln!(&mut ser_body, "}}");
+                }
+                None => ser_body.push_str(&serialize_snippet(&f.expr, &f.id, endian.clone())),
+            }
+            if end {
+                let _ = writeln!(&mut ser_body, "let {}_end = data.count", f.id);
+            }
+        }
+        for f in &pkt.fields {
+            let Some(sum) = f.checksum() else { continue };
+            let endian = f.endianness.as_ref().or(pkt.endianness.as_ref()).unwrap_or(&Endianness::Le);
+            let _ = writeln!(
+                &mut ser_body,
+                "data.storeInteger(PacketChecksum.{}(data[{}_start..<{}_end]), at: {}_at, bigEndian: {})",
+                swift_checksum_name(sum.kind),
+                sum.first,
+                sum.last,
+                f.id,
+                *endian == Endianness::Be
+            );
+        }
+
+        // 4) deserialize; `align` counts from where the packet starts in the shared reader
+        let mut de_body = String::new();
+        if pkt.fields.iter().any(|f| matches!(f.expr, TypeNode::Align(_))) {
+            let _ = writeln!(&mut de_body, "let packetStart = reader.pos");
+        }
+        // calc fields take their checksums from a fresh encoding, so only checksum fields need offsets here
+        let sums: Vec<_> = pkt.fields.iter().filter_map(|f| f.checksum()).collect();
+        let mut bits_owner = "";
+        for (i, (f, slot)) in pkt.fields.iter().zip(&slots).enumerate() {
+            let start = sums.iter().any(|c| c.first == f.id);
+            let end = sums.iter().any(|c| c.last == f.id);
+            if start {
+                let _ = writeln!(&mut de_body, "let {}_start = reader.pos", f.id);
+            }
+            if f.checksum().is_some() {
+                let _ = writeln!(&mut de_body, "let {}_at = reader.pos", f.id);
+            }
+            // a `within` block narrows the reader; its first field names it in errors
+            for (k, region) in pkt.regions_opening(i) {
+                let _ = writeln!(
+                    &mut de_body,
+                    "let outer{k} = try reader.enterRegion(\"{}\", count: {})",
+                    f.id,
+                    emit_swift_len_expr(&region.length)
+                );
+            }
+            if let Some(slot) = slot {
+                if slot.starts_byte() {
+                    bits_owner = &f.id;
+                }
+                de_body.push_str(&deserialize_bit(&f.id, slot, bits_owner));
+            } else if let Some(cond) = &f.condition {
+                // read into `<id>_value`, since a local named like the field would hide it
+                let endian = f.endianness.as_ref().or(pkt.endianness.as_ref()).unwrap_or(&Endianness::Le);
+                let local = format!("{}_value", f.id);
+                let read = deserialize_snippet(&f.expr, &local, endian.clone(), pkt.is_last_in_scope(i))
+                    .replace(&format!("\"{local}\""), &format!("\"{}\"", f.id));
+                let _ = writeln!(&mut de_body, "var {}: {}? = nil", f.id, swift_field_type(&f.expr));
+                let _ = writeln!(&mut de_body, "if {} != 0.0 {{", emit_swift_numeric_expr_in(cond, ""));
+                de_body.push_str(&indent(&read, 1));
+                let _ = writeln!(&mut de_body, "    {} = {local}\n}}", f.id);
+            } else {
+                let endian = f.endianness.as_ref().or(pkt.endianness.as_ref()).unwrap_or(&Endianness::Le);
+                de_body.push_str(&deserialize_snippet(&f.expr, &f.id, endian.clone(), pkt.is_last_in_scope(i)));
+            }
+            if end {
+                let _ = writeln!(&mut de_body, "let {}_end = reader.pos", f.id);
+            }
+            if let Some(value) = f.const_literal() {
+                let _ = writeln!(&mut de_body, "guard {id} == Self.{id} else {{", id = f.id);
+                let _ = writeln!(
+                    &mut de_body,
+                    "    throw PacketDecodingError.wrongConstant(field: \"{id}\", expected: \"{value}\", found: \"0x\" + String({id}, radix: 16))",
+                    id = f.id
+                );
+                let _ = writeln!(&mut de_body, "}}");
+            }
+            if let Some(check) = &f.constraint {
+                de_body.push_str(&check_snippet(check));
+            }
+            for (k, region) in pkt.regions_closing(i) {
+                let _ = writeln!(
+                    &mut de_body,
+                    "try reader.leaveRegion(\"{}\", outer: outer{k}, skip: {})",
+                    pkt.fields[region.first].id,
+                    region.leftover == Leftover::Skip
+                );
+            }
+        }
+        // checksums are recomputed with their own field zeroed, as when they were filled in
+        for f in &pkt.fields {
+            let Some(sum) = f.checksum() else { continue };
+            let _ = writeln!(
+                &mut de_body,
+                "let {id}_sum = PacketChecksum.{}(PacketChecksum.without(reader.data, {}_start, {}_end, at: {id}_at, width: {}))",
+                swift_checksum_name(sum.kind),
+                sum.first,
+                sum.last,
+                sum.kind.width_bytes(),
+                id = f.id
+            );
+            let _ = writeln!(&mut de_body, "guard {id} == {id}_sum else {{", id = f.id);
+            let _ = writeln!(
+                &mut de_body,
+                "    throw PacketDecodingError.wrongChecksum(field: \"{id}\", expected: \"0x\" + String({id}_sum, radix: 16), found: \"0x\" + String({id}, radix: 16))",
+                id = f.id
+            );
+            let _ = writeln!(&mut de_body, "}}");
+        }
+        for check in &pkt.asserts {
+            de_body.push_str(&check_snippet(check));
+        }
+
+        // 5) validate, for checking a packet before serializing it
+        let checks = pkt.checks();
+        let validate = if checks.is_empty() {
+            String::new()
+        } else {
+            let body: String = checks.iter().map(|c| check_snippet(c)).collect();
+            format!(
+                "\n    /// Throws `validationFailed` if a `where` or `assert` rule fails; `deserialize` checks them too.\n    public func validate() throws {{\n{}    }}\n",
+                indent(&body, 2)
+            )
+        };
+
+        // calc fields that checksum a range need the offsets an encoding left
+        let serialize = if encodes {
+            let names: Vec<String> = pkt
+                .checksums()
+                .iter()
+                .flat_map(|c| [format!("{}_start", c.first), format!("{}_end", c.last)])
+                .collect::<std::collections::BTreeSet<_>>()
+                .into_iter()
+                .map(|m| format!("\"{m}\": {m}"))
+                .collect();
+            format!(
+                "    public func serialize() -> Data {{\n        return encoded().data\n    }}\n\n    /// The encoded packet and the offsets checksums in calculated fields are taken over.\n    private func encoded() -> (data: Data, marks: [String: Int]) {{\n        var data = Data()\n{}        return (data, [{}])\n    }}\n",
+                indent(&ser_body, 2),
+                names.join(", ")
+            )
+        } else {
+            format!(
+                "    public func serialize() -> Data {{\n        var data = Data()\n{}        return data\n    }}\n",
+                indent(&ser_body, 2)
+            )
+        };
+
+        let build_args = pkt
+            .fields
+            .iter()
+            .filter(|f| f.value.is_none() && !f.is_filler())
+            .map(|f| format!("{id}: {id}", id = f.id))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!(
+            r#"{doc}public struct {name}: PacketCodable {{
+{props}{calc_props}
+    public init({init_params}) {{
+{init_body}    }}
+
+{serialize}
+    public static func deserialize(_ data: Data) throws -> {name} {{
+        var reader = PacketReader(data)
+        return try read(from: &reader)
+    }}
+
+    /// Decodes one {name} at the reader's position and leaves the reader just past it.
+    static func read(from reader: inout PacketReader) throws -> {name} {{
+{de_body}        return {name}({build_args})
+    }}
+{validate}}}
+"#,
+            doc = doc_comment(&pkt.doc, ""),
+            name = name,
+            props = props,
+            calc_props = calc_props,
+            init_params = init_params.join(", "),
+            init_body = indent(&init_body, 2),
+            serialize = serialize,
+            de_body = indent(&de_body, 2),
+            build_args = build_args,
+        )
+    }
+}
+
+/// Name of the `PacketChecksum` function for `kind`.
+fn swift_checksum_name(kind: ChecksumKind) -> &'static str {
+    match kind {
+        ChecksumKind::Crc32 => "crc32",
+        ChecksumKind::Crc16Ccitt => "crc16Ccitt",
+        ChecksumKind::InetChecksum => "inetChecksum",
+        ChecksumKind::Adler32 => "adler32",
+    }
+}
+
+/// Checksum intrinsics, emitted only when a packet uses one.
+const CHECKSUM_HELPERS: &str = r#"enum PacketChecksum {
+    /// CRC-32 (IEEE 802.3).
+    static func crc32(_ data: Data) -> UInt32 {
+        var crc: UInt32 = 0xFFFF_FFFF
+        for b in data {
+            crc ^= UInt32(b)
+            for _ in 0..<8 {
+                crc = crc & 1 != 0 ? (crc >> 1) ^ 0xEDB8_8320 : crc >> 1
+            }
+        }
+        return ~crc
+    }
+
+    /// CRC-16/CCITT-FALSE.
+    static func crc16Ccitt(_ data: Data) -> UInt16 {
+        var crc: UInt16 = 0xFFFF
+        for b in data {
+            crc ^= UInt16(b) << 8
+            for _ in 0..<8 {
+                crc = crc & 0x8000 != 0 ? (crc << 1) ^ 0x1021 : crc << 1
+            }
+        }
+        return crc
+    }
+
+    /// RFC 1071 Internet checksum; an odd last byte is padded with zero.
+    static func inetChecksum(_ data: Data) -> UInt16 {
+        let bytes = [UInt8](data)
+        var sum: UInt64 = 0
+        for i in stride(from: 0, to: bytes.count, by: 2) {
+            sum += UInt64(bytes[i]) << 8 | UInt64(i + 1 < bytes.count ? bytes[i + 1] : 0)
+        }
+        while sum > 0xFFFF {
+            sum = (sum & 0xFFFF) + (sum >> 16)
+        }
+        return ~UInt16(sum)
+    }
+
+    /// Adler-32 (RFC 1950).
+    static func adler32(_ data: Data) -> UInt32 {
+        var a: UInt32 = 1, b: UInt32 = 0
+        for x in data {
+            a = (a + UInt32(x)) % 65521
+            b = (b + a) % 65521
+        }
+        return b << 16 | a
+    }
+
+    /// Bytes `start..<end` of `data` with the `width` bytes at `at` zeroed,
+    /// as they were when the checksum stored there was computed.
+    static func without(_ data: Data, _ start: Int, _ end: Int, at: Int, width: Int) -> Data {
+        var out = [UInt8](data[(data.startIndex + start)..<(data.startIndex + end)])
+        for i in at..<(at + width) where i >= start && i < end {
+            out[i - start] = 0
+        }
+        return Data(out)
+    }
+}
+
+extension Data {
+    /// Overwrites the integer at byte `at`, for checksums filled in after encoding.
+    mutating func storeInteger<T: FixedWidthInteger>(_ value: T, at: Int, bigEndian: Bool) {
+        var v = bigEndian ? value.bigEndian : value.littleEndian
+        Swift.withUnsafeBytes(of: &v) { replaceSubrange(at..<(at + $0.count), with: $0) }
+    }
+}
+"#;
+
+/// `guard` that throws unless the rule holds over the fields in scope.
+fn check_snippet(check: &Check) -> String {
+    let rule = check.rule.replace('\\', "\\\\").replace('"', "\\\"");
+    format!(
+        "guard {} != 0.0 else {{\n    throw PacketDecodingError.validationFailed(field: \"{}\", rule: \"{rule}\")\n}}\n",
+        emit_swift_numeric_expr_in(&check.cond, ""),
+        check.field
+    )
+}
+
+/* ============================================================
+ * Type mapping
+ * ============================================================
+*/
+
+fn swift_field_type(t: &TypeNode) -> String {
+    use TypeNode::*;
+    let array_of = |base: &str, len: &Option<ExprNode>| {
+        if len.is_some() {
+            format!("[{base}]")
+        } else {
+            base.to_string()
+        }
+    };
+
+    match t {
+        UnsignedInteger8(len) => array_of("UInt8", len),
+        Integer8(len) => array_of("Int8", len),
+        UnsignedInteger16(len) => array_of("UInt16", len),
+        Integer16(len) => array_of("Int16", len),
+        UnsignedInteger32(len) => array_of("UInt32", len),
+        Integer32(len) => array_of("Int32", len),
+        UnsignedInteger64(len) => array_of("UInt64", len),
+        Integer64(len) => array_of("Int64", len),
+        Float32(len) => array_of("Float", len),
+        Float64(len) => array_of("Double", len),
+
+        // DateTime: 64-bit on the wire, converted through PacketTime
+        DateTime(len, _) => array_of("Date", len),
+
+        Bool(len, _) => array_of("Bool", len),
+        // Fixed point: the raw integer scaled by 2^-frac
+        Fixed(len, _, _) => array_of("Double", len),
+        VarInt(len, VarIntEncoding::ZigZag) => array_of("Int64", len),
+        VarInt(len, _) => array_of("UInt64", len),
+
+        Bytes(_len) => "Data".to_string(),
+        MacAddress(_len) => "[UInt8]".to_string(),
+        // Addresses as their network-order octets (4 or 16)
+        Ipv4Address(len) | Ipv6Address(len) => array_of("[UInt8]", len),
+        Port(len) => array_of("UInt16", len),
+        FixedString(_, _) | CString(_) | PString(_, _) => "String".to_string(),
+        Repeat(elem, _) => format!("[{elem}]"),
+        Pad(_) | Align(_) => "Data".to_string(), // never stored
+    }
+}
+
+fn swift_type_from_type_name(name: &str) -> &'static str {
+    match name {
+        "int8" => "Int8",
+        "uint8" => "UInt8",
+        "int16" => "Int16",
+        "uint16" => "UInt16",
+        "int32" => "Int32",
+        "uint32" => "UInt32",
+        "int64" => "Int64",
+        "uint64" => "UInt64",
+        "float32" => "Float",
+        "float64" => "Double",
+        "datetime" => "Int64",
+        _ => "Double", // safe numeric fallback for calculated fields
+    }
+}
+
+/// Converts a Double-valued expression into the calc field's declared type.
+fn swift_cast(ty: &str, expr: &str) -> String {
+    match ty {
+        "Double" => expr.to_string(),
+        "Float" => format!("Float({expr})"),
+        _ => format!("{ty}(truncatingIfNeeded: Int64({expr}))"),
+    }
+}
+
+/// Swift integer type used to move an element of `t` on and off the wire.
+fn wire_int_type(t: &TypeNode) -> &'static str {
+    use TypeNode::*;
+    match t {
+        UnsignedInteger8(_) | Bytes(_) | MacAddress(_) | FixedString(_, _) | CString(_) => "UInt8",
+        Integer8(_) => "Int8",
+        UnsignedInteger16(_) | Port(_) => "UInt16",
+        PString(_, _) => if t.scalar_width_bytes() == 1 { "UInt8" } else { "UInt16" }, // the length prefix
+        Integer16(_) => "Int16",
+        UnsignedInteger32(_) | Float32(_) => "UInt32",
+        Integer32(_) => "Int32",
+        UnsignedInteger64(_) | Float64(_) | VarInt(_, _) => "UInt64",
+        Integer64(_) => "Int64",
+        DateTime(_, unit) => match unit {
+            TimeEncoding::Ntp64 | TimeEncoding::FileTime => "UInt64",
+            _ => "Int64",
+        },
+        Ipv4Address(_) | Ipv6Address(_) => "UInt8", // moved as octets
+        Bool(_, _) | Repeat(_, _) | Pad(_) | Align(_) => "UInt8",
+        Fixed(_, base, _) => match base {
+            FixedBase::Int8 => "Int8",
+            FixedBase::UInt8 => "UInt8",
+            FixedBase::Int16 => "Int16",
+            FixedBase::UInt16 => "UInt16",
+            FixedBase::Int32 => "Int32",
+            FixedBase::UInt32 => "UInt32",
+            FixedBase::Int64 => "Int64",
+            FixedBase::UInt64 => "UInt64",
+        },
+    }
+}
+
+fn is_array_like(t: &TypeNode) -> bool {
+    use TypeNode::*;
+    match t {
+        Bytes(_) | MacAddress(_) => true,
+        FixedString(_, _) | CString(_) | PString(_, _) | Repeat(_, _) | Pad(_) | Align(_) => false,
+        UnsignedInteger8(len)
+        | Integer8(len)
+        | UnsignedInteger16(len)
+        | Integer16(len)
+        | UnsignedInteger32(len)
+        | Integer32(len)
+        | UnsignedInteger64(len)
+        | Integer64(len)
+        | Float32(len)
+        | Float64(len)
+        | DateTime(len, _)
+        | Bool(len, _)
+        | Fixed(len, _, _)
+        | VarInt(len, _)
+        | Ipv4Address(len)
+        | Ipv6Address(len)
+        | Port(len) => len.is_some(),
+    }
+}
+
+fn type_len_expr(t: &TypeNode) -> Option<&ExprNode> {
+    use TypeNode::*;
+    match t {
+        UnsignedInteger8(e) | Integer8(e) | UnsignedInteger16(e) | Integer16(e)
+        | UnsignedInteger32(e) | Integer32(e) | UnsignedInteger64(e) | Integer64(e)
+        | Float32(e) | Float64(e) | DateTime(e, _) | Bytes(e) | MacAddress(e) => e.as_ref(),
+        Bool(e, _) | Fixed(e, _, _) | VarInt(e, _) => e.as_ref(),
+        Ipv4Address(e) | Ipv6Address(e) | Port(e) => e.as_ref(),
+        FixedString(e, _) => Some(e),
+        CString(_) | PString(_, _) | Pad(_) | Align(_) => None,
+        Repeat(_, RepeatBound::Count(e)) => Some(e),
+        Repeat(_, _) => None,
+    }
+}
+
+/// `PacketTime` conversion suffix and trailing argument of a datetime encoding.
+fn swift_time(unit: &TimeEncoding) -> (&'static str, &'static str) {
+    match unit {
+        TimeEncoding::UnixSeconds => ("Unix", ", perSecond: 1"),
+        TimeEncoding::UnixMillis => ("Unix", ", perSecond: 1_000"),
+        TimeEncoding::UnixMicros => ("Unix", ", perSecond: 1_000_000"),
+        TimeEncoding::Ntp64 => ("Ntp64", ""),
+        TimeEncoding::FileTime => ("FileTime", ""),
+    }
+}
+
+/// Foundation encoding and code-unit width (for NUL terminators) of an encoding.
+fn swift_encoding(e: &StringEncoding) -> (&'static str, usize) {
+    match e {
+        StringEncoding::Ascii => (".ascii", 1),
+        StringEncoding::Utf8 => (".utf8", 1),
+        StringEncoding::Utf16Le => (".utf16LittleEndian", 2),
+    }
+}
+
+/* ============================================================
+ * Codegen snippets
+ * ============================================================
+*/
+
+/// `bool(bit)` fields share a byte, filled from the most significant bit down.
+fn serialize_bit(name: &str, slot: &BitSlot) -> String {
+    let mut s = String::new();
+    if slot.starts_byte() {
+        let _ = writeln!(&mut s, "data.append(0)");
+    }
+    let _ = writeln!(&mut s, "if {name} {{ data[data.endIndex - 1] |= 0x{:02X} }}", 1u8 << slot.shift());
+    s
+}
+
+/// The shared byte is read once, into `<owner>Bits`, by the first field in it.
+fn deserialize_bit(name: &str, slot: &BitSlot, owner: &str) -> String {
+    let mut s = String::new();
+    if slot.starts_byte() {
+        let _ = writeln!(
+            &mut s,
+            "let {owner}Bits = try reader.readInteger(\"{owner}\", as: UInt8.self, bigEndian: false)"
+        );
+    }
+    let _ = writeln!(&mut s, "let {name} = ({owner}Bits & 0x{:02X}) != 0", 1u8 << slot.shift());
+    s
+}
+
+/// ` - a` for a length written `l + a`, so the count comes out as `l`.
+fn swift_adjust(adjust: i64) -> String {
+    match adjust {
+        0 => String::new(),
+        a if a < 0 => format!(" + {}", -a),
+        a => format!(" - {a}"),
+    }
+}
+
+fn serialize_snippet(t: &TypeNode, name: &str, endian: Endianness) -> String {
+    let mut s = String::new();
+    let be = endian == Endianness::Be;
+
+    match t {
+        // filler: zeros, up to the next multiple of n for `align(n)`
+        TypeNode::Pad(n) => {
+            let _ = writeln!(&mut s, "data.append(Data(count: {n}))");
+        }
+        TypeNode::Align(n) => {
+            let _ = writeln!(&mut s, "data.append(Data(count: ({n} - data.count % {n}) % {n}))");
+        }
+        TypeNode::Bytes(_) | TypeNode::MacAddress(_) => {
+            let _ = writeln!(&mut s, "data.append(contentsOf: {name})");
+        }
+        TypeNode::Repeat(_, _) => {
+            let _ = writeln!(&mut s, "for v in {name} {{");
+            let _ = writeln!(&mut s, "    data.append(v.serialize())");
+            let _ = writeln!(&mut s, "}}");
+        }
+        TypeNode::FixedString(len, enc) => {
+            let (enc, _) = swift_encoding(enc);
+            let count = emit_swift_len_expr(len);
+            let _ = writeln!(&mut s, "data.append(padded(\"{name}\", encodeString({name}, {enc}), to: {count}))");
+        }
+        TypeNode::CString(enc) => {
+            let (enc, unit) = swift_encoding(enc);
+            let _ = writeln!(&mut s, "data.append(encodeString({name}, {enc}))");
+            let _ = writeln!(&mut s, "data.append(Data(count: {unit}))");
+        }
+        TypeNode::PString(_, enc) => {
+            let (enc, _) = swift_encoding(enc);
+            let wire = wire_int_type(t);
+            let _ = writeln!(&mut s, "let {name}Bytes = encodeString({name}, {enc})");
+            let _ = writeln!(
+                &mut s,
+                "precondition({name}Bytes.count <= {wire}.max, \"{name}: string longer than \\({wire}.max) bytes\")"
+            );
+            let _ = writeln!(&mut s, "data.appendInteger({wire}({name}Bytes.count), bigEndian: {be})");
+            let _ = writeln!(&mut s, "data.append({name}Bytes)");
+        }
+        // octets are already in network order; pad/truncate guards against a wrong count
+        TypeNode::Ipv4Address(len) | TypeNode::Ipv6Address(len) => {
+            let w = t.scalar_width_bytes();
+            if len.is_some() {
+                let _ = writeln!(&mut s, "for v in {name} {{");
+                let _ = writeln!(&mut s, "    data.append(padded(Data(v), to: {w}))");
+                let _ = writeln!(&mut s, "}}");
+            } else {
+                let _ = writeln!(&mut s, "data.append(padded(Data({name}), to: {w}))");
+            }
+        }
+        _ => {
+            let write_scalar = |dst: &mut String, expr: String| match t {
+                TypeNode::Float32(_) | TypeNode::Float64(_) => {
+                    let _ = writeln!(dst, "data.appendInteger({expr}.bitPattern, bigEndian: {be})");
+                }
+                TypeNode::DateTime(_, unit) => {
+                    let (m, arg) = swift_time(unit);
+                    let _ = writeln!(dst, "data.appendInteger(PacketTime.to{m}({expr}{arg}), bigEndian: {be})");
+                }
+                TypeNode::Bool(_, _) => {
+                    let _ = writeln!(dst, "data.appendInteger(UInt8({expr} ? 1 : 0), bigEndian: {be})");
+                }
+                // .rounded() breaks ties away from zero
+                TypeNode::Fixed(_, _, frac) => {
+                    let wire = wire_int_type(t);
+                    let _ = writeln!(
+                        dst,
+                        "data.appendInteger({wire}(({expr} * {}.0).rounded()), bigEndian: {be})",
+                        1u128 << frac
+                    );
+                }
+                TypeNode::VarInt(_, VarIntEncoding::Leb128) => {
+                    let _ = writeln!(dst, "data.appendVarUInt({expr})");
+                }
+                TypeNode::VarInt(_, VarIntEncoding::ZigZag) => {
+                    let _ = writeln!(dst, "data.appendVarUInt(zigzag({expr}))");
+                }
+                TypeNode::VarInt(_, VarIntEncoding::Quic) => {
+                    let _ = writeln!(dst, "data.appendQuicVarInt({expr})");
+                }
+                _ => {
+                    let _ = writeln!(dst, "data.appendInteger({expr}, bigEndian: {be})");
+                }
+            };
+
+            if is_array_like(t) {
+                let _ = writeln!(&mut s, "for v in {name} {{");
+                write_scalar(&mut s, "    v".to_string());
+                let _ = writeln!(&mut s, "}}");
+            } else {
+                write_scalar(&mut s, name.to_string());
+            }
+        }
+    }
+    s
+}
+
+fn deserialize_snippet(t: &TypeNode, name: &str, endian: Endianness, is_last: bool) -> String {
+    let mut s = String::new();
+    let be = endian == Endianness::Be;
+
+    match t {
+        // filler is skipped, whatever it holds
+        TypeNode::Pad(n) => {
+            let _ = writeln!(&mut s, "_ = try reader.readBytes(\"{name}\", count: {n})");
+        }
+        TypeNode::Align(n) => {
+            let _ = writeln!(
+                &mut s,
+                "_ = try reader.readBytes(\"{name}\", count: ({n} - (reader.pos - packetStart) % {n}) % {n})"
+            );
+        }
+        // elements read from the same reader, so they cannot run past its end
+        TypeNode::Repeat(elem, bound) => {
+            let _ = writeln!(&mut s, "var {name}: [{elem}] = []");
+            match bound {
+                RepeatBound::Eof => {
+                    let _ = writeln!(&mut s, "while reader.remaining > 0 {{");
+                    let _ = writeln!(&mut s, "    {name}.append(try {elem}.read(from: &reader))");
+                    let _ = writeln!(&mut s, "}}");
+                }
+                RepeatBound::Until(cond) => {
+                    let _ = writeln!(&mut s, "while true {{");
+                    let _ = writeln!(&mut s, "    let item = try {elem}.read(from: &reader)");
+                    let _ = writeln!(&mut s, "    {name}.append(item)");
+                    let _ = writeln!(&mut s, "    if {} != 0.0 {{ break }}", emit_swift_numeric_expr_in(cond, "item."));
+                    let _ = writeln!(&mut s, "}}");
+                }
+                RepeatBound::Count(expr) => {
+                    let _ = writeln!(&mut s, "for _ in 0..<{} {{", emit_swift_len_expr(expr));
+                    let _ = writeln!(&mut s, "    {name}.append(try {elem}.read(from: &reader))");
+                    let _ = writeln!(&mut s, "}}");
+                }
+            }
+        }
+        TypeNode::Bytes(len_opt) => {
+            if let Some(expr) = len_opt {
+                let _ = writeln!(
+                    &mut s,
+                    "let {name} = try reader.readBytes(\"{name}\", count: {count})",
+                    count = emit_swift_len_expr(expr)
+                );
+            } else if is_last {
+                let _ = writeln!(&mut s, "let {name} = reader.readRest()");
+            } else {
+                let _ = writeln!(
+                    &mut s,
+                    "throw PacketDecodingError.unboundedField(field: \"{name}\")"
+                );
+            }
+        }
+        TypeNode::MacAddress(len_opt) => {
+            let count = len_opt
+                .as_ref()
+                .map(emit_swift_len_expr)
+                .unwrap_or_else(|| "6".to_string());
+            let _ = writeln!(
+                &mut s,
+                "let {name} = [UInt8](try reader.readBytes(\"{name}\", count: {count}))"
+            );
+        }
+        TypeNode::FixedString(len, enc) => {
+            let (enc, unit) = swift_encoding(enc);
+            let count = emit_swift_len_expr(len);
+            let _ = writeln!(
+                &mut s,
+                "let {name} = try decodeString(\"{name}\", stripNul(try reader.readBytes(\"{name}\", count: {count}), unit: {unit}), {enc})"
+            );
+        }
+        TypeNode::CString(enc) => {
+            let (enc, unit) = swift_encoding(enc);
+            let _ = writeln!(
+                &mut s,
+                "let {name} = try decodeString(\"{name}\", try reader.readCString(\"{name}\", unit: {unit}), {enc})"
+            );
+        }
+        TypeNode::PString(_, enc) => {
+            let (enc, _) = swift_encoding(enc);
+            let wire = wire_int_type(t);
+            let _ = writeln!(
+                &mut s,
+                "let {name}Len = try reader.readInteger(\"{name}\", as: {wire}.self, bigEndian: {be})"
+            );
+            let _ = writeln!(
+                &mut s,
+                "let {name} = try decodeString(\"{name}\", try reader.readBytes(\"{name}\", count: Int({name}Len)), {enc})"
+            );
+        }
+        TypeNode::Ipv4Address(len) | TypeNode::Ipv6Address(len) => {
+            let w = t.scalar_width_bytes();
+            let read = format!("[UInt8](try reader.readBytes(\"{name}\", count: {w}))");
+            if let Some(expr) = len {
+                let _ = writeln!(&mut s, "var {name}: {} = []", swift_field_type(t));
+                let _ = writeln!(&mut s, "for _ in 0..<{} {{", emit_swift_len_expr(expr));
+                let _ = writeln!(&mut s, "    {name}.append({read})");
+                let _ = writeln!(&mut s, "}}");
+            } else {
+                let _ = writeln!(&mut s, "let {name} = {read}");
+            }
+        }
+        _ => {
+            let wire = wire_int_type(t);
+            let read_scalar = |field: &str| -> String {
+                let read = format!("try reader.readInteger(\"{field}\", as: {wire}.self, bigEndian: {be})");
+                match t {
+                    TypeNode::Float32(_) => format!("Float(bitPattern: {read})"),
+                    TypeNode::Float64(_) => format!("Double(bitPattern: {read})"),
+                    TypeNode::DateTime(_, unit) => {
+                        let (m, arg) = swift_time(unit);
+                        format!("PacketTime.from{m}({read}{arg})")
+                    }
+                    TypeNode::Bool(_, _) => format!("{read} != 0"),
+                    TypeNode::Fixed(_, _, frac) => format!("Double({read}) / {}.0", 1u128 << frac),
+                    TypeNode::VarInt(_, VarIntEncoding::Leb128) => format!("try reader.readVarUInt(\"{field}\")"),
+                    TypeNode::VarInt(_, VarIntEncoding::ZigZag) => {
+                        format!("unzigzag(try reader.readVarUInt(\"{field}\"))")
+                    }
+                    TypeNode::VarInt(_, VarIntEncoding::Quic) => format!("try reader.readQuicVarInt(\"{field}\")"),
+                    _ => read,
+                }
+            };
+
+            if is_array_like(t) {
+                if let Some(expr) = type_len_expr(t) {
+                    let _ = writeln!(&mut s, "var {name}: {} = []", swift_field_type(t));
+                    let _ = writeln!(&mut s, "for _ in 0..<{} {{", emit_swift_len_expr(expr));
+                    let _ = writeln!(&mut s, "    {name}.append({})", read_scalar(name));
+                    let _ = writeln!(&mut s, "}}");
+                }
+            } else {
+                let _ = writeln!(&mut s, "let {name} = {}", read_scalar(name));
+            }
+        }
+    }
+    s
+}
+
+/* ============================================================
+ * Expr emitters (for counts & calculated)
+ * ============================================================
+*/
+
+fn emit_swift_len_expr(e: &ExprNode) -> String {
+    format!("Int({})", emit_swift_numeric_expr(e))
+}
+
+fn emit_swift_numeric_expr(e: &ExprNode) -> String {
+    emit_swift_numeric_expr_in(e, "")
+}
+
+/// Like `emit_swift_numeric_expr`, with field references read from `root` (e.g. `item.`).
+fn emit_swift_numeric_expr_in(e: &ExprNode, root: &str) -> String {
+    use ExprNode::*;
+    // only calc fields take checksums, after `let encoded = encoded()`
+    if let Some(sum) = e.as_checksum() {
+        return format!(
+            "Double(PacketChecksum.{}(encoded.data[encoded.marks[\"{}_start\"]!..<encoded.marks[\"{}_end\"]!]))",
+            swift_checksum_name(sum.kind),
+            sum.first,
+            sum.last
+        );
+    }
+    match e {
+        UnsignedInteger64Value(u) => format!("Double({})", u),
+        Integer64Value(i) => format!("Double({})", i),
+        Float64Value(f) => format!("Double({})", f),
+
+        StringValue(_) => "0.0".into(), // not expected in numeric context
+
+        ValueReference(name, idx) => {
+            if let Some(ix) = idx {
+                format!("Double({root}{name}[Int({})])", emit_swift_numeric_expr_in(ix, root))
+            } else {
+                format!("Double({root}{name})")
+            }
+        }
+
+        ParenthesizedExpr(x) => format!("({})", emit_swift_numeric_expr_in(x, root)),
+
+        Plus(a, b) => format!("({} + {})", emit_swift_numeric_expr_in(a, root), emit_swift_numeric_expr_in(b, root)),
+        Minus(a, b) => format!("({} - {})", emit_swift_numeric_expr_in(a, root), emit_swift_numeric_expr_in(b, root)),
+        Mult(a, b) => format!("({} * {})", emit_swift_numeric_expr_in(a, root), emit_swift_numeric_expr_in(b, root)),
+        Div(a, b) => format!("({} / {})", emit_swift_numeric_expr_in(a, root), emit_swift_numeric_expr_in(b, root)),
+        Pow(a, b) => format!("pow({}, {})", emit_swift_numeric_expr_in(a, root), emit_swift_numeric_expr_in(b, root)),
+        Mod(a, b) => format!("{}.truncatingRemainder(dividingBy: {})", emit_swift_numeric_expr_in(a, root), emit_swift_numeric_expr_in(b, root)),
+        Negate(a) => format!("(-{})", emit_swift_numeric_expr_in(a, root)),
+
+        // bitwise → round-trip through Int64
+        BitAnd(a, b) => format!("Double(Int64({}) & Int64({}))", emit_swift_numeric_expr_in(a, root), emit_swift_numeric_expr_in(b, root)),
+        BitOr(a, b) => format!("Double(Int64({}) | Int64({}))", emit_swift_numeric_expr_in(a, root), emit_swift_numeric_expr_in(b, root)),
+        BitNot(a) => format!("Double(~Int64({}))", emit_swift_numeric_expr_in(a, root)),
+        ShiftLeft(a, b) => format!("Double(Int64({}) << (Int64({}) & 63))", emit_swift_numeric_expr_in(a, root), emit_swift_numeric_expr_in(b, root)),
+        ShiftRight(a, b) => format!("Double(Int64({}) >> (Int64({}) & 63))", emit_swift_numeric_expr_in(a, root), emit_swift_numeric_expr_in(b, root)),
+
+        // comparisons/logic → 1.0/0.0 to keep expression numeric
+        Gt(a, b) => bool_to_num(&format!("{} > {}", emit_swift_numeric_expr_in(a, root), emit_swift_numeric_expr_in(b, root))),
+        Gte(a, b) => bool_to_num(&format!("{} >= {}", emit_swift_numeric_expr_in(a, root), emit_swift_numeric_expr_in(b, root))),
+        Lt(a, b) => bool_to_num(&format!("{} < {}", emit_swift_numeric_expr_in(a, root), emit_swift_numeric_expr_in(b, root))),
+        Lte(a, b) => bool_to_num(&format!("{} <= {}", emit_swift_numeric_expr_in(a, root), emit_swift_numeric_expr_in(b, root))),
+        Equals(a, b) => bool_to_num(&format!("abs({} - {}) < 1e-9", emit_swift_numeric_expr_in(a, root), emit_swift_numeric_expr_in(b, root))),
+        NotEquals(a, b) => bool_to_num(&format!("abs({} - {}) >= 1e-9", emit_swift_numeric_expr_in(a, root), emit_swift_numeric_expr_in(b, root))),
+        And(a, b) => bool_to_num(&format!("{} != 0.0 && {} != 0.0", emit_swift_numeric_expr_in(a, root), emit_swift_numeric_expr_in(b, root))),
+        Or(a, b) => bool_to_num(&format!("{} != 0.0 || {} != 0.0", emit_swift_numeric_expr_in(a, root), emit_swift_numeric_expr_in(b, root))),
+        Not(a) => bool_to_num(&format!("{} == 0.0", emit_swift_numeric_expr_in(a, root))),
+
+        ActivationRecord(name, args) => {
+            let args_s: Vec<String> = args.iter().map(|a| emit_swift_numeric_expr_in(a, root)).collect();
+            match name.as_str() {
+                "sqrt" => format!("({}).squareRoot()", args_s[0]),
+                "min" => format!("Swift.min({}, {})", args_s[0], args_s[1]),
+                "max" => format!("Swift.max({}, {})", args_s[0], args_s[1]),
+                "abs" => format!("Swift.abs({})", args_s[0]),
+                "floor" => format!("({}).rounded(.down)", args_s[0]),
+                "ceil" => format!("({}).rounded(.up)", args_s[0]),
+                "round" => format!("({}).rounded()", args_s[0]),
+                "log2" => format!("log2({})", args_s[0]),
+                "clamp" => format!("Swift.min(Swift.max({}, {}), {})", args_s[0], args_s[1], args_s[2]),
+                "pow" => format!("pow({}, {})", args_s[0], args_s[1]),
+                "popcount" => format!("Double(Int64({}).nonzeroBitCount)", args_s[0]),
+                "bswap16" => format!("Double(UInt16(truncatingIfNeeded: Int64({})).byteSwapped)", args_s[0]),
+                "bswap32" => format!("Double(UInt32(truncatingIfNeeded: Int64({})).byteSwapped)", args_s[0]),
+                "bswap64" => format!("Double(UInt64(bitPattern: Int64({})).byteSwapped)", args_s[0]),
+                // an `extern fn` taking Doubles
+                _ => format!("Double({}({}))", name, args_s.join(", ")),
+            }
+        }
+
+        GuardExpression(c, t, f) => format!(
+            "({} != 0.0 ? {} : {})",
+            emit_swift_numeric_expr_in(c, root),
+            emit_swift_numeric_expr_in(t, root),
+            emit_swift_numeric_expr_in(f, root)
+        ),
+
+        AggregateSum(_) | AggregateProduct(_) => "0.0".into(),
+
+        NoExpr => "0.0".into(),
+    }
+}
+
+/// Renders DSL `///` docs as Swift `///` documentation comments.
+fn doc_comment(doc: &Option<String>, pad: &str) -> String {
+    let Some(doc) = doc else {
+        return String::new();
+    };
+    doc.lines()
+        .map(|l| if l.is_empty() { format!("{pad}///\n") } else { format!("{pad}/// {l}\n") })
+        .collect()
+}
+
+fn bool_to_num(cond: &str) -> String {
+    format!("({cond} ? 1.0 : 0.0)")
+}
+
+fn indent(s: &str, tabs: usize) -> String {
+    let pad = "    ".repeat(tabs);
+    s.lines()
+        .map(|l| {
+            if l.is_empty() {
+                "\n".to_string()
+            } else {
+                format!("{pad}{l}\n")
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::load_source;
+
+    fn swift(src: &str) -> String {
+        SwiftGenerator::generate(&load_source(src))
+    }
+
+    #[test]
+    fn structs_encode_and_decode_their_fields() {
+        let out = swift("packet Hello be { kind uint8, size uint16, body bytes[size], calc total uint32 = size + 3 }");
+        assert!(out.contains("public struct Hello: PacketCodable {\n    public var kind: UInt8\n    public var size: UInt16\n    public var body: Data\n"));
+        assert!(out.contains("public var total: UInt32 { UInt32(truncatingIfNeeded: Int64((Double(size) + Double(3)))) }"));
+        assert!(out.contains("        data.appendInteger(size, bigEndian: true)\n        data.append(contentsOf: body)\n"));
+        assert!(out.contains("let size = try reader.readInteger(\"size\", as: UInt16.self, bigEndian: true)"));
+        assert!(out.contains("let body = try reader.readBytes(\"body\", count: Int(Double(size)))"));
+        assert!(out.contains("return Hello(kind: kind, size: size, body: body)"));
+    }
+
+    #[test]
+    fn little_endian_is_the_default() {
+        let out = swift("packet Le { a uint32 }");
+        assert!(out.contains("data.appendInteger(a, bigEndian: false)"));
+        assert!(out.contains("try reader.readInteger(\"a\", as: UInt32.self, bigEndian: false)"));
+    }
+
+    #[test]
+    fn shift_counts_use_the_low_six_bits() {
+        let out = swift("packet Bits { n uint8, hi bytes[1 << n] }");
+        assert!(out.contains("Int64(Double(1)) << (Int64(Double(n)) & 63)"));
+    }
+
+    #[test]
+    fn strings_that_do_not_fit_are_rejected() {
+        let out = swift("packet Names be { tag string[4], short pstring<uint8>, long pstring<uint16> }");
+        assert!(out.contains("data.append(padded(\"tag\", encodeString(tag, .utf8), to: Int(Double(4))))"));
+        assert!(out.contains("precondition(shortBytes.count <= UInt8.max, \"short: string longer than \\(UInt8.max) bytes\")"));
+        assert!(out.contains("data.appendInteger(UInt16(longBytes.count), bigEndian: true)"));
+        assert!(!out.contains("truncatingIfNeeded: longBytes"));
+    }
+
+    #[test]
+    fn bad_magic_throws() {
+        let out = swift("packet Flap be { start uint8 = 0x2a, v uint16 }");
+        assert!(out.contains("    public static let start: UInt8 = 0x2a\n"));
+        assert!(out.contains(
+            "throw PacketDecodingError.wrongConstant(field: \"start\", expected: \"0x2a\", found: \"0x\" + String(start, radix: 16))"
+        ));
+    }
+}
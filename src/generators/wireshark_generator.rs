@@ -0,0 +1,914 @@
+use crate::models::parsing_models::{
+    BitSlot, Check, Endianness, ExprNode, Leftover, PacketExpr, PacketExprList, Region, RepeatBound, StringEncoding,
+    TimeEncoding, TypeNode, VarIntEncoding,
+};
+use std::fmt::Write as _;
+
+pub struct WiresharkGenerator {}
+
+impl WiresharkGenerator {
+    pub fn generate(model: &PacketExprList) -> String {
+        let mut out = String::new();
+        out.push_str(&Self::create_headers());
+        out.push_str(&Self::create_spacer());
+        if model.packets.iter().flat_map(|p| &p.fields).any(|f| matches!(f.expr, TypeNode::VarInt(_, _))) {
+            out.push_str(VARINT_HELPERS);
+            out.push_str(&Self::create_spacer());
+        }
+        if model.packets.iter().any(|p| !p.checksums().is_empty()) {
+            out.push_str(CHECKSUM_HELPERS);
+            out.push_str(&Self::create_spacer());
+        }
+        let bit_call = |name: &str| matches!(name, "popcount" | "bswap16" | "bswap32" | "bswap64");
+        if model.packets.iter().flat_map(|p| p.exprs()).any(|(e, _)| e.calls().iter().any(|(name, _)| bit_call(name))) {
+            out.push_str(BIT_HELPERS);
+            out.push_str(&Self::create_spacer());
+        }
+
+        // Repeated packets are dissected by their own proto, which may be
+        // defined further down; declare those up front.
+        let mut repeated: Vec<&str> = Vec::new();
+        for f in model.packets.iter().flat_map(|p| &p.fields) {
+            if let TypeNode::Repeat(elem, _) = &f.expr
+                && !repeated.contains(&elem.as_str())
+            {
+                repeated.push(elem);
+            }
+        }
+        for elem in &repeated {
+            let _ = writeln!(&mut out, "local {}_proto", elem.to_lowercase());
+        }
+        if !repeated.is_empty() {
+            out.push_str(&Self::create_spacer());
+        }
+
+        for pkt in &model.packets {
+            out.push_str(&Self::build_dissector(pkt, repeated.contains(&pkt.name.as_str())));
+            out.push_str(&Self::create_spacer());
+        }
+        out
+    }
+
+    fn create_spacer() -> String {
+        "\n".to_string()
+    }
+
+    fn create_headers() -> String {
+        // Each packet becomes its own Proto; register them against a port or
+        // heuristic table in your init.lua, e.g.
+        //   DissectorTable.get("tcp.port"):add(5190, flap_proto)
+        "\
+-- Generated Wireshark Lua dissectors
+-- Load with: wireshark -X lua_script:packets.lua
+"
+        .to_string()
+    }
+
+    fn build_dissector(pkt: &PacketExpr, forward_declared: bool) -> String {
+        let abbrev = pkt.name.to_lowercase();
+        let proto = format!("{abbrev}_proto");
+        let fields = format!("{abbrev}_fields");
+
+        // 1) ProtoField declarations
+        let mut field_decls = String::new();
+        let mut field_list = Vec::<String>::new();
+        let slots = pkt.bit_slots();
+        for (f, slot) in pkt.fields.iter().zip(&slots) {
+            // elements carry their own fields; the list is only a subtree.
+            // Filler is skipped over and has nothing to show.
+            if matches!(f.expr, TypeNode::Repeat(_, _)) || f.is_filler() {
+                continue;
+            }
+            let decl = match slot {
+                Some(slot) => bit_field_decl(&abbrev, &f.id, slot),
+                None => proto_field_decl(&abbrev, &f.id, &f.expr),
+            };
+            field_decls.push_str(&lua_doc(&f.doc, "    "));
+            let _ = writeln!(&mut field_decls, "    {id} = {decl},", id = f.id);
+            field_list.push(format!("{fields}.{}", f.id));
+        }
+        for cf in &pkt.calculated_fields {
+            field_decls.push_str(&lua_doc(&cf.doc, "    "));
+            let _ = writeln!(
+                &mut field_decls,
+                "    {id} = {decl},",
+                id = cf.name,
+                decl = calc_field_decl(&abbrev, &cf.name, &cf.data_type)
+            );
+            field_list.push(format!("{fields}.{}", cf.name));
+        }
+
+        // 2) dissector body
+        let mut body = String::new();
+        let marks = pkt.checksum_marks();
+        for (i, (f, slot)) in pkt.fields.iter().zip(&slots).enumerate() {
+            for (k, r) in pkt.regions_opening(i) {
+                body.push_str(&region_open(k, r));
+            }
+            let (start, end) = marks[i];
+            if start {
+                let _ = writeln!(&mut body, "local {}_start = offset", f.id);
+            }
+            if f.checksum().is_some() {
+                let _ = writeln!(&mut body, "local {}_at = offset", f.id);
+            }
+            if let Some(slot) = slot {
+                body.push_str(&dissect_bit(&fields, &f.id, slot));
+            } else if let Some(cond) = &f.condition {
+                // an optional field is dissected, and its rule checked, only when present
+                let endian = f.endianness.as_ref().or(pkt.endianness.as_ref()).unwrap_or(&Endianness::Le);
+                let mut inner = dissect_snippet(&fields, &f.id, &f.expr, endian.clone(), pkt.is_last_in_scope(i));
+                if let Some(check) = &f.constraint {
+                    inner.push_str(&check_snippet(check));
+                }
+                let _ = writeln!(&mut body, "if ({}) ~= 0 then", emit_lua_expr(cond));
+                body.push_str(&indent(&inner, 1));
+                let _ = writeln!(&mut body, "end");
+            } else {
+                let endian = f.endianness.as_ref().or(pkt.endianness.as_ref()).unwrap_or(&Endianness::Le);
+                body.push_str(&dissect_snippet(&fields, &f.id, &f.expr, endian.clone(), pkt.is_last_in_scope(i)));
+            }
+            if end {
+                let _ = writeln!(&mut body, "local {}_end = offset", f.id);
+            }
+            if let Some(value) = f.const_literal() {
+                let _ = writeln!(&mut body, "if {} ~= {value} then", f.id);
+                let _ = writeln!(
+                    &mut body,
+                    "    subtree:add_expert_info(PI_MALFORMED, PI_ERROR, \"{}: expected {value}\")",
+                    f.id
+                );
+                let _ = writeln!(&mut body, "end");
+            }
+            if let Some(check) = f.constraint.as_ref().filter(|_| f.condition.is_none()) {
+                body.push_str(&check_snippet(check));
+            }
+            for (k, r) in pkt.regions_closing(i) {
+                body.push_str(&region_close(pkt, k, r));
+            }
+        }
+        // checksums are recomputed with their own field read as zero, as when they were filled in
+        for f in &pkt.fields {
+            let Some(sum) = f.checksum() else { continue };
+            let _ = writeln!(
+                &mut body,
+                "local {id}_sum = sum_{}(buffer, {}_start, {}_end, {id}_at, {})",
+                sum.kind.name(),
+                sum.first,
+                sum.last,
+                sum.kind.width_bytes(),
+                id = f.id
+            );
+            let _ = writeln!(&mut body, "if {id} ~= {id}_sum then", id = f.id);
+            let _ = writeln!(
+                &mut body,
+                "    subtree:add_expert_info(PI_CHECKSUM, PI_ERROR, string.format(\"{id}: the {} of {}..{} is 0x%x\", {id}_sum))",
+                sum.kind.name(),
+                sum.first,
+                sum.last,
+                id = f.id
+            );
+            let _ = writeln!(&mut body, "end");
+        }
+        for check in &pkt.asserts {
+            body.push_str(&check_snippet(check));
+        }
+
+        // values handed back to a packet repeating this one; raw byte fields have no local
+        let values: Vec<String> = pkt
+            .fields
+            .iter()
+            .filter(|f| !matches!(f.expr, TypeNode::Bytes(_) | TypeNode::MacAddress(_)) && !f.is_filler())
+            .map(|f| f.id.clone())
+            .chain(pkt.calculated_fields.iter().map(|cf| cf.name.clone()))
+            .map(|id| format!("{id} = {id}"))
+            .collect();
+
+        // 3) calculated fields → generated items
+        let mut calc_body = String::new();
+        for cf in &pkt.calculated_fields {
+            let _ = writeln!(
+                &mut calc_body,
+                "local {name} = {expr}",
+                name = cf.name,
+                expr = emit_lua_expr(&cf.expr)
+            );
+            let _ = writeln!(
+                &mut calc_body,
+                "subtree:add({fields}.{name}, {value}):set_generated()",
+                name = cf.name,
+                value = calc_value(&cf.data_type, &cf.name)
+            );
+        }
+
+        format!(
+            r#"-- {name}
+{doc}{local}{proto} = Proto("{abbrev}", "{name}")
+local {fields} = {{
+{field_decls}}}
+{proto}.fields = {{ {field_list} }}
+
+function {proto}.dissector(buffer, pinfo, tree)
+    pinfo.cols.protocol = "{name}"
+    local subtree = tree:add({proto}, buffer(), "{name}")
+    local offset = 0
+
+{body}
+{calc_body}
+    subtree:set_len(offset)
+    return offset, {{ {values} }}
+end
+"#,
+            name = pkt.name,
+            doc = lua_doc(&pkt.doc, ""),
+            local = if forward_declared { "" } else { "local " },
+            abbrev = abbrev,
+            proto = proto,
+            fields = fields,
+            field_decls = field_decls,
+            field_list = field_list.join(", "),
+            body = indent(&body, 1),
+            calc_body = indent(&calc_body, 1),
+            values = values.join(", "),
+        )
+    }
+}
+
+/// Flags a broken `where` or `assert` rule as malformed; dissection goes on.
+fn check_snippet(check: &Check) -> String {
+    format!(
+        "if ({}) == 0 then\n    subtree:add_expert_info(PI_MALFORMED, PI_ERROR, \"{}: breaks `{}`\")\nend\n",
+        emit_lua_expr(&check.cond),
+        check.field,
+        lua_escape(&check.rule)
+    )
+}
+
+/// Varint readers, emitted only when a packet has a varint field. Each
+/// returns the value as a UInt64 and its length in bytes.
+const VARINT_HELPERS: &str = r#"local function read_varuint(buffer, offset)
+    local v = UInt64(0)
+    for i = 0, 9 do
+        local b = buffer(offset + i, 1):uint()
+        v = v + UInt64(bit.band(b, 0x7F)):lshift(7 * i)
+        if b < 0x80 then
+            return v, i + 1
+        end
+    end
+    error("varint longer than 10 bytes")
+end
+
+-- RFC 9000: the top two bits of the first byte give the length
+local function read_quicvarint(buffer, offset)
+    local first = buffer(offset, 1):uint()
+    local n = bit.lshift(1, bit.rshift(first, 6))
+    local v = UInt64(bit.band(first, 0x3F))
+    for i = 1, n - 1 do
+        v = v:lshift(8) + buffer(offset + i, 1):uint()
+    end
+    return v, n
+end
+
+-- (u >> 1) ^ -(u & 1), as an Int64
+local function unzigzag(u)
+    local half = Int64(u:rshift(1))
+    if u:band(1):tonumber() == 1 then
+        return -half - 1
+    end
+    return half
+end
+"#;
+
+/// Bit intrinsics, emitted only when a packet calls one. LuaBitOp works on
+/// 32 bits, so these use arithmetic, exact below 2^53 like any Lua number.
+const BIT_HELPERS: &str = r#"local function int_popcount(x)
+    local n = 0
+    x = math.floor(x) % 18446744073709551616
+    while x > 0 do
+        n = n + x % 2
+        x = math.floor(x / 2)
+    end
+    return n
+end
+
+local function int_bswap(x, width)
+    local out = 0
+    x = math.floor(x)
+    for _ = 1, width do
+        out = out * 256 + x % 256
+        x = math.floor(x / 256)
+    end
+    return out
+end
+"#;
+
+/// Checksum intrinsics, emitted only when a packet uses one. Each covers
+/// bytes [start, stop) of the buffer with the `hole_len` bytes at `hole` read
+/// as zero, and returns a non-negative number.
+const CHECKSUM_HELPERS: &str = r#"local function sum_byte(buffer, i, hole, hole_len)
+    if i >= hole and i < hole + hole_len then
+        return 0
+    end
+    return buffer(i, 1):uint()
+end
+
+-- CRC-32 (IEEE 802.3)
+local function sum_crc32(buffer, start, stop, hole, hole_len)
+    local crc = 0xFFFFFFFF
+    for i = start, stop - 1 do
+        crc = bit.bxor(crc, sum_byte(buffer, i, hole, hole_len))
+        for _ = 1, 8 do
+            if bit.band(crc, 1) ~= 0 then
+                crc = bit.bxor(bit.rshift(crc, 1), 0xEDB88320)
+            else
+                crc = bit.rshift(crc, 1)
+            end
+        end
+    end
+    return bit.bnot(crc) % 0x100000000
+end
+
+-- CRC-16/CCITT-FALSE
+local function sum_crc16_ccitt(buffer, start, stop, hole, hole_len)
+    local crc = 0xFFFF
+    for i = start, stop - 1 do
+        crc = bit.bxor(crc, bit.lshift(sum_byte(buffer, i, hole, hole_len), 8))
+        for _ = 1, 8 do
+            if bit.band(crc, 0x8000) ~= 0 then
+                crc = bit.band(bit.bxor(bit.lshift(crc, 1), 0x1021), 0xFFFF)
+            else
+                crc = bit.band(bit.lshift(crc, 1), 0xFFFF)
+            end
+        end
+    end
+    return crc
+end
+
+-- RFC 1071 Internet checksum; an odd last byte is padded with zero
+local function sum_inet_checksum(buffer, start, stop, hole, hole_len)
+    local sum = 0
+    for i = start, stop - 1, 2 do
+        sum = sum + sum_byte(buffer, i, hole, hole_len) * 256
+        if i + 1 < stop then
+            sum = sum + sum_byte(buffer, i + 1, hole, hole_len)
+        end
+    end
+    while sum > 0xFFFF do
+        sum = sum % 0x10000 + math.floor(sum / 0x10000)
+    end
+    return 0xFFFF - sum
+end
+
+-- Adler-32 (RFC 1950)
+local function sum_adler32(buffer, start, stop, hole, hole_len)
+    local a, b = 1, 0
+    for i = start, stop - 1 do
+        a = (a + sum_byte(buffer, i, hole, hole_len)) % 65521
+        b = (b + a) % 65521
+    end
+    return b * 65536 + a
+end
+"#;
+
+/* ============================================================
+ * ProtoField mapping
+ * ============================================================
+*/
+
+fn proto_field_decl(abbrev: &str, id: &str, t: &TypeNode) -> String {
+    use TypeNode::*;
+    let filter = format!("{abbrev}.{id}");
+    // Flag words read better in hex; everything else stays decimal.
+    let base = if id.to_lowercase().contains("flag") {
+        "base.HEX"
+    } else {
+        "base.DEC"
+    };
+
+    match t {
+        UnsignedInteger8(_) => format!("ProtoField.uint8(\"{filter}\", \"{id}\", {base})"),
+        Integer8(_) => format!("ProtoField.int8(\"{filter}\", \"{id}\", base.DEC)"),
+        UnsignedInteger16(_) => format!("ProtoField.uint16(\"{filter}\", \"{id}\", {base})"),
+        Integer16(_) => format!("ProtoField.int16(\"{filter}\", \"{id}\", base.DEC)"),
+        UnsignedInteger32(_) => format!("ProtoField.uint32(\"{filter}\", \"{id}\", {base})"),
+        Integer32(_) => format!("ProtoField.int32(\"{filter}\", \"{id}\", base.DEC)"),
+        UnsignedInteger64(_) => format!("ProtoField.uint64(\"{filter}\", \"{id}\", {base})"),
+        Integer64(_) => format!("ProtoField.int64(\"{filter}\", \"{id}\", base.DEC)"),
+        // Wireshark has no FILETIME encoding; show the raw tick count
+        DateTime(_, TimeEncoding::FileTime) => format!("ProtoField.uint64(\"{filter}\", \"{id}\", base.DEC)"),
+        DateTime(_, _) => format!("ProtoField.absolute_time(\"{filter}\", \"{id}\", base.UTC)"),
+        Float32(_) => format!("ProtoField.float(\"{filter}\", \"{id}\")"),
+        Float64(_) => format!("ProtoField.double(\"{filter}\", \"{id}\")"),
+        Bool(_, _) => format!("ProtoField.bool(\"{filter}\", \"{id}\")"),
+        // shown as the scaled value; the raw integer is still in the bytes pane
+        Fixed(_, _, _) => format!("ProtoField.double(\"{filter}\", \"{id}\")"),
+        VarInt(_, VarIntEncoding::ZigZag) => format!("ProtoField.int64(\"{filter}\", \"{id}\", base.DEC)"),
+        VarInt(_, _) => format!("ProtoField.uint64(\"{filter}\", \"{id}\", {base})"),
+        MacAddress(None) => format!("ProtoField.ether(\"{filter}\", \"{id}\")"),
+        MacAddress(Some(_)) | Bytes(_) => format!("ProtoField.bytes(\"{filter}\", \"{id}\")"),
+        Ipv4Address(_) => format!("ProtoField.ipv4(\"{filter}\", \"{id}\")"),
+        Ipv6Address(_) => format!("ProtoField.ipv6(\"{filter}\", \"{id}\")"),
+        Port(_) => format!("ProtoField.uint16(\"{filter}\", \"{id}\", base.DEC)"),
+        FixedString(_, _) | PString(_, _) => format!("ProtoField.string(\"{filter}\", \"{id}\")"),
+        CString(_) => format!("ProtoField.stringz(\"{filter}\", \"{id}\")"),
+        Repeat(_, _) => unreachable!("repeat fields have no ProtoField of their own"),
+        Pad(_) | Align(_) => unreachable!("filler has no ProtoField"),
+    }
+}
+
+/// A `bool(bit)` field is a one-bit mask over its shared byte.
+fn bit_field_decl(abbrev: &str, id: &str, slot: &BitSlot) -> String {
+    format!(
+        "ProtoField.bool(\"{abbrev}.{id}\", \"{id}\", 8, nil, 0x{:02X})",
+        1u8 << slot.shift()
+    )
+}
+
+fn calc_field_decl(abbrev: &str, id: &str, type_name: &str) -> String {
+    let filter = format!("{abbrev}.{id}");
+    let ctor = match type_name {
+        "int8" | "uint8" | "int16" | "uint16" | "int32" | "uint32" | "int64" | "uint64" => type_name,
+        "datetime" => "int64",
+        "float32" => "float",
+        _ => "double",
+    };
+    if ctor == "float" || ctor == "double" {
+        format!("ProtoField.{ctor}(\"{filter}\", \"{id}\")")
+    } else {
+        format!("ProtoField.{ctor}(\"{filter}\", \"{id}\", base.DEC)")
+    }
+}
+
+/// Lua value added to the tree for a calculated field of the given type.
+fn calc_value(type_name: &str, name: &str) -> String {
+    match type_name {
+        "int64" | "datetime" => format!("Int64(math.floor({name}))"),
+        "uint64" => format!("UInt64(math.floor({name}))"),
+        "float32" | "float64" => name.to_string(),
+        _ => format!("math.floor({name})"),
+    }
+}
+
+/// Returns (reader method, width) for one scalar element.
+fn scalar_reader(t: &TypeNode, endian: &Endianness) -> (String, usize) {
+    use TypeNode::*;
+    let (method, w) = match t {
+        UnsignedInteger8(_) | Bytes(_) | MacAddress(_) | FixedString(_, _) | CString(_) => ("uint", 1),
+        PString(_, _) => ("uint", t.scalar_width_bytes()), // the length prefix
+        Integer8(_) => ("int", 1),
+        Bool(_, _) => ("uint", 1),
+        Fixed(_, base, _) => match (base.is_signed(), base.width_bytes()) {
+            (true, 8) => ("int64", 8),
+            (false, 8) => ("uint64", 8),
+            (true, w) => ("int", w),
+            (false, w) => ("uint", w),
+        },
+        UnsignedInteger16(_) | Port(_) => ("uint", 2),
+        Integer16(_) => ("int", 2),
+        UnsignedInteger32(_) => ("uint", 4),
+        Integer32(_) => ("int", 4),
+        UnsignedInteger64(_) => ("uint64", 8),
+        Integer64(_) => ("int64", 8),
+        DateTime(_, unit) => match unit {
+            TimeEncoding::Ntp64 | TimeEncoding::FileTime => ("uint64", 8),
+            _ => ("int64", 8),
+        },
+        Float32(_) => ("float", 4),
+        Float64(_) => ("float", 8),
+        Ipv4Address(_) => ("ipv4", 4),
+        Ipv6Address(_) => ("bytes", 16), // no ipv6 accessor on TvbRange
+        VarInt(_, _) => unreachable!("varints are read by read_varuint/read_quicvarint"),
+        Repeat(_, _) => unreachable!("repeated packets are read by their own dissector"),
+        Pad(_) | Align(_) => unreachable!("filler is skipped, not read"),
+    };
+    let prefix = if *endian == Endianness::Le && w > 1 { "le_" } else { "" };
+    let suffix = if w == 8 && method != "float" { ":tonumber()" } else { "" };
+    (format!("{prefix}{method}(){suffix}"), w)
+}
+
+fn is_array_like(t: &TypeNode) -> bool {
+    use TypeNode::*;
+    match t {
+        Bytes(_) | MacAddress(_) => true,
+        FixedString(_, _) | CString(_) | PString(_, _) | Repeat(_, _) | Pad(_) | Align(_) => false,
+        UnsignedInteger8(len)
+        | Integer8(len)
+        | UnsignedInteger16(len)
+        | Integer16(len)
+        | UnsignedInteger32(len)
+        | Integer32(len)
+        | UnsignedInteger64(len)
+        | Integer64(len)
+        | Float32(len)
+        | Float64(len)
+        | DateTime(len, _)
+        | Bool(len, _)
+        | Fixed(len, _, _)
+        | VarInt(len, _)
+        | Ipv4Address(len)
+        | Ipv6Address(len)
+        | Port(len) => len.is_some(),
+    }
+}
+
+fn type_len_expr(t: &TypeNode) -> Option<&ExprNode> {
+    use TypeNode::*;
+    match t {
+        UnsignedInteger8(e) | Integer8(e) | UnsignedInteger16(e) | Integer16(e)
+        | UnsignedInteger32(e) | Integer32(e) | UnsignedInteger64(e) | Integer64(e)
+        | Float32(e) | Float64(e) | DateTime(e, _) | Bytes(e) | MacAddress(e) => e.as_ref(),
+        Bool(e, _) | Fixed(e, _, _) | VarInt(e, _) => e.as_ref(),
+        Ipv4Address(e) | Ipv6Address(e) | Port(e) => e.as_ref(),
+        FixedString(e, _) => Some(e),
+        CString(_) | PString(_, _) | Pad(_) | Align(_) => None,
+        Repeat(_, RepeatBound::Count(e)) => Some(e),
+        Repeat(_, _) => None,
+    }
+}
+
+/// `ENC_TIME_*` encoding of a datetime field, or None when it is shown as a plain integer.
+fn lua_time_encoding(unit: &TimeEncoding) -> Option<&'static str> {
+    match unit {
+        TimeEncoding::UnixSeconds => Some("ENC_TIME_SECS"),
+        TimeEncoding::UnixMillis => Some("ENC_TIME_MSECS"),
+        TimeEncoding::UnixMicros => Some("ENC_TIME_USECS"),
+        TimeEncoding::Ntp64 => Some("ENC_TIME_NTP"),
+        TimeEncoding::FileTime => None,
+    }
+}
+
+fn lua_encoding(e: &StringEncoding) -> &'static str {
+    match e {
+        StringEncoding::Ascii => "ENC_ASCII",
+        StringEncoding::Utf8 => "ENC_UTF_8",
+        StringEncoding::Utf16Le => "ENC_UTF_16 + ENC_LITTLE_ENDIAN",
+    }
+}
+
+/* ============================================================
+ * Dissector snippets
+ * ============================================================
+*/
+
+/// Bit fields are added over their shared byte (the ProtoField mask picks the
+/// bit); the local holds 0 or 1 and `offset` moves after the byte's last bit.
+/// Narrows `buffer` to the region so reads past its end raise, keeping
+/// offsets relative to the packet start.
+fn region_open(k: usize, r: &Region) -> String {
+    let mut s = String::new();
+    let _ = writeln!(&mut s, "local end{k} = offset + math.floor({})", emit_lua_expr(&r.length));
+    let _ = writeln!(&mut s, "local outer{k} = buffer");
+    let _ = writeln!(&mut s, "buffer = buffer(0, end{k}):tvb()");
+    s
+}
+
+fn region_close(pkt: &PacketExpr, k: usize, r: &Region) -> String {
+    let mut s = String::new();
+    if r.leftover == Leftover::Error {
+        let first = &pkt.fields[r.first].id;
+        let _ = writeln!(&mut s, "if offset < end{k} then");
+        let _ = writeln!(
+            &mut s,
+            "    subtree:add_expert_info(PI_MALFORMED, PI_ERROR, \"{first}: \" .. (end{k} - offset) .. \" bytes left unread\")"
+        );
+        let _ = writeln!(&mut s, "end");
+    }
+    let _ = writeln!(&mut s, "offset = end{k}");
+    let _ = writeln!(&mut s, "buffer = outer{k}");
+    s
+}
+
+fn dissect_bit(fields: &str, name: &str, slot: &BitSlot) -> String {
+    let mut s = String::new();
+    let _ = writeln!(
+        &mut s,
+        "local {name} = bit.band(bit.rshift(buffer(offset, 1):uint(), {}), 1)",
+        slot.shift()
+    );
+    let _ = writeln!(&mut s, "subtree:add({fields}.{name}, buffer(offset, 1))");
+    if slot.ends_byte() {
+        let _ = writeln!(&mut s, "offset = offset + 1");
+    }
+    s
+}
+
+fn dissect_snippet(fields: &str, name: &str, t: &TypeNode, endian: Endianness, is_last: bool) -> String {
+    let mut s = String::new();
+    let add = if endian == Endianness::Le { "add_le" } else { "add" };
+
+    match t {
+        // the dissector's offset counts from the start of this packet
+        TypeNode::Pad(n) => {
+            let _ = writeln!(&mut s, "offset = offset + {n} -- {name}");
+        }
+        TypeNode::Align(n) => {
+            let _ = writeln!(&mut s, "offset = offset + ({n} - offset % {n}) % {n} -- {name}");
+        }
+        TypeNode::Bytes(_) | TypeNode::MacAddress(_) => {
+            let count = match (type_len_expr(t), t) {
+                (Some(expr), _) => format!("math.floor({})", emit_lua_expr(expr)),
+                (None, TypeNode::MacAddress(_)) => "6".to_string(),
+                (None, _) if is_last => "buffer:len() - offset".to_string(),
+                (None, _) => {
+                    let _ = writeln!(&mut s, "-- {name}: open-ended bytes not at end; skipped");
+                    return s;
+                }
+            };
+            let _ = writeln!(&mut s, "local {name}_len = {count}");
+            let _ = writeln!(&mut s, "subtree:add({fields}.{name}, buffer(offset, {name}_len))");
+            let _ = writeln!(&mut s, "offset = offset + {name}_len");
+        }
+        // Wireshark decodes the text itself; invalid sequences show as replacement characters.
+        TypeNode::FixedString(len, enc) => {
+            let enc = lua_encoding(enc);
+            let _ = writeln!(&mut s, "local {name}_len = math.floor({})", emit_lua_expr(len));
+            let _ = writeln!(&mut s, "local {name} = buffer(offset, {name}_len):stringz({enc})");
+            let _ = writeln!(&mut s, "subtree:add_packet_field({fields}.{name}, buffer(offset, {name}_len), {enc})");
+            let _ = writeln!(&mut s, "offset = offset + {name}_len");
+        }
+        TypeNode::CString(enc) => {
+            let enc = lua_encoding(enc);
+            let _ = writeln!(&mut s, "local {name}_len = buffer(offset):strsize({enc}) -- includes the NUL");
+            let _ = writeln!(&mut s, "local {name} = buffer(offset, {name}_len):stringz({enc})");
+            let _ = writeln!(&mut s, "subtree:add_packet_field({fields}.{name}, buffer(offset, {name}_len), {enc})");
+            let _ = writeln!(&mut s, "offset = offset + {name}_len");
+        }
+        TypeNode::PString(_, enc) => {
+            let enc = lua_encoding(enc);
+            let (reader, w) = scalar_reader(t, &endian);
+            let _ = writeln!(&mut s, "local {name}_len = buffer(offset, {w}):{reader}");
+            let _ = writeln!(&mut s, "offset = offset + {w}");
+            let _ = writeln!(&mut s, "local {name} = buffer(offset, {name}_len):string({enc})");
+            let _ = writeln!(&mut s, "subtree:add_packet_field({fields}.{name}, buffer(offset, {name}_len), {enc})");
+            let _ = writeln!(&mut s, "offset = offset + {name}_len");
+        }
+        // each element is dissected by its own proto into a subtree, which
+        // hands back the bytes it used and a table of its field values
+        TypeNode::Repeat(elem, bound) => {
+            let elem_proto = format!("{}_proto", elem.to_lowercase());
+            let mut one = String::new();
+            let _ = writeln!(&mut one, "local used, item = {elem_proto}.dissector(buffer(offset):tvb(), pinfo, {name}_tree)");
+            let _ = writeln!(&mut one, "offset = offset + used");
+            let _ = writeln!(&mut one, "{name}[#{name} + 1] = item");
+            let _ = writeln!(&mut s, "local {name} = {{}}");
+            let _ = writeln!(&mut s, "local {name}_start = offset");
+            let _ = writeln!(&mut s, "local {name}_tree = subtree:add(buffer(offset), \"{name}\")");
+            match bound {
+                RepeatBound::Eof => {
+                    let _ = writeln!(&mut s, "while offset < buffer:len() do");
+                }
+                RepeatBound::Until(cond) => {
+                    let _ = writeln!(&mut one, "if ({}) ~= 0 then break end", emit_lua_expr_in(cond, "item."));
+                    let _ = writeln!(&mut s, "while true do");
+                }
+                RepeatBound::Count(count) => {
+                    let _ = writeln!(&mut s, "for _ = 1, math.floor({}) do", emit_lua_expr(count));
+                }
+            }
+            s.push_str(&indent(&one, 1));
+            let _ = writeln!(&mut s, "end");
+            let _ = writeln!(&mut s, "{name}_tree:set_len(offset - {name}_start)");
+        }
+        // the tree gets the 64-bit value; the local is a plain number like other 8-byte fields
+        TypeNode::VarInt(len, enc) => {
+            let read = match enc {
+                VarIntEncoding::Quic => "read_quicvarint(buffer, offset)",
+                _ => "read_varuint(buffer, offset)",
+            };
+            let mut one = String::new();
+            let _ = writeln!(&mut one, "local v, n = {read}");
+            if *enc == VarIntEncoding::ZigZag {
+                let _ = writeln!(&mut one, "v = unzigzag(v)");
+            }
+            let _ = writeln!(&mut one, "subtree:add({fields}.{name}, buffer(offset, n), v)");
+            let _ = writeln!(&mut one, "offset = offset + n");
+            match len {
+                Some(expr) => {
+                    let _ = writeln!(&mut s, "local {name} = {{}}");
+                    let _ = writeln!(&mut s, "for i = 1, math.floor({}) do", emit_lua_expr(expr));
+                    s.push_str(&indent(&one, 1));
+                    let _ = writeln!(&mut s, "    {name}[i] = v:tonumber()");
+                    let _ = writeln!(&mut s, "end");
+                }
+                None => {
+                    let _ = writeln!(&mut s, "local {name}");
+                    let _ = writeln!(&mut s, "do");
+                    s.push_str(&indent(&one, 1));
+                    let _ = writeln!(&mut s, "    {name} = v:tonumber()");
+                    let _ = writeln!(&mut s, "end");
+                }
+            }
+        }
+        _ => {
+            let (mut reader, w) = scalar_reader(t, &endian);
+            if let TypeNode::Fixed(_, _, frac) = t {
+                reader = format!("{reader} / {}", 1u128 << frac);
+            }
+            let tree_add = match t {
+                TypeNode::DateTime(_, unit) => match lua_time_encoding(unit) {
+                    Some(enc) => {
+                        let order = if endian == Endianness::Le { "ENC_LITTLE_ENDIAN" } else { "ENC_BIG_ENDIAN" };
+                        format!("subtree:add_packet_field({fields}.{name}, buffer(offset, {w}), {enc} + {order})")
+                    }
+                    None => format!("subtree:{add}({fields}.{name}, buffer(offset, {w}))"),
+                },
+                TypeNode::Fixed(_, _, _) if is_array_like(t) => {
+                    format!("subtree:{add}({fields}.{name}, buffer(offset, {w}), {name}[i])")
+                }
+                TypeNode::Fixed(_, _, _) => format!("subtree:{add}({fields}.{name}, buffer(offset, {w}), {name})"),
+                _ => format!("subtree:{add}({fields}.{name}, buffer(offset, {w}))"),
+            };
+            if is_array_like(t) {
+                let count = type_len_expr(t)
+                    .map(|e| format!("math.floor({})", emit_lua_expr(e)))
+                    .unwrap_or_else(|| format!("math.floor((buffer:len() - offset) / {w})"));
+                let _ = writeln!(&mut s, "local {name} = {{}}");
+                let _ = writeln!(&mut s, "for i = 1, {count} do");
+                let _ = writeln!(&mut s, "    {name}[i] = buffer(offset, {w}):{reader}");
+                let _ = writeln!(&mut s, "    {tree_add}");
+                let _ = writeln!(&mut s, "    offset = offset + {w}");
+                let _ = writeln!(&mut s, "end");
+            } else {
+                let _ = writeln!(&mut s, "local {name} = buffer(offset, {w}):{reader}");
+                let _ = writeln!(&mut s, "{tree_add}");
+                let _ = writeln!(&mut s, "offset = offset + {w}");
+            }
+        }
+    }
+    s
+}
+
+/* ============================================================
+ * Expr → Lua emitter
+ * ============================================================
+*/
+
+fn emit_lua_expr(e: &ExprNode) -> String {
+    emit_lua_expr_in(e, "")
+}
+
+/// `root` prefixes field references, e.g. `item.` for a repeated element's values.
+fn emit_lua_expr_in(e: &ExprNode, root: &str) -> String {
+    use ExprNode::*;
+    // only calc fields take checksums, over the offsets the dissector recorded
+    if let Some(sum) = e.as_checksum() {
+        return format!("sum_{}(buffer, {}_start, {}_end, 0, 0)", sum.kind.name(), sum.first, sum.last);
+    }
+    match e {
+        UnsignedInteger64Value(u) => format!("{}", u),
+        Integer64Value(i) => format!("{}", i),
+        Float64Value(f) => format!("{}", f),
+        StringValue(s) => format!("\"{}\"", lua_escape(s)),
+        ValueReference(name, idx) => {
+            if let Some(ix) = idx {
+                // Lua tables are 1-based
+                format!("{root}{name}[math.floor({}) + 1]", emit_lua_expr_in(ix, root))
+            } else {
+                format!("{root}{name}")
+            }
+        }
+        ActivationRecord(fname, args) => {
+            let args_s: Vec<String> = args.iter().map(|a| emit_lua_expr_in(a, root)).collect();
+            match fname.as_str() {
+                // Lua has no round; halves go away from zero
+                "round" => return format!("(({0}) >= 0 and math.floor(({0}) + 0.5) or math.ceil(({0}) - 0.5))", args_s[0]),
+                "log2" => return format!("(math.log({}) / math.log(2))", args_s[0]),
+                "clamp" => return format!("math.min(math.max({}, {}), {})", args_s[0], args_s[1], args_s[2]),
+                "pow" => return format!("(({}) ^ ({}))", args_s[0], args_s[1]),
+                "bswap16" => return format!("int_bswap({}, 2)", args_s[0]),
+                "bswap32" => return format!("int_bswap({}, 4)", args_s[0]),
+                "bswap64" => return format!("int_bswap({}, 8)", args_s[0]),
+                _ => {}
+            }
+            let mapped = match fname.as_str() {
+                "sqrt" => "math.sqrt",
+                "min" => "math.min",
+                "max" => "math.max",
+                "abs" => "math.abs",
+                "floor" => "math.floor",
+                "ceil" => "math.ceil",
+                "popcount" => "int_popcount",
+                _ => fname,
+            };
+            format!("{}({})", mapped, args_s.join(", "))
+        }
+        AggregateSum(id) => format!("0 --[[ sumof({}) unsupported ]]", id),
+        AggregateProduct(id) => format!("0 --[[ productof({}) unsupported ]]", id),
+        ParenthesizedExpr(inner) => format!("({})", emit_lua_expr_in(inner, root)),
+        // Lua treats 0 as true, so conditions are compared against 0 explicitly
+        GuardExpression(c, t, f) => format!(
+            "((({}) ~= 0) and ({}) or ({}))",
+            emit_lua_expr_in(c, root),
+            emit_lua_expr_in(t, root),
+            emit_lua_expr_in(f, root)
+        ),
+
+        Plus(a, b) => bin(a, b, "+", root),
+        Minus(a, b) => bin(a, b, "-", root),
+        Mult(a, b) => bin(a, b, "*", root),
+        Div(a, b) => bin(a, b, "/", root),
+        Pow(a, b) => bin(a, b, "^", root),
+        // DSL `%` truncates like C; Lua's `%` floors
+        Mod(a, b) => format!("math.fmod({}, {})", emit_lua_expr_in(a, root), emit_lua_expr_in(b, root)),
+        Negate(a) => format!("(-({}))", emit_lua_expr_in(a, root)),
+        // Wireshark ships the LuaBitOp `bit` module on every Lua version
+        BitAnd(a, b) => format!("bit.band({}, {})", emit_lua_expr_in(a, root), emit_lua_expr_in(b, root)),
+        BitOr(a, b) => format!("bit.bor({}, {})", emit_lua_expr_in(a, root), emit_lua_expr_in(b, root)),
+        BitNot(a) => format!("bit.bnot({})", emit_lua_expr_in(a, root)),
+        ShiftLeft(a, b) => format!("bit.lshift({}, ({}) % 64)", emit_lua_expr_in(a, root), emit_lua_expr_in(b, root)),
+        ShiftRight(a, b) => format!("bit.arshift({}, ({}) % 64)", emit_lua_expr_in(a, root), emit_lua_expr_in(b, root)),
+
+        Gt(a, b) => cmp(a, b, ">", root),
+        Gte(a, b) => cmp(a, b, ">=", root),
+        Lt(a, b) => cmp(a, b, "<", root),
+        Lte(a, b) => cmp(a, b, "<=", root),
+        Equals(a, b) => cmp(a, b, "==", root),
+        NotEquals(a, b) => cmp(a, b, "~=", root),
+        And(a, b) => format!(
+            "(((({}) ~= 0) and (({}) ~= 0)) and 1 or 0)",
+            emit_lua_expr_in(a, root),
+            emit_lua_expr_in(b, root)
+        ),
+        Or(a, b) => format!(
+            "(((({}) ~= 0) or (({}) ~= 0)) and 1 or 0)",
+            emit_lua_expr_in(a, root),
+            emit_lua_expr_in(b, root)
+        ),
+        Not(a) => format!("((({}) == 0) and 1 or 0)", emit_lua_expr_in(a, root)),
+
+        NoExpr => "0".to_string(),
+    }
+}
+
+fn bin(a: &ExprNode, b: &ExprNode, op: &str, root: &str) -> String {
+    format!("({}) {} ({})", emit_lua_expr_in(a, root), op, emit_lua_expr_in(b, root))
+}
+
+/// Comparisons yield booleans in Lua; map them to 1/0 to keep expressions numeric.
+fn cmp(a: &ExprNode, b: &ExprNode, op: &str, root: &str) -> String {
+    format!("((({}) {} ({})) and 1 or 0)", emit_lua_expr_in(a, root), op, emit_lua_expr_in(b, root))
+}
+
+/// Renders DSL `///` docs as `--` comment lines.
+fn lua_doc(doc: &Option<String>, pad: &str) -> String {
+    let Some(doc) = doc else {
+        return String::new();
+    };
+    doc.lines()
+        .map(|l| if l.is_empty() { format!("{pad}--\n") } else { format!("{pad}-- {l}\n") })
+        .collect()
+}
+
+fn lua_escape(s: &str) -> String {
+    s.chars()
+        .flat_map(|c| match c {
+            '\\' => "\\\\".chars().collect::<Vec<_>>(),
+            '"' => "\\\"".chars().collect(),
+            '\n' => "\\n".chars().collect(),
+            '\r' => "\\r".chars().collect(),
+            '\t' => "\\t".chars().collect(),
+            _ => vec![c],
+        })
+        .collect()
+}
+
+fn indent(s: &str, tabs: usize) -> String {
+    let pad = "    ".repeat(tabs);
+    s.lines()
+        .map(|l| {
+            if l.is_empty() {
+                "\n".to_string()
+            } else {
+                format!("{pad}{l}\n")
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::load_source;
+
+    fn lua(src: &str) -> String {
+        WiresharkGenerator::generate(&load_source(src))
+    }
+
+    #[test]
+    fn dissectors_add_each_field_to_the_tree() {
+        let out = lua("packet Hello be { kind uint8, size uint16, body bytes[size], calc total uint32 = size + 3 }");
+        assert!(out.contains("local hello_proto = Proto(\"hello\", \"Hello\")"));
+        assert!(out.contains("    size = ProtoField.uint16(\"hello.size\", \"size\", base.DEC),\n"));
+        assert!(out.contains(
+            "    local size = buffer(offset, 2):uint()\n    subtree:add(hello_fields.size, buffer(offset, 2))\n    offset = offset + 2\n"
+        ));
+        assert!(out.contains("    local body_len = math.floor(size)\n    subtree:add(hello_fields.body, buffer(offset, body_len))\n"));
+        assert!(out.contains("    local total = (size) + (3)\n    subtree:add(hello_fields.total, math.floor(total)):set_generated()\n"));
+    }
+
+    #[test]
+    fn little_endian_fields_use_le_readers() {
+        let out = lua("packet Le { a uint32 }");
+        assert!(out.contains("    local a = buffer(offset, 4):le_uint()\n    subtree:add_le(le_fields.a, buffer(offset, 4))\n"));
+    }
+}
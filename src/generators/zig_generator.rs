@@ -1,6 +1,8 @@
 use crate::models::parsing_models::{
-    ExprNode, PacketExpr, PacketExprList, TypeNode,
+    BitSlot, Check, ChecksumKind, ExprNode, FixedBase, Leftover, PacketExpr, PacketExprList, RepeatBound, StringEncoding, TimeEncoding,
+    TypeNode, VarIntEncoding,
 };
+use crate::utilities::{CaseWrapper, Casing};
 
 pub struct ZigGenerator {}
 
@@ -9,6 +11,22 @@ impl ZigGenerator {
         let mut out = String::new();
         out.push_str(&Self::create_headers());
         out.push_str(&Self::create_spacer());
+        if model.packets.iter().flat_map(|p| &p.fields).any(|f| is_string(&f.expr)) {
+            out.push_str(STRING_HELPERS);
+            out.push_str(&Self::create_spacer());
+        }
+        if model.packets.iter().flat_map(|p| &p.fields).any(|f| matches!(f.expr, TypeNode::DateTime(_, _))) {
+            out.push_str(TIME_HELPERS);
+            out.push_str(&Self::create_spacer());
+        }
+        if model.packets.iter().flat_map(|p| &p.fields).any(|f| matches!(f.expr, TypeNode::VarInt(_, _))) {
+            out.push_str(VARINT_HELPERS);
+            out.push_str(&Self::create_spacer());
+        }
+        if model.packets.iter().any(|p| !p.checksums().is_empty()) {
+            out.push_str(CHECKSUM_HELPERS);
+            out.push_str(&Self::create_spacer());
+        }
 
         for pkt in &model.packets {
             out.push_str(&Self::build_packet(pkt));
@@ -33,10 +51,24 @@ impl ZigGenerator {
     fn build_packet(pkt: &PacketExpr) -> String {
         // Fields
         let mut field_lines = String::new();
-        for f in &pkt.fields {
+        // const fields are declarations, written as given and checked when read
+        let mut const_lines = String::new();
+        // filler is not stored, nor are checksums, which are filled in and checked by the codec
+        for f in pkt.fields.iter().filter(|f| !f.is_filler() && f.checksum().is_none()) {
             let ty = zig_field_type(&f.expr);
+            if let Some(value) = f.const_literal() {
+                const_lines.push_str(&doc_comment(&f.doc, "    "));
+                const_lines.push_str(&format!("    pub const {name}: {ty} = {value};\n", name = f.id));
+                continue;
+            }
+            // optional fields are null when absent
+            let ty = if f.condition.is_some() { format!("?{ty}") } else { ty };
+            field_lines.push_str(&doc_comment(&f.doc, "        "));
             field_lines.push_str(&format!("        {name}: {ty},\n", name = f.id, ty = ty));
         }
+        if !const_lines.is_empty() {
+            const_lines.push('\n');
+        }
         // (Optional) calculated fields – if you want them in the struct as stored values,
         // map their type names just like in Rust/C# generators. For now we omit here.
 
@@ -44,48 +76,420 @@ impl ZigGenerator {
         let mut ser_body = String::new();
         ser_body.push_str("            var list = std.ArrayList(u8).init(allocator);\n");
         ser_body.push_str("            defer list.deinit();\n\n");
+        let slots = pkt.bit_slots();
+        // count fields are written from the field they size: the receiver is
+        // copied, the counts filled in, and the copy serialized as `self`
+        let derived = pkt.derived_counts();
+        let receiver = if derived.iter().all(Option::is_none) { "self" } else { "original" };
+        if receiver != "self" {
+            ser_body.push_str("            var copy = original.*;\n");
+            for (f, count) in pkt.fields.iter().zip(&derived) {
+                if let Some(count) = count {
+                    ser_body.push_str(&format!(
+                        "            copy.{} = std.math.cast({}, {}) orelse return error.Overflow;\n",
+                        f.id,
+                        zig_field_type(&f.expr),
+                        zig_count(&pkt.fields[count.source].id, count.adjust)
+                    ));
+                }
+            }
+            ser_body.push_str("            const self = &copy;\n\n");
+        }
+        let marks = pkt.checksum_marks();
+        for ((f, slot), &(start, end)) in pkt.fields.iter().zip(&slots).zip(&marks) {
+            if start {
+                ser_body.push_str(&format!("            const {}_start = list.items.len;\n", f.id));
+            }
+            match slot {
+                Some(slot) => ser_body.push_str(&emit_zig_serialize_bit(&f.id, slot)),
+                // left as zero, then filled in once the packet is encoded
+                None if f.checksum().is_some() => ser_body.push_str(&format!(
+                    "            const {}_at = list.items.len;\n            try list.appendNTimes(0, {});\n",
+                    f.id,
+                    f.checksum().unwrap().kind.width_bytes()
+                )),
+                None if f.value.is_some() => ser_body.push_str(
+                    &emit_zig_serialize_field(&f.id, &f.expr)
+                        .replace(&format!("self.{}", f.id), &format!("{}.{}", pkt.name, f.id)),
+                ),
+                // optional fields are written from the unwrapped `<id>_set` when their condition holds
+                None if f.condition.is_some() => {
+                    let set = format!("{}_set", f.id);
+                    ser_body.push_str(&format!(
+                        "            if ({} != 0.0) {{\n                const {set} = self.{} orelse return error.MissingField;\n",
+                        emit_zig_numeric_expr(f.condition.as_ref().unwrap(), "self."),
+                        f.id
+                    ));
+                    ser_body.push_str(&indent(
+                        &emit_zig_serialize_field(&set, &f.expr).replace(&format!("self.{set}"), &set),
+                        1,
+                    ));
+                    ser_body.push_str("            }\n");
+                }
+                None => ser_body.push_str(&emit_zig_serialize_field(&f.id, &f.expr)),
+            }
+            if end {
+                ser_body.push_str(&format!("            const {}_end = list.items.len;\n", f.id));
+            }
+        }
         for f in &pkt.fields {
-            ser_body.push_str(&emit_zig_serialize_field(&f.id, &f.expr));
+            let Some(sum) = f.checksum() else { continue };
+            let ty = zig_field_type(&f.expr);
+            ser_body.push_str(&format!(
+                "            std.mem.writeIntLittle({ty}, list.items[{id}_at..][0..@sizeOf({ty})], {}(list.items, {}_start, {}_end, 0, 0));\n",
+                zig_checksum_fn(sum.kind),
+                sum.first,
+                sum.last,
+                id = f.id
+            ));
         }
         ser_body.push_str("            return list.toOwnedSlice();\n");
 
         // Deserializer body
         let mut de_body = String::new();
+        // `within` blocks narrow `data` to their end, so the parameter gets a mutable copy
+        let input = if pkt.regions.is_empty() { "data" } else { "input" };
+        if !pkt.regions.is_empty() {
+            de_body.push_str("            var data = input;\n");
+        }
         de_body.push_str("            var i: usize = 0;\n");
-        for (idx, f) in pkt.fields.iter().enumerate() {
-            let is_last = idx + 1 == pkt.fields.len();
-            de_body.push_str(&emit_zig_deserialize_field(&f.id, &f.expr, is_last));
+        for (idx, (f, slot)) in pkt.fields.iter().zip(&slots).enumerate() {
+            for (k, _) in pkt.regions_opening(idx) {
+                de_body.push_str(&emit_zig_region_open(pkt, k));
+            }
+            let is_last = pkt.is_last_in_scope(idx);
+            let (start, end) = marks[idx];
+            if start {
+                de_body.push_str(&format!("            const {}_start = i;\n", f.id));
+            }
+            if f.checksum().is_some() {
+                de_body.push_str(&format!("            const {}_at = i;\n", f.id));
+            }
+            match slot {
+                Some(slot) => de_body.push_str(&emit_zig_deserialize_bit(&f.id, slot)),
+                None if f.checksum().is_some() => de_body.push_str(&emit_zig_deserialize_field(&f.id, &f.expr, is_last)),
+                // a local named like the declaration would shadow it
+                None if f.value.is_some() => {
+                    de_body.push_str(&emit_zig_deserialize_field(&format!("{}_wire", f.id), &f.expr, is_last));
+                    de_body.push_str(&format!(
+                        "            if ({id}_wire != {pkt}.{id}) return error.WrongConstant;\n",
+                        id = f.id,
+                        pkt = pkt.name
+                    ));
+                }
+                // read into `<id>_wire`, since Zig does not allow the local to shadow the optional
+                None if f.condition.is_some() => {
+                    de_body.push_str(&format!(
+                        "            var {id}: ?{ty} = null;\n            if ({} != 0.0) {{\n",
+                        emit_zig_numeric_expr(f.condition.as_ref().unwrap(), ""),
+                        id = f.id,
+                        ty = zig_field_type(&f.expr)
+                    ));
+                    de_body.push_str(&indent(
+                        &emit_zig_deserialize_field(&format!("{}_wire", f.id), &f.expr, is_last),
+                        1,
+                    ));
+                    de_body.push_str(&format!("                {id} = {id}_wire;\n            }}\n", id = f.id));
+                }
+                None => de_body.push_str(&emit_zig_deserialize_field(&f.id, &f.expr, is_last)),
+            }
+            if end {
+                de_body.push_str(&format!("            const {}_end = i;\n", f.id));
+            }
+            if let Some(check) = &f.constraint {
+                de_body.push_str(&emit_zig_check(check, ""));
+            }
+            for (k, _) in pkt.regions_closing(idx) {
+                de_body.push_str(&emit_zig_region_close(pkt, k));
+            }
+        }
+        // checksums are recomputed with their own field read as zero, as when they were filled in
+        for f in &pkt.fields {
+            let Some(sum) = f.checksum() else { continue };
+            de_body.push_str(&format!(
+                "            if ({id} != {}(data, {}_start, {}_end, {id}_at, {})) return error.WrongChecksum;\n",
+                zig_checksum_fn(sum.kind),
+                sum.first,
+                sum.last,
+                sum.kind.width_bytes(),
+                id = f.id
+            ));
         }
+        for check in &pkt.asserts {
+            de_body.push_str(&emit_zig_check(check, ""));
+        }
+
+        // `where` and `assert` rules, for checking a packet before serializing it
+        let checks = pkt.checks();
+        let validate = if checks.is_empty() {
+            String::new()
+        } else {
+            let body: String = checks.iter().map(|c| format!("        {}", emit_zig_check(c, "self.").trim_start())).collect();
+            format!(
+                "\n    /// Fails with error.Invalid<Field> if a `where` or `assert` rule fails; deserialize checks them too.\n    pub fn validate(self: *const {}) !void {{\n{}    }}\n",
+                pkt.name,
+                body
+            )
+        };
 
         // Final struct literal construction
         let mut build_lines = String::new();
-        for f in &pkt.fields {
+        for f in pkt.fields.iter().filter(|f| f.value.is_none() && !f.is_filler()) {
             build_lines.push_str(&format!("                .{name} = {name},\n", name = f.id));
         }
 
         // Emit the struct with methods
         format!(
-            r#"pub const {name} = struct {{
+            r#"{doc}pub const {name} = struct {{
 {fields}
-    pub fn serialize(self: *const {name}, allocator: std.mem.Allocator) ![]u8 {{
+{consts}    pub fn serialize({receiver}: *const {name}, allocator: std.mem.Allocator) ![]u8 {{
 {ser_body}    }}
 
     pub fn deserialize(allocator: std.mem.Allocator, data: []const u8) !{name} {{
+        var used: usize = 0;
+        return deserializePrefix(allocator, data, &used);
+    }}
+
+    /// Decodes one {name} from the front of `data`; `used` is set to the bytes it took.
+    pub fn deserializePrefix(allocator: std.mem.Allocator, {input}: []const u8, used: *usize) !{name} {{
 {de_body}
+        used.* = i;
         return .{{
 {build}        }};
     }}
-}};
+{validate}}};
 "#,
+            doc = doc_comment(&pkt.doc, ""),
             name = pkt.name,
             fields = field_lines,
             ser_body = indent(&ser_body, 2),
             de_body = indent(&de_body, 2),
-            build = build_lines
+            build = build_lines,
+            consts = const_lines,
+            receiver = receiver,
+            input = input,
+            validate = validate
         )
     }
 }
 
+/// Name of the checksum helper for `kind`.
+fn zig_checksum_fn(kind: ChecksumKind) -> &'static str {
+    match kind {
+        ChecksumKind::Crc32 => "sumCrc32",
+        ChecksumKind::Crc16Ccitt => "sumCrc16Ccitt",
+        ChecksumKind::InetChecksum => "sumInetChecksum",
+        ChecksumKind::Adler32 => "sumAdler32",
+    }
+}
+
+/// Checksum intrinsics, emitted only when a packet uses one. Each covers
+/// `data[start..end]` with the `hole_len` bytes at `hole` read as zero.
+const CHECKSUM_HELPERS: &str = r#"fn sumByte(data: []const u8, k: usize, hole: usize, hole_len: usize) u8 {
+    return if (k >= hole and k < hole + hole_len) 0 else data[k];
+}
+
+/// CRC-32 (IEEE 802.3).
+fn sumCrc32(data: []const u8, start: usize, end: usize, hole: usize, hole_len: usize) u32 {
+    var crc: u32 = 0xFFFF_FFFF;
+    var k = start;
+    while (k < end) : (k += 1) {
+        crc ^= sumByte(data, k, hole, hole_len);
+        var b: usize = 0;
+        while (b < 8) : (b += 1) {
+            crc = if (crc & 1 != 0) (crc >> 1) ^ 0xEDB8_8320 else crc >> 1;
+        }
+    }
+    return ~crc;
+}
+
+/// CRC-16/CCITT-FALSE.
+fn sumCrc16Ccitt(data: []const u8, start: usize, end: usize, hole: usize, hole_len: usize) u16 {
+    var crc: u16 = 0xFFFF;
+    var k = start;
+    while (k < end) : (k += 1) {
+        crc ^= @as(u16, sumByte(data, k, hole, hole_len)) << 8;
+        var b: usize = 0;
+        while (b < 8) : (b += 1) {
+            crc = if (crc & 0x8000 != 0) (crc << 1) ^ 0x1021 else crc << 1;
+        }
+    }
+    return crc;
+}
+
+/// RFC 1071 Internet checksum; an odd last byte is padded with zero.
+fn sumInetChecksum(data: []const u8, start: usize, end: usize, hole: usize, hole_len: usize) u16 {
+    var sum: u64 = 0;
+    var k = start;
+    while (k < end) : (k += 2) {
+        sum += @as(u64, sumByte(data, k, hole, hole_len)) << 8;
+        if (k + 1 < end) sum += sumByte(data, k + 1, hole, hole_len);
+    }
+    while (sum > 0xFFFF) sum = (sum & 0xFFFF) + (sum >> 16);
+    return ~@truncate(u16, sum);
+}
+
+/// Adler-32 (RFC 1950).
+fn sumAdler32(data: []const u8, start: usize, end: usize, hole: usize, hole_len: usize) u32 {
+    var a: u32 = 1;
+    var b: u32 = 0;
+    var k = start;
+    while (k < end) : (k += 1) {
+        a = (a + sumByte(data, k, hole, hole_len)) % 65521;
+        b = (b + a) % 65521;
+    }
+    return (b << 16) | a;
+}
+"#;
+
+/// Helpers for string fields, emitted only when a packet has one. Strings are
+/// held as UTF-8; decoding fails with error.InvalidEncoding on bad input.
+const STRING_HELPERS: &str = r#"/// Cuts `raw` at its first NUL code unit (1 byte, or 2 for UTF-16).
+fn stripNul(raw: []const u8, unit: usize) []const u8 {
+    var k: usize = 0;
+    while (k + unit <= raw.len) : (k += unit) {
+        if (raw[k] == 0 and (unit == 1 or raw[k + 1] == 0)) return raw[0..k];
+    }
+    return raw;
+}
+
+/// Number of bytes before the NUL code unit at or after `start`.
+fn cstringLen(data: []const u8, start: usize, unit: usize) !usize {
+    var k: usize = start;
+    while (k + unit <= data.len) : (k += unit) {
+        if (data[k] == 0 and (unit == 1 or data[k + 1] == 0)) return k - start;
+    }
+    return error.EndOfStream;
+}
+
+fn decodeAscii(allocator: std.mem.Allocator, raw: []const u8) ![]const u8 {
+    for (raw) |b| {
+        if (b >= 0x80) return error.InvalidEncoding;
+    }
+    return allocator.dupe(u8, raw);
+}
+
+fn decodeUtf8(allocator: std.mem.Allocator, raw: []const u8) ![]const u8 {
+    if (!std.unicode.utf8ValidateSlice(raw)) return error.InvalidEncoding;
+    return allocator.dupe(u8, raw);
+}
+
+fn decodeUtf16le(allocator: std.mem.Allocator, raw: []const u8) ![]const u8 {
+    if (raw.len % 2 != 0) return error.InvalidEncoding;
+    const units = try allocator.alloc(u16, raw.len / 2);
+    defer allocator.free(units);
+    std.mem.copy(u8, std.mem.sliceAsBytes(units), raw);
+    return std.unicode.utf16leToUtf8Alloc(allocator, units) catch error.InvalidEncoding;
+}
+"#;
+
+/// Conversions for datetime fields, emitted only when a packet has one. Times
+/// are held as i128 nanoseconds since the Unix epoch, like std.time.nanoTimestamp.
+const TIME_HELPERS: &str = r#"const ntp_offset_s: i128 = 2_208_988_800;
+const filetime_offset_s: i128 = 11_644_473_600;
+
+fn toUnix(ns: i128, per_second: i128) i64 {
+    return @intCast(i64, @divFloor(ns * per_second, std.time.ns_per_s));
+}
+
+fn fromUnix(v: i64, per_second: i128) i128 {
+    return @divFloor(@as(i128, v) * std.time.ns_per_s, per_second);
+}
+
+/// NTP 32.32 fixed point seconds since 1900-01-01; wraps at the end of each era.
+fn toNtp64(ns: i128) u64 {
+    const t = ns + ntp_offset_s * std.time.ns_per_s;
+    const secs = @as(u64, @truncate(u32, @bitCast(u128, @divFloor(t, std.time.ns_per_s))));
+    const frac = @intCast(u64, @divFloor(@mod(t, std.time.ns_per_s) << 32, std.time.ns_per_s));
+    return (secs << 32) | frac;
+}
+
+fn fromNtp64(v: u64) i128 {
+    const secs = @as(i128, v >> 32) - ntp_offset_s;
+    const frac = (@as(i128, v & 0xFFFF_FFFF) * std.time.ns_per_s) >> 32;
+    return secs * std.time.ns_per_s + frac;
+}
+
+/// FILETIME: 100 ns ticks since 1601-01-01.
+fn toFileTime(ns: i128) u64 {
+    return @intCast(u64, @divFloor(ns, 100) + filetime_offset_s * 10_000_000);
+}
+
+fn fromFileTime(v: u64) i128 {
+    return (@as(i128, v) - filetime_offset_s * 10_000_000) * 100;
+}
+"#;
+
+/// LEB128, zigzag and QUIC varint codecs, emitted only when a packet has a
+/// varint. Over-long or out-of-range values fail with error.Overflow.
+const VARINT_HELPERS: &str = r#"/// LEB128: 7 bits per byte, least significant group first.
+fn writeVarUint(list: *std.ArrayList(u8), value: u64) !void {
+    var v = value;
+    while (v >= 0x80) : (v >>= 7) {
+        try list.append(@truncate(u8, v) | 0x80);
+    }
+    try list.append(@truncate(u8, v));
+}
+
+fn readVarUint(data: []const u8, i: *usize) !u64 {
+    var v: u64 = 0;
+    var n: usize = 0;
+    while (n < 10) : (n += 1) {
+        if (i.* >= data.len) return error.EndOfStream;
+        const b = data[i.*];
+        i.* += 1;
+        v |= @as(u64, b & 0x7F) << @intCast(u6, 7 * n);
+        if (b & 0x80 == 0) return v;
+    }
+    return error.Overflow;
+}
+
+fn zigzag(v: i64) u64 {
+    return (@bitCast(u64, v) << 1) ^ @bitCast(u64, v >> 63);
+}
+
+fn unzigzag(u: u64) i64 {
+    return @bitCast(i64, u >> 1) ^ -@bitCast(i64, u & 1);
+}
+
+/// RFC 9000 varint: the top two bits of the first byte give its length.
+fn writeQuicVarint(list: *std.ArrayList(u8), value: u64) !void {
+    if (value < 0x40) {
+        try list.append(@intCast(u8, value));
+    } else if (value < 0x4000) {
+        var buf: [2]u8 = undefined;
+        std.mem.writeIntBig(u16, buf[0..], @intCast(u16, value) | 0x4000);
+        try list.appendSlice(buf[0..]);
+    } else if (value < 0x4000_0000) {
+        var buf: [4]u8 = undefined;
+        std.mem.writeIntBig(u32, buf[0..], @intCast(u32, value) | 0x8000_0000);
+        try list.appendSlice(buf[0..]);
+    } else if (value < 0x4000_0000_0000_0000) {
+        var buf: [8]u8 = undefined;
+        std.mem.writeIntBig(u64, buf[0..], value | 0xC000_0000_0000_0000);
+        try list.appendSlice(buf[0..]);
+    } else {
+        return error.Overflow;
+    }
+}
+
+fn readQuicVarint(data: []const u8, i: *usize) !u64 {
+    if (i.* >= data.len) return error.EndOfStream;
+    const n: usize = switch (data[i.*] >> 6) {
+        0 => 1,
+        1 => 2,
+        2 => 4,
+        else => 8,
+    };
+    if (i.* + n > data.len) return error.EndOfStream;
+    var v: u64 = data[i.*] & 0x3F;
+    var k: usize = 1;
+    while (k < n) : (k += 1) v = (v << 8) | data[i.* + k];
+    i.* += n;
+    return v;
+}
+"#;
+
 /* ===========================
  * Type mapping
  * =========================== */
@@ -104,13 +508,102 @@ fn zig_field_type(t: &TypeNode) -> String {
         Integer64(len)        => if len.is_some() { "[]i64".into() } else { "i64".into() },
         Float32(len)          => if len.is_some() { "[]f32".into() } else { "f32".into() },
         Float64(len)          => if len.is_some() { "[]f64".into() } else { "f64".into() },
-        DateTime(len)         => if len.is_some() { "[]i64".into() } else { "i64".into() },
+        DateTime(len, _)      => if len.is_some() { "[]i128".into() } else { "i128".into() },
+        Bool(len, _)          => if len.is_some() { "[]bool".into() } else { "bool".into() },
+        Fixed(len, _, _)      => if len.is_some() { "[]f64".into() } else { "f64".into() },
+        VarInt(len, enc)      => {
+            let ty = if *enc == VarIntEncoding::ZigZag { "i64" } else { "u64" };
+            if len.is_some() { format!("[]{ty}") } else { ty.into() }
+        }
 
         // Bytes: always a slice
         Bytes(_len)           => "[]u8".into(),
+        Pad(_) | Align(_)     => "void".into(), // never stored
 
         // MacAddress: fixed 6 bytes if length not specified, else a slice
         MacAddress(len)       => if len.is_some() { "[]u8".into() } else { "[6]u8".into() },
+
+        // Addresses: raw network-order bytes; ports: host-order integers
+        Ipv4Address(len)      => if len.is_some() { "[][4]u8".into() } else { "[4]u8".into() },
+        Ipv6Address(len)      => if len.is_some() { "[][16]u8".into() } else { "[16]u8".into() },
+        Port(len)             => if len.is_some() { "[]u16".into() } else { "u16".into() },
+
+        // Strings: UTF-8 in memory whatever the wire encoding
+        FixedString(_, _) | CString(_) | PString(_, _) => "[]const u8".into(),
+
+        // Sub-packets: a slice of the element struct
+        Repeat(elem, _)       => format!("[]{elem}"),
+    }
+}
+
+/// Wire integer type, `to`/`from` helpers and trailing argument of a datetime encoding.
+fn zig_time(unit: &TimeEncoding) -> (&'static str, &'static str, &'static str, &'static str) {
+    match unit {
+        TimeEncoding::UnixSeconds => ("i64", "toUnix", "fromUnix", ", 1"),
+        TimeEncoding::UnixMillis => ("i64", "toUnix", "fromUnix", ", 1_000"),
+        TimeEncoding::UnixMicros => ("i64", "toUnix", "fromUnix", ", 1_000_000"),
+        TimeEncoding::Ntp64 => ("u64", "toNtp64", "fromNtp64", ""),
+        TimeEncoding::FileTime => ("u64", "toFileTime", "fromFileTime", ""),
+    }
+}
+
+/// Wire integer type of a fixed-point base.
+fn zig_fixed_int(base: &FixedBase) -> &'static str {
+    match base {
+        FixedBase::Int8 => "i8",
+        FixedBase::UInt8 => "u8",
+        FixedBase::Int16 => "i16",
+        FixedBase::UInt16 => "u16",
+        FixedBase::Int32 => "i32",
+        FixedBase::UInt32 => "u32",
+        FixedBase::Int64 => "i64",
+        FixedBase::UInt64 => "u64",
+    }
+}
+
+/// (write, read) calls of a varint encoding; the write takes `{v}`.
+fn zig_varint(enc: &VarIntEncoding) -> (&'static str, &'static str) {
+    match enc {
+        VarIntEncoding::Leb128 => ("try writeVarUint(&list, {v})", "try readVarUint(data, &i)"),
+        VarIntEncoding::ZigZag => ("try writeVarUint(&list, zigzag({v}))", "unzigzag(try readVarUint(data, &i))"),
+        VarIntEncoding::Quic => ("try writeQuicVarint(&list, {v})", "try readQuicVarint(data, &i)"),
+    }
+}
+
+/// Float literal for 2^frac, the divisor of a fixed-point value.
+fn zig_fixed_scale(frac: u8) -> String {
+    format!("{}.0", 1u128 << frac)
+}
+
+fn is_string(t: &TypeNode) -> bool {
+    matches!(t, TypeNode::FixedString(_, _) | TypeNode::CString(_) | TypeNode::PString(_, _))
+}
+
+/// Code-unit width of an encoding (the size of its NUL terminator).
+fn code_unit(e: &StringEncoding) -> usize {
+    match e {
+        StringEncoding::Utf16Le => 2,
+        _ => 1,
+    }
+}
+
+fn decoder(e: &StringEncoding) -> &'static str {
+    match e {
+        StringEncoding::Ascii => "decodeAscii",
+        StringEncoding::Utf8 => "decodeUtf8",
+        StringEncoding::Utf16Le => "decodeUtf16le",
+    }
+}
+
+/// Lines binding `{name}_b` to the wire bytes of `self.{name}` (inside a block).
+fn encoded_bytes(name: &str, e: &StringEncoding) -> String {
+    match e {
+        StringEncoding::Utf16Le => format!(
+            "                const {name}_u = try std.unicode.utf8ToUtf16LeAlloc(allocator, self.{name});\n\
+             \x20               defer allocator.free({name}_u);\n\
+             \x20               const {name}_b = std.mem.sliceAsBytes({name}_u);\n"
+        ),
+        _ => format!("                const {name}_b = self.{name};\n"),
     }
 }
 
@@ -118,6 +611,19 @@ fn zig_field_type(t: &TypeNode) -> String {
  * Serialization emitters
  * =========================== */
 
+/// `bool(bit)` fields share a byte, filled from the most significant bit down.
+fn emit_zig_serialize_bit(name: &str, slot: &BitSlot) -> String {
+    let mut s = String::new();
+    if slot.starts_byte() {
+        s.push_str("            try list.append(0);\n");
+    }
+    s.push_str(&format!(
+        "            list.items[list.items.len - 1] |= @as(u8, @boolToInt(self.{name})) << {};\n",
+        slot.shift()
+    ));
+    s
+}
+
 fn emit_zig_serialize_field(name: &str, t: &TypeNode) -> String {
     use TypeNode::*;
     let mut s = String::new();
@@ -139,6 +645,13 @@ fn emit_zig_serialize_field(name: &str, t: &TypeNode) -> String {
     };
 
     match t {
+        // Filler: zeros, up to the next multiple of n for `align(n)`
+        Pad(n) => {
+            s.push_str(&format!("            try list.appendNTimes(0, {n});\n"));
+        }
+        Align(n) => {
+            s.push_str(&format!("            try list.appendNTimes(0, ({n} - list.items.len % {n}) % {n});\n"));
+        }
         // Byte blobs and u8 arrays: fast path
         Bytes(_) => {
             s.push_str(&format!("            try list.appendSlice(self.{name});\n"));
@@ -213,15 +726,124 @@ fn emit_zig_serialize_field(name: &str, t: &TypeNode) -> String {
             }
         }
 
-        DateTime(len) => {
-            // Treat as i64 for wire format
-            emit_array_or_scalar(&mut s, name, "i64", len, &write_scalar);
+        DateTime(len, unit) => {
+            let (wire, to, _, arg) = zig_time(unit);
+            if len.is_some() {
+                s.push_str(&format!("            for (self.{name}) |v| {{\n"));
+                write_scalar(&mut s, wire, &format!("{to}(v{arg})"));
+                s.push_str("            }\n");
+            } else {
+                write_scalar(&mut s, wire, &format!("{to}(self.{name}{arg})"));
+            }
+        }
+
+        Bool(len, _) => {
+            if len.is_some() {
+                s.push_str(&format!("            for (self.{name}) |v| {{ try list.append(@boolToInt(v)); }}\n"));
+            } else {
+                s.push_str(&format!("            try list.append(@boolToInt(self.{name}));\n"));
+            }
+        }
+        Fixed(len, base, frac) => {
+            let wire = zig_fixed_int(base);
+            let scale = zig_fixed_scale(*frac);
+            if len.is_some() {
+                s.push_str(&format!("            for (self.{name}) |v| {{\n"));
+                write_scalar(&mut s, wire, &format!("@floatToInt({wire}, @round(v * {scale}))"));
+                s.push_str("            }\n");
+            } else {
+                write_scalar(&mut s, wire, &format!("@floatToInt({wire}, @round(self.{name} * {scale}))"));
+            }
+        }
+
+        Repeat(_, _) => {
+            s.push_str(&format!("            for (self.{name}) |v| {{\n"));
+            s.push_str("                const _b = try v.serialize(allocator);\n");
+            s.push_str("                defer allocator.free(_b);\n");
+            s.push_str("                try list.appendSlice(_b);\n");
+            s.push_str("            }\n");
+        }
+
+        VarInt(len, enc) => {
+            let write = zig_varint(enc).0;
+            if len.is_some() {
+                s.push_str(&format!("            for (self.{name}) |v| {{ {}; }}\n", write.replace("{v}", "v")));
+            } else {
+                s.push_str(&format!("            {};\n", write.replace("{v}", &format!("self.{name}"))));
+            }
+        }
+
+        // Network order regardless of the little-endian default above
+        Ipv4Address(len) | Ipv6Address(len) => {
+            if len.is_some() {
+                s.push_str(&format!("            for (self.{name}) |a| {{ try list.appendSlice(a[0..]); }}\n"));
+            } else {
+                s.push_str(&format!("            try list.appendSlice(self.{name}[0..]);\n"));
+            }
+        }
+        Port(len) => {
+            let write_port = |v: &str| {
+                format!(
+                    "{{ var buf: [2]u8 = undefined; std.mem.writeIntBig(u16, buf[0..], {v}); try list.appendSlice(buf[0..]); }}"
+                )
+            };
+            if len.is_some() {
+                s.push_str(&format!("            for (self.{name}) |v| {}\n", write_port("v")));
+            } else {
+                s.push_str(&format!("            {}\n", write_port(&format!("self.{name}"))));
+            }
+        }
+
+        // Strings: NUL-padded, NUL-terminated, or length-prefixed; one that does not
+        // fit its width or prefix fails with error.StringTooLong
+        FixedString(len, enc) => {
+            s.push_str("            {\n");
+            s.push_str(&format!(
+                "                const {name}_n: usize = {n};\n",
+                n = emit_zig_len_expr_in(len, "self.")
+            ));
+            s.push_str(&encoded_bytes(name, enc));
+            s.push_str(&format!("                if ({name}_b.len > {name}_n) return error.StringTooLong;\n"));
+            s.push_str(&format!("                try list.appendSlice({name}_b);\n"));
+            s.push_str(&format!("                try list.appendNTimes(0, {name}_n - {name}_b.len);\n"));
+            s.push_str("            }\n");
+        }
+        CString(enc) => {
+            s.push_str("            {\n");
+            s.push_str(&encoded_bytes(name, enc));
+            s.push_str(&format!("                try list.appendSlice({name}_b);\n"));
+            s.push_str(&format!("                try list.appendNTimes(0, {});\n", code_unit(enc)));
+            s.push_str("            }\n");
+        }
+        PString(_, enc) => {
+            let ty = if t.scalar_width_bytes() == 1 { "u8" } else { "u16" };
+            s.push_str("            {\n");
+            s.push_str(&encoded_bytes(name, enc));
+            s.push_str(&format!(
+                "                if ({name}_b.len > std.math.maxInt({ty})) return error.StringTooLong;\n"
+            ));
+            s.push_str(&format!("                var buf: [@sizeOf({ty})]u8 = undefined;\n"));
+            s.push_str(&format!(
+                "                std.mem.writeIntLittle({ty}, buf[0..], @intCast({ty}, {name}_b.len));\n"
+            ));
+            s.push_str("                try list.appendSlice(buf[0..]);\n");
+            s.push_str(&format!("                try list.appendSlice({name}_b);\n"));
+            s.push_str("            }\n");
         }
     }
 
     s
 }
 
+/// Element count of `copy.source` less `adjust`, for a length written `l + adjust`.
+fn zig_count(source: &str, adjust: i64) -> String {
+    match adjust {
+        0 => format!("copy.{source}.len"),
+        a if a < 0 => format!("copy.{source}.len + {}", -a),
+        a => format!("(std.math.sub(usize, copy.{source}.len, {a}) catch return error.Overflow)"),
+    }
+}
+
 fn emit_array_or_scalar(
     s: &mut String,
     name: &str,
@@ -244,27 +866,65 @@ fn emit_array_or_scalar(
  * Deserialization emitters
  * =========================== */
 
+// Reads inside a region check against the narrowed `data`, so running past its
+// end fails with error.EndOfStream; unread bytes fail with error.LeftoverBytes.
+/// Returns error.Invalid<Field> (error.ValidationFailed for an assert that reads
+/// no field) unless the rule holds.
+fn emit_zig_check(check: &Check, root: &str) -> String {
+    let err = if check.field.is_empty() {
+        "ValidationFailed".to_string()
+    } else {
+        format!("Invalid{}", CaseWrapper(check.field.clone()).to_pascal_case())
+    };
+    format!(
+        "            if ({} == 0.0) return error.{err}; // {}\n",
+        emit_zig_numeric_expr(&check.cond, root),
+        check.rule
+    )
+}
+
+fn emit_zig_region_open(pkt: &PacketExpr, k: usize) -> String {
+    let mut s = String::new();
+    s.push_str(&format!(
+        "            const end{k}: usize = i + {};\n",
+        emit_zig_len_expr(&pkt.regions[k].length)
+    ));
+    s.push_str(&format!("            if (end{k} > data.len) return error.EndOfStream;\n"));
+    s.push_str(&format!("            const outer{k} = data;\n"));
+    s.push_str(&format!("            data = data[0..end{k}];\n"));
+    s
+}
+
+fn emit_zig_region_close(pkt: &PacketExpr, k: usize) -> String {
+    let mut s = String::new();
+    match pkt.regions[k].leftover {
+        Leftover::Error => s.push_str(&format!("            if (i < end{k}) return error.LeftoverBytes;\n")),
+        Leftover::Skip => s.push_str(&format!("            i = end{k};\n")),
+    }
+    s.push_str(&format!("            data = outer{k};\n"));
+    s
+}
+
+fn emit_zig_deserialize_bit(name: &str, slot: &BitSlot) -> String {
+    let mut s = String::new();
+    if slot.starts_byte() {
+        s.push_str("            if (i + 1 > data.len) return error.EndOfStream;\n");
+    }
+    s.push_str(&format!(
+        "            const {name}: bool = ((data[i] >> {}) & 1) != 0;\n",
+        slot.shift()
+    ));
+    if slot.ends_byte() {
+        s.push_str("            i += 1;\n");
+    }
+    s
+}
+
 fn emit_zig_deserialize_field(name: &str, t: &TypeNode, is_last: bool) -> String {
     use TypeNode::*;
     let mut s = String::new();
 
     // Helpers
-    let read_int = |dst: &mut String, comptime_ty: &str| {
-        dst.push_str(&format!(
-            "            if (i + @sizeOf({ty}) > data.len) return error.EndOfStream;\n",
-            ty = comptime_ty
-        ));
-        dst.push_str(&format!(
-            "            const {name}: {ty} = std.mem.readIntLittle({ty}, data[i .. i + @sizeOf({ty})]);\n",
-            name = name,
-            ty = comptime_ty
-        ));
-        dst.push_str(&format!(
-            "            i += @sizeOf({ty});\n",
-            ty = comptime_ty
-        ));
-    };
-
     let read_int_elem = |dst: &mut String, tmpname: &str, comptime_ty: &str| {
         dst.push_str(&format!(
             "                if (i + @sizeOf({ty}) > data.len) return error.EndOfStream;\n",
@@ -282,6 +942,15 @@ fn emit_zig_deserialize_field(name: &str, t: &TypeNode, is_last: bool) -> String
     };
 
     match t {
+        // Filler is skipped, whatever it holds
+        Pad(n) => {
+            s.push_str(&format!("            if (i + {n} > data.len) return error.EndOfStream;\n"));
+            s.push_str(&format!("            i += {n};\n"));
+        }
+        Align(n) => {
+            s.push_str(&format!("            i = (i + {n} - 1) / {n} * {n};\n"));
+            s.push_str("            if (i > data.len) return error.EndOfStream;\n");
+        }
         Bytes(len_opt) => {
             if let Some(expr) = len_opt {
                 let n_expr = emit_zig_len_expr(expr);
@@ -308,7 +977,7 @@ fn emit_zig_deserialize_field(name: &str, t: &TypeNode, is_last: bool) -> String
                 ));
                 s.push_str("            i = data.len;\n");
             } else {
-                s.push_str(&format!("            return error.InvalidLength; // open-ended bytes not last\n"));
+                s.push_str("            return error.InvalidLength; // open-ended bytes not last\n");
             }
         }
 
@@ -330,9 +999,7 @@ fn emit_zig_deserialize_field(name: &str, t: &TypeNode, is_last: bool) -> String
                 ));
                 s.push_str(&format!("            i += {name}_n;\n"));
             } else {
-                s.push_str(&format!(
-                    "            if (i + 6 > data.len) return error.EndOfStream;\n"
-                ));
+                s.push_str("            if (i + 6 > data.len) return error.EndOfStream;\n");
                 s.push_str(&format!(
                     "            var {name}: [6]u8 = undefined;\n"
                 ));
@@ -361,9 +1028,7 @@ fn emit_zig_deserialize_field(name: &str, t: &TypeNode, is_last: bool) -> String
                 ));
                 s.push_str(&format!("            i += {name}_n;\n"));
             } else {
-                s.push_str(&format!(
-                    "            if (i + 1 > data.len) return error.EndOfStream;\n"
-                ));
+                s.push_str("            if (i + 1 > data.len) return error.EndOfStream;\n");
                 s.push_str(&format!("            const {name}: u8 = data[i];\n"));
                 s.push_str("            i += 1;\n");
             }
@@ -457,8 +1122,8 @@ fn emit_zig_deserialize_field(name: &str, t: &TypeNode, is_last: bool) -> String
             }
         }
 
-        DateTime(len) => {
-            // treat as i64 on the wire
+        DateTime(len, unit) => {
+            let (wire, _, from, arg) = zig_time(unit);
             if len.is_some() {
                 s.push_str(&format!(
                     "            const {name}_n: usize = {n};\n",
@@ -466,18 +1131,224 @@ fn emit_zig_deserialize_field(name: &str, t: &TypeNode, is_last: bool) -> String
                     n = emit_zig_len_expr(len.as_ref().unwrap())
                 ));
                 s.push_str(&format!(
-                    "            var {name} = try allocator.alloc(i64, {name}_n);\n"
+                    "            var {name} = try allocator.alloc(i128, {name}_n);\n"
                 ));
                 s.push_str(&format!(
                     "            var _k: usize = 0; while (_k < {name}_n) : (_k += 1) {{\n"
                 ));
-                read_int_elem(&mut s, "_tmp", "i64");
-                s.push_str(&format!("                {name}[_k] = _tmp;\n"));
+                read_int_elem(&mut s, "_tmp", wire);
+                s.push_str(&format!("                {name}[_k] = {from}(_tmp{arg});\n"));
                 s.push_str("            }\n");
             } else {
-                read_int(&mut s, "i64");
+                s.push_str("            if (i + 8 > data.len) return error.EndOfStream;\n");
+                s.push_str(&format!(
+                    "            const {name}: i128 = {from}(std.mem.readIntLittle({wire}, data[i .. i + 8]){arg});\n"
+                ));
+                s.push_str("            i += 8;\n");
+            }
+        }
+
+        Bool(len, _) => {
+            if let Some(expr) = len {
+                s.push_str(&format!(
+                    "            const {name}_n: usize = {n};\n",
+                    n = emit_zig_len_expr(expr)
+                ));
+                s.push_str(&format!(
+                    "            if (i + {name}_n > data.len) return error.EndOfStream;\n"
+                ));
+                s.push_str(&format!(
+                    "            var {name} = try allocator.alloc(bool, {name}_n);\n"
+                ));
+                s.push_str(&format!(
+                    "            var _k: usize = 0; while (_k < {name}_n) : (_k += 1) {{ {name}[_k] = data[i + _k] != 0; }}\n"
+                ));
+                s.push_str(&format!("            i += {name}_n;\n"));
+            } else {
+                s.push_str("            if (i + 1 > data.len) return error.EndOfStream;\n");
+                s.push_str(&format!("            const {name}: bool = data[i] != 0;\n"));
+                s.push_str("            i += 1;\n");
             }
         }
+
+        Fixed(len, base, frac) => {
+            let wire = zig_fixed_int(base);
+            let scale = zig_fixed_scale(*frac);
+            let w = base.width_bytes();
+            if len.is_some() {
+                s.push_str(&format!(
+                    "            const {name}_n: usize = {n};\n",
+                    name = name,
+                    n = emit_zig_len_expr(len.as_ref().unwrap())
+                ));
+                s.push_str(&format!(
+                    "            var {name} = try allocator.alloc(f64, {name}_n);\n"
+                ));
+                s.push_str(&format!(
+                    "            var _k: usize = 0; while (_k < {name}_n) : (_k += 1) {{\n"
+                ));
+                read_int_elem(&mut s, "_tmp", wire);
+                s.push_str(&format!("                {name}[_k] = @intToFloat(f64, _tmp) / {scale};\n"));
+                s.push_str("            }\n");
+            } else {
+                s.push_str(&format!("            if (i + {w} > data.len) return error.EndOfStream;\n"));
+                s.push_str(&format!(
+                    "            const {name}: f64 = @intToFloat(f64, std.mem.readIntLittle({wire}, data[i .. i + {w}])) / {scale};\n"
+                ));
+                s.push_str(&format!("            i += {w};\n"));
+            }
+        }
+
+        // Each element is decoded from the rest of the data and reports the bytes it used
+        Repeat(elem, bound) => {
+            let mut read = String::from("                var _used: usize = 0;\n");
+            read.push_str(&format!(
+                "                const _item = try {elem}.deserializePrefix(allocator, data[i..], &_used);\n"
+            ));
+            read.push_str("                i += _used;\n");
+            read.push_str(&format!("                try {name}_list.append(_item);\n"));
+            s.push_str(&format!("            var {name}_list = std.ArrayList({elem}).init(allocator);\n"));
+            match bound {
+                RepeatBound::Eof => {
+                    s.push_str("            while (i < data.len) {\n");
+                    s.push_str(&read);
+                    s.push_str("            }\n");
+                }
+                RepeatBound::Until(cond) => {
+                    s.push_str("            while (true) {\n");
+                    s.push_str(&read);
+                    s.push_str(&format!(
+                        "                if ({} != 0.0) break;\n",
+                        emit_zig_numeric_expr(cond, "_item.")
+                    ));
+                    s.push_str("            }\n");
+                }
+                RepeatBound::Count(expr) => {
+                    s.push_str(&format!(
+                        "            const {name}_n: usize = {n};\n",
+                        n = emit_zig_len_expr(expr)
+                    ));
+                    s.push_str(&format!("            {{ var _k: usize = 0; while (_k < {name}_n) : (_k += 1) {{\n"));
+                    s.push_str(&read);
+                    s.push_str("            } }\n");
+                }
+            }
+            s.push_str(&format!("            const {name} = try {name}_list.toOwnedSlice();\n"));
+        }
+
+        VarInt(len, enc) => {
+            let ty = zig_field_type(t);
+            let read = zig_varint(enc).1;
+            if let Some(expr) = len {
+                s.push_str(&format!(
+                    "            const {name}_n: usize = {n};\n",
+                    n = emit_zig_len_expr(expr)
+                ));
+                s.push_str(&format!(
+                    "            var {name} = try allocator.alloc({}, {name}_n);\n",
+                    ty.trim_start_matches("[]")
+                ));
+                s.push_str(&format!(
+                    "            var _k: usize = 0; while (_k < {name}_n) : (_k += 1) {{ {name}[_k] = {read}; }}\n"
+                ));
+            } else {
+                s.push_str(&format!("            const {name}: {ty} = {read};\n"));
+            }
+        }
+
+        Ipv4Address(len) | Ipv6Address(len) => {
+            let w = t.scalar_width_bytes();
+            if let Some(expr) = len {
+                s.push_str(&format!(
+                    "            const {name}_n: usize = {n};\n",
+                    n = emit_zig_len_expr(expr)
+                ));
+                s.push_str(&format!(
+                    "            var {name} = try allocator.alloc([{w}]u8, {name}_n);\n"
+                ));
+                s.push_str(&format!(
+                    "            var _k: usize = 0; while (_k < {name}_n) : (_k += 1) {{\n"
+                ));
+                s.push_str(&format!("                if (i + {w} > data.len) return error.EndOfStream;\n"));
+                s.push_str(&format!("                std.mem.copy(u8, {name}[_k][0..], data[i .. i + {w}]);\n"));
+                s.push_str(&format!("                i += {w};\n"));
+                s.push_str("            }\n");
+            } else {
+                s.push_str(&format!("            if (i + {w} > data.len) return error.EndOfStream;\n"));
+                s.push_str(&format!("            var {name}: [{w}]u8 = undefined;\n"));
+                s.push_str(&format!("            std.mem.copy(u8, {name}[0..], data[i .. i + {w}]);\n"));
+                s.push_str(&format!("            i += {w};\n"));
+            }
+        }
+
+        Port(len) => {
+            if let Some(expr) = len {
+                s.push_str(&format!(
+                    "            const {name}_n: usize = {n};\n",
+                    n = emit_zig_len_expr(expr)
+                ));
+                s.push_str(&format!(
+                    "            var {name} = try allocator.alloc(u16, {name}_n);\n"
+                ));
+                s.push_str(&format!(
+                    "            var _k: usize = 0; while (_k < {name}_n) : (_k += 1) {{\n"
+                ));
+                s.push_str("                if (i + 2 > data.len) return error.EndOfStream;\n");
+                s.push_str(&format!("                {name}[_k] = std.mem.readIntBig(u16, data[i .. i + 2]);\n"));
+                s.push_str("                i += 2;\n");
+                s.push_str("            }\n");
+            } else {
+                s.push_str("            if (i + 2 > data.len) return error.EndOfStream;\n");
+                s.push_str(&format!("            const {name}: u16 = std.mem.readIntBig(u16, data[i .. i + 2]);\n"));
+                s.push_str("            i += 2;\n");
+            }
+        }
+
+        // Strings: decoded into owned UTF-8 slices
+        FixedString(len, enc) => {
+            s.push_str(&format!(
+                "            const {name}_n: usize = {n};\n",
+                n = emit_zig_len_expr(len)
+            ));
+            s.push_str(&format!(
+                "            if (i + {name}_n > data.len) return error.EndOfStream;\n"
+            ));
+            s.push_str(&format!(
+                "            const {name} = try {dec}(allocator, stripNul(data[i .. i + {name}_n], {unit}));\n",
+                dec = decoder(enc),
+                unit = code_unit(enc)
+            ));
+            s.push_str(&format!("            i += {name}_n;\n"));
+        }
+        CString(enc) => {
+            let unit = code_unit(enc);
+            s.push_str(&format!(
+                "            const {name}_n = try cstringLen(data, i, {unit});\n"
+            ));
+            s.push_str(&format!(
+                "            const {name} = try {dec}(allocator, data[i .. i + {name}_n]);\n",
+                dec = decoder(enc)
+            ));
+            s.push_str(&format!("            i += {name}_n + {unit};\n"));
+        }
+        PString(_, enc) => {
+            let ty = if t.scalar_width_bytes() == 1 { "u8" } else { "u16" };
+            s.push_str(&format!(
+                "            if (i + @sizeOf({ty}) > data.len) return error.EndOfStream;\n"
+            ));
+            s.push_str(&format!(
+                "            const {name}_n: usize = std.mem.readIntLittle({ty}, data[i .. i + @sizeOf({ty})]);\n"
+            ));
+            s.push_str(&format!("            i += @sizeOf({ty});\n"));
+            s.push_str(&format!(
+                "            if (i + {name}_n > data.len) return error.EndOfStream;\n"
+            ));
+            s.push_str(&format!(
+                "            const {name} = try {dec}(allocator, data[i .. i + {name}_n]);\n",
+                dec = decoder(enc)
+            ));
+            s.push_str(&format!("            i += {name}_n;\n"));
+        }
     }
 
     // Helper local function for many int arrays/scalars
@@ -499,10 +1370,19 @@ fn emit_zig_deserialize_field(name: &str, t: &TypeNode, is_last: bool) -> String
  * =========================== */
 
 fn emit_zig_len_expr(e: &ExprNode) -> String {
-    format!("@intFromFloat(usize, {})", emit_zig_numeric_expr(e))
+    emit_zig_len_expr_in(e, "")
+}
+
+/// Like `emit_zig_len_expr`, with field references prefixed by `root` (e.g. `self.`).
+fn emit_zig_len_expr_in(e: &ExprNode, root: &str) -> String {
+    format!("@intFromFloat(usize, {})", emit_zig_numeric_expr(e, root))
+}
+
+fn zig_as_i64(e: &ExprNode, root: &str) -> String {
+    format!("@intFromFloat(i64, {})", emit_zig_numeric_expr(e, root))
 }
 
-fn emit_zig_numeric_expr(e: &ExprNode) -> String {
+fn emit_zig_numeric_expr(e: &ExprNode, root: &str) -> String {
     use ExprNode::*;
     match e {
         UnsignedInteger64Value(u) => format!("({}e0)", *u as f64),
@@ -514,49 +1394,72 @@ fn emit_zig_numeric_expr(e: &ExprNode) -> String {
         ValueReference(name, idx) => {
             if let Some(ix) = idx {
                 format!(
-                    "@floatFromInt(f64, {name}[{idx}])",
+                    "@floatFromInt(f64, {root}{name}[{idx}])",
                     name = name,
-                    idx = emit_zig_len_expr(ix) // index must be usize
+                    idx = emit_zig_len_expr_in(ix, root) // index must be usize
                 )
             } else {
-                format!("@floatFromInt(f64, {name})")
+                format!("@floatFromInt(f64, {root}{name})")
             }
         }
 
-        ParenthesizedExpr(x) => format!("({})", emit_zig_numeric_expr(x)),
+        ParenthesizedExpr(x) => format!("({})", emit_zig_numeric_expr(x, root)),
+
+        Plus(a,b)  => format!("({} + {})", emit_zig_numeric_expr(a, root), emit_zig_numeric_expr(b, root)),
+        Minus(a,b) => format!("({} - {})", emit_zig_numeric_expr(a, root), emit_zig_numeric_expr(b, root)),
+        Mult(a,b)  => format!("({} * {})", emit_zig_numeric_expr(a, root), emit_zig_numeric_expr(b, root)),
+        Div(a,b)   => format!("({} / {})", emit_zig_numeric_expr(a, root), emit_zig_numeric_expr(b, root)),
+        Pow(a,b)   => format!("std.math.pow(f64, {}, {})", emit_zig_numeric_expr(a, root), emit_zig_numeric_expr(b, root)),
+        Mod(a,b)   => format!("@rem({}, {})", emit_zig_numeric_expr(a, root), emit_zig_numeric_expr(b, root)),
+        Negate(a)  => format!("(-{})", emit_zig_numeric_expr(a, root)),
 
-        Plus(a,b)  => format!("({} + {})", emit_zig_numeric_expr(a), emit_zig_numeric_expr(b)),
-        Minus(a,b) => format!("({} - {})", emit_zig_numeric_expr(a), emit_zig_numeric_expr(b)),
-        Mult(a,b)  => format!("({} * {})", emit_zig_numeric_expr(a), emit_zig_numeric_expr(b)),
-        Div(a,b)   => format!("({} / {})", emit_zig_numeric_expr(a), emit_zig_numeric_expr(b)),
-        Pow(a,b)   => format!("std.math.pow(f64, {}, {})", emit_zig_numeric_expr(a), emit_zig_numeric_expr(b)),
+        // bitwise → round-trip through i64
+        BitAnd(a,b)     => format!("@floatFromInt(f64, ({} & {}))", zig_as_i64(a, root), zig_as_i64(b, root)),
+        BitOr(a,b)      => format!("@floatFromInt(f64, ({} | {}))", zig_as_i64(a, root), zig_as_i64(b, root)),
+        BitNot(a)       => format!("@floatFromInt(f64, ~{})", zig_as_i64(a, root)),
+        ShiftLeft(a,b)  => format!("@floatFromInt(f64, std.math.shl(i64, {}, {} & 63))", zig_as_i64(a, root), zig_as_i64(b, root)),
+        ShiftRight(a,b) => format!("@floatFromInt(f64, std.math.shr(i64, {}, {} & 63))", zig_as_i64(a, root), zig_as_i64(b, root)),
 
         // comparisons → 1.0/0.0
-        Gt(a,b)    => format!("(if ({} > {}) 1.0 else 0.0)", emit_zig_numeric_expr(a), emit_zig_numeric_expr(b)),
-        Gte(a,b)   => format!("(if ({} >= {}) 1.0 else 0.0)", emit_zig_numeric_expr(a), emit_zig_numeric_expr(b)),
-        Lt(a,b)    => format!("(if ({} < {}) 1.0 else 0.0)", emit_zig_numeric_expr(a), emit_zig_numeric_expr(b)),
-        Lte(a,b)   => format!("(if ({} <= {}) 1.0 else 0.0)", emit_zig_numeric_expr(a), emit_zig_numeric_expr(b)),
-        Equals(a,b)=> format!("(if (std.math.approxEqAbs(f64, {}, {}, 1e-9)) 1.0 else 0.0)", emit_zig_numeric_expr(a), emit_zig_numeric_expr(b)),
-        NotEquals(a,b)=> format!("(if (!std.math.approxEqAbs(f64, {}, {}, 1e-9)) 1.0 else 0.0)", emit_zig_numeric_expr(a), emit_zig_numeric_expr(b)),
+        Gt(a,b)    => format!("(if ({} > {}) 1.0 else 0.0)", emit_zig_numeric_expr(a, root), emit_zig_numeric_expr(b, root)),
+        Gte(a,b)   => format!("(if ({} >= {}) 1.0 else 0.0)", emit_zig_numeric_expr(a, root), emit_zig_numeric_expr(b, root)),
+        Lt(a,b)    => format!("(if ({} < {}) 1.0 else 0.0)", emit_zig_numeric_expr(a, root), emit_zig_numeric_expr(b, root)),
+        Lte(a,b)   => format!("(if ({} <= {}) 1.0 else 0.0)", emit_zig_numeric_expr(a, root), emit_zig_numeric_expr(b, root)),
+        Equals(a,b)=> format!("(if (std.math.approxEqAbs(f64, {}, {}, 1e-9)) 1.0 else 0.0)", emit_zig_numeric_expr(a, root), emit_zig_numeric_expr(b, root)),
+        NotEquals(a,b)=> format!("(if (!std.math.approxEqAbs(f64, {}, {}, 1e-9)) 1.0 else 0.0)", emit_zig_numeric_expr(a, root), emit_zig_numeric_expr(b, root)),
 
-        And(a,b)   => format!("(if (({} != 0.0) and ({} != 0.0)) 1.0 else 0.0)", emit_zig_numeric_expr(a), emit_zig_numeric_expr(b)),
-        Or(a,b)    => format!("(if (({} != 0.0) or  ({} != 0.0)) 1.0 else 0.0)", emit_zig_numeric_expr(a), emit_zig_numeric_expr(b)),
+        And(a,b)   => format!("(if (({} != 0.0) and ({} != 0.0)) 1.0 else 0.0)", emit_zig_numeric_expr(a, root), emit_zig_numeric_expr(b, root)),
+        Or(a,b)    => format!("(if (({} != 0.0) or  ({} != 0.0)) 1.0 else 0.0)", emit_zig_numeric_expr(a, root), emit_zig_numeric_expr(b, root)),
+        Not(a)     => format!("(if ({} == 0.0) 1.0 else 0.0)", emit_zig_numeric_expr(a, root)),
 
         ActivationRecord(name, args) => {
-            let args_s: Vec<String> = args.iter().map(|arg0: &Box<ExprNode>| emit_zig_numeric_expr(arg0)).collect();
+            let args_s: Vec<String> = args.iter().map(|a| emit_zig_numeric_expr(a, root)).collect();
             match name.as_str() {
                 "sqrt" => format!("std.math.sqrt({})", args_s[0]),
                 "min"  => format!("std.math.min({}, {})", args_s[0], args_s[1]),
                 "max"  => format!("std.math.max({}, {})", args_s[0], args_s[1]),
-                _ => "0.0".into(),
+                "abs"   => format!("@fabs({})", args_s[0]),
+                "floor" => format!("@floor({})", args_s[0]),
+                "ceil"  => format!("@ceil({})", args_s[0]),
+                "round" => format!("@round({})", args_s[0]),
+                "log2"  => format!("@log2({})", args_s[0]),
+                "clamp" => format!("std.math.clamp({}, {}, {})", args_s[0], args_s[1], args_s[2]),
+                "pow"   => format!("std.math.pow(f64, {}, {})", args_s[0], args_s[1]),
+                // bit functions → round-trip through the i64's bits
+                "popcount" => format!("@floatFromInt(f64, @popCount(@bitCast(u64, {})))", zig_as_i64(&args[0], root)),
+                "bswap16"  => format!("@floatFromInt(f64, @byteSwap(@truncate(u16, @bitCast(u64, {}))))", zig_as_i64(&args[0], root)),
+                "bswap32"  => format!("@floatFromInt(f64, @byteSwap(@truncate(u32, @bitCast(u64, {}))))", zig_as_i64(&args[0], root)),
+                "bswap64"  => format!("@floatFromInt(f64, @byteSwap(@bitCast(u64, {})))", zig_as_i64(&args[0], root)),
+                // an `extern fn` taking and returning f64
+                _ => format!("{}({})", name, args_s.join(", ")),
             }
         }
 
         GuardExpression(c,t,f) => format!(
             "(if ({} != 0.0) {} else {})",
-            emit_zig_numeric_expr(c),
-            emit_zig_numeric_expr(t),
-            emit_zig_numeric_expr(f)
+            emit_zig_numeric_expr(c, root),
+            emit_zig_numeric_expr(t, root),
+            emit_zig_numeric_expr(f, root)
         ),
 
         AggregateSum(_) | AggregateProduct(_) | NoExpr => "0.0".into(),
@@ -567,9 +1470,44 @@ fn emit_zig_numeric_expr(e: &ExprNode) -> String {
  * Utilities
  * =========================== */
 
+/// Renders DSL `///` docs as Zig doc comments.
+fn doc_comment(doc: &Option<String>, pad: &str) -> String {
+    let Some(doc) = doc else {
+        return String::new();
+    };
+    doc.lines()
+        .map(|l| if l.is_empty() { format!("{pad}///\n") } else { format!("{pad}/// {l}\n") })
+        .collect()
+}
+
 fn indent(s: &str, tabs: usize) -> String {
     let pad = "    ".repeat(tabs);
     s.lines()
         .map(|l| if l.is_empty() { "\n".to_string() } else { format!("{pad}{l}\n") })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::load_source;
+
+    fn zig(src: &str) -> String {
+        ZigGenerator::generate(&load_source(src))
+    }
+
+    #[test]
+    fn strings_that_do_not_fit_are_rejected() {
+        let out = zig("packet Names be { tag string[4], short pstring<uint8>, long pstring<uint16> }");
+        assert!(out.contains("if (tag_b.len > tag_n) return error.StringTooLong;"));
+        assert!(out.contains("if (short_b.len > std.math.maxInt(u8)) return error.StringTooLong;"));
+        assert!(out.contains("if (long_b.len > std.math.maxInt(u16)) return error.StringTooLong;"));
+    }
+
+    #[test]
+    fn bad_magic_is_an_error() {
+        let out = zig("packet Flap be { start uint8 = 0x2a, v uint16 }");
+        assert!(out.contains("    pub const start: u8 = 0x2a;\n"));
+        assert!(out.contains("if (start_wire != Flap.start) return error.WrongConstant;"));
+    }
+}
@@ -0,0 +1,462 @@
+// loader.rs
+//
+// Resolves `import "x.packet";` statements and merges every reachable file
+// into one PacketExprList.
+// - Import paths are relative to the directory of the importing file
+// - A file reached twice (diamond imports) is only loaded once
+// - Import cycles and duplicate packet names are errors
+// - Imported packets come before the packets of the importing file
+// - All files that declare a `package` must agree on it
+// - `repeat` fields must name a packet from one of the loaded files
+// - `extern fn` names are unique across files, and every call names an
+//   intrinsic, a checksum or an `extern fn`, with the right number of arguments
+// - `sizeof(Packet)` becomes the packet's size, which must not depend on its values
+//
+// Every file read is kept in `sources` so errors can be rendered against it.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use pest::error::Error;
+
+use crate::models::parsing_models::{
+    ChecksumKind, ExprNode, ExternFn, PackageExpr, PacketExpr, PacketExprList, SourceSpan, TypeNode, intrinsic_arity,
+};
+use crate::parser::{self, Rule};
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io {
+        path: String,
+        error: std::io::Error,
+        imported_at: Option<SourceSpan>,
+    },
+    Parse {
+        file: String,
+        error: Box<Error<Rule>>,
+    },
+    ImportCycle {
+        chain: Vec<String>, // first file repeated at the end
+        span: SourceSpan,
+    },
+    DuplicatePacket {
+        name: String,
+        first: SourceSpan,
+        second: SourceSpan,
+    },
+    PackageMismatch {
+        first: Box<PackageExpr>,
+        second: Box<PackageExpr>,
+    },
+    UnknownPacket {
+        name: String,
+        span: SourceSpan, // the field that refers to it
+    },
+    UnsizedPacket {
+        name: String,
+        span: SourceSpan, // the field that takes its `sizeof`
+    },
+    DuplicateFunction {
+        name: String,
+        first: SourceSpan,
+        second: SourceSpan,
+    },
+    UnknownFunction {
+        name: String,
+        span: SourceSpan, // the field or packet that calls it
+    },
+    WrongArity {
+        name: String,
+        expected: usize,
+        found: usize,
+        span: SourceSpan,
+    },
+}
+
+#[derive(Default)]
+pub struct Loader {
+    sources: Vec<(String, String)>, // (display path, contents)
+    loaded: HashSet<PathBuf>,
+    stack: Vec<(PathBuf, String)>, // (canonical, display) of files being loaded
+    packets: Vec<PacketExpr>,
+    names: HashMap<String, SourceSpan>,
+    externs: Vec<ExternFn>,
+    package: Option<PackageExpr>,
+}
+
+impl Loader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads `path` and everything it imports.
+    pub fn load(&mut self, path: &Path) -> Result<PacketExprList, LoadError> {
+        self.load_file(path, None)?;
+        self.check_repeat_elements()?;
+        self.check_calls()?;
+        self.resolve_sizes()?;
+        Ok(PacketExprList {
+            package: self.package.as_ref().map(|p| p.name.clone()),
+            packets: std::mem::take(&mut self.packets),
+            externs: std::mem::take(&mut self.externs),
+        })
+    }
+
+    /// Source text of every file read so far, keyed by display path.
+    pub fn sources(&self) -> &[(String, String)] {
+        &self.sources
+    }
+
+    fn check_repeat_elements(&self) -> Result<(), LoadError> {
+        for field in self.packets.iter().flat_map(|p| &p.fields) {
+            if let TypeNode::Repeat(name, _) = &field.expr
+                && !self.names.contains_key(name)
+            {
+                return Err(LoadError::UnknownPacket {
+                    name: name.clone(),
+                    span: field.span.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn check_calls(&self) -> Result<(), LoadError> {
+        for (e, span) in self.packets.iter().flat_map(|p| p.exprs()) {
+            for (name, args) in e.calls() {
+                let found = args.len();
+                let expected = match intrinsic_arity(name) {
+                    Some(arity) => arity,
+                    None if ChecksumKind::from_name(name).is_some() => continue, // parser checks the range
+                    None => match self.externs.iter().find(|x| x.name == name) {
+                        Some(x) => x.params.len(),
+                        None => {
+                            return Err(LoadError::UnknownFunction { name: name.to_string(), span: span.clone() });
+                        }
+                    },
+                };
+                if found != expected {
+                    return Err(LoadError::WrongArity {
+                        name: name.to_string(),
+                        expected,
+                        found,
+                        span: span.clone(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Sizes are worked out in rounds, as a packet may repeat another or take
+    // `sizeof` one; whatever is left has no fixed size.
+    fn resolve_sizes(&mut self) -> Result<(), LoadError> {
+        let mut sizes = HashMap::<String, usize>::new();
+        loop {
+            for pkt in &mut self.packets {
+                for e in pkt.exprs_mut() {
+                    e.rewrite_calls(&|name, args| match (name, args) {
+                        ("sizeof", [ExprNode::ValueReference(p, None)]) => {
+                            sizes.get(p).map(|&n| ExprNode::UnsignedInteger64Value(n as u64))
+                        }
+                        _ => None,
+                    });
+                }
+            }
+            let found: Vec<(String, usize)> = self
+                .packets
+                .iter()
+                .filter(|p| !sizes.contains_key(&p.name))
+                .filter_map(|p| Some((p.name.clone(), p.fixed_size(&|name| sizes.get(name).copied())?)))
+                .collect();
+            if found.is_empty() {
+                break;
+            }
+            sizes.extend(found);
+        }
+        for (e, span) in self.packets.iter().flat_map(|p| p.exprs()) {
+            for (name, args) in e.calls() {
+                if let ("sizeof", [ExprNode::ValueReference(p, None)]) = (name, args) {
+                    let span = span.clone();
+                    let name = p.clone();
+                    return Err(if self.names.contains_key(p) {
+                        LoadError::UnsizedPacket { name, span }
+                    } else {
+                        LoadError::UnknownPacket { name, span }
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn load_file(&mut self, path: &Path, imported_at: Option<&SourceSpan>) -> Result<(), LoadError> {
+        let display = path.display().to_string();
+        let io_error = |error| LoadError::Io {
+            path: display.clone(),
+            error,
+            imported_at: imported_at.cloned(),
+        };
+        let canonical = fs::canonicalize(path).map_err(io_error)?;
+
+        if let Some(pos) = self.stack.iter().position(|(p, _)| *p == canonical) {
+            let mut chain: Vec<String> = self.stack[pos..].iter().map(|(_, d)| d.clone()).collect();
+            chain.push(display);
+            return Err(LoadError::ImportCycle {
+                chain,
+                span: imported_at.cloned().unwrap_or_default(),
+            });
+        }
+        if self.loaded.contains(&canonical) {
+            return Ok(());
+        }
+
+        let text = fs::read_to_string(&canonical).map_err(io_error)?;
+        self.sources.push((display.clone(), text));
+        let text = &self.sources.last().unwrap().1;
+        let file = parser::parse_file(text, &display).map_err(|error| LoadError::Parse {
+            file: display.clone(),
+            error,
+        })?;
+
+        if let Some(package) = file.package.clone() {
+            match &self.package {
+                Some(first) if first.name != package.name => {
+                    return Err(LoadError::PackageMismatch { first: Box::new(first.clone()), second: Box::new(package) });
+                }
+                Some(_) => {}
+                None => self.package = Some(package),
+            }
+        }
+
+        self.stack.push((canonical.clone(), display));
+        let base = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        for import in &file.imports {
+            self.load_file(&base.join(&import.path), Some(&import.span))?;
+        }
+        self.stack.pop();
+        self.loaded.insert(canonical);
+
+        for packet in file.packets {
+            if let Some(first) = self.names.get(&packet.name) {
+                return Err(LoadError::DuplicatePacket {
+                    name: packet.name.clone(),
+                    first: first.clone(),
+                    second: packet.span.clone(),
+                });
+            }
+            self.names.insert(packet.name.clone(), packet.span.clone());
+            self.packets.push(packet);
+        }
+        for x in file.externs {
+            if let Some(first) = self.externs.iter().find(|y| y.name == x.name) {
+                return Err(LoadError::DuplicateFunction {
+                    name: x.name.clone(),
+                    first: first.span.clone(),
+                    second: x.span.clone(),
+                });
+            }
+            self.externs.push(x);
+        }
+        Ok(())
+    }
+}
+
+/// Loads `src` through a scratch file, the way `main` loads its input; for generator tests.
+#[cfg(test)]
+pub fn load_source(src: &str) -> PacketExprList {
+    let dir = tests::scratch_dir();
+    let path = dir.join("main.packet");
+    fs::write(&path, src).unwrap();
+    let loaded = Loader::new().load(&path);
+    let _ = fs::remove_dir_all(&dir);
+    loaded.unwrap_or_else(|e| panic!("failed to load:\n{src}\n{e:?}"))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A fresh, empty directory under the system temp dir.
+    pub(crate) fn scratch_dir() -> PathBuf {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let n = NEXT.fetch_add(1, Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("packet_builder_{}_{n}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Writes `files` (relative path, contents) into a scratch dir and loads the first one.
+    fn load(files: &[(&str, &str)]) -> Result<PacketExprList, LoadError> {
+        let dir = scratch_dir();
+        for (name, text) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        let loaded = Loader::new().load(&dir.join(files[0].0));
+        let _ = fs::remove_dir_all(&dir);
+        loaded
+    }
+
+    fn names(list: &PacketExprList) -> Vec<&str> {
+        list.packets.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    #[test]
+    fn imports_resolve_relative_to_the_importing_file() {
+        let list = load(&[
+            ("main.packet", "import \"proto/a.packet\"; packet Main { x uint8 }"),
+            ("proto/a.packet", "import \"b.packet\"; packet A { x uint8 }"),
+            ("proto/b.packet", "packet B { x uint8 }"),
+        ])
+        .unwrap();
+        assert_eq!(names(&list), ["B", "A", "Main"]);
+    }
+
+    #[test]
+    fn diamond_imports_are_loaded_once() {
+        let list = load(&[
+            ("main.packet", "import \"left.packet\"; import \"right.packet\"; packet Main { x uint8 }"),
+            ("left.packet", "import \"base.packet\"; packet Left { x uint8 }"),
+            ("right.packet", "import \"base.packet\"; packet Right { x uint8 }"),
+            ("base.packet", "packet Base { x uint8 }"),
+        ])
+        .unwrap();
+        assert_eq!(names(&list), ["Base", "Left", "Right", "Main"]);
+    }
+
+    #[test]
+    fn later_keywords_are_still_valid_field_names() {
+        let src = "packet Main { import uint8, package uint8, not uint8, x bytes[not],
+            repeat uint8, until uint8, eof uint8, y bytes[eof], items repeat Main until eof,
+            within uint8, within(within) { z uint8 },
+            where uint8, assert uint8, in uint8 where in in [1..3], assert in <= where,
+            if uint8, maybe uint8 if if > 0, pad uint8, align uint8, pad(1), align(4),
+            crc32 uint32, crc16_ccitt uint16, inet_checksum uint16, adler32 uint32, sum uint32 = crc32(crc32..adler32),
+            extern uint8, fn uint8, len uint8, round uint8, calc r uint8 = round(len) }";
+        let list = load(&[("main.packet", src)]).unwrap();
+        let ids: Vec<&str> = list.packets[0].fields.iter().map(|f| f.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            [
+                "import", "package", "not", "x", "repeat", "until", "eof", "y", "items", "within", "z", "where", "assert",
+                "in", "if", "maybe", "pad", "align", "_pad18", "_align19", "crc32", "crc16_ccitt", "inet_checksum", "adler32",
+                "sum", "extern", "fn", "len", "round",
+            ]
+        );
+    }
+
+    #[test]
+    fn imports_share_the_package_of_the_importing_file() {
+        let list = load(&[
+            ("main.packet", "package oscar.v1; import \"other.packet\"; packet Main { x uint8 }"),
+            ("other.packet", "package oscar.v1; packet Other { x uint8 }"),
+        ])
+        .unwrap();
+        assert_eq!(list.package.as_deref(), Some("oscar.v1"));
+    }
+
+    #[test]
+    fn conflicting_packages_are_rejected() {
+        let err = load(&[
+            ("main.packet", "package oscar.v1; import \"other.packet\"; packet Main { x uint8 }"),
+            ("other.packet", "package oscar.v2; packet Other { x uint8 }"),
+        ])
+        .unwrap_err();
+        match err {
+            LoadError::PackageMismatch { first, second } => {
+                assert_eq!((first.name.as_str(), second.name.as_str()), ("oscar.v1", "oscar.v2"));
+            }
+            other => panic!("expected PackageMismatch, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn cycles_report_the_whole_chain() {
+        let err = load(&[
+            ("a.packet", "import \"b.packet\"; packet A { x uint8 }"),
+            ("b.packet", "import \"a.packet\"; packet B { x uint8 }"),
+        ])
+        .unwrap_err();
+        match err {
+            LoadError::ImportCycle { chain, span } => {
+                let files: Vec<&str> = chain.iter().map(|c| c.rsplit('/').next().unwrap()).collect();
+                assert_eq!(files, ["a.packet", "b.packet", "a.packet"]);
+                assert!(span.file.ends_with("b.packet"));
+            }
+            other => panic!("expected ImportCycle, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn packets_defined_twice_are_rejected() {
+        let err = load(&[
+            ("main.packet", "import \"other.packet\"; packet Hello { x uint8 }"),
+            ("other.packet", "packet Hello { y uint8 }"),
+        ])
+        .unwrap_err();
+        match err {
+            LoadError::DuplicatePacket { name, first, second } => {
+                assert_eq!(name, "Hello");
+                assert!(first.file.ends_with("other.packet"));
+                assert!(second.file.ends_with("main.packet"));
+            }
+            other => panic!("expected DuplicatePacket, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn missing_imports_point_at_the_import() {
+        let err = load(&[("main.packet", "import \"nowhere.packet\"; packet Main { x uint8 }")]).unwrap_err();
+        match err {
+            LoadError::Io { path, imported_at: Some(span), .. } => {
+                assert!(path.ends_with("nowhere.packet"));
+                assert!(span.file.ends_with("main.packet"));
+            }
+            other => panic!("expected Io, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn syntax_errors_name_the_file() {
+        let err = load(&[
+            ("main.packet", "import \"bad.packet\"; packet Main { x uint8 }"),
+            ("bad.packet", "packet Bad { x uint8 y uint8 }"),
+        ])
+        .unwrap_err();
+        match err {
+            LoadError::Parse { file, .. } => assert!(file.ends_with("bad.packet")),
+            other => panic!("expected Parse, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn fixed_point_fractions_must_fit_their_base() {
+        let err = load(&[("main.packet", "packet F { gain fixed<int8, 9> }")]).unwrap_err();
+        match err {
+            LoadError::Parse { error, .. } => {
+                assert!(error.to_string().contains("`gain` has 9 fraction bits but its base holds only 8"));
+            }
+            other => panic!("expected Parse, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn reserved_is_not_a_name() {
+        for src in ["packet R { a uint8, reserved bytes[4] }", "packet R { n uint8, b bytes[reserved] }"] {
+            match load(&[("main.packet", src)]).unwrap_err() {
+                LoadError::Parse { error, .. } => {
+                    assert!(error.to_string().contains("`reserved` is a keyword (`reserved uint16` is filler) and cannot be a name"));
+                }
+                other => panic!("expected Parse, got {other:?}"),
+            }
+        }
+        // an integer field named `reserved` is read as filler
+        let list = load(&[("main.packet", "packet R { reserved uint16 }")]).unwrap();
+        assert_eq!(list.packets[0].fields[0].expr, TypeNode::Pad(2));
+    }
+}
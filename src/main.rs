@@ -1,16 +1,15 @@
-#[macro_use]
 extern crate pest;
 
 #[macro_use]
 extern crate serde;
 
-#[macro_use]
 extern crate pest_derive;
 
-#[macro_use]
 extern crate lazy_static;
 
+mod cli;
 mod generators;
+mod loader;
 mod models;
 mod parser;
 mod utilities;
@@ -22,18 +21,48 @@ use crate::generators::zig_generator::ZigGenerator;
 use crate::generators::net_generator::CSharpGenerator;
 use crate::generators::python_generator::PythonGenerator;
 use crate::generators::go_generator::GoGenerator;
+use crate::generators::swift_generator::SwiftGenerator;
+use crate::generators::wireshark_generator::WiresharkGenerator;
+use crate::generators::kaitai_generator::KaitaiGenerator;
+use crate::generators::json_generator::JsonGenerator;
+use crate::generators::markdown_generator::MarkdownGenerator;
+use crate::models::parsing_models::PacketExprList;
 
 use std::fs;
 use std::fs::File;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 fn main() -> std::io::Result<()> {
     println!("--- Packet Builder ---");
-    let file =
-        fs::read_to_string("./test_packet.packet").expect("Something went wrong reading the file");
-    let packet = parser::parse_file(&file).unwrap();
+    let options = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            std::process::exit(2);
+        }
+    };
+    let mut loader = loader::Loader::new();
+    let packet = match loader.load(Path::new(&options.input)) {
+        Ok(packet) => packet,
+        Err(e) => {
+            errors::report_load_error(&e, loader.sources());
+            std::process::exit(1);
+        }
+    };
 
-    for item in vec!["c", "rust", "zig", "net", "python", "go"] {
+    for item in options.targets.iter().map(String::as_str) {
+        // a later --package for the same target wins
+        let override_package = options
+            .package_overrides
+            .iter()
+            .rev()
+            .find(|(target, _)| target == item)
+            .map(|(_, name)| name.clone());
+        // extern fn calls take the name this target maps them to
+        let mut packet = packet.for_target(item);
+        if override_package.is_some() {
+            packet.package = override_package;
+        }
         let packet_result = match item {
             "c" => CGenerator::generate(&packet),
             "rust" => RustGenerator::generate(&packet),
@@ -41,6 +70,11 @@ fn main() -> std::io::Result<()> {
             "net" => CSharpGenerator::generate(&packet),
             "python" => PythonGenerator::generate(&packet),
             "go" => GoGenerator::generate(&packet),
+            "swift" => SwiftGenerator::generate(&packet),
+            "wireshark" => WiresharkGenerator::generate(&packet),
+            "kaitai" => KaitaiGenerator::generate(&packet),
+            "json" => JsonGenerator::generate(&packet),
+            "markdown" => MarkdownGenerator::generate(&packet),
             _ => String::new(),
         };
         let file_extension = match item {
@@ -50,16 +84,47 @@ fn main() -> std::io::Result<()> {
             "net" => "cs",
             "python" => "py",
             "go" => "go",
+            "swift" => "swift",
+            "wireshark" => "lua",
+            "kaitai" => "ksy",
+            "json" => "json",
+            "markdown" => "md",
             _ => "",
         };
 
         if !Path::new("./results").exists() {
             fs::create_dir("./results")?;
         }
-        let filename = format!("./results/{}.{}", "packets", file_extension);
+        let filename = output_path(item, file_extension, &packet)?;
         File::create(&filename)?;
         fs::write(&filename, packet_result)?;
+
+        if item == "json" {
+            fs::write("./results/packets.schema.json", JsonGenerator::generate_schema())?;
+        }
     }
-    println!("{}", "Done!");
+    println!("Done!");
     Ok(())
 }
+
+/// `./results/packets.<ext>`, except Python output with a package, which is
+/// laid out as a module: `oscar.v1` -> `./results/oscar/v1.py` (+ `__init__.py`).
+fn output_path(target: &str, extension: &str, packet: &PacketExprList) -> std::io::Result<PathBuf> {
+    let mut path = PathBuf::from("./results");
+    let segments = packet.package_segments();
+    match segments.split_last() {
+        Some((module, parents)) if target == "python" => {
+            for dir in parents {
+                path.push(dir.to_lowercase());
+                fs::create_dir_all(&path)?;
+                let init = path.join("__init__.py");
+                if !init.exists() {
+                    File::create(init)?;
+                }
+            }
+            path.push(format!("{}.{}", module.to_lowercase(), extension));
+        }
+        _ => path.push(format!("packets.{}", extension)),
+    }
+    Ok(path)
+}
@@ -1,2 +1 @@
-pub mod parsing_models;
-pub mod codegen_models;
\ No newline at end of file
+pub mod parsing_models;
\ No newline at end of file
@@ -1,92 +1,845 @@
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Endianness {
     Le,
     Be,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+// The serde names below are part of the JSON export format (see
+// generators::json_generator); renaming them is a breaking change.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", content = "args")]
 pub enum ExprNode {
+    #[serde(rename = "none")]
     NoExpr,
+    #[serde(rename = "uint")]
     UnsignedInteger64Value(u64),
+    #[serde(rename = "int")]
     Integer64Value(i64),
+    #[serde(rename = "float")]
     Float64Value(f64),
+    #[serde(rename = "string")]
     StringValue(String),
 
     // identifiers and optional index expression (e.g., foo[expr])
+    #[serde(rename = "ref")]
     ValueReference(String, Option<Box<ExprNode>>),
 
     // function call: name(args...)
-    ActivationRecord(String, Vec<Box<ExprNode>>),
+    #[serde(rename = "call")]
+    ActivationRecord(String, Vec<ExprNode>),
 
+    #[serde(rename = "paren")]
     ParenthesizedExpr(Box<ExprNode>),
+    #[serde(rename = "when")]
     GuardExpression(Box<ExprNode>, Box<ExprNode>, Box<ExprNode>), // when cond then a otherwise b
 
     // aggregates: sumof foo / productof bar
+    #[serde(rename = "sumof")]
     AggregateSum(String),
+    #[serde(rename = "productof")]
     AggregateProduct(String),
 
     // arithmetic
+    #[serde(rename = "+")]
     Plus(Box<ExprNode>, Box<ExprNode>),
+    #[serde(rename = "-")]
     Minus(Box<ExprNode>, Box<ExprNode>),
+    #[serde(rename = "*")]
     Mult(Box<ExprNode>, Box<ExprNode>),
+    #[serde(rename = "/")]
     Div(Box<ExprNode>, Box<ExprNode>),
+    #[serde(rename = "%")]
+    Mod(Box<ExprNode>, Box<ExprNode>), // truncated, like C
+    #[serde(rename = "^")]
     Pow(Box<ExprNode>, Box<ExprNode>),
+    #[serde(rename = "neg")]
+    Negate(Box<ExprNode>),
+
+    // bitwise (operands treated as 64-bit integers)
+    #[serde(rename = "&")]
+    BitAnd(Box<ExprNode>, Box<ExprNode>),
+    #[serde(rename = "|")]
+    BitOr(Box<ExprNode>, Box<ExprNode>),
+    #[serde(rename = "~")]
+    BitNot(Box<ExprNode>),
+    #[serde(rename = "<<")]
+    ShiftLeft(Box<ExprNode>, Box<ExprNode>),
+    #[serde(rename = ">>")]
+    ShiftRight(Box<ExprNode>, Box<ExprNode>),
 
     // comparisons
+    #[serde(rename = ">")]
     Gt(Box<ExprNode>, Box<ExprNode>),
+    #[serde(rename = ">=")]
     Gte(Box<ExprNode>, Box<ExprNode>),
+    #[serde(rename = "<")]
     Lt(Box<ExprNode>, Box<ExprNode>),
+    #[serde(rename = "<=")]
     Lte(Box<ExprNode>, Box<ExprNode>),
+    #[serde(rename = "==")]
     Equals(Box<ExprNode>, Box<ExprNode>),
+    #[serde(rename = "!=")]
     NotEquals(Box<ExprNode>, Box<ExprNode>),
 
     // boolean
+    #[serde(rename = "and")]
     And(Box<ExprNode>, Box<ExprNode>),
+    #[serde(rename = "or")]
     Or(Box<ExprNode>, Box<ExprNode>),
+    #[serde(rename = "not")]
+    Not(Box<ExprNode>),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "args")]
 pub enum TypeNode {
+    #[serde(rename = "int8")]
     Integer8(Option<ExprNode>),
+    #[serde(rename = "uint8")]
     UnsignedInteger8(Option<ExprNode>),
+    #[serde(rename = "int16")]
     Integer16(Option<ExprNode>),
+    #[serde(rename = "uint16")]
     UnsignedInteger16(Option<ExprNode>),
+    #[serde(rename = "int32")]
     Integer32(Option<ExprNode>),
+    #[serde(rename = "uint32")]
     UnsignedInteger32(Option<ExprNode>),
+    #[serde(rename = "int64")]
     Integer64(Option<ExprNode>),
+    #[serde(rename = "uint64")]
     UnsignedInteger64(Option<ExprNode>),
+    #[serde(rename = "float32")]
     Float32(Option<ExprNode>),
+    #[serde(rename = "float64")]
     Float64(Option<ExprNode>),
+    #[serde(rename = "macaddress")]
     MacAddress(Option<ExprNode>),
-    DateTime(Option<ExprNode>),
+    #[serde(rename = "datetime")]
+    DateTime(Option<ExprNode>, TimeEncoding), // always 8 bytes on the wire
+    #[serde(rename = "bytes")]
     Bytes(Option<ExprNode>), // opaque blob
+    #[serde(rename = "bool")]
+    Bool(Option<ExprNode>, BoolWidth), // bit-width bools never carry a length
+    #[serde(rename = "fixed")]
+    Fixed(Option<ExprNode>, FixedBase, u8), // fixed<base, n>: raw integer / 2^n
+    #[serde(rename = "varint")]
+    VarInt(Option<ExprNode>, VarIntEncoding), // 1 to 10 bytes; takes no endianness
+
+    // always big-endian on the wire, whatever the packet says
+    #[serde(rename = "ipv4")]
+    Ipv4Address(Option<ExprNode>),
+    #[serde(rename = "ipv6")]
+    Ipv6Address(Option<ExprNode>),
+    #[serde(rename = "port")]
+    Port(Option<ExprNode>),
+
+    // strings; lengths are in bytes, not characters
+    #[serde(rename = "string")]
+    FixedString(ExprNode, StringEncoding), // string[n]: NUL-padded to n bytes
+    #[serde(rename = "cstring")]
+    CString(StringEncoding), // NUL-terminated (a 2-byte NUL for utf16le)
+    #[serde(rename = "pstring")]
+    PString(LengthPrefix, StringEncoding), // pstring<uint8|uint16>: byte count, then bytes
+
+    // sub-packets of the named packet, back to back
+    #[serde(rename = "repeat")]
+    Repeat(String, RepeatBound),
+
+    // filler: written as zeros, skipped when read and not stored
+    #[serde(rename = "pad")]
+    Pad(usize), // `pad(n)`; `reserved uint16` is `pad(2)`
+    #[serde(rename = "align")]
+    Align(usize), // zeros up to the next multiple of n bytes from the start of the packet
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Where a `repeat` field stops reading elements.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RepeatBound {
+    Eof,             // `until eof`: to the end of the enclosing data
+    Until(ExprNode), // `until (cond)`: through the first element whose fields satisfy cond
+    Count(ExprNode), // `[n]`: exactly n elements
+}
+
+/// Text encoding of a string field. Decoders reject bytes that are not valid in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StringEncoding {
+    Ascii,
+    Utf8,
+    Utf16Le,
+}
+
+/// Epoch and unit of a `datetime` field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeEncoding {
+    #[serde(rename = "unix_s")]
+    UnixSeconds, // i64 seconds since 1970-01-01 UTC
+    #[serde(rename = "unix_ms")]
+    UnixMillis,
+    #[serde(rename = "unix_us")]
+    UnixMicros,
+    #[serde(rename = "ntp64")]
+    Ntp64, // u64: seconds since 1900-01-01 in the high 32 bits, binary fraction in the low 32
+    #[serde(rename = "filetime")]
+    FileTime, // u64 100 ns ticks since 1601-01-01 UTC
+}
+
+/// Storage of a `bool` field on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BoolWidth {
+    Byte, // 0 or 1; any nonzero byte decodes as true
+    Bit,  // packed MSB first with neighbouring `bool(bit)` fields
+}
+
+/// Wire form of a variable-length integer field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VarIntEncoding {
+    Leb128, // `varuint`: u64, 7 bits per byte, least significant group first, high bit = more
+    ZigZag, // `varint`: i64 mapped to u64 by zigzag (0, -1, 1, -2, ...), then LEB128
+    Quic,   // `quicvarint`: u62; the top 2 bits of the first byte give 1, 2, 4 or 8 bytes, big-endian
+}
+
+/// Integer that holds the raw value of a `fixed<base, n>` field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FixedBase {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+}
+
+impl FixedBase {
+    pub fn width_bytes(&self) -> usize {
+        match self {
+            FixedBase::Int8 | FixedBase::UInt8 => 1,
+            FixedBase::Int16 | FixedBase::UInt16 => 2,
+            FixedBase::Int32 | FixedBase::UInt32 => 4,
+            FixedBase::Int64 | FixedBase::UInt64 => 8,
+        }
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(self, FixedBase::Int8 | FixedBase::Int16 | FixedBase::Int32 | FixedBase::Int64)
+    }
+}
+
+/// Checksum intrinsics over the encoded bytes of a field range, as in `crc32(first..last)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumKind {
+    Crc32,        // IEEE 802.3: reflected, poly 0xEDB88320, init and final xor 0xFFFFFFFF
+    Crc16Ccitt,   // CRC-16/CCITT-FALSE: poly 0x1021, init 0xFFFF, not reflected
+    InetChecksum, // RFC 1071: ones' complement of the ones' complement sum of big-endian 16-bit words
+    Adler32,      // RFC 1950
+}
+
+impl ChecksumKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "crc32" => Some(ChecksumKind::Crc32),
+            "crc16_ccitt" => Some(ChecksumKind::Crc16Ccitt),
+            "inet_checksum" => Some(ChecksumKind::InetChecksum),
+            "adler32" => Some(ChecksumKind::Adler32),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChecksumKind::Crc32 => "crc32",
+            ChecksumKind::Crc16Ccitt => "crc16_ccitt",
+            ChecksumKind::InetChecksum => "inet_checksum",
+            ChecksumKind::Adler32 => "adler32",
+        }
+    }
+
+    /// Bytes of the result; a field holding it is an unsigned integer this wide.
+    pub fn width_bytes(&self) -> usize {
+        match self {
+            ChecksumKind::Crc32 | ChecksumKind::Adler32 => 4,
+            ChecksumKind::Crc16Ccitt | ChecksumKind::InetChecksum => 2,
+        }
+    }
+}
+
+/// A checksum call: what it computes and the first and last field it covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChecksumRange<'a> {
+    pub kind: ChecksumKind,
+    pub first: &'a str,
+    pub last: &'a str,
+}
+
+impl std::fmt::Display for ChecksumRange<'_> {
+    /// The call as written in the DSL, e.g. `crc32(hdr..payload)` or `crc32(hdr)`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.first == self.last {
+            write!(f, "{}({})", self.kind.name(), self.first)
+        } else {
+            write!(f, "{}({}..{})", self.kind.name(), self.first, self.last)
+        }
+    }
+}
+
+/// Built-in functions and the number of arguments each takes. `len(field)`
+/// and `sizeof(Packet)` never reach the generators: the parser replaces `len`
+/// with the field's count and the loader replaces `sizeof` with the size.
+pub const INTRINSICS: [(&str, usize); 16] = [
+    ("sqrt", 1),
+    ("min", 2),
+    ("max", 2),
+    ("abs", 1),
+    ("floor", 1),
+    ("ceil", 1),
+    ("round", 1),
+    ("clamp", 3),
+    ("log2", 1),
+    ("pow", 2),
+    ("popcount", 1),
+    ("bswap16", 1),
+    ("bswap32", 1),
+    ("bswap64", 1),
+    ("len", 1),
+    ("sizeof", 1),
+];
+
+pub fn intrinsic_arity(name: &str) -> Option<usize> {
+    INTRINSICS.iter().find(|(n, _)| *n == name).map(|(_, arity)| *arity)
+}
+
+/// Folds an intrinsic call whose arguments `eval` resolves. Integer arguments
+/// make `floor`, `ceil` and `round` the identity and truncate `sqrt` and
+/// `log2`, as casting their result to a length does; a float literal is
+/// rounded as written. Bit functions work on the 64-bit two's complement value.
+pub fn eval_intrinsic(name: &str, args: &[ExprNode], eval: fn(&ExprNode) -> Option<i128>) -> Option<i128> {
+    if let ("floor" | "ceil" | "round", [ExprNode::Float64Value(f)]) = (name, args) {
+        let v = match name {
+            "floor" => f.floor(),
+            "ceil" => f.ceil(),
+            _ => f.round(),
+        };
+        return Some(v as i128);
+    }
+    let v: Vec<i128> = args.iter().map(eval).collect::<Option<_>>()?;
+    let bits = |x: i128| i64::try_from(x).ok().or_else(|| u64::try_from(x).ok().map(|u| u as i64)).map(|x| x as u64);
+    match (name, &v[..]) {
+        ("sqrt", [x]) if *x >= 0 => Some(x.isqrt()),
+        ("min", [a, b]) => Some(*a.min(b)),
+        ("max", [a, b]) => Some(*a.max(b)),
+        ("abs", [x]) => x.checked_abs(),
+        ("floor" | "ceil" | "round", [x]) => Some(*x),
+        ("clamp", [x, lo, hi]) if lo <= hi => Some(*x.clamp(lo, hi)),
+        ("log2", [x]) if *x > 0 => Some(x.ilog2().into()),
+        ("pow", [base, exp]) if *exp >= 0 => ipow_i128(*base, u32::try_from(*exp).ok()?),
+        ("popcount", [x]) => Some(bits(*x)?.count_ones().into()),
+        ("bswap16", [x]) => Some((bits(*x)? as u16).swap_bytes().into()),
+        ("bswap32", [x]) => Some((bits(*x)? as u32).swap_bytes().into()),
+        ("bswap64", [x]) => Some(bits(*x)?.swap_bytes().into()),
+        _ => None,
+    }
+}
+
+/// Width of the byte count in front of a `pstring`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LengthPrefix {
+    #[serde(rename = "uint8")]
+    UnsignedInteger8,
+    #[serde(rename = "uint16")]
+    UnsignedInteger16,
+}
+
+/// Byte range of a construct inside one source file, kept for diagnostics.
+/// Not part of the JSON export.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceSpan {
+    pub file: String, // path as shown to the user
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TypeExpr {
     pub id: String,
     pub expr: TypeNode,
     pub endianness: Option<Endianness>,
+    pub value: Option<ExprNode>, // `= expr` on a const field
+    pub condition: Option<ExprNode>, // `if expr`: present only when it holds
+    pub constraint: Option<Check>, // `where expr`
+    pub doc: Option<String>, // `///` lines, markers stripped, joined with '\n'
+    #[serde(skip)]
+    pub span: SourceSpan,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl TypeExpr {
+    /// Value of a const field, folded to an integer.
+    pub fn const_value(&self) -> Option<i128> {
+        self.value.as_ref().and_then(eval_i128)
+    }
+
+    /// The const value as a literal every target accepts: hex, or decimal when negative.
+    pub fn const_literal(&self) -> Option<String> {
+        self.const_value().map(|v| if v < 0 { v.to_string() } else { format!("{v:#x}") })
+    }
+
+    /// The checksum a `crc uint32 = crc32(a..b)` field is filled with after encoding.
+    pub fn checksum(&self) -> Option<ChecksumRange<'_>> {
+        self.value.as_ref()?.as_checksum()
+    }
+
+    /// `pad`, `align` and `reserved` entries, which have no value to store.
+    pub fn is_filler(&self) -> bool {
+        matches!(self.expr, TypeNode::Pad(_) | TypeNode::Align(_))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CalculatedField {
     pub name: String,
     pub data_type: String, // textual typename as written (e.g. "uint16")
     pub expr: Box<ExprNode>,
+    pub doc: Option<String>,
+    #[serde(skip)]
+    pub span: SourceSpan,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// `within(length) { ... }`: fields `first..end` decode from exactly `length`
+/// bytes. Regions are listed outermost first and nest by field range.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Region {
+    pub length: ExprNode,
+    pub leftover: Leftover,
+    pub first: usize, // index of the first field inside
+    pub end: usize,   // index one past the last field inside
+    #[serde(skip)]
+    pub span: SourceSpan,
+}
+
+/// What decoders do when a region's fields stop short of its length.
+/// Reading past the end is always an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Leftover {
+    Error, // the default
+    Skip,  // `within(n, skip)`: jump to the end of the region
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PacketExpr {
     pub name: String,
     pub fields: Vec<TypeExpr>,
     pub calculated_fields: Vec<CalculatedField>,
+    pub regions: Vec<Region>,
+    pub asserts: Vec<Check>,
     pub endianness: Option<Endianness>,
+    pub doc: Option<String>,
+    #[serde(skip)]
+    pub span: SourceSpan,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// A `where` clause or `assert` rule. Decoders reject packets where `cond` is
+/// false and every target gets a `validate` that checks it before encoding.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Check {
+    pub cond: ExprNode,
+    pub rule: String,  // the condition as written, whitespace collapsed
+    pub field: String, // field reported in errors: the declared one, or the first an assert reads
+    #[serde(skip)]
+    pub span: SourceSpan,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PacketExprList {
+    pub package: Option<String>, // dotted name from `package a.b;`
     pub packets: Vec<PacketExpr>,
+    pub externs: Vec<ExternFn>,
+}
+
+/// `extern fn popcount(x) -> uint8 { c: "__builtin_popcount", ... }`: a
+/// function the generated code calls under the name given for its target.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExternFn {
+    pub name: String,
+    pub params: Vec<String>,
+    pub returns: String,                // textual typename as written (e.g. "uint8")
+    pub targets: Vec<(String, String)>, // (target, function), as declared
+    pub doc: Option<String>,
+    #[serde(skip)]
+    pub span: SourceSpan,
+}
+
+impl ExternFn {
+    /// Targets a mapping may name: those whose expressions call functions.
+    pub const TARGETS: [&'static str; 7] = ["c", "rust", "zig", "net", "python", "swift", "wireshark"];
+
+    /// The function `target` calls; its DSL name when no mapping is given.
+    pub fn function_for(&self, target: &str) -> &str {
+        self.targets.iter().find(|(t, _)| t == target).map_or(&self.name, |(_, f)| f)
+    }
+}
+
+/// Where a `bool(bit)` field sits inside its run of consecutive bit fields.
+/// A run is packed MSB first and padded with zero bits to a whole byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitSlot {
+    pub index: usize, // position in the run
+    pub last: bool,   // the next field is not a bit field
+}
+
+impl BitSlot {
+    /// First bit of a new byte: encoders append a zero byte here.
+    pub fn starts_byte(&self) -> bool {
+        self.index.is_multiple_of(8)
+    }
+
+    /// Last bit read from the current byte: decoders advance past it here.
+    pub fn ends_byte(&self) -> bool {
+        self.index % 8 == 7 || self.last
+    }
+
+    /// Left shift of this bit inside its byte.
+    pub fn shift(&self) -> usize {
+        7 - self.index % 8
+    }
+}
+
+impl PacketExpr {
+    /// Bit slot of each field, `None` for fields that are not `bool(bit)`.
+    /// A region boundary ends a run, so a byte never straddles one.
+    pub fn bit_slots(&self) -> Vec<Option<BitSlot>> {
+        let is_bit = |i: usize| {
+            self.fields
+                .get(i)
+                .is_some_and(|f| matches!(f.expr, TypeNode::Bool(_, BoolWidth::Bit)))
+        };
+        let boundary = |i: usize| self.regions.iter().any(|r| r.first == i || r.end == i);
+        let mut run = 0;
+        (0..self.fields.len())
+            .map(|i| {
+                if !is_bit(i) || boundary(i) {
+                    run = 0;
+                }
+                if !is_bit(i) {
+                    return None;
+                }
+                let slot = BitSlot { index: run, last: !is_bit(i + 1) || boundary(i + 1) };
+                run += 1;
+                Some(slot)
+            })
+            .collect()
+    }
+
+    /// Regions that start at field `i`, outermost first, with their index in `regions`.
+    pub fn regions_opening(&self, i: usize) -> Vec<(usize, &Region)> {
+        self.regions.iter().enumerate().filter(|(_, r)| r.first == i).collect()
+    }
+
+    /// Regions whose last field is `i`, innermost first.
+    pub fn regions_closing(&self, i: usize) -> Vec<(usize, &Region)> {
+        let mut v: Vec<_> = self.regions.iter().enumerate().filter(|(_, r)| r.end == i + 1).collect();
+        v.reverse();
+        v
+    }
+
+    /// Index of the innermost region around region `k`, if it is nested.
+    pub fn enclosing_region(&self, k: usize) -> Option<usize> {
+        let inner = &self.regions[k];
+        (0..k).rev().find(|&j| self.regions[j].first <= inner.first && inner.end <= self.regions[j].end)
+    }
+
+    /// Whether field `i` is the last one before the end of the data or of a
+    /// region, so an open-ended field there can read up to that end.
+    pub fn is_last_in_scope(&self, i: usize) -> bool {
+        i + 1 == self.fields.len() || self.regions.iter().any(|r| r.end == i + 1)
+    }
+
+    /// Every rule `validate` checks: `where` clauses in field order, then asserts.
+    pub fn checks(&self) -> Vec<&Check> {
+        self.fields.iter().filter_map(|f| f.constraint.as_ref()).chain(&self.asserts).collect()
+    }
+
+    /// Every checksum the packet computes: checksum fields first, then calls in calc fields.
+    pub fn checksums(&self) -> Vec<ChecksumRange<'_>> {
+        self.fields
+            .iter()
+            .filter_map(|f| f.checksum())
+            .chain(self.calculated_fields.iter().flat_map(|cf| cf.expr.checksums()))
+            .collect()
+    }
+
+    /// Every expression in the packet with the span of what holds it: each
+    /// field's lengths, `until` condition, value, condition and rule, then calc
+    /// fields, asserts and `within` lengths.
+    pub fn exprs(&self) -> Vec<(&ExprNode, &SourceSpan)> {
+        let mut out = Vec::new();
+        for f in &self.fields {
+            let until = match &f.expr {
+                TypeNode::Repeat(_, RepeatBound::Until(e)) => Some(e),
+                _ => None,
+            };
+            let own = f.expr.length_exprs().into_iter().chain(until).chain(&f.value).chain(&f.condition);
+            out.extend(own.chain(f.constraint.as_ref().map(|c| &c.cond)).map(|e| (e, &f.span)));
+        }
+        out.extend(self.calculated_fields.iter().map(|cf| (&*cf.expr, &cf.span)));
+        out.extend(self.asserts.iter().map(|a| (&a.cond, &a.span)));
+        out.extend(self.regions.iter().map(|r| (&r.length, &r.span)));
+        out
+    }
+
+    /// Like `exprs`, for rewriting them.
+    pub fn exprs_mut(&mut self) -> Vec<&mut ExprNode> {
+        let mut out = Vec::new();
+        for f in &mut self.fields {
+            out.extend(f.expr.exprs_mut());
+            out.extend(f.value.as_mut());
+            out.extend(f.condition.as_mut());
+            out.extend(f.constraint.as_mut().map(|c| &mut c.cond));
+        }
+        out.extend(self.calculated_fields.iter_mut().map(|cf| &mut *cf.expr));
+        out.extend(self.asserts.iter_mut().map(|a| &mut a.cond));
+        out.extend(self.regions.iter_mut().map(|r| &mut r.length));
+        out
+    }
+
+    /// For each field, whether codecs record the offset where it starts and
+    /// where it ends, because a checksum range begins or stops there. The
+    /// offsets are kept as `_<field>_start` and `_<field>_end`.
+    pub fn checksum_marks(&self) -> Vec<(bool, bool)> {
+        let sums = self.checksums();
+        self.fields
+            .iter()
+            .map(|f| (sums.iter().any(|c| c.first == f.id), sums.iter().any(|c| c.last == f.id)))
+            .collect()
+    }
+
+    /// For each field, the field it counts when serializers should fill it in.
+    /// That is an integer field named by exactly one later length, where the
+    /// length is the name plus or minus a constant and counts the elements of a
+    /// `bytes`, array or `repeat [n]` field.
+    pub fn derived_counts(&self) -> Vec<Option<DerivedCount>> {
+        self.fields
+            .iter()
+            .enumerate()
+            .map(|(i, f)| {
+                if !f.expr.is_plain_integer() {
+                    return None;
+                }
+                let users: Vec<usize> = (0..self.fields.len())
+                    .filter(|&j| self.fields[j].expr.length_exprs().iter().any(|e| e.mentions(&f.id)))
+                    .collect();
+                let [source] = users[..] else { return None };
+                if self.fields[source].condition.is_some() {
+                    return None; // an absent field has no length to take
+                }
+                let count = self.fields[source].expr.count_expr()?;
+                let adjust = count_adjust(count, &f.id)?;
+                (source > i).then_some(DerivedCount { source, adjust })
+            })
+            .collect()
+    }
+
+    /// Encoded size in bytes when it does not depend on the values: no
+    /// optional, variable-length or open-ended fields. `elem` gives the size of
+    /// a packet repeated a constant number of times.
+    pub fn fixed_size(&self, elem: &dyn Fn(&str) -> Option<usize>) -> Option<usize> {
+        let mut size = 0;
+        for (f, slot) in self.fields.iter().zip(self.bit_slots()) {
+            if f.condition.is_some() {
+                return None;
+            }
+            size += match (&f.expr, slot) {
+                (_, Some(slot)) => usize::from(slot.last),
+                (TypeNode::Pad(n), None) => *n,
+                (TypeNode::Align(n), None) => (n - size % n) % n,
+                (TypeNode::Repeat(name, RepeatBound::Count(e)), None) => eval_len_count(e)? * elem(name)?,
+                (TypeNode::Bytes(None) | TypeNode::CString(_) | TypeNode::PString(_, _), None) => return None,
+                (TypeNode::VarInt(_, _) | TypeNode::Repeat(_, _), None) => return None,
+                (t, None) => match t.length_exprs().first() {
+                    Some(e) => eval_len_count(e)? * t.scalar_width_bytes(),
+                    None => t.scalar_width_bytes(),
+                },
+            };
+        }
+        Some(size)
+    }
+}
+
+impl ExprNode {
+    /// `crc32(a..b)` and the other checksum intrinsics; `crc32(a)` covers just `a`.
+    pub fn as_checksum(&self) -> Option<ChecksumRange<'_>> {
+        let ExprNode::ActivationRecord(name, args) = self else { return None };
+        let kind = ChecksumKind::from_name(name)?;
+        match &args[..] {
+            [ExprNode::ValueReference(first, None), ExprNode::ValueReference(last, None)] => {
+                Some(ChecksumRange { kind, first, last })
+            }
+            _ => None,
+        }
+    }
+
+    /// Every checksum call in the expression, in order.
+    pub fn checksums(&self) -> Vec<ChecksumRange<'_>> {
+        use ExprNode::*;
+        if let Some(c) = self.as_checksum() {
+            return vec![c];
+        }
+        match self {
+            NoExpr | UnsignedInteger64Value(_) | Integer64Value(_) | Float64Value(_) | StringValue(_) => vec![],
+            AggregateSum(_) | AggregateProduct(_) => vec![],
+            ValueReference(_, index) => index.as_ref().map(|e| e.checksums()).unwrap_or_default(),
+            ActivationRecord(_, args) => args.iter().flat_map(|e| e.checksums()).collect(),
+            ParenthesizedExpr(a) | Negate(a) | BitNot(a) | Not(a) => a.checksums(),
+            GuardExpression(c, a, b) => [c, a, b].iter().flat_map(|e| e.checksums()).collect(),
+            Plus(a, b) | Minus(a, b) | Mult(a, b) | Div(a, b) | Mod(a, b) | Pow(a, b)
+            | BitAnd(a, b) | BitOr(a, b) | ShiftLeft(a, b) | ShiftRight(a, b)
+            | Gt(a, b) | Gte(a, b) | Lt(a, b) | Lte(a, b) | Equals(a, b) | NotEquals(a, b)
+            | And(a, b) | Or(a, b) => a.checksums().into_iter().chain(b.checksums()).collect(),
+        }
+    }
+
+    /// Every function call in the expression with its arguments, outermost first.
+    pub fn calls(&self) -> Vec<(&str, &[ExprNode])> {
+        use ExprNode::*;
+        match self {
+            NoExpr | UnsignedInteger64Value(_) | Integer64Value(_) | Float64Value(_) | StringValue(_) => vec![],
+            AggregateSum(_) | AggregateProduct(_) => vec![],
+            ValueReference(_, index) => index.as_ref().map(|e| e.calls()).unwrap_or_default(),
+            ActivationRecord(name, args) => {
+                std::iter::once((name.as_str(), &args[..])).chain(args.iter().flat_map(|e| e.calls())).collect()
+            }
+            ParenthesizedExpr(a) | Negate(a) | BitNot(a) | Not(a) => a.calls(),
+            GuardExpression(c, a, b) => [c, a, b].iter().flat_map(|e| e.calls()).collect(),
+            Plus(a, b) | Minus(a, b) | Mult(a, b) | Div(a, b) | Mod(a, b) | Pow(a, b)
+            | BitAnd(a, b) | BitOr(a, b) | ShiftLeft(a, b) | ShiftRight(a, b)
+            | Gt(a, b) | Gte(a, b) | Lt(a, b) | Lte(a, b) | Equals(a, b) | NotEquals(a, b)
+            | And(a, b) | Or(a, b) => a.calls().into_iter().chain(b.calls()).collect(),
+        }
+    }
+
+    /// Replaces each call `rewrite` gives a new expression for, arguments first.
+    pub fn rewrite_calls(&mut self, rewrite: &dyn Fn(&str, &[ExprNode]) -> Option<ExprNode>) {
+        use ExprNode::*;
+        match self {
+            NoExpr | UnsignedInteger64Value(_) | Integer64Value(_) | Float64Value(_) | StringValue(_) => {}
+            AggregateSum(_) | AggregateProduct(_) => {}
+            ValueReference(_, index) => index.iter_mut().for_each(|e| e.rewrite_calls(rewrite)),
+            ActivationRecord(name, args) => {
+                args.iter_mut().for_each(|e| e.rewrite_calls(rewrite));
+                if let Some(to) = rewrite(name, args) {
+                    *self = to;
+                }
+            }
+            ParenthesizedExpr(a) | Negate(a) | BitNot(a) | Not(a) => a.rewrite_calls(rewrite),
+            GuardExpression(c, a, b) => [c, a, b].into_iter().for_each(|e| e.rewrite_calls(rewrite)),
+            Plus(a, b) | Minus(a, b) | Mult(a, b) | Div(a, b) | Mod(a, b) | Pow(a, b)
+            | BitAnd(a, b) | BitOr(a, b) | ShiftLeft(a, b) | ShiftRight(a, b)
+            | Gt(a, b) | Gte(a, b) | Lt(a, b) | Lte(a, b) | Equals(a, b) | NotEquals(a, b)
+            | And(a, b) | Or(a, b) => {
+                a.rewrite_calls(rewrite);
+                b.rewrite_calls(rewrite);
+            }
+        }
+    }
+
+    /// Whether the expression reads the field `name` anywhere.
+    pub fn mentions(&self, name: &str) -> bool {
+        use ExprNode::*;
+        match self {
+            NoExpr | UnsignedInteger64Value(_) | Integer64Value(_) | Float64Value(_) | StringValue(_) => false,
+            ValueReference(id, index) => id == name || index.as_ref().is_some_and(|e| e.mentions(name)),
+            // a checksum reads the bytes of its range, not the values there
+            ActivationRecord(_, _) if self.as_checksum().is_some() => false,
+            // `sizeof` names a packet
+            ActivationRecord(f, _) if f == "sizeof" => false,
+            ActivationRecord(_, args) => args.iter().any(|e| e.mentions(name)),
+            AggregateSum(id) | AggregateProduct(id) => id == name,
+            ParenthesizedExpr(a) | Negate(a) | BitNot(a) | Not(a) => a.mentions(name),
+            GuardExpression(c, a, b) => c.mentions(name) || a.mentions(name) || b.mentions(name),
+            Plus(a, b) | Minus(a, b) | Mult(a, b) | Div(a, b) | Mod(a, b) | Pow(a, b)
+            | BitAnd(a, b) | BitOr(a, b) | ShiftLeft(a, b) | ShiftRight(a, b)
+            | Gt(a, b) | Gte(a, b) | Lt(a, b) | Lte(a, b) | Equals(a, b) | NotEquals(a, b)
+            | And(a, b) | Or(a, b) => a.mentions(name) || b.mentions(name),
+        }
+    }
+}
+
+/// `adjust` when `e` is `name`, `name + c`, `c + name` or `name - c`.
+fn count_adjust(e: &ExprNode, name: &str) -> Option<i64> {
+    use ExprNode::*;
+    let is_name = |e: &ExprNode| matches!(e, ValueReference(id, None) if id == name);
+    let constant = |e: &ExprNode| match e {
+        UnsignedInteger64Value(u) => i64::try_from(*u).ok(),
+        Integer64Value(i) => Some(*i),
+        _ => None,
+    };
+    match e {
+        _ if is_name(e) => Some(0),
+        ParenthesizedExpr(a) => count_adjust(a, name),
+        Plus(a, b) if is_name(a) => constant(b),
+        Plus(a, b) if is_name(b) => constant(a),
+        Minus(a, b) if is_name(a) => constant(b)?.checked_neg(),
+        _ => None,
+    }
+}
+
+impl PacketExprList {
+    /// Segments of the package name (`oscar.v1` -> ["oscar", "v1"]); empty without a package.
+    pub fn package_segments(&self) -> Vec<&str> {
+        self.package.as_deref().map(|p| p.split('.').collect()).unwrap_or_default()
+    }
+
+    /// The model with every `extern fn` call renamed to the function `target` maps it to.
+    pub fn for_target(&self, target: &str) -> PacketExprList {
+        let mut out = self.clone();
+        for pkt in &mut out.packets {
+            for e in pkt.exprs_mut() {
+                e.rewrite_calls(&|name, args| {
+                    let x = self.externs.iter().find(|x| x.name == name)?;
+                    Some(ExprNode::ActivationRecord(x.function_for(target).to_string(), args.to_vec()))
+                });
+            }
+        }
+        out
+    }
+}
+
+/// `package a.b;` as written in a source file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageExpr {
+    pub name: String,
+    pub span: SourceSpan,
+}
+
+/// `import "path";` as written in a source file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportExpr {
+    pub path: String,
+    pub span: SourceSpan,
+}
+
+/// A count field that serializers compute from the field it sizes, like `l`
+/// in `v bytes[l]`. For a length written `l + adjust`, `l = len(v) - adjust`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DerivedCount {
+    pub source: usize, // index of the sized field
+    pub adjust: i64,
+}
+
+/// One parsed `.packet` file, before its imports are resolved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceFile {
+    pub package: Option<PackageExpr>,
+    pub imports: Vec<ImportExpr>,
+    pub packets: Vec<PacketExpr>,
+    pub externs: Vec<ExternFn>,
 }
 
 impl TypeNode {
@@ -103,13 +856,111 @@ impl TypeNode {
             TypeNode::Float32(_)           => 4,
             TypeNode::Float64(_)           => 8,
             TypeNode::MacAddress(_)        => 6,
-            TypeNode::DateTime(_)          => 8,
+            TypeNode::DateTime(_, _)       => 8,
             TypeNode::Bytes(_)             => 1,
+            TypeNode::Bool(_, _)           => 1, // bit fields share bytes, see BitSlot
+            TypeNode::Fixed(_, base, _)    => base.width_bytes(),
+            TypeNode::VarInt(_, _)         => 0, // variable, see VarIntEncoding
+            TypeNode::Ipv4Address(_)       => 4,
+            TypeNode::Ipv6Address(_)       => 16,
+            TypeNode::Port(_)              => 2,
+            TypeNode::FixedString(_, _)    => 1,
+            TypeNode::CString(_)           => 1,
+            TypeNode::PString(LengthPrefix::UnsignedInteger8, _)  => 1, // the prefix
+            TypeNode::PString(LengthPrefix::UnsignedInteger16, _) => 2,
+            TypeNode::Repeat(_, _)         => 0, // sized by its elements
+            TypeNode::Pad(_)               => 1,
+            TypeNode::Align(_)             => 0, // depends on the offset
         }
     }
 
-    pub fn get_type_length_bytes(&self) -> usize {
-        self.scalar_width_bytes()
+    /// Integer types without an array length, the only ones a count can be written in.
+    pub fn is_plain_integer(&self) -> bool {
+        self.int_range().is_some()
+    }
+
+    /// Smallest and largest value of an integer type, for checking const fields.
+    pub fn int_range(&self) -> Option<(i128, i128)> {
+        use TypeNode::*;
+        match self {
+            UnsignedInteger8(None) => Some((0, u8::MAX.into())),
+            Integer8(None) => Some((i8::MIN.into(), i8::MAX.into())),
+            UnsignedInteger16(None) => Some((0, u16::MAX.into())),
+            Integer16(None) => Some((i16::MIN.into(), i16::MAX.into())),
+            UnsignedInteger32(None) => Some((0, u32::MAX.into())),
+            Integer32(None) => Some((i32::MIN.into(), i32::MAX.into())),
+            UnsignedInteger64(None) | VarInt(None, VarIntEncoding::Leb128) => Some((0, u64::MAX.into())),
+            Integer64(None) | VarInt(None, VarIntEncoding::ZigZag) => Some((i64::MIN.into(), i64::MAX.into())),
+            VarInt(None, VarIntEncoding::Quic) => Some((0, (1 << 62) - 1)),
+            _ => None,
+        }
+    }
+
+    /// Every length expression in the type: array lengths, string widths and `repeat` counts.
+    pub fn length_exprs(&self) -> Vec<&ExprNode> {
+        use TypeNode::*;
+        match self {
+            FixedString(e, _) | Repeat(_, RepeatBound::Count(e)) => vec![e],
+            // `until` conditions name the element's fields, not this packet's
+            CString(_) | PString(_, _) | Repeat(_, _) | Pad(_) | Align(_) => vec![],
+            UnsignedInteger8(e)
+            | Integer8(e)
+            | UnsignedInteger16(e)
+            | Integer16(e)
+            | UnsignedInteger32(e)
+            | Integer32(e)
+            | UnsignedInteger64(e)
+            | Integer64(e)
+            | Float32(e)
+            | Float64(e)
+            | MacAddress(e)
+            | DateTime(e, _)
+            | Bytes(e)
+            | Bool(e, _)
+            | Fixed(e, _, _)
+            | VarInt(e, _)
+            | Ipv4Address(e)
+            | Ipv6Address(e)
+            | Port(e) => e.iter().collect(),
+        }
+    }
+
+    /// Every expression in the type, `until` conditions included.
+    pub fn exprs_mut(&mut self) -> Vec<&mut ExprNode> {
+        use TypeNode::*;
+        match self {
+            FixedString(e, _) | Repeat(_, RepeatBound::Count(e) | RepeatBound::Until(e)) => vec![e],
+            CString(_) | PString(_, _) | Repeat(_, RepeatBound::Eof) | Pad(_) | Align(_) => vec![],
+            UnsignedInteger8(e)
+            | Integer8(e)
+            | UnsignedInteger16(e)
+            | Integer16(e)
+            | UnsignedInteger32(e)
+            | Integer32(e)
+            | UnsignedInteger64(e)
+            | Integer64(e)
+            | Float32(e)
+            | Float64(e)
+            | MacAddress(e)
+            | DateTime(e, _)
+            | Bytes(e)
+            | Bool(e, _)
+            | Fixed(e, _, _)
+            | VarInt(e, _)
+            | Ipv4Address(e)
+            | Ipv6Address(e)
+            | Port(e) => e.iter_mut().collect(),
+        }
+    }
+
+    /// The element count of a `bytes`, array or `repeat [n]` field. Strings and
+    /// MAC addresses are left out: their lengths are widths, not counts.
+    pub fn count_expr(&self) -> Option<&ExprNode> {
+        match self {
+            TypeNode::Repeat(_, RepeatBound::Count(e)) => Some(e),
+            TypeNode::FixedString(_, _) | TypeNode::MacAddress(_) | TypeNode::Repeat(_, _) => None,
+            t => t.length_exprs().first().copied(),
+        }
     }
 
     pub fn get_length_bytes(&self) -> usize {
@@ -120,6 +971,12 @@ impl TypeNode {
                     .and_then(eval_len_count)
                     .unwrap_or(0)
             }
+            TypeNode::FixedString(len_expr, _) => eval_len_count(len_expr).unwrap_or(0),
+            // variable-length on the wire
+            TypeNode::CString(_) | TypeNode::PString(_, _) | TypeNode::VarInt(_, _) | TypeNode::Repeat(_, _) => 0,
+            TypeNode::Pad(n) => *n,
+            // zero to n - 1 bytes, by the offset it starts at
+            TypeNode::Align(_) => 0,
 
             TypeNode::UnsignedInteger8(m)
             | TypeNode::Integer8(m)
@@ -132,7 +989,12 @@ impl TypeNode {
             | TypeNode::Float32(m)
             | TypeNode::Float64(m)
             | TypeNode::MacAddress(m)
-            | TypeNode::DateTime(m) => {
+            | TypeNode::DateTime(m, _)
+            | TypeNode::Bool(m, _)
+            | TypeNode::Fixed(m, _, _)
+            | TypeNode::Ipv4Address(m)
+            | TypeNode::Ipv6Address(m)
+            | TypeNode::Port(m) => {
                 let elem = self.scalar_width_bytes();
                 let count = m.as_ref().and_then(eval_len_count).unwrap_or(1);
                 elem * count
@@ -164,12 +1026,23 @@ fn eval_i128(e: &ExprNode) -> Option<i128> {
             let rhs = eval_i128(b)?;
             if rhs == 0 { None } else { Some(eval_i128(a)? / rhs) }
         }
+        Mod(a, b)   => {
+            let rhs = eval_i128(b)?;
+            if rhs == 0 { None } else { Some(eval_i128(a)? % rhs) }
+        }
         Pow(a, b)   => {
             let base = eval_i128(a)?;
             let exp  = eval_i128(b)?;
             if exp < 0 { return None; }
             Some(ipow_i128(base, exp as u32)?)
         }
+        Negate(a)   => eval_i128(a)?.checked_neg(),
+
+        BitAnd(a, b)     => Some((eval_i64(a)? & eval_i64(b)?) as i128),
+        BitOr(a, b)      => Some((eval_i64(a)? | eval_i64(b)?) as i128),
+        BitNot(a)        => Some((!eval_i64(a)?) as i128),
+        ShiftLeft(a, b)  => Some(eval_i64(a)?.wrapping_shl((eval_i128(b)? & 63) as u32) as i128),
+        ShiftRight(a, b) => Some(eval_i64(a)?.wrapping_shr((eval_i128(b)? & 63) as u32) as i128),
 
         GuardExpression(cond, then_e, else_e) => {
             let c = eval_bool(cond)?;
@@ -186,10 +1059,12 @@ fn eval_i128(e: &ExprNode) -> Option<i128> {
 
         And(a, b)     => Some((eval_bool(a)? && eval_bool(b)?) as i128),
         Or(a, b)      => Some((eval_bool(a)? || eval_bool(b)?) as i128),
+        Not(a)        => Some((!eval_bool(a)?) as i128),
 
         // Non-constant constructs we cannot resolve here (need runtime context):
+        ActivationRecord(name, args) => eval_intrinsic(name, args, eval_i128),
+
         ValueReference(_, _) |
-        ActivationRecord(_, _) |
         AggregateSum(_) |
         AggregateProduct(_) => None,
 
@@ -208,15 +1083,25 @@ fn eval_bool(e: &ExprNode) -> Option<bool> {
         NotEquals(a,b)=> Some(eval_i128(a)? != eval_i128(b)?),
         And(a, b)     => Some(eval_bool(a)? && eval_bool(b)?),
         Or(a, b)      => Some(eval_bool(a)? || eval_bool(b)?),
+        Not(a)        => Some(!eval_bool(a)?),
         ParenthesizedExpr(inner) => eval_bool(inner),
         // allow numeric-as-bool (nonzero -> true) if it's a pure constant
         UnsignedInteger64Value(_) |
         Integer64Value(_) |
-        Float64Value(_) => Some(eval_i128(e)? != 0),
+        Float64Value(_) |
+        Negate(_) | Mod(_, _) |
+        BitAnd(_, _) | BitOr(_, _) | BitNot(_) |
+        ShiftLeft(_, _) | ShiftRight(_, _) => Some(eval_i128(e)? != 0),
         _ => None,
     }
 }
 
+// bitwise operators work on the 64-bit two's complement value
+fn eval_i64(e: &ExprNode) -> Option<i64> {
+    let v = eval_i128(e)?;
+    i64::try_from(v).ok().or_else(|| u64::try_from(v).ok().map(|u| u as i64))
+}
+
 fn ipow_i128(mut base: i128, mut exp: u32) -> Option<i128> {
     let mut acc: i128 = 1;
     while exp > 0 {
@@ -229,4 +1114,45 @@ fn ipow_i128(mut base: i128, mut exp: u32) -> Option<i128> {
         }
     }
     Some(acc)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::loader::load_source;
+
+    /// `(count field, sized field, adjust)` for each derived count in the packet.
+    fn derived(src: &str) -> Vec<(String, String, i64)> {
+        let list = load_source(src);
+        let pkt = &list.packets[0];
+        pkt.derived_counts()
+            .iter()
+            .enumerate()
+            .filter_map(|(i, d)| d.map(|d| (pkt.fields[i].id.clone(), pkt.fields[d.source].id.clone(), d.adjust)))
+            .collect()
+    }
+
+    #[test]
+    fn counts_are_derived_from_the_field_they_size() {
+        assert_eq!(
+            derived("packet P { l uint16, v bytes[l], n uint8, a uint32[n + 1], m varuint, b uint8[2 + m], k int8, c bytes[k - 4] }"),
+            [
+                ("l".to_string(), "v".to_string(), 0),
+                ("n".to_string(), "a".to_string(), 1),
+                ("m".to_string(), "b".to_string(), 2),
+                ("k".to_string(), "c".to_string(), -4),
+            ]
+        );
+    }
+
+    #[test]
+    fn counts_that_are_not_simple_are_left_alone() {
+        // named twice, scaled, a string width, a MAC address length, read before it is
+        // written, and not a plain integer
+        assert!(derived("packet P { n uint8, a bytes[n], b bytes[n] }").is_empty());
+        assert!(derived("packet P { n uint8, a bytes[n * 2] }").is_empty());
+        assert!(derived("packet P { n uint8, s string[n] }").is_empty());
+        assert!(derived("packet P { n uint8, m macaddress[n] }").is_empty());
+        assert!(derived("packet P { n uint8[2], a bytes[n[0]] }").is_empty());
+        assert!(derived("packet P { n float32, a bytes[n] }").is_empty());
+    }
+}
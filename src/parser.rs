@@ -5,16 +5,35 @@
 // - Expression-sized arrays (array_specifier = "[" expr "]")
 // - Numbers: decimal, hex (0x...), binary (0b...)
 // - Strings, intrinsics, aggregates (sumof/productof), guard expressions
+// - `///` doc comments on packets, fields and calc fields
+// - `import "x.packet";` statements (resolved by loader.rs)
+// - `package a.b;` declarations
+// - String types: `string[n]`, `cstring`, `pstring<uint8|uint16>` with an encoding
+// - `bool`, `bool(bit)` and `fixed<base, n>` types
+// - `varuint`, `varint` and `quicvarint` variable-length integers
+// - `repeat P until eof | until (cond) | [n]` lists of sub-packets
+// - `within(len[, skip]) { ... }` length-bounded groups of fields
+// - `name type = value` const fields
+// - `where` clauses, `assert` rules and `x in [a..b, c]` sets
+// - `name type if cond` optional fields
+// - `pad(n)`, `align(n)` and `reserved <int type>` filler
+// - `crc32(a..b)`-style checksums in calc fields and as field values
+// - `extern fn name(params) -> type { target: "function", ... }` declarations
+// - `len(field)`, replaced by the field's element count
 //
 // If you already have your own models, replace the models below with your crate's
 // and keep the parsing functions as-is (types and constructors are aligned).
 
-use pest::error::Error;
+use pest::error::{Error, ErrorVariant, InputLocation};
 use pest::iterators::Pair;
 use pest::Parser;
 use pest_derive::Parser;
 
-use crate::models::parsing_models::{CalculatedField, Endianness, ExprNode, PacketExpr, PacketExprList, TypeExpr, TypeNode};
+use crate::models::parsing_models::{
+    BoolWidth, CalculatedField, Check, ChecksumKind, Endianness, ExprNode, ExternFn, FixedBase, ImportExpr, LengthPrefix, Leftover, PackageExpr,
+    PacketExpr, Region, RepeatBound, SourceFile, SourceSpan, StringEncoding, TimeEncoding, TypeExpr, TypeNode, VarIntEncoding,
+    intrinsic_arity,
+};
 
 // ===============================
 // Pest Parser
@@ -24,61 +43,665 @@ use crate::models::parsing_models::{CalculatedField, Endianness, ExprNode, Packe
 #[grammar = "../grammar.pest"]
 pub struct PacketParser2;
 
-// Entry point
-pub fn parse_file(input: &str) -> Result<PacketExprList, Error<Rule>> {
-    let mut pairs = PacketParser2::parse(Rule::packets, input)?;
+// Entry point. `file` is the name recorded in spans for diagnostics.
+pub fn parse_file(input: &str, file: &str) -> Result<SourceFile, Box<Error<Rule>>> {
+    let mut pairs = PacketParser2::parse(Rule::packets, input).map_err(|e| Box::new(explain_reserved(e, input)))?;
     let root = pairs.next().expect("packets rule must produce a pair");
-    let results = parse_packets(root);
-    Ok(PacketExprList { packets: results })
+    let source = parse_packets(root, file);
+    check_fixed_fields(&source, input)?;
+    check_regions(&source, input)?;
+    check_const_fields(&source, input)?;
+    check_rules(&source, input)?;
+    check_optional_fields(&source, input)?;
+    check_fillers(&source, input)?;
+    check_checksums(&source, input)?;
+    check_externs(&source, input)?;
+    check_intrinsics(&source, input)?;
+    Ok(source)
 }
 
-fn parse_packets(packets: Pair<Rule>) -> Vec<PacketExpr> {
+// `reserved` became a keyword with filler, so older files that name a field
+// `reserved` fail to parse at or right after the word; say why.
+fn explain_reserved(err: Error<Rule>, input: &str) -> Error<Rule> {
+    let at = match err.location {
+        InputLocation::Pos(p) => p,
+        InputLocation::Span((start, _)) => start,
+    };
+    let is_word = |start: usize| {
+        input.is_char_boundary(start)
+            && input[start..].starts_with("reserved")
+            && !input[start + 8..].starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_')
+            && !input[..start].ends_with(|c: char| c.is_ascii_alphanumeric() || c == '_')
+    };
+    let before = input[..at].trim_end().len();
+    let start = match (is_word(at), before.checked_sub(8)) {
+        (true, _) => at,
+        (false, Some(prev)) if is_word(prev) => prev,
+        _ => return err,
+    };
+    let message = "`reserved` is a keyword (`reserved uint16` is filler) and cannot be a name".to_string();
+    match pest::Span::new(input, start, start + 8) {
+        Some(span) => Error::new_from_span(ErrorVariant::CustomError { message }, span),
+        None => err,
+    }
+}
+
+// `fixed<int16, 20>` parses but has more fraction bits than the integer holds.
+fn check_fixed_fields(source: &SourceFile, input: &str) -> Result<(), Box<Error<Rule>>> {
+    for field in source.packets.iter().flat_map(|p| &p.fields) {
+        if let TypeNode::Fixed(_, base, frac) = &field.expr {
+            let bits = base.width_bytes() * 8;
+            if usize::from(*frac) > bits {
+                let span = pest::Span::new(input, field.span.start, field.span.end).expect("field span is in the input");
+                return Err(Box::new(Error::new_from_span(
+                    ErrorVariant::CustomError {
+                        message: format!("`{}` has {} fraction bits but its base holds only {}", field.id, frac, bits),
+                    },
+                    span,
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Const fields must be integers with a constant value that fits their type.
+fn check_const_fields(source: &SourceFile, input: &str) -> Result<(), Box<Error<Rule>>> {
+    for field in source.packets.iter().flat_map(|p| &p.fields) {
+        // values taking a checksum are left to check_checksums
+        if field.value.as_ref().is_none_or(|v| !v.checksums().is_empty()) {
+            continue;
+        }
+        let message = match (field.expr.int_range(), field.const_value()) {
+            (None, _) => format!("`{}` cannot be const: only integer fields can", field.id),
+            (Some(_), None) => format!("the value of `{}` is not a constant", field.id),
+            (Some((min, max)), Some(v)) if v < min || v > max => {
+                format!("{} does not fit in `{}` ({}..={})", field.const_literal().unwrap_or_default(), field.id, min, max)
+            }
+            _ => continue,
+        };
+        let span = pest::Span::new(input, field.span.start, field.span.end).expect("field span is in the input");
+        return Err(Box::new(Error::new_from_span(ErrorVariant::CustomError { message }, span)));
+    }
+    Ok(())
+}
+
+// Decoders check a `where` clause as soon as its field is read, so it can only
+// read that field and the ones before it. Rules never read calc fields, which
+// not every target stores.
+fn check_rules(source: &SourceFile, input: &str) -> Result<(), Box<Error<Rule>>> {
+    for pkt in &source.packets {
+        let calcs = || pkt.calculated_fields.iter().map(|c| &c.name);
+        let wheres = pkt.fields.iter().enumerate().filter_map(|(i, f)| {
+            let check = f.constraint.as_ref()?;
+            let later = pkt.fields[i + 1..].iter().map(|f| &f.id).find(|name| check.cond.mentions(name));
+            let message = match (later, calcs().find(|name| check.cond.mentions(name))) {
+                (Some(name), _) => format!("the `where` clause of `{}` reads `{}`, which is not decoded yet; use `assert`", f.id, name),
+                (None, Some(name)) => format!("the `where` clause of `{}` reads the calc field `{}`", f.id, name),
+                (None, None) => return None,
+            };
+            Some((message, &check.span))
+        });
+        let asserts = pkt.asserts.iter().filter_map(|check| {
+            let name = calcs().find(|name| check.cond.mentions(name))?;
+            Some((format!("`assert {}` reads the calc field `{}`", check.rule, name), &check.span))
+        });
+        if let Some((message, at)) = wheres.chain(asserts).next() {
+            let span = pest::Span::new(input, at.start, at.end).expect("rule span is in the input");
+            return Err(Box::new(Error::new_from_span(ErrorVariant::CustomError { message }, span)));
+        }
+    }
+    Ok(())
+}
+
+// Optional fields hold no value when absent, so they cannot be const, packed
+// bits, or read by any expression of the packet.
+fn check_optional_fields(source: &SourceFile, input: &str) -> Result<(), Box<Error<Rule>>> {
+    for pkt in &source.packets {
+        let optional: Vec<&str> = pkt.fields.iter().filter(|f| f.condition.is_some()).map(|f| f.id.as_str()).collect();
+        if optional.is_empty() {
+            continue;
+        }
+        let mut readers: Vec<(&str, Vec<&ExprNode>, &SourceSpan)> = Vec::new();
+        for f in &pkt.fields {
+            let mut exprs = f.expr.length_exprs();
+            exprs.extend(&f.condition);
+            exprs.extend(f.constraint.as_ref().map(|c| &c.cond));
+            readers.push((&f.id, exprs, &f.span));
+        }
+        for cf in &pkt.calculated_fields {
+            readers.push((&cf.name, vec![&*cf.expr], &cf.span));
+        }
+        for r in &pkt.regions {
+            readers.push(("within", vec![&r.length], &r.span));
+        }
+        for a in &pkt.asserts {
+            readers.push(("assert", vec![&a.cond], &a.span));
+        }
+
+        let mut found: Option<(String, &SourceSpan)> = None;
+        for (i, f) in pkt.fields.iter().enumerate() {
+            let Some(cond) = &f.condition else { continue };
+            // decoders evaluate the condition before reading the field
+            let later = pkt.fields[i..]
+                .iter()
+                .map(|f| &f.id)
+                .chain(pkt.calculated_fields.iter().map(|c| &c.name))
+                .find(|name| cond.mentions(name));
+            if let Some(name) = later {
+                found = Some((format!("the condition of `{}` reads `{}`, which is not decoded before it", f.id, name), &f.span));
+            } else if f.value.is_some() {
+                found = Some((format!("`{}` cannot be both const and optional", f.id), &f.span));
+            } else if matches!(f.expr, TypeNode::Bool(None, BoolWidth::Bit)) {
+                found = Some((format!("`{}` is a packed bit and cannot be optional", f.id), &f.span));
+            }
+            if found.is_some() {
+                break;
+            }
+        }
+        if found.is_none() {
+            found = readers.iter().find_map(|(reader, exprs, span)| {
+                let name = optional.iter().find(|name| exprs.iter().any(|e| e.mentions(name)))?;
+                Some((format!("`{reader}` reads `{name}`, which is optional"), *span))
+            });
+        }
+        if let Some((message, at)) = found {
+            let span = pest::Span::new(input, at.start, at.end).expect("span is in the input");
+            return Err(Box::new(Error::new_from_span(ErrorVariant::CustomError { message }, span)));
+        }
+    }
+    Ok(())
+}
+
+// `pad(n)` and `align(n)` take a whole number of bytes, at least one.
+fn check_fillers(source: &SourceFile, input: &str) -> Result<(), Box<Error<Rule>>> {
+    for field in source.packets.iter().flat_map(|p| &p.fields) {
+        if let TypeNode::Pad(0) | TypeNode::Align(0) = field.expr {
+            let span = pest::Span::new(input, field.span.start, field.span.end).expect("field span is in the input");
+            return Err(Box::new(Error::new_from_span(
+                ErrorVariant::CustomError { message: format!("`{}` needs a whole number of bytes, at least 1", span.as_str()) },
+                span,
+            )));
+        }
+    }
+    Ok(())
+}
+
+// Checksums are computed from encoded bytes, so only calc fields and the
+// value of a checksum field may use them. A checksum field is an unsigned
+// integer as wide as its sum, and nothing else reads it since it is only known
+// once the packet is encoded. Checksums are filled in field order, so a range
+// may cover earlier checksum fields but not later ones.
+fn check_checksums(source: &SourceFile, input: &str) -> Result<(), Box<Error<Rule>>> {
+    for pkt in &source.packets {
+        let index = |name: &str| pkt.fields.iter().position(|f| f.id == name);
+        let slots = pkt.bit_slots();
+        let sums: Vec<&str> = pkt.fields.iter().filter(|f| f.checksum().is_some()).map(|f| f.id.as_str()).collect();
+
+        let mut found: Option<(String, &SourceSpan)> = None;
+        for (i, f) in pkt.fields.iter().enumerate() {
+            let mut exprs = f.expr.length_exprs();
+            exprs.extend(&f.condition);
+            exprs.extend(f.constraint.as_ref().map(|c| &c.cond));
+            let message = if exprs.iter().any(|e| !e.checksums().is_empty()) {
+                Some(format!("`{}` uses a checksum outside a calc field or field value", f.id))
+            } else if let Some(name) = sums.iter().find(|name| exprs.iter().any(|e| e.mentions(name))) {
+                Some(format!("`{}` reads the checksum field `{name}`, which is only known once encoded", f.id))
+            } else if let Some(value) = f.value.as_ref().filter(|v| !v.checksums().is_empty()) {
+                match value.as_checksum() {
+                    None => Some(format!("a checksum must be the whole value of `{}`", f.id)),
+                    Some(sum) => {
+                        let bits = sum.kind.width_bytes() * 8;
+                        let unsigned = match sum.kind.width_bytes() {
+                            2 => matches!(f.expr, TypeNode::UnsignedInteger16(None)),
+                            _ => matches!(f.expr, TypeNode::UnsignedInteger32(None)),
+                        };
+                        if !unsigned {
+                            Some(format!("`{}` holds a {} and must be a uint{bits}", f.id, sum.kind.name()))
+                        } else if f.condition.is_some() || f.constraint.is_some() {
+                            Some(format!("checksum field `{}` cannot be optional or have a `where` clause", f.id))
+                        } else {
+                            let covered = match (index(sum.first), index(sum.last)) {
+                                (Some(a), Some(b)) => pkt.fields.get(a.max(i + 1)..=b).unwrap_or_default(),
+                                _ => &[],
+                            };
+                            covered.iter().find(|g| g.checksum().is_some()).map(|later| {
+                                format!("`{}` covers the checksum field `{}`, which is filled after it", f.id, later.id)
+                            })
+                        }
+                    }
+                }
+            } else {
+                None
+            };
+            if let Some(message) = message {
+                found = Some((message, &f.span));
+                break;
+            }
+        }
+
+        // ranges name fields of this packet, in order, on byte boundaries
+        if found.is_none() {
+            let ranges = pkt
+                .fields
+                .iter()
+                .filter_map(|f| Some((f.checksum()?, &f.span)))
+                .chain(pkt.calculated_fields.iter().flat_map(|cf| cf.expr.checksums().into_iter().map(|c| (c, &cf.span))));
+            for (sum, span) in ranges {
+                let message = match (index(sum.first), index(sum.last)) {
+                    (None, _) => format!("`{}` is not a field of `{}`", sum.first, pkt.name),
+                    (_, None) => format!("`{}` is not a field of `{}`", sum.last, pkt.name),
+                    (Some(a), Some(b)) if a > b => format!("`{}..{}` runs backwards", sum.first, sum.last),
+                    (Some(a), Some(b)) => {
+                        let starts = slots[a].is_none_or(|s| s.starts_byte());
+                        let ends = slots[b].is_none_or(|s| s.ends_byte());
+                        if starts && ends {
+                            continue;
+                        }
+                        format!("`{}..{}` starts or ends inside a byte of packed bits", sum.first, sum.last)
+                    }
+                };
+                found = Some((message, span));
+                break;
+            }
+        }
+
+        // calc fields, asserts and regions may not read a checksum field either
+        if found.is_none() {
+            let readers = pkt
+                .calculated_fields
+                .iter()
+                .map(|cf| (cf.name.as_str(), &*cf.expr, &cf.span, true))
+                .chain(pkt.asserts.iter().map(|a| ("assert", &a.cond, &a.span, false)))
+                .chain(pkt.regions.iter().map(|r| ("within", &r.length, &r.span, false)));
+            for (reader, e, span, may_sum) in readers {
+                let message = if !may_sum && !e.checksums().is_empty() {
+                    format!("`{reader}` uses a checksum outside a calc field or field value")
+                } else if let Some(name) = sums.iter().find(|name| e.mentions(name)) {
+                    format!("`{reader}` reads the checksum field `{name}`, which is only known once encoded")
+                } else {
+                    continue;
+                };
+                found = Some((message, span));
+                break;
+            }
+        }
+
+        if let Some((message, at)) = found {
+            let span = pest::Span::new(input, at.start, at.end).expect("span is in the input");
+            return Err(Box::new(Error::new_from_span(ErrorVariant::CustomError { message }, span)));
+        }
+    }
+    Ok(())
+}
+
+// Extern mappings name each target once, and only targets whose expressions
+// call functions. Calls are checked by the loader, which sees every import.
+fn check_externs(source: &SourceFile, input: &str) -> Result<(), Box<Error<Rule>>> {
+    for x in &source.externs {
+        let mut seen = Vec::<&str>::new();
+        let mut message = None;
+        if intrinsic_arity(&x.name).is_some() {
+            message = Some(format!("`{}` is a built-in function", x.name));
+        }
+        for param in &x.params {
+            if seen.contains(&param.as_str()) {
+                message = Some(format!("`{}` has two parameters named `{param}`", x.name));
+            }
+            seen.push(param);
+        }
+        seen.clear();
+        for (target, function) in &x.targets {
+            if !ExternFn::TARGETS.contains(&target.as_str()) {
+                message = Some(format!(
+                    "`{target}` is not a target that calls functions; expected one of {}",
+                    ExternFn::TARGETS.join(", ")
+                ));
+            } else if seen.contains(&target.as_str()) {
+                message = Some(format!("`{}` maps `{target}` more than once", x.name));
+            } else if function.trim().is_empty() {
+                message = Some(format!("`{}` maps `{target}` to an empty name", x.name));
+            }
+            seen.push(target);
+        }
+        if let Some(message) = message {
+            let span = pest::Span::new(input, x.span.start, x.span.end).expect("extern span is in the input");
+            return Err(Box::new(Error::new_from_span(ErrorVariant::CustomError { message }, span)));
+        }
+    }
+    Ok(())
+}
+
+// `len` and `sizeof` take a name. A `len` left after `inline_lengths` names
+// something without a count; the loader resolves `sizeof`.
+fn check_intrinsics(source: &SourceFile, input: &str) -> Result<(), Box<Error<Rule>>> {
+    for pkt in &source.packets {
+        for (e, span) in pkt.exprs() {
+            let message = e.calls().into_iter().find_map(|call| match call {
+                ("len", [_]) => Some(format!("`len` takes a `bytes`, array or `repeat [n]` field of `{}`", pkt.name)),
+                ("sizeof", [arg]) if !matches!(arg, ExprNode::ValueReference(_, None)) => {
+                    Some("`sizeof` takes a packet name".to_string())
+                }
+                _ => None,
+            });
+            if let Some(message) = message {
+                let span = pest::Span::new(input, span.start, span.end).expect("span is in the input");
+                return Err(Box::new(Error::new_from_span(ErrorVariant::CustomError { message }, span)));
+            }
+        }
+    }
+    Ok(())
+}
+
+// A `within` block holding only calc fields has nothing to bound.
+fn check_regions(source: &SourceFile, input: &str) -> Result<(), Box<Error<Rule>>> {
+    for region in source.packets.iter().flat_map(|p| &p.regions) {
+        if region.first == region.end {
+            let span = pest::Span::new(input, region.span.start, region.span.end).expect("region span is in the input");
+            return Err(Box::new(Error::new_from_span(
+                ErrorVariant::CustomError { message: "`within` block has no fields".to_string() },
+                span,
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn parse_packets(packets: Pair<Rule>, file: &str) -> SourceFile {
+    let mut package: Option<PackageExpr> = None;
+    let mut imports = Vec::<ImportExpr>::new();
     let mut results = Vec::<PacketExpr>::new();
+    let mut externs = Vec::<ExternFn>::new();
     if packets.as_rule() == Rule::packets {
-        for packet in packets.into_inner() {
-            if packet.as_rule() == Rule::packet {
-                results.push(parse_packet(packet));
+        for item in packets.into_inner() {
+            match item.as_rule() {
+                Rule::package_decl => {
+                    let span = to_span(&item, file);
+                    let name = item
+                        .into_inner()
+                        .find(|p| p.as_rule() == Rule::qualified_name)
+                        .map(|p| p.as_str().to_string())
+                        .unwrap_or_default();
+                    package = Some(PackageExpr { name, span });
+                }
+                Rule::import_stmt => imports.push(parse_import(item, file)),
+                Rule::packet => results.push(parse_packet(item, file)),
+                Rule::extern_fn => externs.push(parse_extern_fn(item, file)),
+                _ => {}
             }
         }
     }
-    results
+    SourceFile { package, imports, packets: results, externs }
 }
 
-fn parse_packet(packet: Pair<Rule>) -> PacketExpr {
+fn parse_extern_fn(item: Pair<Rule>, file: &str) -> ExternFn {
+    let span = to_span(&item, file);
+    let mut doc_lines = Vec::<&str>::new();
+    let mut name = String::new();
+    let mut params = Vec::new();
+    let mut returns = String::new();
+    let mut targets = Vec::new();
+    for p in item.into_inner() {
+        match p.as_rule() {
+            Rule::doc_comment => doc_lines.push(p.as_str()),
+            Rule::identifier => name = p.as_str().to_string(),
+            Rule::extern_params => {
+                params = p.into_inner().filter(|q| q.as_rule() == Rule::identifier).map(|q| q.as_str().to_string()).collect();
+            }
+            Rule::type_name => returns = p.as_str().to_string(),
+            Rule::extern_target => {
+                let mut target = String::new();
+                let mut function = String::new();
+                for q in p.into_inner() {
+                    match q.as_rule() {
+                        Rule::identifier => target = q.as_str().to_string(),
+                        Rule::string => function = parse_string_constant(q),
+                        _ => {}
+                    }
+                }
+                targets.push((target, function));
+            }
+            _ => {}
+        }
+    }
+    ExternFn { name, params, returns, targets, doc: doc_text(&doc_lines), span }
+}
+
+fn parse_import(import: Pair<Rule>, file: &str) -> ImportExpr {
+    let span = to_span(&import, file);
+    let path = import
+        .into_inner()
+        .find(|p| p.as_rule() == Rule::string)
+        .map(parse_string_constant)
+        .unwrap_or_default();
+    ImportExpr { path, span }
+}
+
+fn parse_packet(packet: Pair<Rule>, file: &str) -> PacketExpr {
+    let span = to_span(&packet, file);
     let mut type_rules = Vec::<TypeExpr>::new();
     let mut calculated_fields = Vec::<CalculatedField>::new();
+    let mut regions = Vec::<Region>::new();
+    let mut asserts = Vec::<Check>::new();
     let mut identifier = String::new();
     let mut packet_endianness: Option<Endianness> = None;
+    let mut doc_lines = Vec::<&str>::new();
 
     for detail in packet.into_inner() {
         match detail.as_rule() {
+            Rule::doc_comment => doc_lines.push(detail.as_str()),
             Rule::identifier => identifier = detail.as_str().to_string(),
             Rule::endianness => packet_endianness = to_endianness(detail.as_str()),
             Rule::rule_list => {
-                for rule in detail.into_inner() {
-                    if rule.as_rule() != Rule::rule {
-                        continue;
-                    }
-                    for elem in rule.into_inner() {
-                        match elem.as_rule() {
-                            Rule::declaration => type_rules.push(parse_declaration(elem)),
-                            Rule::calculated_field => {
-                                calculated_fields.push(parse_calculated_field(elem))
-                            }
-                            _ => {}
-                        }
-                    }
-                }
+                parse_rule_list(detail, file, &mut type_rules, &mut calculated_fields, &mut regions, &mut asserts);
             }
             _ => {}
         }
     }
 
-    PacketExpr {
+    // an assert is reported against the first field it reads
+    for check in &mut asserts {
+        check.field = type_rules.iter().map(|f| &f.id).find(|n| check.cond.mentions(n)).cloned().unwrap_or_default();
+    }
+
+    let mut pkt = PacketExpr {
         name: identifier,
         fields: type_rules,
         calculated_fields,
+        regions,
+        asserts,
         endianness: packet_endianness,
+        doc: doc_text(&doc_lines),
+        span,
+    };
+    inline_consts(&mut pkt);
+    inline_lengths(&mut pkt);
+    pkt
+}
+
+// `len(items)` is the count `items` is declared with, which decoders have read
+// by the time they need it and which counts what encoders write.
+fn inline_lengths(pkt: &mut PacketExpr) {
+    let counts: Vec<(String, ExprNode)> = pkt
+        .fields
+        .iter()
+        .filter_map(|f| Some((f.id.clone(), f.expr.count_expr()?.clone())))
+        .collect();
+    for e in pkt.exprs_mut() {
+        e.rewrite_calls(&|name, args| match (name, args) {
+            ("len", [ExprNode::ValueReference(id, None)]) => {
+                let (_, count) = counts.iter().find(|(field, _)| field == id)?;
+                Some(ExprNode::ParenthesizedExpr(Box::new(count.clone())))
+            }
+            _ => None,
+        });
+    }
+}
+
+// Const fields are not stored by the generated types, so every expression
+// naming one gets its value instead. `until` conditions name the element's
+// fields and are left alone.
+fn inline_consts(pkt: &mut PacketExpr) {
+    let consts: Vec<(String, ExprNode)> = pkt
+        .fields
+        .iter()
+        .filter(|f| f.checksum().is_none())
+        .filter_map(|f| Some((f.id.clone(), f.value.clone()?)))
+        .collect();
+    if consts.is_empty() {
+        return;
+    }
+    for f in &mut pkt.fields {
+        match &mut f.expr {
+            TypeNode::FixedString(e, _) | TypeNode::Repeat(_, RepeatBound::Count(e)) => substitute(e, &consts),
+            TypeNode::Repeat(_, _)
+            | TypeNode::CString(_)
+            | TypeNode::PString(_, _)
+            | TypeNode::Pad(_)
+            | TypeNode::Align(_) => {}
+            TypeNode::UnsignedInteger8(e)
+            | TypeNode::Integer8(e)
+            | TypeNode::UnsignedInteger16(e)
+            | TypeNode::Integer16(e)
+            | TypeNode::UnsignedInteger32(e)
+            | TypeNode::Integer32(e)
+            | TypeNode::UnsignedInteger64(e)
+            | TypeNode::Integer64(e)
+            | TypeNode::Float32(e)
+            | TypeNode::Float64(e)
+            | TypeNode::MacAddress(e)
+            | TypeNode::DateTime(e, _)
+            | TypeNode::Bytes(e)
+            | TypeNode::Bool(e, _)
+            | TypeNode::Fixed(e, _, _)
+            | TypeNode::VarInt(e, _)
+            | TypeNode::Ipv4Address(e)
+            | TypeNode::Ipv6Address(e)
+            | TypeNode::Port(e) => {
+                if let Some(e) = e {
+                    substitute(e, &consts);
+                }
+            }
+        }
+        if let Some(cond) = &mut f.condition {
+            substitute(cond, &consts);
+        }
+        if let Some(check) = &mut f.constraint {
+            substitute(&mut check.cond, &consts);
+        }
+    }
+    for check in &mut pkt.asserts {
+        substitute(&mut check.cond, &consts);
+    }
+    for cf in &mut pkt.calculated_fields {
+        substitute(&mut cf.expr, &consts);
+    }
+    for r in &mut pkt.regions {
+        substitute(&mut r.length, &consts);
+    }
+}
+
+fn substitute(e: &mut ExprNode, consts: &[(String, ExprNode)]) {
+    use ExprNode::*;
+    match e {
+        ValueReference(id, None) => {
+            if let Some((_, v)) = consts.iter().find(|(name, _)| name == id) {
+                *e = v.clone();
+            }
+        }
+        ValueReference(_, Some(index)) => substitute(index, consts),
+        // checksum ranges name fields, not their values, and `sizeof` a packet
+        ActivationRecord(name, _) if ChecksumKind::from_name(name).is_some() || name == "sizeof" => {}
+        ActivationRecord(_, args) => args.iter_mut().for_each(|a| substitute(a, consts)),
+        NoExpr | UnsignedInteger64Value(_) | Integer64Value(_) | Float64Value(_) | StringValue(_)
+        | AggregateSum(_) | AggregateProduct(_) => {}
+        ParenthesizedExpr(a) | Negate(a) | BitNot(a) | Not(a) => substitute(a, consts),
+        GuardExpression(c, a, b) => {
+            substitute(c, consts);
+            substitute(a, consts);
+            substitute(b, consts);
+        }
+        Plus(a, b) | Minus(a, b) | Mult(a, b) | Div(a, b) | Mod(a, b) | Pow(a, b)
+        | BitAnd(a, b) | BitOr(a, b) | ShiftLeft(a, b) | ShiftRight(a, b)
+        | Gt(a, b) | Gte(a, b) | Lt(a, b) | Lte(a, b) | Equals(a, b) | NotEquals(a, b)
+        | And(a, b) | Or(a, b) => {
+            substitute(a, consts);
+            substitute(b, consts);
+        }
+    }
+}
+
+// Fields of `within` blocks join the packet's flat field list; the block itself
+// becomes a Region over their index range (pushed before any nested ones).
+fn parse_rule_list(
+    list: Pair<Rule>,
+    file: &str,
+    fields: &mut Vec<TypeExpr>,
+    calcs: &mut Vec<CalculatedField>,
+    regions: &mut Vec<Region>,
+    asserts: &mut Vec<Check>,
+) {
+    for rule in list.into_inner() {
+        if rule.as_rule() != Rule::rule {
+            continue;
+        }
+        let mut rule_doc = Vec::<&str>::new();
+        for elem in rule.into_inner() {
+            match elem.as_rule() {
+                Rule::doc_comment => rule_doc.push(elem.as_str()),
+                Rule::declaration => {
+                    let span = to_span(&elem, file);
+                    let mut decl = parse_declaration(elem);
+                    decl.doc = doc_text(&rule_doc);
+                    if let Some(check) = &mut decl.constraint {
+                        check.span = span.clone();
+                    }
+                    decl.span = span;
+                    fields.push(decl);
+                }
+                Rule::filler => {
+                    let span = to_span(&elem, file);
+                    let mut filler = parse_filler(elem, fields.len());
+                    filler.doc = doc_text(&rule_doc);
+                    filler.span = span;
+                    fields.push(filler);
+                }
+                Rule::calculated_field => {
+                    let span = to_span(&elem, file);
+                    let mut calc = parse_calculated_field(elem);
+                    calc.doc = doc_text(&rule_doc);
+                    calc.span = span;
+                    calcs.push(calc);
+                }
+                Rule::within_block => {
+                    let span = to_span(&elem, file);
+                    let index = regions.len();
+                    regions.push(Region {
+                        length: ExprNode::NoExpr,
+                        leftover: Leftover::Error,
+                        first: fields.len(),
+                        end: fields.len(),
+                        span,
+                    });
+                    for part in elem.into_inner() {
+                        match part.as_rule() {
+                            Rule::expr => regions[index].length = parse_expr(part),
+                            Rule::leftover if part.as_str() == "skip" => regions[index].leftover = Leftover::Skip,
+                            Rule::rule_list => parse_rule_list(part, file, fields, calcs, regions, asserts),
+                            _ => {}
+                        }
+                    }
+                    regions[index].end = fields.len();
+                }
+                Rule::assert_rule => {
+                    let span = to_span(&elem, file);
+                    if let Some(e) = elem.into_inner().find(|p| p.as_rule() == Rule::expr) {
+                        asserts.push(Check { span, ..parse_check(e, String::new()) });
+                    }
+                }
+                _ => {}
+            }
+        }
     }
 }
 
@@ -87,11 +710,33 @@ fn parse_declaration(parser_rule: Pair<Rule>) -> TypeExpr {
     let mut type_name = String::new();
     let mut array_len_expr: Option<ExprNode> = None;
     let mut field_endianness: Option<Endianness> = None;
+    let mut string_type: Option<Pair<Rule>> = None;
+    let mut encoding = StringEncoding::Utf8;
+    let mut time_encoding: Option<TimeEncoding> = None;
+    let mut special: Option<TypeNode> = None; // types that carry more than a length
+    let mut value: Option<ExprNode> = None;
+    let mut where_expr: Option<Pair<Rule>> = None;
+    let mut condition: Option<ExprNode> = None;
 
     for field in parser_rule.into_inner() {
         match field.as_rule() {
             Rule::identifier => identifier = field.as_str().to_string(),
             Rule::type_name => type_name = field.as_str().to_string(), // actual text like "uint16"
+            Rule::net_type => {
+                type_name = field.as_str().to_string();
+                field_endianness = Some(Endianness::Be); // network order
+            }
+            Rule::string_type => string_type = Some(field),
+            Rule::encoding => encoding = to_encoding(field.as_str()),
+            Rule::time_type => {
+                let unit = field.into_inner().find(|p| p.as_rule() == Rule::time_unit);
+                time_encoding = Some(unit.map_or(TimeEncoding::UnixSeconds, |u| to_time_encoding(u.as_str())));
+            }
+            Rule::bit_bool => special = Some(TypeNode::Bool(None, BoolWidth::Bit)),
+            Rule::bool_t => special = Some(TypeNode::Bool(None, BoolWidth::Byte)),
+            Rule::fixed_type => special = Some(parse_fixed_type(field)),
+            Rule::var_type => special = Some(TypeNode::VarInt(None, to_var_encoding(field.as_str()))),
+            Rule::repeat_type => special = Some(parse_repeat_type(field)),
             Rule::array_specifier => {
                 // array_specifier: "[" expr "]"
                 for inner in field.into_inner() {
@@ -101,17 +746,75 @@ fn parse_declaration(parser_rule: Pair<Rule>) -> TypeExpr {
                 }
             }
             Rule::endianness => field_endianness = to_endianness_opt(field.as_str()),
+            Rule::const_value => {
+                value = field.into_inner().find(|p| p.as_rule() == Rule::expr).map(parse_expr);
+            }
+            Rule::presence => condition = field.into_inner().find(|p| p.as_rule() == Rule::expr).map(parse_expr),
+            Rule::where_clause => where_expr = field.into_inner().find(|p| p.as_rule() == Rule::expr),
             _ => {}
         }
     }
 
+    let expr = match (string_type, time_encoding, special) {
+        (Some(st), _, _) => parse_string_type(st, encoding),
+        (None, Some(unit), _) => TypeNode::DateTime(array_len_expr, unit),
+        (None, None, Some(TypeNode::Bool(_, width))) => TypeNode::Bool(array_len_expr, width),
+        (None, None, Some(TypeNode::Fixed(_, base, frac))) => TypeNode::Fixed(array_len_expr, base, frac),
+        (None, None, Some(TypeNode::VarInt(_, enc))) => TypeNode::VarInt(array_len_expr, enc),
+        (None, None, Some(repeat @ TypeNode::Repeat(_, _))) => repeat,
+        (None, None, _) => expr_from_type_name(type_name, array_len_expr),
+    };
+
+    let constraint = where_expr.map(|e| parse_check(e, identifier.clone()));
     TypeExpr {
         id: identifier,
-        expr: expr_from_type_name(type_name, array_len_expr),
+        expr,
         endianness: field_endianness,
+        value,
+        condition,
+        constraint,
+        doc: None,
+        span: SourceSpan::default(),
     }
 }
 
+// Filler is named after its kind and position (`_pad3`, `_reserved4`), so the
+// generators can treat it as a field; nothing can refer to it.
+fn parse_filler(parser_rule: Pair<Rule>, index: usize) -> TypeExpr {
+    let mut kind = "";
+    let mut expr = TypeNode::Pad(0);
+    for item in parser_rule.into_inner() {
+        match item.as_rule() {
+            Rule::pad_kw | Rule::align_kw | Rule::reserved_kw => kind = item.as_str(),
+            Rule::numeric_constant => {
+                let n = match parse_numeric_constant(item) {
+                    ExprNode::UnsignedInteger64Value(n) => usize::try_from(n).unwrap_or(0),
+                    _ => 0, // fractions; rejected with zero in parse_file
+                };
+                expr = if kind == "align" { TypeNode::Align(n) } else { TypeNode::Pad(n) };
+            }
+            Rule::fixed_base => expr = TypeNode::Pad(to_fixed_base(item.as_str()).width_bytes()),
+            _ => {}
+        }
+    }
+    TypeExpr {
+        id: format!("_{kind}{index}"),
+        expr,
+        endianness: None,
+        value: None,
+        condition: None,
+        constraint: None,
+        doc: None,
+        span: SourceSpan::default(),
+    }
+}
+
+// `field` is left empty for asserts; parse_packet fills it in.
+fn parse_check(expr: Pair<Rule>, field: String) -> Check {
+    let rule = expr.as_str().split_whitespace().collect::<Vec<_>>().join(" ");
+    Check { cond: parse_expr(expr), rule, field, span: SourceSpan::default() }
+}
+
 fn parse_calculated_field(parser_rule: Pair<Rule>) -> CalculatedField {
     let mut identifier = String::new();
     let mut type_name = String::new();
@@ -130,6 +833,8 @@ fn parse_calculated_field(parser_rule: Pair<Rule>) -> CalculatedField {
         name: identifier,
         data_type: type_name,
         expr: Box::new(option_expr.expect("calc field must have an expression")),
+        doc: None,
+        span: SourceSpan::default(),
     }
 }
 
@@ -167,13 +872,13 @@ fn parse_bool_or(parser_rule: Pair<Rule>) -> ExprNode {
 }
 
 fn parse_bool_and(parser_rule: Pair<Rule>) -> ExprNode {
-    // bool_and = { cmp ~ (and_kw ~ cmp )* }
+    // bool_and = { bool_not ~ (and_kw ~ bool_not )* }
     let mut terms = Vec::<ExprNode>::new();
     let mut ops = Vec::<Rule>::new();
 
     for item in parser_rule.clone().into_inner() {
         match item.as_rule() {
-            Rule::cmp => terms.push(parse_cmp(item)),
+            Rule::bool_not => terms.push(parse_bool_not(item)),
             Rule::and_kw => ops.push(Rule::and_kw),
             _ => {}
         }
@@ -185,14 +890,30 @@ fn parse_bool_and(parser_rule: Pair<Rule>) -> ExprNode {
     })
 }
 
+fn parse_bool_not(parser_rule: Pair<Rule>) -> ExprNode {
+    // bool_not = { not_op ~ bool_not | cmp }
+    for item in parser_rule.into_inner() {
+        match item.as_rule() {
+            Rule::bool_not => return ExprNode::Not(Box::new(parse_bool_not(item))),
+            Rule::cmp => return parse_cmp(item),
+            _ => {}
+        }
+    }
+    ExprNode::NoExpr
+}
+
 fn parse_cmp(parser_rule: Pair<Rule>) -> ExprNode {
-    // cmp = { sum ~ ((gte|lte|gt|lt|eq|neq) ~ sum)* }
+    // cmp = { bitwise_or ~ (in_kw ~ value_set | ((gte|lte|neq|gt|lt|eq) ~ bitwise_or)*) }
     let mut terms = Vec::<ExprNode>::new();
     let mut ops = Vec::<Rule>::new();
 
     for item in parser_rule.clone().into_inner() {
         match item.as_rule() {
-            Rule::sum => terms.push(parse_sum(item)),
+            Rule::bitwise_or => terms.push(parse_bitwise_or(item)),
+            Rule::value_set => {
+                let subject = terms.pop().unwrap_or(ExprNode::NoExpr);
+                return parse_value_set(item, &subject);
+            }
             Rule::gte | Rule::lte | Rule::gt | Rule::lt | Rule::eq | Rule::neq => {
                 ops.push(item.as_rule())
             }
@@ -200,22 +921,102 @@ fn parse_cmp(parser_rule: Pair<Rule>) -> ExprNode {
         }
     }
 
-    // If exactly one operator, build a binary comparison; otherwise just return first term
-    if terms.len() == 2 && ops.len() == 1 {
-        let lhs = Box::new(terms[0].clone());
-        let rhs = Box::new(terms[1].clone());
-        return match ops[0] {
+    // Chains expand pairwise: `a < b <= c` → `(a < b) and (b <= c)`.
+    let mut comparisons = terms.windows(2).zip(ops).map(|(pair, op)| {
+        let lhs = Box::new(pair[0].clone());
+        let rhs = Box::new(pair[1].clone());
+        match op {
             Rule::gt => ExprNode::Gt(lhs, rhs),
             Rule::gte => ExprNode::Gte(lhs, rhs),
             Rule::lt => ExprNode::Lt(lhs, rhs),
             Rule::lte => ExprNode::Lte(lhs, rhs),
             Rule::eq => ExprNode::Equals(lhs, rhs),
             Rule::neq => ExprNode::NotEquals(lhs, rhs),
-            _ => ExprNode::NoExpr,
-        };
+            _ => unreachable!(),
+        }
+    });
+
+    match comparisons.next() {
+        None => terms.into_iter().next().unwrap_or(ExprNode::NoExpr),
+        Some(first) => comparisons.fold(first, |acc, c| ExprNode::And(Box::new(acc), Box::new(c))),
+    }
+}
+
+// `x in [a..b, c]` → `(x >= a and x <= b) or x == c`
+fn parse_value_set(parser_rule: Pair<Rule>, subject: &ExprNode) -> ExprNode {
+    let lhs = || Box::new(subject.clone());
+    let alternatives = parser_rule.into_inner().filter(|p| p.as_rule() == Rule::value_item).map(|item| {
+        let mut bounds = item.into_inner().filter(|p| p.as_rule() == Rule::bitwise_or).map(parse_bitwise_or);
+        let low = bounds.next().unwrap_or(ExprNode::NoExpr);
+        match bounds.next() {
+            None => ExprNode::Equals(lhs(), Box::new(low)),
+            Some(high) => ExprNode::ParenthesizedExpr(Box::new(ExprNode::And(
+                Box::new(ExprNode::Gte(lhs(), Box::new(low))),
+                Box::new(ExprNode::Lte(lhs(), Box::new(high))),
+            ))),
+        }
+    });
+    alternatives
+        .reduce(|acc, alt| ExprNode::Or(Box::new(acc), Box::new(alt)))
+        .unwrap_or(ExprNode::NoExpr)
+}
+
+fn parse_bitwise_or(parser_rule: Pair<Rule>) -> ExprNode {
+    // bitwise_or = { bitwise_and ~ (bit_or ~ bitwise_and)* }
+    let mut terms = Vec::<ExprNode>::new();
+    let mut ops = Vec::<Rule>::new();
+
+    for item in parser_rule.clone().into_inner() {
+        match item.as_rule() {
+            Rule::bitwise_and => terms.push(parse_bitwise_and(item)),
+            Rule::bit_or => ops.push(Rule::bit_or),
+            _ => {}
+        }
+    }
+
+    fold_left(terms, ops, |lhs, rhs, op| match op {
+        Rule::bit_or => ExprNode::BitOr(Box::new(lhs), Box::new(rhs)),
+        _ => unreachable!(),
+    })
+}
+
+fn parse_bitwise_and(parser_rule: Pair<Rule>) -> ExprNode {
+    // bitwise_and = { shift ~ (bit_and ~ shift)* }
+    let mut terms = Vec::<ExprNode>::new();
+    let mut ops = Vec::<Rule>::new();
+
+    for item in parser_rule.clone().into_inner() {
+        match item.as_rule() {
+            Rule::shift => terms.push(parse_shift(item)),
+            Rule::bit_and => ops.push(Rule::bit_and),
+            _ => {}
+        }
+    }
+
+    fold_left(terms, ops, |lhs, rhs, op| match op {
+        Rule::bit_and => ExprNode::BitAnd(Box::new(lhs), Box::new(rhs)),
+        _ => unreachable!(),
+    })
+}
+
+fn parse_shift(parser_rule: Pair<Rule>) -> ExprNode {
+    // shift = { sum ~ ((shl | shr) ~ sum)* }
+    let mut terms = Vec::<ExprNode>::new();
+    let mut ops = Vec::<Rule>::new();
+
+    for item in parser_rule.clone().into_inner() {
+        match item.as_rule() {
+            Rule::sum => terms.push(parse_sum(item)),
+            Rule::shl | Rule::shr => ops.push(item.as_rule()),
+            _ => {}
+        }
     }
 
-    terms.into_iter().next().unwrap_or(ExprNode::NoExpr)
+    fold_left(terms, ops, |lhs, rhs, op| match op {
+        Rule::shl => ExprNode::ShiftLeft(Box::new(lhs), Box::new(rhs)),
+        Rule::shr => ExprNode::ShiftRight(Box::new(lhs), Box::new(rhs)),
+        _ => unreachable!(),
+    })
 }
 
 fn parse_sum(parser_rule: Pair<Rule>) -> ExprNode {
@@ -239,14 +1040,14 @@ fn parse_sum(parser_rule: Pair<Rule>) -> ExprNode {
 }
 
 fn parse_product(parser_rule: Pair<Rule>) -> ExprNode {
-    // product = { power ~ ((mult | div) ~ power)* }
+    // product = { unary ~ ((mult | div | modulo) ~ unary)* }
     let mut terms = Vec::<ExprNode>::new();
     let mut ops = Vec::<Rule>::new();
 
     for item in parser_rule.clone().into_inner() {
         match item.as_rule() {
-            Rule::power => terms.push(parse_power(item)),
-            Rule::mult | Rule::div => ops.push(item.as_rule()),
+            Rule::unary => terms.push(parse_unary(item)),
+            Rule::mult | Rule::div | Rule::modulo => ops.push(item.as_rule()),
             _ => {}
         }
     }
@@ -254,36 +1055,64 @@ fn parse_product(parser_rule: Pair<Rule>) -> ExprNode {
     fold_left(terms, ops, |lhs, rhs, op| match op {
         Rule::mult => ExprNode::Mult(Box::new(lhs), Box::new(rhs)),
         Rule::div => ExprNode::Div(Box::new(lhs), Box::new(rhs)),
+        Rule::modulo => ExprNode::Mod(Box::new(lhs), Box::new(rhs)),
         _ => unreachable!(),
     })
 }
 
+fn parse_unary(parser_rule: Pair<Rule>) -> ExprNode {
+    // unary = { (minus | bit_not) ~ unary | power }
+    let mut op: Option<Rule> = None;
+    for item in parser_rule.into_inner() {
+        match item.as_rule() {
+            Rule::minus | Rule::bit_not => op = Some(item.as_rule()),
+            Rule::unary => {
+                let operand = parse_unary(item);
+                return match (op, operand) {
+                    // fold `-<literal>` into a signed literal
+                    (Some(Rule::minus), ExprNode::UnsignedInteger64Value(u)) if u <= i64::MAX as u64 => {
+                        ExprNode::Integer64Value(-(u as i64))
+                    }
+                    (Some(Rule::minus), ExprNode::Float64Value(f)) => ExprNode::Float64Value(-f),
+                    (Some(Rule::minus), operand) => ExprNode::Negate(Box::new(operand)),
+                    (_, operand) => ExprNode::BitNot(Box::new(operand)),
+                };
+            }
+            Rule::power => return parse_power(item),
+            _ => {}
+        }
+    }
+    ExprNode::NoExpr
+}
+
 fn parse_power(parser_rule: Pair<Rule>) -> ExprNode {
-    // power = { primary ~ (pw ~ primary)? }
-    let mut prims = Vec::<ExprNode>::new();
-    let mut has_pow = false;
+    // power = { primary ~ (pw ~ unary)? }
+    // The exponent is a `unary`, which itself may be a power, so `a ^ b ^ c`
+    // nests to the right: a ^ (b ^ c).
+    let mut base = ExprNode::NoExpr;
+    let mut exponent: Option<ExprNode> = None;
 
-    for item in parser_rule.clone().into_inner() {
+    for item in parser_rule.into_inner() {
         match item.as_rule() {
-            Rule::primary => prims.push(parse_primary(item)),
-            Rule::pw => has_pow = true,
+            Rule::primary => base = parse_primary(item),
+            Rule::unary => exponent = Some(parse_unary(item)),
             _ => {}
         }
     }
 
-    if has_pow && prims.len() == 2 {
-        ExprNode::Pow(Box::new(prims[0].clone()), Box::new(prims[1].clone()))
-    } else {
-        prims.into_iter().next().unwrap_or(ExprNode::NoExpr)
+    match exponent {
+        Some(exp) => ExprNode::Pow(Box::new(base), Box::new(exp)),
+        None => base,
     }
 }
 
 fn parse_primary(parser_rule: Pair<Rule>) -> ExprNode {
-    // primary = { guard_expression | literal | function_call | accessor | inner_expr }
-    for node in parser_rule.into_inner() {
+    // primary = { guard_expression | literal | checksum_call | function_call | accessor | inner_expr }
+    if let Some(node) = parser_rule.into_inner().next() {
         return match node.as_rule() {
             Rule::guard_expression => parse_guard_expression(node),
             Rule::literal => parse_literal(node),
+            Rule::checksum_call => parse_checksum_call(node),
             Rule::function_call => parse_function_call(node),
             Rule::accessor => parse_accessor(node),
             Rule::inner_expr => parse_inner_expr(node),
@@ -294,7 +1123,7 @@ fn parse_primary(parser_rule: Pair<Rule>) -> ExprNode {
 }
 
 fn parse_literal(parser_rule: Pair<Rule>) -> ExprNode {
-    for node in parser_rule.into_inner() {
+    if let Some(node) = parser_rule.into_inner().next() {
         return match node.as_rule() {
             Rule::numeric_constant => parse_numeric_constant(node),
             Rule::string => ExprNode::StringValue(parse_string_constant(node)),
@@ -305,12 +1134,12 @@ fn parse_literal(parser_rule: Pair<Rule>) -> ExprNode {
 }
 
 fn parse_function_call(parser_rule: Pair<Rule>) -> ExprNode {
-    // function_call = { intrinsic_function ~ parameter_list }
+    // function_call = { (intrinsic_function | identifier) ~ parameter_list }
     let mut fname = String::new();
     let mut args = Vec::new();
     for p in parser_rule.into_inner() {
         match p.as_rule() {
-            Rule::intrinsic_function => fname = p.as_str().to_string(),
+            Rule::intrinsic_function | Rule::identifier => fname = p.as_str().to_string(),
             Rule::parameter_list => args = parse_parameter_list(p),
             _ => {}
         }
@@ -318,8 +1147,26 @@ fn parse_function_call(parser_rule: Pair<Rule>) -> ExprNode {
     ExprNode::ActivationRecord(fname, args)
 }
 
-fn parse_accessor(parser_rule: Pair<Rule>) -> ExprNode {
+fn parse_checksum_call(parser_rule: Pair<Rule>) -> ExprNode {
+    // checksum_call = { checksum_function ~ lparen ~ identifier ~ (range_op ~ identifier)? ~ rparen }
+    let mut fname = String::new();
+    let mut range = Vec::new();
     for p in parser_rule.into_inner() {
+        match p.as_rule() {
+            Rule::checksum_function => fname = p.as_str().to_string(),
+            Rule::identifier => range.push(ExprNode::ValueReference(p.as_str().to_string(), None)),
+            _ => {}
+        }
+    }
+    // `crc32(f)` is `crc32(f..f)`
+    if range.len() == 1 {
+        range.push(range[0].clone());
+    }
+    ExprNode::ActivationRecord(fname, range)
+}
+
+fn parse_accessor(parser_rule: Pair<Rule>) -> ExprNode {
+    if let Some(p) = parser_rule.into_inner().next() {
         return match p.as_rule() {
             Rule::aggregate_accessor => parse_aggregate_accessor(p),
             Rule::direct_value_accessor => parse_direct_value_accessor(p),
@@ -378,7 +1225,7 @@ fn parse_numeric_constant(parser_rule: Pair<Rule>) -> ExprNode {
         return ExprNode::Float64Value(s.parse::<f64>().expect("invalid float literal"));
     }
 
-    // Decimal (unsigned); note: grammar does not support unary '-'
+    // Decimal (unsigned); a leading '-' is folded in by parse_unary
     ExprNode::UnsignedInteger64Value(s.parse::<u64>().expect("invalid decimal literal"))
 }
 
@@ -433,12 +1280,12 @@ fn parse_direct_value_accessor(parser_rule: Pair<Rule>) -> ExprNode {
     ExprNode::ValueReference(identifier, array_index_expr)
 }
 
-fn parse_parameter_list(parser_rule: Pair<Rule>) -> Vec<Box<ExprNode>> {
+fn parse_parameter_list(parser_rule: Pair<Rule>) -> Vec<ExprNode> {
     // lparen ~ (expr ~ (comma ~ expr)*)? ~ comma? ~ rparen
     let mut expression_list = Vec::new();
     for value in parser_rule.into_inner() {
         if value.as_rule() == Rule::expr {
-            expression_list.push(Box::new(parse_expr(value)));
+            expression_list.push(parse_expr(value));
         }
     }
     expression_list
@@ -461,12 +1308,110 @@ fn expr_from_type_name(type_name: String, array_length: Option<ExprNode>) -> Typ
         "float32" => TypeNode::Float32(array_length),
         "float64" => TypeNode::Float64(array_length),
         "macaddress" => TypeNode::MacAddress(array_length),
-        "datetime" => TypeNode::DateTime(array_length),
         "bytes" => TypeNode::Bytes(array_length),
+        "ipv4" => TypeNode::Ipv4Address(array_length),
+        "ipv6" => TypeNode::Ipv6Address(array_length),
+        "port" => TypeNode::Port(array_length),
         _ => panic!("Not a supported type: {}", type_name),
     }
 }
 
+/// `fixed<base, n>`; `n` is range-checked against the base in `parse_file`.
+fn parse_fixed_type(parser_rule: Pair<Rule>) -> TypeNode {
+    let mut base = FixedBase::Int32;
+    let mut frac = 0u8;
+    for item in parser_rule.into_inner() {
+        match item.as_rule() {
+            Rule::fixed_base => base = to_fixed_base(item.as_str()),
+            Rule::fixed_frac => frac = item.as_str().parse().unwrap_or(u8::MAX),
+            _ => {}
+        }
+    }
+    TypeNode::Fixed(None, base, frac)
+}
+
+fn parse_repeat_type(parser_rule: Pair<Rule>) -> TypeNode {
+    let mut element = String::new();
+    let mut bound = RepeatBound::Eof;
+    for item in parser_rule.into_inner() {
+        match item.as_rule() {
+            Rule::identifier => element = item.as_str().to_string(),
+            Rule::eof_kw => bound = RepeatBound::Eof,
+            Rule::inner_expr => bound = RepeatBound::Until(parse_inner_expr(item)),
+            Rule::array_specifier => {
+                if let Some(e) = item.into_inner().find(|p| p.as_rule() == Rule::expr) {
+                    bound = RepeatBound::Count(parse_expr(e));
+                }
+            }
+            _ => {}
+        }
+    }
+    TypeNode::Repeat(element, bound)
+}
+
+fn parse_string_type(parser_rule: Pair<Rule>, encoding: StringEncoding) -> TypeNode {
+    let mut inner = parser_rule.into_inner();
+    let kind = inner.next().expect("string_type starts with its keyword");
+    match kind.as_rule() {
+        Rule::string_t => {
+            let len = inner
+                .next()
+                .and_then(|spec| spec.into_inner().find(|p| p.as_rule() == Rule::expr))
+                .map(parse_expr)
+                .expect("string[n] must have a length");
+            TypeNode::FixedString(len, encoding)
+        }
+        Rule::cstring_t => TypeNode::CString(encoding),
+        Rule::pstring_t => {
+            let prefix = match inner.find(|p| matches!(p.as_rule(), Rule::uint8 | Rule::uint16)).map(|p| p.as_rule()) {
+                Some(Rule::uint16) => LengthPrefix::UnsignedInteger16,
+                _ => LengthPrefix::UnsignedInteger8,
+            };
+            TypeNode::PString(prefix, encoding)
+        }
+        r => panic!("Not a supported string type: {:?}", r),
+    }
+}
+
+fn to_time_encoding(s: &str) -> TimeEncoding {
+    match s {
+        "unix_ms" => TimeEncoding::UnixMillis,
+        "unix_us" => TimeEncoding::UnixMicros,
+        "ntp64" => TimeEncoding::Ntp64,
+        "filetime" => TimeEncoding::FileTime,
+        _ => TimeEncoding::UnixSeconds,
+    }
+}
+
+fn to_fixed_base(s: &str) -> FixedBase {
+    match s {
+        "int8" => FixedBase::Int8,
+        "uint8" => FixedBase::UInt8,
+        "int16" => FixedBase::Int16,
+        "uint16" => FixedBase::UInt16,
+        "uint32" => FixedBase::UInt32,
+        "int64" => FixedBase::Int64,
+        "uint64" => FixedBase::UInt64,
+        _ => FixedBase::Int32,
+    }
+}
+
+fn to_var_encoding(s: &str) -> VarIntEncoding {
+    match s {
+        "varint" => VarIntEncoding::ZigZag,
+        "quicvarint" => VarIntEncoding::Quic,
+        _ => VarIntEncoding::Leb128,
+    }
+}
+
+fn to_encoding(s: &str) -> StringEncoding {
+    match s {
+        "ascii" => StringEncoding::Ascii,
+        "utf16le" => StringEncoding::Utf16Le,
+        _ => StringEncoding::Utf8,
+    }
+}
+
 // ===============================
 // Utilities
 // ===============================
@@ -482,6 +1427,27 @@ fn to_endianness_opt(s: &str) -> Option<Endianness> {
     to_endianness(s)
 }
 
+/// Joins `///` lines into one doc string, stripping the marker and one leading space.
+fn doc_text(lines: &[&str]) -> Option<String> {
+    if lines.is_empty() {
+        return None;
+    }
+    let text = lines
+        .iter()
+        .map(|l| {
+            let body = l.trim_end().trim_start_matches("///");
+            body.strip_prefix(' ').unwrap_or(body)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    Some(text)
+}
+
+fn to_span(pair: &Pair<Rule>, file: &str) -> SourceSpan {
+    let span = pair.as_span();
+    SourceSpan { file: file.to_string(), start: span.start(), end: span.end() }
+}
+
 /// Generic left-fold builder for expression sequences.
 fn fold_left<F>(mut terms: Vec<ExprNode>, ops: Vec<Rule>, mut f: F) -> ExprNode
 where
@@ -503,3 +1469,244 @@ where
     }
     acc
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses `src` as a complete expression.
+    fn parse(src: &str) -> ExprNode {
+        let mut pairs = PacketParser2::parse(Rule::expr, src)
+            .unwrap_or_else(|e| panic!("failed to parse `{src}`:\n{e}"));
+        let pair = pairs.next().unwrap();
+        assert_eq!(pair.as_str(), src.trim(), "`{src}` was only partially parsed");
+        parse_expr(pair)
+    }
+
+    /// Compact prefix rendering so expected trees stay readable.
+    fn sexp(e: &ExprNode) -> String {
+        use ExprNode::*;
+        let bin = |op: &str, a: &ExprNode, b: &ExprNode| format!("({op} {} {})", sexp(a), sexp(b));
+        match e {
+            NoExpr => "<none>".to_string(),
+            UnsignedInteger64Value(u) => u.to_string(),
+            Integer64Value(i) => format!("#{i}"),
+            Float64Value(f) => format!("{f:?}"),
+            StringValue(s) => format!("{s:?}"),
+            ValueReference(name, None) => name.clone(),
+            ValueReference(name, Some(ix)) => format!("(index {name} {})", sexp(ix)),
+            ActivationRecord(name, args) => {
+                let args: Vec<String> = args.iter().map(sexp).collect();
+                format!("({name} {})", args.join(" "))
+            }
+            ParenthesizedExpr(x) => format!("(paren {})", sexp(x)),
+            GuardExpression(c, t, f) => format!("(when {} {} {})", sexp(c), sexp(t), sexp(f)),
+            AggregateSum(id) => format!("(sumof {id})"),
+            AggregateProduct(id) => format!("(productof {id})"),
+            Plus(a, b) => bin("+", a, b),
+            Minus(a, b) => bin("-", a, b),
+            Mult(a, b) => bin("*", a, b),
+            Div(a, b) => bin("/", a, b),
+            Mod(a, b) => bin("%", a, b),
+            Pow(a, b) => bin("^", a, b),
+            Negate(a) => format!("(neg {})", sexp(a)),
+            BitAnd(a, b) => bin("&", a, b),
+            BitOr(a, b) => bin("|", a, b),
+            BitNot(a) => format!("(~ {})", sexp(a)),
+            ShiftLeft(a, b) => bin("<<", a, b),
+            ShiftRight(a, b) => bin(">>", a, b),
+            Gt(a, b) => bin(">", a, b),
+            Gte(a, b) => bin(">=", a, b),
+            Lt(a, b) => bin("<", a, b),
+            Lte(a, b) => bin("<=", a, b),
+            Equals(a, b) => bin("==", a, b),
+            NotEquals(a, b) => bin("!=", a, b),
+            And(a, b) => bin("and", a, b),
+            Or(a, b) => bin("or", a, b),
+            Not(a) => format!("(not {})", sexp(a)),
+        }
+    }
+
+    fn check(cases: &[(&str, &str)]) {
+        for (src, expected) in cases {
+            assert_eq!(sexp(&parse(src)), *expected, "parsing `{src}`");
+        }
+    }
+
+    #[test]
+    fn literals() {
+        check(&[
+            ("42", "42"),
+            ("0x2a", "42"),
+            ("0b101010", "42"),
+            ("1.5", "1.5"),
+            ("\"abc\"", "\"abc\""),
+            ("-7", "#-7"),
+            ("-1.5", "-1.5"),
+            ("- 7", "#-7"),
+        ]);
+    }
+
+    #[test]
+    fn accessors_and_calls() {
+        check(&[
+            ("len", "len"),
+            ("nothing", "nothing"),
+            ("not", "not"),
+            ("not not", "(not not)"),
+            ("order_id", "order_id"),
+            ("items[i + 1]", "(index items (+ i 1))"),
+            ("sumof sizes", "(sumof sizes)"),
+            ("productof dims", "(productof dims)"),
+            ("sqrt(x)", "(sqrt x)"),
+            ("min(a, b + 1)", "(min a (+ b 1))"),
+            ("max(a, b,)", "(max a b)"),
+            ("crc32(hdr..payload)", "(crc32 hdr payload)"),
+            ("inet_checksum(hdr)", "(inet_checksum hdr hdr)"),
+            ("crc16_ccitt(a..b) ^ 1", "(^ (crc16_ccitt a b) 1)"),
+            ("abs(a - b)", "(abs (- a b))"),
+            ("clamp(x, 0, 255)", "(clamp x 0 255)"),
+            ("bswap16(v) >> 8", "(>> (bswap16 v) 8)"),
+            ("len(items) * sizeof(Entry)", "(* (len items) (sizeof Entry))"),
+            ("round", "round"),
+            ("lengths(x)", "(lengths x)"),
+            ("to_hex(mask) + 1", "(+ (to_hex mask) 1)"),
+        ]);
+    }
+
+    #[test]
+    fn binary_operators_are_left_associative() {
+        check(&[
+            ("a - b - c", "(- (- a b) c)"),
+            ("a / b / c", "(/ (/ a b) c)"),
+            ("a % b * c", "(* (% a b) c)"),
+            ("a << b >> c", "(>> (<< a b) c)"),
+            ("a & b & c", "(& (& a b) c)"),
+            ("a | b | c", "(| (| a b) c)"),
+            ("a and b and c", "(and (and a b) c)"),
+            ("a or b or c", "(or (or a b) c)"),
+        ]);
+    }
+
+    #[test]
+    fn precedence_between_adjacent_levels() {
+        check(&[
+            ("a or b and c", "(or a (and b c))"),
+            ("a and b or c", "(or (and a b) c)"),
+            ("not a and b", "(and (not a) b)"),
+            ("not a < b", "(not (< a b))"),
+            ("a < b | c", "(< a (| b c))"),
+            ("a | b & c", "(| a (& b c))"),
+            ("a & b << c", "(& a (<< b c))"),
+            ("a << b + c", "(<< a (+ b c))"),
+            ("a + b * c", "(+ a (* b c))"),
+            ("a * b + c", "(+ (* a b) c)"),
+            ("a * -b", "(* a (neg b))"),
+            ("-a ^ b", "(neg (^ a b))"),
+            ("~a ^ 2", "(~ (^ a 2))"),
+            ("-a * b", "(* (neg a) b)"),
+        ]);
+    }
+
+    #[test]
+    fn precedence_across_the_whole_ladder() {
+        check(&[
+            (
+                "a or not b == c | d & e << f + g * h ^ i",
+                "(or a (not (== b (| c (& d (<< e (+ f (* g (^ h i)))))))))",
+            ),
+            (
+                "a ^ b * c + d << e & f | g != h and i or j",
+                "(or (and (!= (| (& (<< (+ (* (^ a b) c) d) e) f) g) h) i) j)",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn unary_operators_nest() {
+        check(&[
+            ("--a", "(neg (neg a))"),
+            ("-~a", "(neg (~ a))"),
+            ("~-a", "(~ (neg a))"),
+            ("- -3", "(neg #-3)"),
+            ("not not a", "(not (not a))"),
+            ("not nothing", "(not nothing)"),
+        ]);
+    }
+
+    #[test]
+    fn power_is_right_associative() {
+        check(&[
+            ("2 ^ 3", "(^ 2 3)"),
+            ("2 ^ 3 ^ 2", "(^ 2 (^ 3 2))"),
+            ("a ^ b ^ c ^ d", "(^ a (^ b (^ c d)))"),
+            ("2 ^ -1", "(^ 2 #-1)"),
+            ("2 ^ -x ^ 2", "(^ 2 (neg (^ x 2)))"),
+            ("(2 ^ 3) ^ 2", "(^ (^ 2 3) 2)"),
+        ]);
+    }
+
+    #[test]
+    fn comparisons_chain_with_and() {
+        check(&[
+            ("a < b", "(< a b)"),
+            ("a < b < c", "(and (< a b) (< b c))"),
+            ("a < b == c", "(and (< a b) (== b c))"),
+            ("0 <= x < 10 != y", "(and (and (<= 0 x) (< x 10)) (!= 10 y))"),
+            ("a + 1 > b * 2 >= c", "(and (> (+ a 1) (* b 2)) (>= (* b 2) c))"),
+            ("a <> b", "(!= a b)"),
+        ]);
+    }
+
+    #[test]
+    fn shifts_are_not_comparisons() {
+        check(&[
+            ("a << 2 < b", "(< (<< a 2) b)"),
+            ("a >> 1 >= b", "(>= (>> a 1) b)"),
+            ("a < b << 1", "(< a (<< b 1))"),
+        ]);
+    }
+
+    #[test]
+    fn parentheses_override_precedence() {
+        check(&[
+            ("(a + b) * c", "(* (+ a b) c)"),
+            ("a - (b - c)", "(- a (- b c))"),
+            ("(a or b) and c", "(and (or a b) c)"),
+            ("-(a + b)", "(neg (+ a b))"),
+        ]);
+    }
+
+    #[test]
+    fn guard_expressions() {
+        check(&[
+            ("when a > 0 then a otherwise 0", "(when (> a 0) a 0)"),
+            ("when a then b otherwise c + 1", "(when a b (+ c 1))"),
+            (
+                "when a then when b then 1 otherwise 2 otherwise 3",
+                "(when a (when b 1 2) 3)",
+            ),
+            ("1 + when a then 2 otherwise 3", "(+ 1 (when a 2 3))"),
+        ]);
+    }
+
+    #[test]
+    fn value_sets_expand_to_comparisons() {
+        check(&[
+            ("x in [3]", "(== x 3)"),
+            ("x in [1..5]", "(paren (and (>= x 1) (<= x 5)))"),
+            ("x in [1..5, 7, 0x10..0x1f,]", "(or (or (paren (and (>= x 1) (<= x 5))) (== x 7)) (paren (and (>= x 16) (<= x 31))))"),
+            ("x + 1 in [a..b] and y", "(and (paren (and (>= (+ x 1) a) (<= (+ x 1) b))) y)"),
+            ("not x in [1, 2]", "(not (or (== x 1) (== x 2)))"),
+            ("index in [1.5..2]", "(paren (and (>= index 1.5) (<= index 2)))"),
+        ]);
+    }
+
+    #[test]
+    fn keywords_are_not_identifiers() {
+        for src in ["and", "or", "packet", "when", "sumof", "reserved"] {
+            let parsed = PacketParser2::parse(Rule::expr, src);
+            assert!(parsed.is_err(), "`{src}` should not parse as an expression");
+        }
+    }
+}
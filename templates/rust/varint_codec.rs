@@ -0,0 +1,51 @@
+    use std::io::{self, Cursor, Read};
+
+    pub fn write_leb128(data: &mut Vec<u8>, mut v: u64) {
+        while v >= 0x80 {
+            data.push(v as u8 | 0x80);
+            v >>= 7;
+        }
+        data.push(v as u8);
+    }
+
+    pub fn read_leb128(cur: &mut Cursor<&[u8]>) -> io::Result<u64> {
+        let mut v = 0u64;
+        let mut b = [0u8; 1];
+        for i in 0..10 {
+            cur.read_exact(&mut b)?;
+            v |= u64::from(b[0] & 0x7f) << (7 * i);
+            if b[0] & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err(io::Error::new(io::ErrorKind::InvalidData, "varint longer than 10 bytes"))
+    }
+
+    pub fn write_zigzag(data: &mut Vec<u8>, v: i64) {
+        write_leb128(data, ((v << 1) ^ (v >> 63)) as u64);
+    }
+
+    pub fn read_zigzag(cur: &mut Cursor<&[u8]>) -> io::Result<i64> {
+        let u = read_leb128(cur)?;
+        Ok((u >> 1) as i64 ^ -((u & 1) as i64))
+    }
+
+    /// Shortest of the 1, 2, 4 and 8 byte forms; panics from 2^62 up.
+    pub fn write_quic(data: &mut Vec<u8>, v: u64) {
+        match v {
+            0..=0x3f => data.push(v as u8),
+            0x40..=0x3fff => data.extend_from_slice(&(v as u16 | 0x4000).to_be_bytes()),
+            0x4000..=0x3fff_ffff => data.extend_from_slice(&(v as u32 | 0x8000_0000).to_be_bytes()),
+            0x4000_0000..=0x3fff_ffff_ffff_ffff => data.extend_from_slice(&(v | 0xc000_0000_0000_0000).to_be_bytes()),
+            _ => panic!("{v} does not fit in a QUIC varint"),
+        }
+    }
+
+    pub fn read_quic(cur: &mut Cursor<&[u8]>) -> io::Result<u64> {
+        let mut buf = [0u8; 8];
+        cur.read_exact(&mut buf[..1])?;
+        let n = 1usize << (buf[0] >> 6);
+        cur.read_exact(&mut buf[1..n])?;
+        buf[0] &= 0x3f;
+        Ok(buf[..n].iter().fold(0u64, |acc, &b| (acc << 8) | u64::from(b)))
+    }